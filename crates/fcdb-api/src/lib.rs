@@ -2,19 +2,152 @@
 //!
 //! GraphQL and gRPC API interfaces for the Enishi database.
 //!
-//! Merkle DAG: enishi_api -> graphql_schema, grpc_services, http_handlers
+//! Every resolver requires a capability token, attached to the request with
+//! [`with_token`]: it must verify against the schema's [`CapGuard`] and
+//! grant the operation's permissions over the nodes involved. Refusals carry
+//! the extension code `UNAUTHENTICATED` or `FORBIDDEN` and, like grants, are
+//! recorded in the guard's audit trail.
+//!
+//! Merkle DAG: enishi_api -> graphql_schema, grpc_services, http_handlers, cap_guard
 
-use async_graphql::{Context, EmptySubscription, Object, Schema, SimpleObject, ID};
-use fcdb_graph::{GraphDB, Rid, LabelId, Timestamp};
+use async_graphql::{Context, EmptySubscription, ErrorExtensions, Object, Schema, SimpleObject, ID};
+use fcdb_concur::{gate, perms, CapAuthority, CapGate, CapRefusal, CapTracer};
+use fcdb_core::Cap;
+use fcdb_graph::{ConstraintViolation, GraphDB, Rid, PropertyRecord, ScopeViolation, Timestamp, Transaction};
 use fcdb_rdf::{RdfExporter, SparqlRunner};
 use fcdb_shacl::{validate_shapes, ValidationConfig};
-use fcdb_cypher::execute_cypher;
+use fcdb_cypher::execute_cypher_in;
 use fcdb_gremlin::{execute_traversal, Traversal, g};
 use fcdb_owl::classify_ontology;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+/// Render stored node data as the GraphQL `data` string (property records as JSON)
+fn node_data(data: Vec<u8>) -> Option<String> {
+    if PropertyRecord::is_encoded(&data) {
+        Some(PropertyRecord::data_to_string(&data))
+    } else {
+        String::from_utf8(data).ok()
+    }
+}
+
+/// GraphQL error for a constraint violation, tagged with
+/// `code: CONSTRAINT_VIOLATION` and the constraint name as extensions
+fn constraint_error(message: String, violation: &ConstraintViolation) -> async_graphql::Error {
+    async_graphql::Error::new(message).extend_with(|_, extensions| {
+        extensions.set("code", "CONSTRAINT_VIOLATION");
+        extensions.set("constraint", violation.constraint());
+    })
+}
+
+/// GraphQL error for a failed graph write
+fn write_error(context: &str, err: Box<dyn std::error::Error>) -> async_graphql::Error {
+    if let Some(violation) = err.downcast_ref::<ScopeViolation>() {
+        return coded_error("FORBIDDEN", violation.to_string());
+    }
+    match err.downcast_ref::<ConstraintViolation>() {
+        Some(violation) => constraint_error(format!("{}: {}", context, violation), violation),
+        None => async_graphql::Error::new(format!("{}: {}", context, err)),
+    }
+}
+
+fn coded_error(code: &'static str, message: String) -> async_graphql::Error {
+    async_graphql::Error::new(message).extend_with(|_, extensions| extensions.set("code", code))
+}
+
+/// Capability token presented with a GraphQL request
+pub struct BearerToken(pub String);
+
+/// Attach the caller's capability token (the `Authorization: Bearer` value)
+/// to a request
+pub fn with_token(request: async_graphql::Request, token: Option<&str>) -> async_graphql::Request {
+    match token {
+        Some(token) => request.data(BearerToken(token.to_string())),
+        None => request,
+    }
+}
+
+/// Verifies request capabilities for the schema and audits the outcome,
+/// through the same [`CapGate`] checks as the HTTP server. Operations are
+/// audited as `graphql:<operation>`, under the gate's namespace if any.
+pub struct CapGuard {
+    gate: CapGate,
+}
+
+impl CapGuard {
+    pub fn new(authority: CapAuthority) -> Self {
+        Self::with_tracer(authority, CapTracer::new())
+    }
+
+    /// Guard auditing to `tracer`, e.g. the server's persistent one
+    pub fn with_tracer(authority: CapAuthority, tracer: CapTracer) -> Self {
+        Self::with_gate(CapGate::new(authority, Some(tracer)))
+    }
+
+    /// Guard checking through `gate`, e.g. a namespace's from
+    /// [`CapGate::for_namespace`]
+    pub fn with_gate(gate: CapGate) -> Self {
+        Self { gate }
+    }
+
+    pub fn authority(&self) -> &CapAuthority {
+        self.gate.authority()
+    }
+
+    pub fn tracer(&self) -> &CapTracer {
+        self.gate.tracer()
+    }
+
+    /// Verified cap of the request, which must grant every bit of `required`
+    async fn authorize(&self, ctx: &Context<'_>, operation: &str, required: u32) -> async_graphql::Result<Cap> {
+        let token = ctx.data_opt::<BearerToken>().map(|token| token.0.as_str());
+        self.gate.authorize(token, &audited(operation), required).await.map_err(refused)
+    }
+
+    async fn deny(&self, cap: &Cap, operation: &str, details: &str) -> async_graphql::Error {
+        refused(self.gate.deny(cap, &audited(operation), details).await)
+    }
+
+    async fn check_rid(&self, cap: &Cap, operation: &str, rid: Rid) -> async_graphql::Result<()> {
+        self.gate.check_rid(cap, &audited(operation), rid.0).await.map_err(refused)
+    }
+
+    /// Operations over the whole graph need a cap spanning every issued RID
+    async fn check_whole_graph(&self, cap: &Cap, operation: &str, graph: &GraphDB) -> async_graphql::Result<()> {
+        self.gate.check_whole_graph(cap, &audited(operation), graph.next_rid().await.0).await.map_err(refused)
+    }
+}
+
+/// Audited name of a GraphQL operation, distinct from the HTTP endpoints'
+fn audited(operation: &str) -> String {
+    format!("graphql:{}", operation)
+}
+
+fn refused(refusal: CapRefusal) -> async_graphql::Error {
+    let code = match refusal {
+        CapRefusal::Unauthenticated(_) => "UNAUTHENTICATED",
+        CapRefusal::Forbidden(_) => "FORBIDDEN",
+        CapRefusal::AuditFailed(_) => "AUDIT_FAILED",
+    };
+    coded_error(code, refusal.to_string())
+}
+
+/// RIDs a cap covers
+fn scope(cap: &Cap) -> std::ops::Range<Rid> {
+    let rids = gate::scope(cap);
+    Rid(rids.start)..Rid(rids.end)
+}
+
+/// Transaction confined to the cap's RIDs, storing new objects against its
+/// holder's quota as the server's write endpoints do
+fn begin_for<'a>(graph: &'a GraphDB, cap: &Cap) -> Transaction<'a> {
+    let mut tx = graph.begin();
+    tx.restrict(scope(cap));
+    tx.charge_to(gate::holder(cap));
+    tx
+}
+
 /// GraphQL node representation
 #[derive(SimpleObject, Serialize, Deserialize)]
 pub struct Node {
@@ -238,15 +371,17 @@ pub struct Query;
 impl Query {
     /// Get a node by ID
     async fn node(&self, ctx: &Context<'_>, id: ID) -> async_graphql::Result<Option<Node>> {
+        let guard = ctx.data::<Arc<CapGuard>>()?;
+        let cap = guard.authorize(ctx, "node", perms::READ).await?;
         let graph = ctx.data::<Arc<RwLock<GraphDB>>>()?;
         let graph = graph.read().await;
 
         let rid = Rid(id.parse().map_err(|_| "Invalid node ID")?);
+        guard.check_rid(&cap, "node", rid).await?;
 
         match graph.get_node(rid).await {
             Ok(Some(data)) => {
-                let data_str = String::from_utf8(data)
-                    .map_err(|_| "Invalid UTF-8 data")?;
+                let data_str = node_data(data).ok_or("Invalid UTF-8 data")?;
                 Ok(Some(Node {
                     id,
                     data: data_str,
@@ -260,16 +395,18 @@ impl Query {
 
     /// Get a node at a specific historical timestamp
     async fn node_at(&self, ctx: &Context<'_>, id: ID, as_of: String) -> async_graphql::Result<Option<Node>> {
+        let guard = ctx.data::<Arc<CapGuard>>()?;
+        let cap = guard.authorize(ctx, "nodeAt", perms::READ).await?;
         let graph = ctx.data::<Arc<RwLock<GraphDB>>>()?;
         let graph = graph.read().await;
 
         let rid = Rid(id.parse().map_err(|_| "Invalid node ID")?);
+        guard.check_rid(&cap, "nodeAt", rid).await?;
         let timestamp = Timestamp(as_of.parse().map_err(|_| "Invalid timestamp")?);
 
         match graph.get_node_at(rid, timestamp).await {
             Ok(Some(data)) => {
-                let data_str = String::from_utf8(data)
-                    .map_err(|_| "Invalid UTF-8 data")?;
+                let data_str = node_data(data).ok_or("Invalid UTF-8 data")?;
                 Ok(Some(Node {
                     id,
                     data: data_str,
@@ -283,24 +420,36 @@ impl Query {
 
     /// Traverse the graph from a starting node
     async fn traverse(&self, ctx: &Context<'_>, input: TraverseInput) -> async_graphql::Result<Vec<TraversalResult>> {
+        let guard = ctx.data::<Arc<CapGuard>>()?;
+        let cap = guard.authorize(ctx, "traverse", perms::READ).await?;
         let graph = ctx.data::<Arc<RwLock<GraphDB>>>()?;
         let graph = graph.read().await;
 
         let from_rid = Rid(input.from.parse().map_err(|_| "Invalid node ID")?);
-        let labels: Option<Vec<LabelId>> = input.labels.map(|ls|
-            ls.into_iter().map(|l| LabelId(l.parse().unwrap_or(0))).collect()
-        );
+        guard.check_rid(&cap, "traverse", from_rid).await?;
+        // Label names that were never interned cannot match any edge
+        let labels = match input.labels {
+            Some(names) => {
+                let mut labels = Vec::new();
+                for name in &names {
+                    labels.extend(graph.lookup_label(name).await);
+                }
+                Some(labels)
+            }
+            None => None,
+        };
         let max_depth = input.max_depth as usize;
         let as_of = input.as_of.map(|ts| Timestamp(ts.parse().unwrap_or(0)));
 
-        let traversal = graph.traverse(from_rid, labels.as_deref(), max_depth, as_of).await
+        // Paths through nodes outside the capability are not followed
+        let traversal = graph.traverse_within(from_rid, labels.as_deref(), max_depth, as_of, scope(&cap)).await
             .map_err(|e| async_graphql::Error::new(format!("Traversal error: {}", e)))?;
 
         let mut results = Vec::new();
         for (rid, depth) in traversal {
             // Get node data for each result
             if let Ok(Some(data)) = graph.get_node(rid).await {
-                if let Ok(data_str) = String::from_utf8(data) {
+                if let Some(data_str) = node_data(data) {
                     results.push(TraversalResult {
                         node: Node {
                             id: ID::from(rid.0.to_string()),
@@ -318,6 +467,7 @@ impl Query {
 
     /// Search nodes by text content
     async fn search(&self, ctx: &Context<'_>, query: String) -> async_graphql::Result<Vec<SearchResult>> {
+        let cap = ctx.data::<Arc<CapGuard>>()?.authorize(ctx, "search", perms::READ).await?;
         let graph = ctx.data::<Arc<RwLock<GraphDB>>>()?;
         let graph = graph.read().await;
 
@@ -325,9 +475,9 @@ impl Query {
             .map_err(|e| async_graphql::Error::new(format!("Search error: {}", e)))?;
 
         let mut results = Vec::new();
-        for (rid, score) in search_results {
+        for (rid, score) in search_results.into_iter().filter(|(rid, _)| cap.contains(rid.0)) {
             if let Ok(Some(data)) = graph.get_node(rid).await {
-                if let Ok(data_str) = String::from_utf8(data) {
+                if let Some(data_str) = node_data(data) {
                     results.push(SearchResult {
                         node: Node {
                             id: ID::from(rid.0.to_string()),
@@ -345,8 +495,11 @@ impl Query {
 
     /// Execute a SPARQL query over the RDF projection
     async fn sparql(&self, ctx: &Context<'_>, query: String) -> async_graphql::Result<String> {
+        let guard = ctx.data::<Arc<CapGuard>>()?;
+        let cap = guard.authorize(ctx, "sparql", perms::READ | perms::EXECUTE).await?;
         let graph = ctx.data::<Arc<RwLock<GraphDB>>>()?;
         let graph = graph.read().await;
+        guard.check_whole_graph(&cap, "sparql", &graph).await?;
         let exporter = RdfExporter::new(&graph, "https://enishi.local/");
         let runner = SparqlRunner::new(exporter);
         runner.execute(&query).await.map_err(|e| async_graphql::Error::new(e))
@@ -354,8 +507,11 @@ impl Query {
 
     /// Validate data against SHACL shapes
     async fn validate_shacl(&self, ctx: &Context<'_>, input: ShaclValidateInput) -> async_graphql::Result<GraphQLValidationReport> {
+        let guard = ctx.data::<Arc<CapGuard>>()?;
+        let cap = guard.authorize(ctx, "validateShacl", perms::READ | perms::EXECUTE).await?;
         let graph = ctx.data::<Arc<RwLock<GraphDB>>>()?;
         let graph = graph.read().await;
+        guard.check_whole_graph(&cap, "validateShacl", &graph).await?;
 
         let config = ValidationConfig {
             max_violations: input.config.as_ref().and_then(|c| c.max_violations).unwrap_or(100),
@@ -386,18 +542,38 @@ impl Query {
         Ok(graphql_report)
     }
 
-    /// Execute a Cypher query
+    /// Execute a Cypher query over the nodes the capability covers; queries
+    /// that write also need WRITE
     async fn cypher(&self, ctx: &Context<'_>, query: String) -> async_graphql::Result<GraphQLCypherResult> {
+        let guard = ctx.data::<Arc<CapGuard>>()?;
+        let cap = guard.authorize(ctx, "cypher", perms::READ | perms::EXECUTE).await?;
         let graph = ctx.data::<Arc<RwLock<GraphDB>>>()?;
         let graph = graph.read().await;
 
-        let result = execute_cypher(&query, &graph).await
-            .map_err(|e| async_graphql::Error::new(format!("Cypher execution error: {:?}", e)))?;
+        let cypher_error = |e: fcdb_cypher::CypherError| match &e {
+            fcdb_cypher::CypherError::Constraint(violation) => constraint_error(e.to_string(), violation),
+            fcdb_cypher::CypherError::Conflict(_) => coded_error("TRANSACTION_CONFLICT", e.to_string()),
+            fcdb_cypher::CypherError::Forbidden(_) => coded_error("FORBIDDEN", e.to_string()),
+            _ => async_graphql::Error::new(format!("Cypher execution error: {:?}", e)),
+        };
+        let mut tx = begin_for(&graph, &cap);
+        let result = match execute_cypher_in(&query, &mut tx).await {
+            Ok(result) => result,
+            Err(fcdb_cypher::CypherError::Forbidden(violation)) => {
+                return Err(guard.deny(&cap, "cypher", &violation.to_string()).await);
+            }
+            Err(e) => return Err(cypher_error(e)),
+        };
+        if !tx.is_empty() && !cap.has_perm(perms::WRITE) {
+            return Err(guard.deny(&cap, "cypher", "query writes but the capability lacks WRITE").await);
+        }
+        tx.commit().await
+            .map_err(|e| cypher_error(fcdb_cypher::CypherError::graph(e)))?;
 
         // Convert internal result to GraphQL representation
         let graphql_result = GraphQLCypherResult {
             columns: result.columns,
-            rows: result.rows,
+            rows: result.rows.into_iter().map(|row| serde_json::json!(row)).collect(),
             stats: GraphQLQueryStats {
                 nodes_created: result.stats.nodes_created as i32,
                 nodes_deleted: result.stats.nodes_deleted as i32,
@@ -415,6 +591,8 @@ impl Query {
 
     /// Execute a Gremlin traversal
     async fn gremlin(&self, ctx: &Context<'_>, input: GremlinTraversalInput) -> async_graphql::Result<GraphQLGremlinResult> {
+        let guard = ctx.data::<Arc<CapGuard>>()?;
+        let cap = guard.authorize(ctx, "gremlin", perms::READ | perms::EXECUTE).await?;
         let graph = ctx.data::<Arc<RwLock<GraphDB>>>()?;
         let graph = graph.read().await;
 
@@ -427,6 +605,7 @@ impl Query {
         } else if input.start.starts_with("V(") && input.start.ends_with(")") {
             let id_str = &input.start[2..input.start.len() - 1];
             if let Ok(id) = id_str.parse::<u64>() {
+                guard.check_rid(&cap, "gremlin", Rid(id)).await?;
                 traversal_builder = traversal_builder.V_id(id);
             } else {
                 return Err(async_graphql::Error::new("Invalid vertex ID in start"));
@@ -444,9 +623,13 @@ impl Query {
         let result = execute_traversal(&graph, traversal).await
             .map_err(|e| async_graphql::Error::new(format!("Gremlin execution error: {:?}", e)))?;
 
-        // Convert internal result to GraphQL representation
+        // Convert internal result to GraphQL representation, dropping
+        // traversers that passed through nodes outside the capability
+        let visible = |rid: &Rid| cap.contains(rid.0);
         let graphql_result = GraphQLGremlinResult {
-            traversers: result.traversers.into_iter().map(|t| GraphQLTraverser {
+            traversers: result.traversers.into_iter()
+                .filter(|t| visible(&t.current) && t.path.iter().all(visible))
+                .map(|t| GraphQLTraverser {
                 current: t.current.0.to_string(),
                 path: t.path.iter().map(|rid| rid.0.to_string()).collect(),
                 value: t.get_side_effect("value").cloned(),
@@ -458,8 +641,11 @@ impl Query {
 
     /// Classify ontology and return inferred triples
     async fn classify_owl(&self, ctx: &Context<'_>, input: OwlClassifyInput) -> async_graphql::Result<GraphQLOwlResult> {
+        let guard = ctx.data::<Arc<CapGuard>>()?;
+        let cap = guard.authorize(ctx, "classifyOwl", perms::READ | perms::EXECUTE).await?;
         let graph = ctx.data::<Arc<RwLock<GraphDB>>>()?;
         let graph = graph.read().await;
+        guard.check_whole_graph(&cap, "classifyOwl", &graph).await?;
 
         let inferred_triples = classify_ontology(&input.ontology, &graph).await
             .map_err(|e| async_graphql::Error::new(format!("OWL classification error: {:?}", e)))?;
@@ -479,7 +665,7 @@ impl Query {
     }
 }
 
-fn parse_and_apply_step(builder: crate::fcdb_gremlin::TraversalBuilder, step: &str) -> Result<crate::fcdb_gremlin::TraversalBuilder, async_graphql::Error> {
+fn parse_and_apply_step(builder: fcdb_gremlin::TraversalBuilder, step: &str) -> Result<fcdb_gremlin::TraversalBuilder, async_graphql::Error> {
     if step.starts_with("out(") && step.ends_with(")") {
         let label = &step[4..step.len() - 1];
         let label_opt = if label.is_empty() { None } else { Some(label.to_string()) };
@@ -520,14 +706,23 @@ pub struct Mutation;
 
 #[Object]
 impl Mutation {
-    /// Create a new node
+    /// Create a new node; its RID must fall in the capability's range
     async fn create_node(&self, ctx: &Context<'_>, input: CreateNodeInput) -> async_graphql::Result<Node> {
+        let guard = ctx.data::<Arc<CapGuard>>()?;
+        let cap = guard.authorize(ctx, "createNode", perms::WRITE).await?;
         let graph = ctx.data::<Arc<RwLock<GraphDB>>>()?;
-        let mut graph = graph.write().await;
-
-        let data_bytes = input.data.as_bytes();
-        let rid = graph.create_node(data_bytes).await
-            .map_err(|e| async_graphql::Error::new(format!("Create node error: {}", e)))?;
+        let graph = graph.write().await;
+
+        let mut tx = begin_for(&graph, &cap);
+        let created = tx.create_node(input.data.as_bytes()).await
+            .map_err(|e| e.downcast::<ScopeViolation>().map_err(|e| write_error("Create node error", e)));
+        let rid = match created {
+            Ok(rid) => rid,
+            Err(Ok(violation)) => return Err(guard.deny(&cap, "createNode", &violation.to_string()).await),
+            Err(Err(e)) => return Err(e),
+        };
+        tx.commit().await
+            .map_err(|e| write_error("Create node error", e))?;
 
         Ok(Node {
             id: ID::from(rid.0.to_string()),
@@ -538,14 +733,18 @@ impl Mutation {
 
     /// Update an existing node
     async fn update_node(&self, ctx: &Context<'_>, input: UpdateNodeInput) -> async_graphql::Result<Node> {
+        let guard = ctx.data::<Arc<CapGuard>>()?;
+        let cap = guard.authorize(ctx, "updateNode", perms::WRITE).await?;
         let graph = ctx.data::<Arc<RwLock<GraphDB>>>()?;
-        let mut graph = graph.write().await;
+        let graph = graph.write().await;
 
         let rid = Rid(input.id.parse().map_err(|_| "Invalid node ID")?);
-        let data_bytes = input.data.as_bytes();
-
-        graph.update_node(rid, data_bytes).await
-            .map_err(|e| async_graphql::Error::new(format!("Update node error: {}", e)))?;
+        guard.check_rid(&cap, "updateNode", rid).await?;
+        let mut tx = begin_for(&graph, &cap);
+        tx.update_node(rid, input.data.as_bytes()).await
+            .map_err(|e| write_error("Update node error", e))?;
+        tx.commit().await
+            .map_err(|e| write_error("Update node error", e))?;
 
         Ok(Node {
             id: input.id,
@@ -556,16 +755,22 @@ impl Mutation {
 
     /// Create an edge between nodes
     async fn create_edge(&self, ctx: &Context<'_>, input: CreateEdgeInput) -> async_graphql::Result<GraphEdge> {
+        let guard = ctx.data::<Arc<CapGuard>>()?;
+        let cap = guard.authorize(ctx, "createEdge", perms::WRITE).await?;
         let graph = ctx.data::<Arc<RwLock<GraphDB>>>()?;
-        let mut graph = graph.write().await;
+        let graph = graph.write().await;
 
         let from_rid = Rid(input.from.parse().map_err(|_| "Invalid from ID")?);
         let to_rid = Rid(input.to.parse().map_err(|_| "Invalid to ID")?);
-        let label_id = LabelId(input.label.parse().map_err(|_| "Invalid label")?);
-        let prop_bytes = input.properties.as_bytes();
-
-        graph.create_edge(from_rid, to_rid, label_id, prop_bytes).await
-            .map_err(|e| async_graphql::Error::new(format!("Create edge error: {}", e)))?;
+        guard.check_rid(&cap, "createEdge", from_rid).await?;
+        guard.check_rid(&cap, "createEdge", to_rid).await?;
+        let mut tx = begin_for(&graph, &cap);
+        let label_id = tx.label_id(&input.label).await
+            .map_err(|e| async_graphql::Error::new(format!("Invalid label: {}", e)))?;
+        tx.create_edge(from_rid, to_rid, label_id, input.properties.as_bytes()).await
+            .map_err(|e| write_error("Create edge error", e))?;
+        tx.commit().await
+            .map_err(|e| write_error("Create edge error", e))?;
 
         Ok(GraphEdge {
             from: input.from,
@@ -574,15 +779,55 @@ impl Mutation {
             properties: input.properties,
         })
     }
+
+    /// Delete a node and its edges; false if it did not exist
+    async fn delete_node(&self, ctx: &Context<'_>, id: ID) -> async_graphql::Result<bool> {
+        let guard = ctx.data::<Arc<CapGuard>>()?;
+        let cap = guard.authorize(ctx, "deleteNode", perms::WRITE).await?;
+        let graph = ctx.data::<Arc<RwLock<GraphDB>>>()?;
+        let graph = graph.write().await;
+
+        let rid = Rid(id.parse().map_err(|_| "Invalid node ID")?);
+        guard.check_rid(&cap, "deleteNode", rid).await?;
+        let mut tx = begin_for(&graph, &cap);
+        let deleted = tx.delete_node(rid).await
+            .map_err(|e| write_error("Delete node error", e))?;
+        tx.commit().await
+            .map_err(|e| write_error("Delete node error", e))?;
+        Ok(deleted)
+    }
+
+    /// Delete the edges from -> to with a label; false if there were none
+    async fn delete_edge(&self, ctx: &Context<'_>, from: ID, to: ID, label: String) -> async_graphql::Result<bool> {
+        let guard = ctx.data::<Arc<CapGuard>>()?;
+        let cap = guard.authorize(ctx, "deleteEdge", perms::WRITE).await?;
+        let graph = ctx.data::<Arc<RwLock<GraphDB>>>()?;
+        let graph = graph.write().await;
+
+        let from_rid = Rid(from.parse().map_err(|_| "Invalid from ID")?);
+        let to_rid = Rid(to.parse().map_err(|_| "Invalid to ID")?);
+        guard.check_rid(&cap, "deleteEdge", from_rid).await?;
+        guard.check_rid(&cap, "deleteEdge", to_rid).await?;
+        let mut tx = begin_for(&graph, &cap);
+        let Some(label_id) = tx.lookup_label(&label).await else {
+            return Ok(false);
+        };
+        let deleted = tx.delete_edges(from_rid, to_rid, label_id).await
+            .map_err(|e| write_error("Delete edge error", e))?;
+        tx.commit().await
+            .map_err(|e| write_error("Delete edge error", e))?;
+        Ok(deleted)
+    }
 }
 
 /// GraphQL schema type
 pub type EnishiSchema = Schema<Query, Mutation, EmptySubscription>;
 
-/// Create the GraphQL schema
-pub fn create_schema(graph: Arc<RwLock<GraphDB>>) -> EnishiSchema {
+/// Create the GraphQL schema; requests are checked against `guard`
+pub fn create_schema(graph: Arc<RwLock<GraphDB>>, guard: Arc<CapGuard>) -> EnishiSchema {
     Schema::build(Query, Mutation, EmptySubscription)
         .data(graph)
+        .data(guard)
         .finish()
 }
 
@@ -724,6 +969,8 @@ pub const GRAPHQL_SCHEMA: &str = r#"
         createNode(input: CreateNodeInput!): Node!
         updateNode(input: UpdateNodeInput!): Node!
         createEdge(input: CreateEdgeInput!): GraphEdge!
+        deleteNode(id: ID!): Boolean!
+        deleteEdge(from: ID!, to: ID!, label: String!): Boolean!
     }
 "#;
 
@@ -739,8 +986,109 @@ mod tests {
         let graph = GraphDB::new(cas).await;
         let graph = Arc::new(RwLock::new(graph));
 
-        let schema = create_schema(graph);
+        let schema = create_schema(graph, Arc::new(CapGuard::new(CapAuthority::generate())));
         let result = schema.execute("query { __typename }").await;
         assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_graphql_capabilities() {
+        let temp_dir = tempdir().unwrap();
+        let cas = fcdb_cas::PackCAS::open(temp_dir.path()).await.unwrap();
+        let graph = Arc::new(RwLock::new(GraphDB::new(cas).await));
+        let guard = Arc::new(CapGuard::new(CapAuthority::generate()));
+        let schema = create_schema(graph.clone(), guard.clone());
+        let code = |response: &async_graphql::Response| response.errors[0].extensions.as_ref()
+            .and_then(|extensions| extensions.get("code").cloned());
+
+        let create = r#"mutation { createNode(input: { data: "{}" }) { id } }"#;
+        let response = schema.execute(create).await;
+        assert_eq!(code(&response), Some(async_graphql::Value::from("UNAUTHENTICATED")));
+
+        let reader = guard.authority().mint(0, 100, perms::READ, 0).to_token();
+        let response = schema.execute(with_token(create.into(), Some(&reader))).await;
+        assert_eq!(code(&response), Some(async_graphql::Value::from("FORBIDDEN")));
+
+        let writer = guard.authority().mint(0, 100, perms::READ | perms::WRITE, 0).to_token();
+        let response = schema.execute(with_token(create.into(), Some(&writer))).await;
+        assert!(response.errors.is_empty());
+
+        // Nodes outside the cap's range are refused
+        let narrow = guard.authority().mint(50, 10, perms::READ | perms::WRITE, 0).to_token();
+        let response = schema.execute(with_token(r#"{ node(id: "1") { id } }"#.into(), Some(&narrow))).await;
+        assert_eq!(code(&response), Some(async_graphql::Value::from("FORBIDDEN")));
+
+        // Traversals do not pass through nodes outside the cap's range
+        let (a, c) = {
+            let graph = graph.read().await;
+            let knows = graph.label_id("knows").await.unwrap();
+            let a = graph.create_node(b"{}").await.unwrap();
+            let c = graph.create_node(b"{}").await.unwrap();
+            let b = graph.create_node(b"{}").await.unwrap();
+            graph.create_edge(a, b, knows, b"").await.unwrap();
+            graph.create_edge(b, c, knows, b"").await.unwrap();
+            (a, c)
+        };
+        let traverse = format!(r#"{{ traverse(input: {{ from: "{}", maxDepth: 2 }}) {{ node {{ id }} }} }}"#, a.0);
+        let reached = |token: String| {
+            let schema = schema.clone();
+            let traverse = traverse.clone();
+            async move {
+                let response = schema.execute(with_token(traverse.into(), Some(&token))).await;
+                assert!(response.errors.is_empty());
+                response.data.into_json().unwrap()["traverse"].as_array().unwrap().len()
+            }
+        };
+        assert_eq!(reached(guard.authority().mint(0, 100, perms::READ, 0).to_token()).await, 3);
+        assert_eq!(reached(guard.authority().mint(a.0, c.0 - a.0 + 1, perms::READ, 0).to_token()).await, 1);
+
+        let trail = guard.tracer().get_actor_operations("anonymous").await;
+        assert_eq!(trail.len(), 1);
+        assert!(!trail[0].success);
+
+        // A namespace's guard audits under that namespace
+        let gate = CapGate::new(CapAuthority::generate(), Some(CapTracer::new())).for_namespace("tenant", 1);
+        let tenant = Arc::new(CapGuard::with_gate(gate));
+        let schema = create_schema(graph.clone(), tenant.clone());
+        let writer = tenant.authority().mint(0, 100, perms::READ | perms::WRITE, 0).to_token();
+        let response = schema.execute(with_token(create.into(), Some(&writer))).await;
+        assert!(response.errors.is_empty());
+        let trail = tenant.tracer().get_audit_trail(&gate::resource("tenant/graphql:createNode")).await;
+        assert_eq!(trail.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_graphql_writes_charge_the_holder() {
+        let temp_dir = tempdir().unwrap();
+        let config = fcdb_cas::CasConfig { actor_quota_bytes: Some(512), ..Default::default() };
+        let cas = fcdb_cas::PackCAS::open_with_config(temp_dir.path(), config).await.unwrap();
+        let graph = Arc::new(RwLock::new(GraphDB::new(cas).await));
+        let guard = Arc::new(CapGuard::new(CapAuthority::generate()));
+        let schema = create_schema(graph.clone(), guard.clone());
+        let alice = guard.authority().mint(0, 100, perms::READ | perms::WRITE, 0).to_token();
+        let bob = guard.authority().mint(0, 200, perms::READ | perms::WRITE, 0).to_token();
+
+        let create = r#"mutation { createNode(input: { data: "{}" }) { id } }"#;
+        let response = schema.execute(with_token(create.into(), Some(&alice))).await;
+        assert!(response.errors.is_empty());
+        let id = response.data.into_json().unwrap()["createNode"]["id"].as_str().unwrap().to_string();
+
+        // Updates and edges count against the writer's quota
+        let big = "x".repeat(1024);
+        let update = format!(r#"mutation {{ updateNode(input: {{ id: "{}", data: "{}" }}) {{ id }} }}"#, id, big);
+        let response = schema.execute(with_token(update.clone().into(), Some(&alice))).await;
+        assert!(response.errors[0].message.contains("quota exceeded"));
+        let edge = format!(
+            r#"mutation {{ createEdge(input: {{ from: "{0}", to: "{0}", label: "knows", properties: "{1}" }}) {{ label }} }}"#,
+            id, big);
+        let response = schema.execute(with_token(edge.into(), Some(&alice))).await;
+        assert!(response.errors[0].message.contains("quota exceeded"));
+
+        // Another holder has a quota of its own
+        let small = format!(r#"mutation {{ updateNode(input: {{ id: "{}", data: "{{}}" }}) {{ id }} }}"#, id);
+        let response = schema.execute(with_token(small.into(), Some(&bob))).await;
+        assert!(response.errors.is_empty());
+        let response = schema.execute(with_token(update.into(), Some(&bob))).await;
+        assert!(response.errors[0].message.contains("quota exceeded"));
+    }
 }
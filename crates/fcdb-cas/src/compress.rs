@@ -0,0 +1,289 @@
+//! Transparent per-object compression for PackCAS.
+//!
+//! Merkle DAG: enishi_cas -> compress -> {zstd, lz4, band_dictionaries, band_stats}
+//!
+//! Objects are compressed on write and the codec is recorded in
+//! `CidxRec.flags`; CIDs are always computed over the uncompressed content, so
+//! dedup and verification do not depend on the codec. Zstd can use a
+//! dictionary trained per [`PackBand`]; dictionaries are kept next to the packs
+//! as `zdict_<band>_<seq>.dat` and looked up by the dictionary ID stored in
+//! each zstd frame.
+
+use crate::wal::{band_from_u8, band_to_u8};
+use crate::{PackBand, PackCAS};
+use fcdb_core::Cid;
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use tracing::info;
+
+/// Codec bits of `CidxRec.flags`
+pub const FLAG_CODEC_MASK: u8 = 0x03;
+/// Object is zstd-compressed
+pub const FLAG_ZSTD: u8 = 0x01;
+/// Object is lz4-compressed (block format)
+pub const FLAG_LZ4: u8 = 0x02;
+/// Zstd frame was compressed with a band dictionary
+pub const FLAG_DICT: u8 = 0x04;
+
+/// Upper bound on the bytes sampled when training a dictionary
+const DICT_SAMPLE_BYTES: usize = 8 * 1024 * 1024;
+
+/// Compression applied to newly written objects
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    Zstd { level: i32 },
+    Lz4,
+}
+
+impl Compression {
+    /// Default zstd level used when compression is simply switched on
+    pub const DEFAULT_ZSTD_LEVEL: i32 = 3;
+}
+
+/// Compression statistics for one band
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BandStats {
+    pub objects: u64,
+    pub compressed_objects: u64,
+    /// Uncompressed size of every object
+    pub raw_bytes: u64,
+    /// Bytes actually occupied in packs
+    pub stored_bytes: u64,
+}
+
+impl BandStats {
+    /// raw / stored; 1.0 when nothing is stored
+    pub fn ratio(&self) -> f64 {
+        if self.stored_bytes == 0 {
+            1.0
+        } else {
+            self.raw_bytes as f64 / self.stored_bytes as f64
+        }
+    }
+}
+
+/// Trained zstd dictionaries: every known one by ID (for reads) and the
+/// newest one per band (for writes)
+#[derive(Default)]
+pub(crate) struct Dictionaries {
+    by_id: HashMap<u32, Vec<u8>>,
+    active: HashMap<PackBand, (u32, u32)>, // band -> (seq, dict id)
+}
+
+impl Dictionaries {
+    pub(crate) fn load(dir: &Path) -> io::Result<Self> {
+        let mut dicts = Self::default();
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let Some((band, seq)) = name.to_str()
+                .and_then(|name| name.strip_prefix("zdict_"))
+                .and_then(|name| name.strip_suffix(".dat"))
+                .and_then(|name| name.split_once('_'))
+                .and_then(|(band, seq)| Some((band_from_u8(band.parse().ok()?)?, seq.parse::<u32>().ok()?)))
+            else {
+                continue;
+            };
+
+            let dict = std::fs::read(entry.path())?;
+            let Some(id) = zstd::zstd_safe::get_dict_id_from_dict(&dict) else {
+                continue;
+            };
+            dicts.by_id.insert(id.get(), dict);
+            if dicts.active.get(&band).is_none_or(|(active_seq, _)| seq > *active_seq) {
+                dicts.active.insert(band, (seq, id.get()));
+            }
+        }
+        Ok(dicts)
+    }
+
+    fn active(&self, band: PackBand) -> Option<&[u8]> {
+        let (_, id) = self.active.get(&band)?;
+        self.by_id.get(id).map(Vec::as_slice)
+    }
+}
+
+/// Compress `data`, returning the stored bytes and their codec flags.
+/// Data that does not shrink is stored raw.
+pub(crate) fn encode(data: &[u8], compression: Compression, dict: Option<&[u8]>) -> io::Result<(Vec<u8>, u8)> {
+    let (stored, flags) = match compression {
+        Compression::None => return Ok((data.to_vec(), 0)),
+        Compression::Zstd { level } => match dict {
+            Some(dict) => (zstd::bulk::Compressor::with_dictionary(level, dict)?.compress(data)?, FLAG_ZSTD | FLAG_DICT),
+            None => (zstd::bulk::compress(data, level)?, FLAG_ZSTD),
+        },
+        Compression::Lz4 => (lz4_flex::block::compress(data), FLAG_LZ4),
+    };
+
+    if stored.len() < data.len() {
+        Ok((stored, flags))
+    } else {
+        Ok((data.to_vec(), 0))
+    }
+}
+
+/// Inverse of [`encode`]
+pub(crate) fn decode(stored: Vec<u8>, flags: u8, raw_len: usize, dicts: &Dictionaries) -> io::Result<Vec<u8>> {
+    match flags & FLAG_CODEC_MASK {
+        0 => Ok(stored),
+        FLAG_ZSTD if flags & FLAG_DICT != 0 => {
+            let dict = zstd::zstd_safe::get_dict_id_from_frame(&stored)
+                .and_then(|id| dicts.by_id.get(&id.get()))
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "zstd dictionary missing"))?;
+            zstd::bulk::Decompressor::with_dictionary(dict)?.decompress(&stored, raw_len)
+        }
+        FLAG_ZSTD => zstd::bulk::decompress(&stored, raw_len),
+        FLAG_LZ4 => lz4_flex::block::decompress(&stored, raw_len)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, "unknown compression flags")),
+    }
+}
+
+impl PackCAS {
+    /// Compression used for new objects in `band`
+    pub fn compression_for(&self, band: PackBand) -> Compression {
+        self.config.band_compression.get(&band).copied().unwrap_or(self.config.compression)
+    }
+
+    /// Compress an object for storage in `band`
+    pub(crate) fn encode_object(&self, data: &[u8], band: PackBand) -> io::Result<(Vec<u8>, u8)> {
+        encode(data, self.compression_for(band), self.dictionaries.active(band))
+    }
+
+    /// Per-band object counts and raw vs. stored sizes
+    pub fn compression_stats(&self) -> HashMap<PackBand, BandStats> {
+        let mut stats: HashMap<PackBand, BandStats> = HashMap::new();
+        for record in self.cidx_index.values() {
+            let band = band_from_u8(record.band).unwrap_or(PackBand::Small);
+            let entry = stats.entry(band).or_default();
+            entry.objects += 1;
+            entry.raw_bytes += record.raw_len() as u64;
+            entry.stored_bytes += record.len as u64;
+            if record.flags & FLAG_CODEC_MASK != 0 {
+                entry.compressed_objects += 1;
+            }
+        }
+        stats
+    }
+
+    /// Train a zstd dictionary from objects already stored in `band` and use
+    /// it for subsequent writes to that band. Returns the dictionary ID.
+    pub async fn train_dictionary(&mut self, band: PackBand, max_size: usize) -> io::Result<u32> {
+        let band_id = band_to_u8(band);
+        let cids: Vec<Cid> = self.cidx_index.iter()
+            .filter(|(_, record)| record.band == band_id)
+            .map(|(cid, _)| *cid)
+            .collect();
+
+        let mut samples = Vec::new();
+        let mut sampled = 0;
+        for cid in cids {
+            if sampled >= DICT_SAMPLE_BYTES {
+                break;
+            }
+            let data = self.get(&cid).await?;
+            sampled += data.len();
+            samples.push(data);
+        }
+
+        let dict = zstd::dict::from_samples(&samples, max_size)?;
+        let id = zstd::zstd_safe::get_dict_id_from_dict(&dict)
+            .ok_or_else(|| io::Error::other("trained dictionary has no ID"))?
+            .get();
+
+        let seq = self.dictionaries.active.get(&band).map_or(0, |(seq, _)| seq + 1);
+        let path = self.base_path.join(format!("zdict_{}_{:04}.dat", band_id, seq));
+        let tmp_path = path.with_extension("tmp");
+        {
+            use std::io::Write;
+            let mut file = std::fs::File::create(&tmp_path)?;
+            file.write_all(&dict)?;
+            file.sync_all()?;
+        }
+        std::fs::rename(tmp_path, path)?;
+
+        info!("Trained {:?} dictionary {} ({} bytes from {} samples)", band, id, dict.len(), samples.len());
+        self.dictionaries.by_id.insert(id, dict);
+        self.dictionaries.active.insert(band, (seq, id));
+        Ok(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CasConfig;
+    use tempfile::tempdir;
+
+    fn document(i: usize) -> Vec<u8> {
+        format!(
+            r#"{{"id":{},"type":"person","name":"user {}","email":"user{}@example.com","tags":["graph","cas","temporal"]}}"#,
+            i, i, i
+        ).into_bytes()
+    }
+
+    #[tokio::test]
+    async fn test_codecs_roundtrip_and_keep_cids() {
+        for compression in [Compression::Zstd { level: 3 }, Compression::Lz4] {
+            let temp_dir = tempdir().unwrap();
+            let data = b"compressible ".repeat(64);
+            let config = CasConfig { compression, ..CasConfig::default() };
+
+            let cid = {
+                let mut cas = PackCAS::open_with_config(temp_dir.path(), config).await.unwrap();
+                let cid = cas.put(&data, 1, PackBand::Blob).await.unwrap();
+                assert_eq!(cid, Cid::hash(&data));
+
+                let record = *cas.record(&cid).unwrap();
+                assert_ne!(record.flags & FLAG_CODEC_MASK, 0);
+                assert!((record.len as usize) < data.len());
+                assert_eq!(record.raw_len() as usize, data.len());
+
+                // Incompressible data is stored raw
+                let tiny = cas.put(b"x", 1, PackBand::Blob).await.unwrap();
+                assert_eq!(cas.record(&tiny).unwrap().flags, 0);
+                cid
+            };
+
+            // Compressed objects stay readable without compression configured
+            let cas = PackCAS::open(temp_dir.path()).await.unwrap();
+            assert_eq!(cas.get(&cid).await.unwrap(), data);
+
+            let stats = cas.compression_stats();
+            let blob = &stats[&PackBand::Blob];
+            assert_eq!(blob.objects, 2);
+            assert_eq!(blob.compressed_objects, 1);
+            assert!(blob.ratio() > 1.0);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_band_dictionary() {
+        let temp_dir = tempdir().unwrap();
+        let config = CasConfig {
+            compression: Compression::None,
+            band_compression: [(PackBand::Small, Compression::Zstd { level: 3 })].into_iter().collect(),
+            ..CasConfig::default()
+        };
+
+        let cid = {
+            let mut cas = PackCAS::open_with_config(temp_dir.path(), config).await.unwrap();
+            for i in 0..200 {
+                cas.put(&document(i), 1, PackBand::Small).await.unwrap();
+            }
+            cas.train_dictionary(PackBand::Small, 4096).await.unwrap();
+
+            let cid = cas.put(&document(1000), 1, PackBand::Small).await.unwrap();
+            assert_ne!(cas.record(&cid).unwrap().flags & FLAG_DICT, 0);
+            assert_eq!(cas.compression_for(PackBand::Blob), Compression::None);
+            cid
+        };
+
+        let cas = PackCAS::open(temp_dir.path()).await.unwrap();
+        assert_eq!(cas.get(&cid).await.unwrap(), document(1000));
+        assert!(cas.compression_stats()[&PackBand::Small].compressed_objects >= 1);
+    }
+}
@@ -0,0 +1,269 @@
+//! Garbage collection and pack compaction.
+//!
+//! Merkle DAG: enishi_cas -> gc -> {prepare_compaction, apply_compaction}
+//!
+//! PackCAS does not know which objects are reachable; callers (e.g. GraphDB)
+//! mark the live set and PackCAS sweeps: sealed packs whose live ratio falls
+//! below a threshold are rewritten into a fresh pack and unreachable objects
+//! in them are dropped from the cidx. The copy phase only needs shared access,
+//! so readers keep running; the swap phase is a short exclusive step.
+
+use crate::{BloomFilters, CidxRec, PackBand, PackCAS, PackMeta};
+use fcdb_core::Cid;
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+/// Temporary name of a pack being written by compaction
+const COMPACT_TMP_FILE: &str = "compact.tmp";
+
+/// Default live-bytes ratio below which a pack is rewritten
+pub const DEFAULT_LIVE_RATIO_THRESHOLD: f64 = 0.5;
+
+/// Outcome of a GC run
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GcReport {
+    pub packs_rewritten: usize,
+    pub objects_moved: usize,
+    pub objects_removed: usize,
+    pub bytes_reclaimed: u64,
+}
+
+/// Compaction whose live objects have already been copied to a new pack
+pub struct Compaction {
+    tmp_path: PathBuf,
+    /// Records of moved objects; offsets refer to the new pack
+    moved: Vec<CidxRec>,
+    removed: Vec<Cid>,
+    /// (pack id, size) of every pack being replaced
+    source_packs: Vec<(u32, u64)>,
+    new_pack_size: u64,
+}
+
+impl Compaction {
+    /// Whether applying this compaction would change anything
+    pub fn is_empty(&self) -> bool {
+        self.source_packs.is_empty()
+    }
+}
+
+impl PackCAS {
+    /// Stop appending to the current pack so it becomes eligible for compaction.
+    ///
+    /// This also checkpoints the WAL, so logged puts of soon-to-be-dropped
+    /// objects are not replayed later; higher-layer records must already be
+    /// checkpointed by their owner.
+    pub async fn seal_current_pack(&mut self) -> io::Result<()> {
+        self.checkpoint_wal()?;
+        self.close_current_pack().await
+    }
+
+    /// Copy live objects out of sealed packs whose live ratio is below
+    /// `threshold` (0.0..=1.0). Only needs shared access.
+    ///
+    /// Writers that might re-reference an object outside `live` must be held
+    /// off until [`PackCAS::apply_compaction`] returns, since `put` dedups
+    /// against objects that compaction is about to drop.
+    pub async fn prepare_compaction(&self, live: &HashSet<Cid>, threshold: f64) -> io::Result<Compaction> {
+        let current_pack = self.current_pack.as_ref().map(|writer| writer.pack_id);
+
+        let mut by_pack: HashMap<u32, Vec<&CidxRec>> = HashMap::new();
+        for record in self.cidx_index.values() {
+            by_pack.entry(record.pack_id).or_default().push(record);
+        }
+
+        let mut compaction = Compaction {
+            tmp_path: self.base_path.join(COMPACT_TMP_FILE),
+            moved: Vec::new(),
+            removed: Vec::new(),
+            source_packs: Vec::new(),
+            new_pack_size: 0,
+        };
+
+        let mut candidates: Vec<(u32, u64)> = self.packs.values()
+            .filter(|meta| Some(meta.id) != current_pack && meta.size > 0)
+            .filter(|meta| {
+                let live_bytes: u64 = by_pack.get(&meta.id).into_iter().flatten()
+                    .filter(|record| live.contains(&Cid::from_bytes(record.cid)))
+                    .map(|record| record.len as u64)
+                    .sum();
+                (live_bytes as f64) < threshold * meta.size as f64
+            })
+            .map(|meta| (meta.id, meta.size))
+            .collect();
+        candidates.sort();
+
+        if candidates.is_empty() {
+            return Ok(compaction);
+        }
+
+        let mut out = File::create(&compaction.tmp_path)?;
+        for &(pack_id, _) in &candidates {
+            let mut records = by_pack.remove(&pack_id).unwrap_or_default();
+            records.sort_by_key(|record| record.offset);
+
+            let pack_path = self.base_path.join(format!("pack_{:08}.dat", pack_id));
+            let mut file = File::open(pack_path)?;
+
+            for record in records {
+                let cid = Cid::from_bytes(record.cid);
+                if !live.contains(&cid) {
+                    compaction.removed.push(cid);
+                    continue;
+                }
+
+                // Stored bytes are copied as-is; decoding is only for verification
+                let mut stored = vec![0u8; record.len as usize];
+                file.seek(SeekFrom::Start(record.offset))?;
+                file.read_exact(&mut stored)?;
+                if Cid::hash(&self.decode_object(record, stored.clone())?) != cid {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "content hash mismatch during compaction"));
+                }
+
+                out.write_all(&stored)?;
+                compaction.moved.push(CidxRec { offset: compaction.new_pack_size, ..*record });
+                compaction.new_pack_size += record.len as u64;
+            }
+        }
+        out.sync_all()?;
+        compaction.source_packs = candidates;
+
+        Ok(compaction)
+    }
+
+    /// Swap a prepared compaction in: install the new pack, atomically rewrite
+    /// the cidx, rebuild bloom filters and delete the replaced packs
+    pub fn apply_compaction(&mut self, compaction: Compaction) -> io::Result<GcReport> {
+        if compaction.is_empty() {
+            return Ok(GcReport::default());
+        }
+
+        let new_pack_id = if compaction.moved.is_empty() {
+            std::fs::remove_file(&compaction.tmp_path)?;
+            None
+        } else {
+            let pack_id = self.next_pack_id;
+            self.next_pack_id += 1;
+            std::fs::rename(&compaction.tmp_path, self.base_path.join(format!("pack_{:08}.dat", pack_id)))?;
+            self.packs.insert(pack_id, PackMeta {
+                id: pack_id,
+                band: PackBand::Blob,
+                size: compaction.new_pack_size,
+                object_count: compaction.moved.len() as u64,
+                created_at: std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+            });
+            Some(pack_id)
+        };
+
+        for cid in &compaction.removed {
+            self.cidx_index.remove(cid);
+        }
+        if let Some(pack_id) = new_pack_id {
+            for moved in &compaction.moved {
+                let mut record = CidxRec { pack_id, ..*moved };
+                record.crc = record.compute_crc();
+                self.cidx_index.insert(Cid::from_bytes(record.cid), record);
+            }
+        }
+
+        self.rewrite_cidx()?;
+        self.rebuild_bloom_filters();
+
+        let mut reclaimed = 0;
+        for &(pack_id, size) in &compaction.source_packs {
+            let pack_path = self.base_path.join(format!("pack_{:08}.dat", pack_id));
+            if let Err(e) = std::fs::remove_file(&pack_path) {
+                warn!("Failed to delete compacted pack {}: {}", pack_id, e);
+            }
+            self.packs.remove(&pack_id);
+            reclaimed += size;
+        }
+
+        let report = GcReport {
+            packs_rewritten: compaction.source_packs.len(),
+            objects_moved: compaction.moved.len(),
+            objects_removed: compaction.removed.len(),
+            bytes_reclaimed: reclaimed.saturating_sub(compaction.new_pack_size),
+        };
+        info!("Compaction finished: {:?}", report);
+        Ok(report)
+    }
+
+    /// Seal, prepare and apply a compaction in one exclusive call
+    pub async fn collect_garbage(&mut self, live: &HashSet<Cid>, threshold: f64) -> io::Result<GcReport> {
+        self.seal_current_pack().await?;
+        let compaction = self.prepare_compaction(live, threshold).await?;
+        self.apply_compaction(compaction)
+    }
+
+    /// Replace the cidx file with exactly the records in the in-memory index
+    fn rewrite_cidx(&mut self) -> io::Result<()> {
+        let cidx_path = self.base_path.join("cidx.dat");
+        let tmp_path = self.base_path.join("cidx.dat.tmp");
+        {
+            let mut file = File::create(&tmp_path)?;
+            for record in self.cidx_index.values() {
+                file.write_all(record.as_bytes())?;
+            }
+            file.sync_all()?;
+        }
+        std::fs::rename(&tmp_path, &cidx_path)?;
+
+        self.cidx_file = OpenOptions::new().read(true).write(true).open(cidx_path)?;
+        Ok(())
+    }
+
+    fn rebuild_bloom_filters(&mut self) {
+        let mut filters = BloomFilters::new();
+        for record in self.cidx_index.values() {
+            let type_part = (record.kind as u16) << 8;
+            filters.insert(&Cid::from_bytes(record.cid), record.pack_id, type_part, 0);
+        }
+        self.bloom_filters = filters;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_compaction_drops_unreachable_objects() {
+        let temp_dir = tempdir().unwrap();
+        let (live_cid, dead_cid) = {
+            let mut cas = PackCAS::open(temp_dir.path()).await.unwrap();
+            let live_cid = cas.put(b"live object", 1, PackBand::Small).await.unwrap();
+            let dead_cid = cas.put(b"dead object", 1, PackBand::Small).await.unwrap();
+            cas.put(b"another dead object", 1, PackBand::Small).await.unwrap();
+
+            let live: HashSet<Cid> = [live_cid].into_iter().collect();
+            let report = cas.collect_garbage(&live, DEFAULT_LIVE_RATIO_THRESHOLD).await.unwrap();
+            assert_eq!(report.packs_rewritten, 1);
+            assert_eq!(report.objects_moved, 1);
+            assert_eq!(report.objects_removed, 2);
+            assert!(report.bytes_reclaimed > 0);
+
+            assert_eq!(cas.get(&live_cid).await.unwrap(), b"live object");
+            assert!(!cas.has(&dead_cid));
+
+            // Nothing left to reclaim on a second pass
+            let report = cas.collect_garbage(&live, DEFAULT_LIVE_RATIO_THRESHOLD).await.unwrap();
+            assert_eq!(report, GcReport::default());
+
+            // New writes go to a fresh pack
+            cas.put(b"after gc", 1, PackBand::Small).await.unwrap();
+            (live_cid, dead_cid)
+        };
+
+        let cas = PackCAS::open(temp_dir.path()).await.unwrap();
+        assert_eq!(cas.get(&live_cid).await.unwrap(), b"live object");
+        assert!(!cas.has(&dead_cid));
+        assert_eq!(cas.get(&Cid::hash(b"after gc")).await.unwrap(), b"after gc");
+    }
+}
@@ -2,7 +2,17 @@
 //!
 //! PackCAS implementation with cidx indexing and bloom filters.
 //!
-//! Merkle DAG: enishi_cas -> pack_cas, cidx, bloom_filters, wal, gc
+//! Merkle DAG: enishi_cas -> pack_cas, cidx, bloom_filters, wal, gc, compress, quota
+
+pub mod compress;
+pub mod gc;
+pub mod quota;
+pub mod wal;
+
+pub use compress::{BandStats, Compression};
+pub use gc::{Compaction, GcReport, DEFAULT_LIVE_RATIO_THRESHOLD};
+pub use quota::{Charge, QuotaExceeded, QuotaScope, Usage};
+pub use wal::{Wal, WalRecord};
 
 use fcdb_core::{Cid, varint};
 use std::collections::HashMap;
@@ -19,7 +29,7 @@ const PACK_SIZE_TARGET: u64 = 256 * 1024 * 1024; // 256 MiB
 const PACK_SIZE_MAX: u64 = 512 * 1024 * 1024;    // 512 MiB
 
 /// Temperature bands for pack organization
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum PackBand {
     Small,  // Small objects (< 4KB)
     Index,  // Index structures
@@ -45,42 +55,74 @@ pub struct CidxRec {
     pub offset: u64,        // Offset in pack
     pub len: u32,           // Object length
     pub kind: u8,           // Object kind/type
-    pub flags: u8,          // Flags
+    pub flags: u8,          // Flags (compression codec)
     pub crc: u32,           // CRC32 checksum
-    pub _pad: [u8; 10],     // Padding to 64B
+    pub raw_len: u32,       // Uncompressed length (0 = same as len)
+    pub band: u8,           // PackBand the object was written to
+    pub _pad: [u8; 5],      // Padding
 }
 
 impl CidxRec {
     /// Create a new cidx record
     pub fn new(cid: Cid, pack_id: u32, offset: u64, len: u32, kind: u8, flags: u8) -> Self {
-        let mut crc = Crc32::new();
-        crc.update(cid.as_bytes());
-        crc.update(&pack_id.to_le_bytes());
-        crc.update(&offset.to_le_bytes());
-        crc.update(&len.to_le_bytes());
-        crc.update(&[kind, flags]);
-
-        Self {
+        let mut record = Self {
             cid: *cid.as_bytes(),
             pack_id,
             offset,
             len,
             kind,
             flags,
-            crc: crc.finalize(),
-            _pad: [0; 10],
-        }
+            crc: 0,
+            raw_len: 0,
+            band: 0,
+            _pad: [0; 5],
+        };
+        record.crc = record.compute_crc();
+        record
+    }
+
+    /// Set the uncompressed length and band, re-sealing the CRC
+    pub fn with_origin(mut self, raw_len: u32, band: PackBand) -> Self {
+        self.raw_len = raw_len;
+        self.band = wal::band_to_u8(band);
+        self.crc = self.compute_crc();
+        self
+    }
+
+    /// Uncompressed object length
+    pub fn raw_len(&self) -> u32 {
+        if self.raw_len == 0 { self.len } else { self.raw_len }
     }
 
     /// Verify CRC
     pub fn verify_crc(&self) -> bool {
+        self.compute_crc() == self.crc
+    }
+
+    // raw_len/band are only hashed when set, so records written before
+    // they existed still verify
+    fn compute_crc(&self) -> u32 {
         let mut crc = Crc32::new();
         crc.update(&self.cid);
         crc.update(&self.pack_id.to_le_bytes());
         crc.update(&self.offset.to_le_bytes());
         crc.update(&self.len.to_le_bytes());
         crc.update(&[self.kind, self.flags]);
-        crc.finalize() == self.crc
+        if self.raw_len != 0 || self.band != 0 {
+            crc.update(&self.raw_len.to_le_bytes());
+            crc.update(&[self.band]);
+        }
+        crc.finalize()
+    }
+
+    /// On-disk byte representation
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            std::slice::from_raw_parts(
+                self as *const CidxRec as *const u8,
+                std::mem::size_of::<CidxRec>(),
+            )
+        }
     }
 }
 
@@ -161,19 +203,46 @@ impl BloomFilters {
     }
 }
 
+/// PackCAS open-time configuration
+#[derive(Clone, Debug, Default)]
+pub struct CasConfig {
+    /// fsync the WAL on every commit (otherwise commits only reach the OS)
+    pub sync_writes: bool,
+    /// Compression for new objects (existing objects are read regardless)
+    pub compression: Compression,
+    /// Per-band overrides of `compression`
+    pub band_compression: HashMap<PackBand, Compression>,
+    /// Size of all packs beyond which metered writes are refused
+    pub max_bytes: Option<u64>,
+    /// Bytes each actor may add through metered writes
+    pub actor_quota_bytes: Option<u64>,
+}
+
 /// PackCAS - Content Addressable Storage with pack files
 pub struct PackCAS {
     base_path: PathBuf,
     current_pack: Option<PackWriter>,
     packs: HashMap<u32, PackMeta>,
     cidx_file: File,
+    cidx_index: HashMap<Cid, CidxRec>,
     bloom_filters: BloomFilters,
     next_pack_id: u32,
+    wal: Wal,
+    recovered_ops: HashMap<u32, Vec<(u64, Vec<u8>)>>,
+    config: CasConfig,
+    dictionaries: compress::Dictionaries,
+    quotas: quota::Quotas,
 }
 
 impl PackCAS {
     /// Open or create a PackCAS instance
     pub async fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::open_with_config(path, CasConfig::default()).await
+    }
+
+    /// Open or create a PackCAS instance, repairing torn pack/cidx tails and
+    /// replaying the WAL
+    pub async fn open_with_config<P: AsRef<Path>>(path: P, config: CasConfig) -> io::Result<Self> {
         let base_path = path.as_ref().to_path_buf();
         std::fs::create_dir_all(&base_path)?;
 
@@ -184,51 +253,73 @@ impl PackCAS {
             .create(true)
             .open(cidx_path)?;
 
+        let (wal, wal_records) = Wal::open(&base_path, config.sync_writes)?;
+        let dictionaries = compress::Dictionaries::load(&base_path)?;
+        let quotas = quota::Quotas::load(&base_path, config.max_bytes, config.actor_quota_bytes)?;
+
         let mut cas = Self {
             base_path,
             current_pack: None,
             packs: HashMap::new(),
             cidx_file,
+            cidx_index: HashMap::new(),
             bloom_filters: BloomFilters::new(),
             next_pack_id: 0,
+            wal,
+            recovered_ops: HashMap::new(),
+            config,
+            dictionaries,
+            quotas,
         };
 
         cas.load_existing_packs().await?;
         cas.load_cidx().await?;
+        cas.truncate_pack_tails()?;
+        cas.replay_wal(wal_records).await?;
 
         Ok(cas)
     }
 
-    /// Load existing pack metadata
+    /// Load existing pack metadata (pack ids may have gaps after compaction)
     async fn load_existing_packs(&mut self) -> io::Result<()> {
-        let mut pack_id = 0;
-        loop {
-            let pack_path = self.base_path.join(format!("pack_{:08}.dat", pack_id));
-            if !pack_path.exists() {
-                break;
-            }
+        for entry in std::fs::read_dir(&self.base_path)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let Some(pack_id) = name.to_str()
+                .and_then(|name| name.strip_prefix("pack_"))
+                .and_then(|name| name.strip_suffix(".dat"))
+                .and_then(|id| id.parse::<u32>().ok())
+            else {
+                continue;
+            };
 
             // Load pack metadata (simplified - in real impl, read from manifest)
             let meta = PackMeta {
                 id: pack_id,
                 band: PackBand::Blob, // Default
-                size: std::fs::metadata(&pack_path)?.len(),
+                size: entry.metadata()?.len(),
                 object_count: 0, // Would be loaded from manifest
                 created_at: 0,
             };
 
             self.packs.insert(pack_id, meta);
-            pack_id += 1;
+            self.next_pack_id = self.next_pack_id.max(pack_id + 1);
         }
-        self.next_pack_id = pack_id;
 
         Ok(())
     }
 
-    /// Load content index
+    /// Load content index and rebuild the in-memory CID -> record map
     async fn load_cidx(&mut self) -> io::Result<()> {
         let file_size = self.cidx_file.metadata()?.len();
         let record_count = file_size / std::mem::size_of::<CidxRec>() as u64;
+        if record_count == 0 {
+            if file_size > 0 {
+                warn!("Truncating torn cidx tail: {} bytes", file_size);
+                self.cidx_file.set_len(0)?;
+            }
+            return Ok(());
+        }
 
         // Memory map the cidx file for fast access
         let mmap = unsafe { Mmap::map(&self.cidx_file)? };
@@ -239,34 +330,232 @@ impl PackCAS {
             )
         };
 
-        // Rebuild bloom filters from cidx
-        for record in records {
+        // Rebuild the index and bloom filters from cidx (later records win).
+        // Records pointing past the end of their pack were torn by a crash.
+        let mut valid_records = 0;
+        for (i, record) in records.iter().enumerate() {
             if !record.verify_crc() {
                 warn!("Cidx record CRC mismatch, skipping");
                 continue;
             }
 
+            let in_bounds = self.packs.get(&record.pack_id)
+                .is_some_and(|meta| record.offset + record.len as u64 <= meta.size);
+            if !in_bounds {
+                warn!("Cidx record points past the end of pack {}, skipping", record.pack_id);
+                continue;
+            }
+
             let cid = Cid::from_bytes(record.cid);
             let pack_id = record.pack_id;
             let type_part = (record.kind as u16) << 8; // Simplified type extraction
             let time_bucket = 0; // Would be derived from metadata
 
+            if let Some(meta) = self.packs.get_mut(&pack_id) {
+                meta.object_count += 1;
+            }
+            self.cidx_index.insert(cid, *record);
             self.bloom_filters.insert(&cid, pack_id, type_part, time_bucket);
+            valid_records = i + 1;
+        }
+        drop(mmap);
+
+        // Cut off a torn tail (partial or invalid trailing records)
+        let valid_len = (valid_records * std::mem::size_of::<CidxRec>()) as u64;
+        if valid_len < file_size {
+            warn!("Truncating torn cidx tail: {} bytes", file_size - valid_len);
+            self.cidx_file.set_len(valid_len)?;
+        }
+
+        info!("Loaded {} cidx records", self.cidx_index.len());
+        Ok(())
+    }
+
+    /// Truncate pack bytes not covered by any cidx record (torn appends)
+    fn truncate_pack_tails(&mut self) -> io::Result<()> {
+        let mut ends: HashMap<u32, u64> = HashMap::new();
+        for record in self.cidx_index.values() {
+            let end = ends.entry(record.pack_id).or_insert(0);
+            *end = (*end).max(record.offset + record.len as u64);
+        }
+
+        // Packs with no indexed objects are leftovers of an interrupted
+        // compaction (or an empty writer); drop them entirely
+        let orphans: Vec<u32> = self.packs.keys()
+            .filter(|id| !ends.contains_key(id))
+            .copied()
+            .collect();
+        for pack_id in orphans {
+            warn!("Removing unreferenced pack {}", pack_id);
+            std::fs::remove_file(self.base_path.join(format!("pack_{:08}.dat", pack_id)))?;
+            self.packs.remove(&pack_id);
+        }
+
+        for meta in self.packs.values_mut() {
+            let end = ends.get(&meta.id).copied().unwrap_or(0);
+            if meta.size > end {
+                warn!("Truncating torn tail of pack {}: {} bytes", meta.id, meta.size - end);
+                let pack_path = self.base_path.join(format!("pack_{:08}.dat", meta.id));
+                OpenOptions::new().write(true).open(pack_path)?.set_len(end)?;
+                meta.size = end;
+            }
+        }
+        Ok(())
+    }
+
+    /// Redo logged puts missing from the packs and keep higher-layer records
+    /// for [`PackCAS::take_recovered_ops`]
+    async fn replay_wal(&mut self, records: Vec<(u64, WalRecord)>) -> io::Result<()> {
+        let mut redone = 0;
+        for (lsn, record) in records {
+            match record {
+                WalRecord::Put { kind, band, data } => {
+                    let cid = Cid::hash(&data);
+                    if !self.has(&cid) {
+                        self.write_object(cid, &data, kind, band).await?;
+                        redone += 1;
+                    }
+                }
+                WalRecord::Graph(payload) => self.recovered_ops.entry(0).or_default().push((lsn, payload)),
+                WalRecord::NamespaceGraph { namespace, payload } => {
+                    self.recovered_ops.entry(namespace).or_default().push((lsn, payload))
+                }
+            }
         }
 
-        info!("Loaded {} cidx records", record_count);
+        if redone > 0 {
+            self.sync()?;
+            info!("Replayed {} objects from the WAL", redone);
+        }
         Ok(())
     }
 
-    /// Store data and return CID
+    /// Store data and return CID; the write is committed to the WAL before returning
     pub async fn put(&mut self, data: &[u8], kind: u8, band: PackBand) -> io::Result<Cid> {
+        let cid = self.put_uncommitted(data, kind, band).await?;
+        self.commit()?;
+        Ok(cid)
+    }
+
+    /// Store data, leaving its WAL record buffered until the next [`PackCAS::commit`]
+    /// so several writes can share one group commit
+    pub async fn put_uncommitted(&mut self, data: &[u8], kind: u8, band: PackBand) -> io::Result<Cid> {
         let cid = Cid::hash(data);
 
         // Check if already exists
-        if self.bloom_filters.contains(&cid, None, None) {
-            // Could do a full lookup here, but for now assume it's there
+        if self.has(&cid) {
+            return Ok(cid);
+        }
+
+        self.wal.append(&WalRecord::Put { kind, band, data: data.to_vec() });
+        self.write_object(cid, data, kind, band).await?;
+
+        Ok(cid)
+    }
+
+    /// [`PackCAS::put_uncommitted`] metered against the quotas of `charge`;
+    /// a refused write fails with an error carrying [`QuotaExceeded`]
+    pub async fn put_charged(&mut self, charge: Charge<'_>, data: &[u8], kind: u8, band: PackBand) -> io::Result<Cid> {
+        let cid = Cid::hash(data);
+        if self.has(&cid) {
             return Ok(cid);
         }
+        self.quotas.check(charge, data.len() as u64, self.stored_bytes())
+            .map_err(io::Error::other)?;
+        self.put_uncommitted(data, kind, band).await?;
+        self.quotas.charge(charge, data.len() as u64);
+        Ok(cid)
+    }
+
+    /// Total size of every pack
+    pub fn stored_bytes(&self) -> u64 {
+        self.packs.values().map(|meta| meta.size).sum()
+    }
+
+    /// Limit the bytes `namespace` may reference; `None` lifts the limit
+    pub fn set_namespace_quota(&mut self, namespace: u32, limit: Option<u64>) {
+        self.quotas.set_namespace_limit(namespace, limit);
+    }
+
+    pub fn namespace_quota(&self, namespace: u32) -> Option<u64> {
+        self.quotas.namespace_limit(namespace)
+    }
+
+    /// Replace the usage charged to `namespace`, e.g. with the bytes a
+    /// collection found it still references; `None` forgets the namespace
+    pub fn set_namespace_usage(&mut self, namespace: u32, bytes: Option<u64>) {
+        self.quotas.set_namespace_usage(namespace, bytes);
+    }
+
+    pub fn namespace_usage(&self, namespace: u32) -> u64 {
+        self.quotas.namespace_usage(namespace)
+    }
+
+    /// Bytes charged to every namespace and actor
+    pub fn usage(&self) -> &Usage {
+        self.quotas.usage()
+    }
+
+    /// Persist the usage counters; done by every WAL checkpoint
+    pub fn save_usage(&mut self) -> io::Result<()> {
+        self.quotas.save()
+    }
+
+    /// Log a higher-layer mutation record, returning its LSN
+    pub fn log_op(&mut self, payload: &[u8]) -> u64 {
+        self.log_op_in(0, payload)
+    }
+
+    /// [`PackCAS::log_op`] for one of several namespaces sharing the store;
+    /// namespace 0 is the one `log_op` writes to
+    pub fn log_op_in(&mut self, namespace: u32, payload: &[u8]) -> u64 {
+        let record = match namespace {
+            0 => WalRecord::Graph(payload.to_vec()),
+            namespace => WalRecord::NamespaceGraph { namespace, payload: payload.to_vec() },
+        };
+        self.wal.append(&record)
+    }
+
+    /// Make every buffered WAL record durable (group commit)
+    pub fn commit(&mut self) -> io::Result<()> {
+        self.wal.commit()
+    }
+
+    /// Higher-layer records found in the WAL on open, with their LSNs
+    pub fn take_recovered_ops(&mut self) -> Vec<(u64, Vec<u8>)> {
+        self.take_recovered_ops_in(0)
+    }
+
+    /// [`PackCAS::take_recovered_ops`] of one namespace
+    pub fn take_recovered_ops_in(&mut self, namespace: u32) -> Vec<(u64, Vec<u8>)> {
+        self.recovered_ops.remove(&namespace).unwrap_or_default()
+    }
+
+    /// LSN the next WAL record will receive
+    pub fn next_lsn(&self) -> u64 {
+        self.wal.next_lsn()
+    }
+
+    /// fsync pack and cidx data written so far
+    pub fn sync(&mut self) -> io::Result<()> {
+        if let Some(writer) = &mut self.current_pack {
+            writer.file.sync_data()?;
+        }
+        self.cidx_file.sync_data()
+    }
+
+    /// Make packs durable and discard the WAL; callers must have persisted
+    /// anything they need from their own logged records first
+    pub fn checkpoint_wal(&mut self) -> io::Result<()> {
+        self.wal.commit()?;
+        self.sync()?;
+        self.quotas.save()?;
+        self.wal.reset()
+    }
+
+    /// Append an object to the current pack and index it (no WAL record)
+    async fn write_object(&mut self, cid: Cid, data: &[u8], kind: u8, band: PackBand) -> io::Result<()> {
+        let (stored, flags) = self.encode_object(data, band)?;
 
         // Ensure we have a pack writer
         self.ensure_pack_writer(band).await?;
@@ -274,15 +563,16 @@ impl PackCAS {
         let (offset, pack_id) = if let Some(writer) = &mut self.current_pack {
             let offset = writer.current_offset;
             let pack_id = writer.pack_id;
-            writer.file.write_all(data)?;
-            writer.current_offset += data.len() as u64;
+            writer.file.write_all(&stored)?;
+            writer.current_offset += stored.len() as u64;
             (offset, pack_id)
         } else {
             return Err(io::Error::new(io::ErrorKind::Other, "No current pack writer"));
         };
 
         // Add to cidx
-        let record = CidxRec::new(cid, pack_id, offset, data.len() as u32, kind, 0);
+        let record = CidxRec::new(cid, pack_id, offset, stored.len() as u32, kind, flags)
+            .with_origin(data.len() as u32, band);
         self.append_cidx_record(&record).await?;
 
         // Update bloom filters
@@ -290,38 +580,68 @@ impl PackCAS {
         let time_bucket = 0; // Would be current time bucket
         self.bloom_filters.insert(&cid, pack_id, type_part, time_bucket);
 
+        if let Some(meta) = self.packs.get_mut(&pack_id) {
+            meta.size = offset + stored.len() as u64;
+            meta.object_count += 1;
+        }
+
         // Check if pack is full
-        if offset + data.len() as u64 >= PACK_SIZE_TARGET {
+        if offset + stored.len() as u64 >= PACK_SIZE_TARGET {
             self.close_current_pack().await?;
         }
 
-        Ok(cid)
+        Ok(())
+    }
+
+    /// Check whether a CID is stored (exact, not probabilistic)
+    pub fn has(&self, cid: &Cid) -> bool {
+        self.bloom_filters.contains(cid, None, None) && self.cidx_index.contains_key(cid)
     }
 
     /// Retrieve data by CID
     pub async fn get(&self, cid: &Cid) -> io::Result<Vec<u8>> {
-        // Use bloom filters to narrow search
+        // Use bloom filters to rule out absent CIDs without a map lookup
         if !self.bloom_filters.contains(cid, None, None) {
             return Err(io::Error::new(io::ErrorKind::NotFound, "CID not found"));
         }
 
-        // For now, do a linear search through packs
-        // In real implementation, would use cidx for direct lookup
-        for (pack_id, _meta) in &self.packs {
-            let pack_path = self.base_path.join(format!("pack_{:08}.dat", pack_id));
-            let mut file = File::open(pack_path)?;
+        let record = self.cidx_index.get(cid)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "CID not found"))?;
+
+        if !record.verify_crc() {
+            error!("Cidx record CRC mismatch for {:?}", cid);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "cidx record CRC mismatch"));
+        }
 
-            // This is highly inefficient - real impl would use cidx for direct access
-            let mut data = Vec::new();
-            file.read_to_end(&mut data)?;
+        let pack_path = self.base_path.join(format!("pack_{:08}.dat", record.pack_id));
+        let mut file = File::open(pack_path)?;
+        file.seek(SeekFrom::Start(record.offset))?;
 
-            // Check if this pack contains our data (simplified)
-            if data.len() > 32 && &data[..32] == cid.as_bytes() {
-                return Ok(data[32..].to_vec()); // Remove CID prefix if stored
-            }
+        let mut stored = vec![0u8; record.len as usize];
+        file.read_exact(&mut stored)?;
+        let data = self.decode_object(record, stored)?;
+
+        if Cid::hash(&data) != *cid {
+            error!("Content hash mismatch for {:?} in pack {}", cid, record.pack_id);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "content hash mismatch"));
         }
 
-        Err(io::Error::new(io::ErrorKind::NotFound, "CID not found"))
+        Ok(data)
+    }
+
+    /// Decompress the stored bytes of an object
+    pub(crate) fn decode_object(&self, record: &CidxRec, stored: Vec<u8>) -> io::Result<Vec<u8>> {
+        compress::decode(stored, record.flags, record.raw_len() as usize, &self.dictionaries)
+    }
+
+    /// Look up the cidx record for a CID
+    pub fn record(&self, cid: &Cid) -> Option<&CidxRec> {
+        self.cidx_index.get(cid)
+    }
+
+    /// Base directory holding packs and the cidx file
+    pub fn path(&self) -> &Path {
+        &self.base_path
     }
 
     /// Ensure we have an active pack writer
@@ -359,10 +679,13 @@ impl PackCAS {
         Ok(())
     }
 
-    /// Close current pack
+    /// Close current pack. It is fsynced here because [`PackCAS::sync`] only
+    /// reaches the pack still open, and a later checkpoint drops the WAL
+    /// records that could rebuild this one.
     async fn close_current_pack(&mut self) -> io::Result<()> {
         if let Some(mut writer) = self.current_pack.take() {
             writer.file.flush()?;
+            writer.file.sync_data()?;
             info!("Closed pack {}", writer.pack_id);
         }
         Ok(())
@@ -371,14 +694,10 @@ impl PackCAS {
     /// Append record to cidx file
     async fn append_cidx_record(&mut self, record: &CidxRec) -> io::Result<()> {
         self.cidx_file.seek(SeekFrom::End(0))?;
-        let bytes = unsafe {
-            std::slice::from_raw_parts(
-                record as *const CidxRec as *const u8,
-                std::mem::size_of::<CidxRec>(),
-            )
-        };
-        self.cidx_file.write_all(bytes)?;
+        self.cidx_file.write_all(record.as_bytes())?;
         self.cidx_file.flush()?;
+
+        self.cidx_index.insert(Cid::from_bytes(record.cid), *record);
         Ok(())
     }
 }
@@ -409,6 +728,208 @@ mod tests {
         assert_eq!(cid, Cid::hash(data));
     }
 
+    #[tokio::test]
+    async fn test_pack_cas_get_after_reopen() {
+        let temp_dir = tempdir().unwrap();
+
+        let (first, second) = {
+            let mut cas = PackCAS::open(temp_dir.path()).await.unwrap();
+            let first = cas.put(b"first object", 1, PackBand::Small).await.unwrap();
+            let second = cas.put(b"second object", 1, PackBand::Small).await.unwrap();
+            assert_eq!(cas.get(&second).await.unwrap(), b"second object");
+            (first, second)
+        };
+
+        let cas = PackCAS::open(temp_dir.path()).await.unwrap();
+        assert_eq!(cas.get(&first).await.unwrap(), b"first object");
+        assert_eq!(cas.get(&second).await.unwrap(), b"second object");
+        assert!(cas.get(&Cid::hash(b"missing")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pack_cas_has_and_hash_verification() {
+        let temp_dir = tempdir().unwrap();
+        let mut cas = PackCAS::open(temp_dir.path()).await.unwrap();
+
+        let cid = cas.put(b"verified object", 1, PackBand::Small).await.unwrap();
+        assert!(cas.has(&cid));
+        assert!(!cas.has(&Cid::hash(b"missing")));
+
+        // Re-putting the same content must not append a second copy
+        let before = cas.record(&cid).copied().unwrap();
+        cas.put(b"verified object", 1, PackBand::Small).await.unwrap();
+        assert_eq!(cas.record(&cid).unwrap().offset, before.offset);
+
+        // Flip a byte inside the stored object; get must detect it
+        let pack_path = temp_dir.path().join(format!("pack_{:08}.dat", before.pack_id));
+        let mut bytes = std::fs::read(&pack_path).unwrap();
+        bytes[before.offset as usize] ^= 0xff;
+        std::fs::write(&pack_path, bytes).unwrap();
+
+        let err = cas.get(&cid).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    /// Copy a CAS directory and truncate one of its files, simulating a crash
+    fn crashed_copy(src: &Path, file: &str, len: u64) -> tempfile::TempDir {
+        let dst = tempdir().unwrap();
+        for entry in std::fs::read_dir(src).unwrap() {
+            let entry = entry.unwrap();
+            std::fs::copy(entry.path(), dst.path().join(entry.file_name())).unwrap();
+        }
+        OpenOptions::new().write(true).open(dst.path().join(file)).unwrap().set_len(len).unwrap();
+        dst
+    }
+
+    #[tokio::test]
+    async fn test_pack_cas_recovers_torn_pack_and_cidx() {
+        let temp_dir = tempdir().unwrap();
+        let objects: Vec<&[u8]> = vec![b"alpha", b"bravo bravo", b"charlie charlie charlie"];
+        {
+            let mut cas = PackCAS::open(temp_dir.path()).await.unwrap();
+            for data in &objects {
+                cas.put(data, 1, PackBand::Small).await.unwrap();
+            }
+        }
+
+        // Every prefix of the pack or the cidx file is a possible crash point;
+        // the WAL still holds every committed put, so nothing may be lost
+        for file in ["pack_00000000.dat", "cidx.dat"] {
+            let full_len = std::fs::metadata(temp_dir.path().join(file)).unwrap().len();
+            for len in 0..full_len {
+                let crashed = crashed_copy(temp_dir.path(), file, len);
+                let cas = PackCAS::open(crashed.path()).await.unwrap();
+                for data in &objects {
+                    assert_eq!(cas.get(&Cid::hash(data)).await.unwrap(), *data, "{} cut at {}", file, len);
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pack_cas_recovers_across_pack_rollover() {
+        let temp_dir = tempdir().unwrap();
+        let sealed: Vec<&[u8]> = vec![b"first pack", b"first pack again"];
+        let open: Vec<&[u8]> = vec![b"second pack", b"second pack again"];
+        let synced_cidx_len = {
+            let mut cas = PackCAS::open(temp_dir.path()).await.unwrap();
+            for data in &sealed {
+                cas.put(data, 1, PackBand::Small).await.unwrap();
+            }
+            // Rolling over checkpoints the WAL, so the closed pack must be durable
+            cas.seal_current_pack().await.unwrap();
+            let synced_cidx_len = std::fs::metadata(temp_dir.path().join("cidx.dat")).unwrap().len();
+            for data in &open {
+                cas.put(data, 1, PackBand::Small).await.unwrap();
+            }
+            synced_cidx_len
+        };
+
+        // Only writes after the rollover can be torn; the WAL restores them
+        let pack_len = std::fs::metadata(temp_dir.path().join("pack_00000001.dat")).unwrap().len();
+        let cidx_len = std::fs::metadata(temp_dir.path().join("cidx.dat")).unwrap().len();
+        let cuts = (0..pack_len).map(|len| ("pack_00000001.dat", len))
+            .chain((synced_cidx_len..cidx_len).map(|len| ("cidx.dat", len)));
+        for (file, len) in cuts {
+            let crashed = crashed_copy(temp_dir.path(), file, len);
+            let cas = PackCAS::open(crashed.path()).await.unwrap();
+            for data in sealed.iter().chain(&open) {
+                assert_eq!(cas.get(&Cid::hash(data)).await.unwrap(), *data, "{} cut at {}", file, len);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pack_cas_replays_wal_prefix() {
+        let temp_dir = tempdir().unwrap();
+        let objects: Vec<&[u8]> = vec![b"one", b"two two", b"three three three"];
+        let mut wal_ends = Vec::new();
+        {
+            let mut cas = PackCAS::open(temp_dir.path()).await.unwrap();
+            for data in &objects {
+                cas.put(data, 1, PackBand::Small).await.unwrap();
+                wal_ends.push(std::fs::metadata(temp_dir.path().join(wal::WAL_FILE)).unwrap().len());
+            }
+        }
+
+        // Lose the pack writes entirely and cut the WAL at every byte
+        let wal_len = *wal_ends.last().unwrap();
+        for len in 16..=wal_len {
+            let crashed = crashed_copy(temp_dir.path(), wal::WAL_FILE, len);
+            OpenOptions::new().write(true).open(crashed.path().join("pack_00000000.dat")).unwrap().set_len(0).unwrap();
+            OpenOptions::new().write(true).open(crashed.path().join("cidx.dat")).unwrap().set_len(0).unwrap();
+
+            let mut cas = PackCAS::open(crashed.path()).await.unwrap();
+            for (data, end) in objects.iter().zip(&wal_ends) {
+                let cid = Cid::hash(data);
+                assert_eq!(cas.has(&cid), len >= *end, "WAL cut at {}", len);
+            }
+
+            // The repaired store keeps accepting writes and survives another reopen
+            let cid = cas.put(b"after recovery", 1, PackBand::Small).await.unwrap();
+            drop(cas);
+            let cas = PackCAS::open(crashed.path()).await.unwrap();
+            assert_eq!(cas.get(&cid).await.unwrap(), b"after recovery");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pack_cas_checkpoint_wal() {
+        let temp_dir = tempdir().unwrap();
+        let cid = {
+            let mut cas = PackCAS::open_with_config(temp_dir.path(), CasConfig { sync_writes: true, ..CasConfig::default() }).await.unwrap();
+            let cid = cas.put(b"checkpointed", 1, PackBand::Small).await.unwrap();
+            let lsn = cas.log_op(b"graph op");
+            cas.commit().unwrap();
+            assert_eq!(cas.next_lsn(), lsn + 1);
+            cas.checkpoint_wal().unwrap();
+            cas.log_op_in(4, b"namespaced op");
+            cas.commit().unwrap();
+            cid
+        };
+
+        let mut cas = PackCAS::open(temp_dir.path()).await.unwrap();
+        assert!(cas.take_recovered_ops().is_empty());
+        let namespaced = cas.take_recovered_ops_in(4);
+        assert_eq!(namespaced.iter().map(|(_, op)| op.as_slice()).collect::<Vec<_>>(), vec![b"namespaced op"]);
+        assert!(cas.take_recovered_ops_in(4).is_empty());
+        assert_eq!(cas.get(&cid).await.unwrap(), b"checkpointed");
+    }
+
+    #[tokio::test]
+    async fn test_pack_cas_quotas() {
+        let temp_dir = tempdir().unwrap();
+        let config = CasConfig { actor_quota_bytes: Some(12), ..CasConfig::default() };
+        {
+            let mut cas = PackCAS::open_with_config(temp_dir.path(), config.clone()).await.unwrap();
+            cas.set_namespace_quota(1, Some(10));
+            let tenant = Charge { namespace: 1, actor: None };
+            cas.put_charged(tenant, b"12345678", 0, PackBand::Small).await.unwrap();
+            // Content already stored is free, even for another namespace
+            cas.put_charged(tenant, b"12345678", 0, PackBand::Small).await.unwrap();
+
+            let err = cas.put_charged(tenant, b"abc", 0, PackBand::Small).await.unwrap_err();
+            let exceeded = QuotaExceeded::find(&err).unwrap();
+            assert_eq!((exceeded.scope.clone(), exceeded.used, exceeded.requested, exceeded.limit), (QuotaScope::Namespace(1), 8, 3, 10));
+            assert!(!cas.has(&Cid::hash(b"abc")));
+
+            let alice = Charge { namespace: 0, actor: Some("alice") };
+            cas.put_charged(alice, b"0123456789", 0, PackBand::Small).await.unwrap();
+            let err = cas.put_charged(alice, b"xyz", 0, PackBand::Small).await.unwrap_err();
+            assert_eq!(QuotaExceeded::find(&err).unwrap().scope, QuotaScope::Actor("alice".to_string()));
+            cas.checkpoint_wal().unwrap();
+        }
+
+        let mut cas = PackCAS::open_with_config(temp_dir.path(), CasConfig { max_bytes: Some(1), ..config }).await.unwrap();
+        assert_eq!((cas.namespace_usage(1), cas.usage().actors["alice"]), (8, 10));
+        let err = cas.put_charged(Charge::default(), b"too big", 0, PackBand::Small).await.unwrap_err();
+        assert_eq!(QuotaExceeded::find(&err).unwrap().scope, QuotaScope::Store);
+        // Unmetered writes such as indexes are never refused
+        cas.put(b"index", 2, PackBand::Index).await.unwrap();
+        cas.set_namespace_usage(1, None);
+        assert_eq!(cas.namespace_usage(1), 0);
+    }
+
     #[test]
     fn test_cidx_record() {
         let cid = Cid::hash(b"test data");
@@ -418,6 +939,14 @@ mod tests {
         assert_eq!(record.pack_id, 42);
         assert_eq!(record.offset, 1024);
         assert_eq!(record.len, 100);
+        assert_eq!(record.raw_len(), 100);
+
+        let record = record.with_origin(400, PackBand::Blob);
+        assert!(record.verify_crc());
+        assert_eq!(record.raw_len(), 400);
+
+        // The on-disk layout is unchanged by the raw_len/band fields
+        assert_eq!(std::mem::size_of::<CidxRec>(), 72);
     }
 
     #[test]
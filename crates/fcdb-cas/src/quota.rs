@@ -0,0 +1,179 @@
+//! Storage quotas for metered writes.
+//!
+//! Merkle DAG: enishi_cas -> quota -> {check, charge, recount, persist}
+//!
+//! Writes made through [`PackCAS::put_charged`] are charged to a namespace
+//! and optionally to the actor making them, and are refused once they would
+//! take the store, the namespace or the actor past its limit. Only objects
+//! not already stored are charged, at their uncompressed size, so
+//! deduplicated content is free. Garbage collection resets a namespace's
+//! usage to the bytes its graph still references ([`PackCAS::set_namespace_usage`]);
+//! an actor's usage counts every byte it has added. Usage is saved at WAL
+//! checkpoints, so a crash forgets at most what was charged since the last
+//! one.
+//!
+//! [`PackCAS::put_charged`]: crate::PackCAS::put_charged
+//! [`PackCAS::set_namespace_usage`]: crate::PackCAS::set_namespace_usage
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const USAGE_FILE: &str = "usage.json";
+
+/// Who a metered write is charged to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Charge<'a> {
+    pub namespace: u32,
+    pub actor: Option<&'a str>,
+}
+
+/// Limit a refused write would have exceeded
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum QuotaScope {
+    Store,
+    Namespace(u32),
+    Actor(String),
+}
+
+impl fmt::Display for QuotaScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuotaScope::Store => f.write_str("store"),
+            QuotaScope::Namespace(id) => write!(f, "namespace {}", id),
+            QuotaScope::Actor(actor) => write!(f, "actor {}", actor),
+        }
+    }
+}
+
+/// A metered write was refused; carried inside the `io::Error` it returns
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QuotaExceeded {
+    pub scope: QuotaScope,
+    pub used: u64,
+    pub requested: u64,
+    pub limit: u64,
+}
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} storage quota exceeded: {} of {} bytes used, {} more requested",
+            self.scope, self.used, self.limit, self.requested)
+    }
+}
+
+impl std::error::Error for QuotaExceeded {}
+
+impl QuotaExceeded {
+    /// The refusal behind `err`, looking through `io::Error` wrappers and
+    /// error sources
+    pub fn find<'a>(err: &'a (dyn std::error::Error + 'static)) -> Option<&'a QuotaExceeded> {
+        let mut current = Some(err);
+        while let Some(err) = current {
+            if let Some(exceeded) = err.downcast_ref::<QuotaExceeded>() {
+                return Some(exceeded);
+            }
+            let inner = err.downcast_ref::<io::Error>().and_then(|e| e.get_ref());
+            if let Some(exceeded) = inner.and_then(|inner| inner.downcast_ref::<QuotaExceeded>()) {
+                return Some(exceeded);
+            }
+            current = err.source();
+        }
+        None
+    }
+}
+
+/// Bytes charged so far
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    pub namespaces: BTreeMap<u32, u64>,
+    pub actors: BTreeMap<String, u64>,
+}
+
+pub(crate) struct Quotas {
+    store_limit: Option<u64>,
+    actor_limit: Option<u64>,
+    namespace_limits: HashMap<u32, u64>,
+    usage: Usage,
+    path: PathBuf,
+    dirty: bool,
+}
+
+impl Quotas {
+    pub(crate) fn load(base: &Path, store_limit: Option<u64>, actor_limit: Option<u64>) -> io::Result<Self> {
+        let path = base.join(USAGE_FILE);
+        let usage = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(io::Error::from)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Usage::default(),
+            Err(e) => return Err(e),
+        };
+        Ok(Self { store_limit, actor_limit, namespace_limits: HashMap::new(), usage, path, dirty: false })
+    }
+
+    /// Refuse `bytes` more for `charge` if any limit would be exceeded;
+    /// `stored` is the store's current size
+    pub(crate) fn check(&self, charge: Charge<'_>, bytes: u64, stored: u64) -> Result<(), QuotaExceeded> {
+        let over = |scope: QuotaScope, used: u64, limit: Option<u64>| match limit {
+            Some(limit) if used.saturating_add(bytes) > limit => Err(QuotaExceeded { scope, used, requested: bytes, limit }),
+            _ => Ok(()),
+        };
+        over(QuotaScope::Store, stored, self.store_limit)?;
+        over(QuotaScope::Namespace(charge.namespace), self.namespace_usage(charge.namespace),
+            self.namespace_limits.get(&charge.namespace).copied())?;
+        if let Some(actor) = charge.actor {
+            let used = self.usage.actors.get(actor).copied().unwrap_or(0);
+            over(QuotaScope::Actor(actor.to_string()), used, self.actor_limit)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn charge(&mut self, charge: Charge<'_>, bytes: u64) {
+        *self.usage.namespaces.entry(charge.namespace).or_default() += bytes;
+        if let Some(actor) = charge.actor {
+            *self.usage.actors.entry(actor.to_string()).or_default() += bytes;
+        }
+        self.dirty = true;
+    }
+
+    pub(crate) fn namespace_usage(&self, namespace: u32) -> u64 {
+        self.usage.namespaces.get(&namespace).copied().unwrap_or(0)
+    }
+
+    pub(crate) fn set_namespace_usage(&mut self, namespace: u32, bytes: Option<u64>) {
+        match bytes {
+            Some(bytes) => self.usage.namespaces.insert(namespace, bytes),
+            None => self.usage.namespaces.remove(&namespace),
+        };
+        self.dirty = true;
+    }
+
+    pub(crate) fn namespace_limit(&self, namespace: u32) -> Option<u64> {
+        self.namespace_limits.get(&namespace).copied()
+    }
+
+    pub(crate) fn set_namespace_limit(&mut self, namespace: u32, limit: Option<u64>) {
+        match limit {
+            Some(limit) => self.namespace_limits.insert(namespace, limit),
+            None => self.namespace_limits.remove(&namespace),
+        };
+    }
+
+    pub(crate) fn usage(&self) -> &Usage {
+        &self.usage
+    }
+
+    /// Atomically write the usage counters if they changed
+    pub(crate) fn save(&mut self) -> io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        let tmp_path = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, serde_json::to_vec(&self.usage).map_err(io::Error::from)?)?;
+        std::fs::File::open(&tmp_path)?.sync_all()?;
+        std::fs::rename(&tmp_path, &self.path)?;
+        self.dirty = false;
+        Ok(())
+    }
+}
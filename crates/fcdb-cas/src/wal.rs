@@ -0,0 +1,274 @@
+//! Write-ahead log for PackCAS and the layers built on top of it.
+//!
+//! Merkle DAG: enishi_cas -> wal -> {put records, graph records}
+//!
+//! File layout: a 16-byte header (magic + first LSN) followed by records of
+//! `[len u32][crc u32][lsn u64][tag u8][payload]`. The CRC covers lsn, tag and
+//! payload, so a torn tail is detected and cut off on open.
+
+use crate::PackBand;
+use crc32fast::Hasher as Crc32;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+/// File (inside the CAS directory) holding the write-ahead log
+pub const WAL_FILE: &str = "wal.log";
+
+const WAL_MAGIC: &[u8; 8] = b"FCDBWAL\0";
+const HEADER_LEN: u64 = 16;
+const RECORD_HEADER_LEN: usize = 4 + 4 + 8 + 1;
+
+const TAG_PUT: u8 = 1;
+const TAG_GRAPH: u8 = 2;
+const TAG_NAMESPACE_GRAPH: u8 = 3;
+
+/// A single logged mutation
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WalRecord {
+    /// Pack append + cidx append of one object
+    Put { kind: u8, band: PackBand, data: Vec<u8> },
+    /// Opaque mutation record owned by a higher layer (e.g. GraphDB)
+    Graph(Vec<u8>),
+    /// [`WalRecord::Graph`] of one of several namespaces sharing the store
+    NamespaceGraph { namespace: u32, payload: Vec<u8> },
+}
+
+impl WalRecord {
+    fn tag(&self) -> u8 {
+        match self {
+            WalRecord::Put { .. } => TAG_PUT,
+            WalRecord::Graph(_) => TAG_GRAPH,
+            WalRecord::NamespaceGraph { .. } => TAG_NAMESPACE_GRAPH,
+        }
+    }
+
+    fn encode_payload(&self, buf: &mut Vec<u8>) {
+        match self {
+            WalRecord::Put { kind, band, data } => {
+                buf.push(*kind);
+                buf.push(band_to_u8(*band));
+                buf.extend_from_slice(data);
+            }
+            WalRecord::Graph(payload) => buf.extend_from_slice(payload),
+            WalRecord::NamespaceGraph { namespace, payload } => {
+                buf.extend_from_slice(&namespace.to_le_bytes());
+                buf.extend_from_slice(payload);
+            }
+        }
+    }
+
+    fn decode(tag: u8, payload: &[u8]) -> Option<Self> {
+        match tag {
+            TAG_PUT if payload.len() >= 2 => Some(WalRecord::Put {
+                kind: payload[0],
+                band: band_from_u8(payload[1])?,
+                data: payload[2..].to_vec(),
+            }),
+            TAG_GRAPH => Some(WalRecord::Graph(payload.to_vec())),
+            TAG_NAMESPACE_GRAPH if payload.len() >= 4 => Some(WalRecord::NamespaceGraph {
+                namespace: u32::from_le_bytes(payload[..4].try_into().unwrap()),
+                payload: payload[4..].to_vec(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) fn band_to_u8(band: PackBand) -> u8 {
+    match band {
+        PackBand::Small => 0,
+        PackBand::Index => 1,
+        PackBand::Blob => 2,
+    }
+}
+
+pub(crate) fn band_from_u8(value: u8) -> Option<PackBand> {
+    match value {
+        0 => Some(PackBand::Small),
+        1 => Some(PackBand::Index),
+        2 => Some(PackBand::Blob),
+        _ => None,
+    }
+}
+
+fn record_crc(lsn: u64, tag: u8, payload: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(&lsn.to_le_bytes());
+    crc.update(&[tag]);
+    crc.update(payload);
+    crc.finalize()
+}
+
+/// Append-only write-ahead log with group commit
+///
+/// Records are buffered by [`Wal::append`] and written together by
+/// [`Wal::commit`], so every record appended since the last commit shares a
+/// single write (and, with `sync_writes`, a single fsync).
+pub struct Wal {
+    path: PathBuf,
+    file: File,
+    buffer: Vec<u8>,
+    next_lsn: u64,
+    sync_writes: bool,
+}
+
+impl Wal {
+    /// Open (or create) the WAL in `dir`, returning every intact record.
+    /// A torn or corrupt tail is truncated away.
+    pub fn open(dir: &Path, sync_writes: bool) -> io::Result<(Self, Vec<(u64, WalRecord)>)> {
+        let path = dir.join(WAL_FILE);
+        if !path.exists() {
+            write_fresh(&path, 1)?;
+        }
+
+        let mut file = OpenOptions::new().read(true).write(true).open(&path)?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+
+        if bytes.len() < HEADER_LEN as usize || &bytes[..8] != WAL_MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "WAL header is corrupt"));
+        }
+        let first_lsn = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+
+        let mut records = Vec::new();
+        let mut pos = HEADER_LEN as usize;
+        let mut next_lsn = first_lsn;
+        while let Some((lsn, record, end)) = decode_record(&bytes, pos) {
+            if lsn != next_lsn {
+                warn!("WAL record out of sequence (expected {}, found {}), stopping replay", next_lsn, lsn);
+                break;
+            }
+            records.push((lsn, record));
+            next_lsn = lsn + 1;
+            pos = end;
+        }
+
+        if pos < bytes.len() {
+            warn!("Truncating torn WAL tail: {} bytes", bytes.len() - pos);
+            file.set_len(pos as u64)?;
+            file.sync_all()?;
+        }
+        file.seek(SeekFrom::End(0))?;
+
+        debug!("Opened WAL with {} records (next LSN {})", records.len(), next_lsn);
+        Ok((
+            Self { path, file, buffer: Vec::new(), next_lsn, sync_writes },
+            records,
+        ))
+    }
+
+    /// Buffer a record and return its LSN; it becomes durable on the next commit
+    pub fn append(&mut self, record: &WalRecord) -> u64 {
+        let lsn = self.next_lsn;
+        self.next_lsn += 1;
+
+        let mut payload = Vec::new();
+        record.encode_payload(&mut payload);
+        let tag = record.tag();
+
+        self.buffer.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        self.buffer.extend_from_slice(&record_crc(lsn, tag, &payload).to_le_bytes());
+        self.buffer.extend_from_slice(&lsn.to_le_bytes());
+        self.buffer.push(tag);
+        self.buffer.extend_from_slice(&payload);
+        lsn
+    }
+
+    /// Write every buffered record, fsyncing when `sync_writes` is set
+    pub fn commit(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        self.file.write_all(&self.buffer)?;
+        self.file.flush()?;
+        if self.sync_writes {
+            self.file.sync_data()?;
+        }
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Discard every record (after their effects are durable elsewhere).
+    /// LSNs keep increasing across resets.
+    pub fn reset(&mut self) -> io::Result<()> {
+        self.commit()?;
+        write_fresh(&self.path, self.next_lsn)?;
+        self.file = OpenOptions::new().read(true).write(true).open(&self.path)?;
+        self.file.seek(SeekFrom::End(0))?;
+        Ok(())
+    }
+
+    /// LSN the next appended record will receive
+    pub fn next_lsn(&self) -> u64 {
+        self.next_lsn
+    }
+}
+
+/// Atomically replace the WAL with an empty one starting at `first_lsn`
+fn write_fresh(path: &Path, first_lsn: u64) -> io::Result<()> {
+    let tmp_path = path.with_extension("log.tmp");
+    {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(WAL_MAGIC)?;
+        file.write_all(&first_lsn.to_le_bytes())?;
+        file.sync_all()?;
+    }
+    std::fs::rename(tmp_path, path)
+}
+
+fn decode_record(bytes: &[u8], pos: usize) -> Option<(u64, WalRecord, usize)> {
+    let header = bytes.get(pos..pos + RECORD_HEADER_LEN)?;
+    let len = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
+    let crc = u32::from_le_bytes(header[4..8].try_into().unwrap());
+    let lsn = u64::from_le_bytes(header[8..16].try_into().unwrap());
+    let tag = header[16];
+
+    let start = pos + RECORD_HEADER_LEN;
+    let payload = bytes.get(start..start.checked_add(len)?)?;
+    if record_crc(lsn, tag, payload) != crc {
+        return None;
+    }
+    Some((lsn, WalRecord::decode(tag, payload)?, start + len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_wal_roundtrip_and_torn_tail() {
+        let temp_dir = tempdir().unwrap();
+
+        {
+            let (mut wal, records) = Wal::open(temp_dir.path(), true).unwrap();
+            assert!(records.is_empty());
+            assert_eq!(wal.append(&WalRecord::Graph(b"one".to_vec())), 1);
+            assert_eq!(wal.append(&WalRecord::Put { kind: 3, band: PackBand::Blob, data: b"two".to_vec() }), 2);
+            wal.commit().unwrap();
+            wal.append(&WalRecord::NamespaceGraph { namespace: 7, payload: b"three".to_vec() });
+            wal.commit().unwrap();
+            wal.append(&WalRecord::Graph(b"four".to_vec()));
+            wal.commit().unwrap();
+        }
+
+        // Cut the last record in half
+        let path = temp_dir.path().join(WAL_FILE);
+        let len = std::fs::metadata(&path).unwrap().len();
+        OpenOptions::new().write(true).open(&path).unwrap().set_len(len - 3).unwrap();
+
+        let (mut wal, records) = Wal::open(temp_dir.path(), true).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[1].1, WalRecord::Put { kind: 3, band: PackBand::Blob, data: b"two".to_vec() });
+        assert_eq!(records[2].1, WalRecord::NamespaceGraph { namespace: 7, payload: b"three".to_vec() });
+        assert_eq!(wal.next_lsn(), 4);
+
+        wal.reset().unwrap();
+        drop(wal);
+        let (wal, records) = Wal::open(temp_dir.path(), true).unwrap();
+        assert!(records.is_empty());
+        assert_eq!(wal.next_lsn(), 4);
+    }
+}
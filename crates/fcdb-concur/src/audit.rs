@@ -0,0 +1,292 @@
+//! Hash-chained capability audit log.
+//!
+//! Merkle DAG: enishi_concur -> audit_log -> {append, rotate, verify, query}
+//!
+//! Every [`CapTraceEntry`] is sealed into an [`AuditRecord`] carrying its
+//! sequence number, the Cid of the record before it and its own Cid, the
+//! BLAKE3 hash of `(seq, prev, entry)`. Editing, dropping or reordering a
+//! record therefore breaks the chain at that point. On disk the records are
+//! JSON lines appended to the active segment (`audit.log`); once it reaches
+//! the size limit it is renamed to `audit.log.1`, `audit.log.2`, ... and the
+//! chain continues in a fresh file. Truncating the newest records leaves a
+//! valid shorter chain, so operators who need to detect that should keep the
+//! head Cid reported by [`AuditFile::verify`] somewhere else.
+
+use crate::CapTraceEntry;
+use fcdb_core::Cid;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// `prev` of the first record in a log
+pub const GENESIS: Cid = Cid::from_bytes([0; 32]);
+
+/// Default size at which the active segment is rotated
+pub const DEFAULT_SEGMENT_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Errors reading, writing or verifying an audit log
+#[derive(Error, Debug)]
+pub enum AuditError {
+    #[error("Audit log I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Audit log tampered at record {seq}: {reason}")]
+    Tampered { seq: u64, reason: String },
+}
+
+/// One link of the audit chain
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub seq: u64,
+    pub prev: Cid,
+    pub entry: CapTraceEntry,
+    pub cid: Cid,
+}
+
+impl AuditRecord {
+    /// Seal `entry` as record `seq` following `prev`
+    pub fn seal(seq: u64, prev: Cid, entry: CapTraceEntry) -> Self {
+        let cid = digest(seq, &prev, &entry);
+        Self { seq, prev, entry, cid }
+    }
+
+    /// Whether the record's Cid matches its contents
+    pub fn is_intact(&self) -> bool {
+        digest(self.seq, &self.prev, &self.entry) == self.cid
+    }
+}
+
+fn digest(seq: u64, prev: &Cid, entry: &CapTraceEntry) -> Cid {
+    let bytes = serde_json::to_vec(&(seq, prev, entry)).expect("audit entries serialize");
+    Cid::hash(&bytes)
+}
+
+/// Incremental chain check: records must follow one another with
+/// consecutive sequence numbers and intact Cids
+pub(crate) struct ChainCheck {
+    next_seq: u64,
+    head: Cid,
+}
+
+impl ChainCheck {
+    pub(crate) fn new(next_seq: u64, head: Cid) -> Self {
+        Self { next_seq, head }
+    }
+
+    pub(crate) fn check(&mut self, record: &AuditRecord) -> Result<(), AuditError> {
+        let tampered = |reason: &str| AuditError::Tampered { seq: record.seq, reason: reason.to_string() };
+        if record.seq != self.next_seq {
+            return Err(AuditError::Tampered {
+                seq: self.next_seq,
+                reason: format!("expected sequence {}, found {}", self.next_seq, record.seq),
+            });
+        }
+        if record.prev != self.head {
+            return Err(tampered("previous Cid does not match the chain"));
+        }
+        if !record.is_intact() {
+            return Err(tampered("record contents do not match its Cid"));
+        }
+        self.next_seq += 1;
+        self.head = record.cid;
+        Ok(())
+    }
+}
+
+/// Result of a successful chain verification
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct AuditVerification {
+    pub records: u64,
+    pub segments: usize,
+    pub head: Cid,
+}
+
+/// Filter for [`crate::CapTracer::query`]; unset fields match everything
+/// and the time range is inclusive unix seconds
+#[derive(Clone, Debug, Default)]
+pub struct AuditQuery {
+    pub actor: Option<String>,
+    pub resource: Option<Cid>,
+    pub operation: Option<String>,
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+    /// Keep only the newest `limit` matches
+    pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+    pub fn matches(&self, entry: &CapTraceEntry) -> bool {
+        self.actor.as_ref().is_none_or(|actor| entry.actor == *actor)
+            && self.resource.is_none_or(|resource| entry.resource == resource)
+            && self.operation.as_ref().is_none_or(|operation| entry.operation == *operation)
+            && self.since.is_none_or(|since| entry.timestamp >= since)
+            && self.until.is_none_or(|until| entry.timestamp <= until)
+    }
+
+    /// Matching records of `records`, oldest first, capped by `limit`
+    pub(crate) fn select<'a>(&self, records: impl Iterator<Item = &'a AuditRecord>) -> Vec<AuditRecord> {
+        let mut matched: Vec<_> = records.filter(|record| self.matches(&record.entry)).cloned().collect();
+        if let Some(limit) = self.limit {
+            matched.drain(..matched.len().saturating_sub(limit));
+        }
+        matched
+    }
+}
+
+/// Append-only, rotated on-disk audit log
+pub struct AuditFile {
+    path: PathBuf,
+    file: File,
+    len: u64,
+    rotated: u64,
+    max_segment_bytes: u64,
+}
+
+impl AuditFile {
+    /// Open (or create) the log whose active segment is `path`, returning the
+    /// last record so the chain can be resumed. A torn final line left by a
+    /// crash is truncated away.
+    pub fn open(path: &Path, max_segment_bytes: u64) -> Result<(Self, Option<AuditRecord>), AuditError> {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().read(true).append(true).create(true).open(path)?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+
+        let complete = bytes.iter().rposition(|&b| b == b'\n').map_or(0, |pos| pos + 1);
+        if complete < bytes.len() {
+            file.set_len(complete as u64)?;
+            file.sync_all()?;
+            bytes.truncate(complete);
+        }
+        file.seek(SeekFrom::End(0))?;
+
+        let rotated = rotated_segments(path)?.last().map_or(0, |(n, _)| *n);
+        let mut last = last_record(&bytes)?;
+        if last.is_none() && rotated > 0 {
+            last = last_record(&fs::read(segment_path(path, rotated))?)?;
+        }
+
+        Ok((
+            Self { path: path.to_path_buf(), file, len: complete as u64, rotated, max_segment_bytes },
+            last,
+        ))
+    }
+
+    /// Durably append `record`, rotating the segment once it is full
+    pub fn append(&mut self, record: &AuditRecord) -> Result<(), AuditError> {
+        let mut line = serde_json::to_vec(record).map_err(io::Error::from)?;
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+        self.file.sync_data()?;
+        self.len += line.len() as u64;
+
+        if self.len >= self.max_segment_bytes {
+            self.rotate()?;
+        }
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> Result<(), AuditError> {
+        self.rotated += 1;
+        fs::rename(&self.path, segment_path(&self.path, self.rotated))?;
+        self.file = OpenOptions::new().read(true).append(true).create(true).open(&self.path)?;
+        self.len = 0;
+        Ok(())
+    }
+
+    /// Every segment, oldest first
+    pub fn segments(&self) -> io::Result<Vec<PathBuf>> {
+        let mut segments: Vec<_> = rotated_segments(&self.path)?.into_iter().map(|(_, path)| path).collect();
+        segments.push(self.path.clone());
+        Ok(segments)
+    }
+
+    /// Walk every segment from the genesis record and check the chain
+    pub fn verify(&self) -> Result<AuditVerification, AuditError> {
+        let segments = self.segments()?;
+        let mut chain = ChainCheck::new(1, GENESIS);
+        for segment in &segments {
+            for_each_record(segment, chain.next_seq, |record| chain.check(&record))?;
+        }
+        Ok(AuditVerification { records: chain.next_seq - 1, segments: segments.len(), head: chain.head })
+    }
+
+    /// Records matching `query`, oldest first
+    pub fn query(&self, query: &AuditQuery) -> Result<Vec<AuditRecord>, AuditError> {
+        let mut matched = Vec::new();
+        let mut next_seq = 1;
+        for segment in self.segments()? {
+            for_each_record(&segment, next_seq, |record| {
+                next_seq = record.seq + 1;
+                if query.matches(&record.entry) {
+                    matched.push(record);
+                }
+                Ok(())
+            })?;
+        }
+        if let Some(limit) = query.limit {
+            matched.drain(..matched.len().saturating_sub(limit));
+        }
+        Ok(matched)
+    }
+}
+
+fn segment_path(path: &Path, n: u64) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+/// Rotated segments of the log at `path`, by number
+fn rotated_segments(path: &Path) -> io::Result<Vec<(u64, PathBuf)>> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let prefix = format!("{}.", path.file_name().unwrap_or_default().to_string_lossy());
+
+    let mut segments = Vec::new();
+    for dir_entry in fs::read_dir(dir)? {
+        let name = dir_entry?.file_name();
+        let n = name.to_string_lossy().strip_prefix(&prefix).and_then(|suffix| suffix.parse::<u64>().ok());
+        if let Some(n) = n {
+            segments.push((n, segment_path(path, n)));
+        }
+    }
+    segments.sort_unstable();
+    Ok(segments)
+}
+
+fn last_record(bytes: &[u8]) -> Result<Option<AuditRecord>, AuditError> {
+    let Some(line) = bytes.rsplit(|&b| b == b'\n').find(|line| !line.is_empty()) else {
+        return Ok(None);
+    };
+    serde_json::from_slice(line).map(Some).map_err(|e| AuditError::Tampered {
+        seq: 0,
+        reason: format!("unreadable final record: {}", e),
+    })
+}
+
+/// Parse each line of `segment`; `next_seq` only labels parse failures
+fn for_each_record(
+    segment: &Path,
+    mut next_seq: u64,
+    mut visit: impl FnMut(AuditRecord) -> Result<(), AuditError>,
+) -> Result<(), AuditError> {
+    for line in BufReader::new(File::open(segment)?).lines() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let record: AuditRecord = serde_json::from_str(&line).map_err(|e| AuditError::Tampered {
+            seq: next_seq,
+            reason: format!("unreadable record: {}", e),
+        })?;
+        next_seq = record.seq + 1;
+        visit(record)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,154 @@
+//! Capability checks shared by the request front ends.
+//!
+//! Merkle DAG: enishi_concur -> cap_gate -> {authorize, check_rid, check_whole_graph, audit}
+//!
+//! A [`CapGate`] verifies a presented token against its [`CapAuthority`],
+//! checks that the cap grants an operation's permissions and covers the
+//! nodes involved, and records every decision in a [`CapTracer`]. The HTTP
+//! server and the GraphQL schema each map a [`CapRefusal`] to their own
+//! error type. Auditing fails closed: a decision that cannot be recorded
+//! refuses the request.
+//!
+//! Operations of a namespace other than the default are audited as
+//! `namespace/operation`, so each namespace has its own audit resources.
+
+use crate::{AuditError, CapAuthority, CapTracer};
+use fcdb_core::{Cap, Cid};
+use std::ops::Range;
+use thiserror::Error;
+
+/// Why a [`CapGate`] refused a request
+#[derive(Error, Debug)]
+pub enum CapRefusal {
+    /// No token, a malformed one, or one that does not verify
+    #[error("{0}")]
+    Unauthenticated(String),
+    /// A valid cap that does not cover the request
+    #[error("{0}")]
+    Forbidden(String),
+    /// The decision could not be written to the audit trail
+    #[error(transparent)]
+    AuditFailed(#[from] AuditError),
+}
+
+/// Verifies capabilities and audits the outcome
+#[derive(Clone)]
+pub struct CapGate {
+    authority: CapAuthority,
+    tracer: CapTracer,
+    audit: bool,
+    // Prefix of audited operations, for namespaces other than the default
+    namespace: Option<String>,
+}
+
+impl CapGate {
+    /// Gate auditing to `tracer`, or not auditing at all if `None`
+    pub fn new(authority: CapAuthority, tracer: Option<CapTracer>) -> Self {
+        let audit = tracer.is_some();
+        Self { authority, tracer: tracer.unwrap_or_else(CapTracer::new), audit, namespace: None }
+    }
+
+    /// Gate for namespace `name` (with id `id`), auditing to the same trail
+    /// under operations named `name/operation`
+    pub fn for_namespace(&self, name: &str, id: u32) -> Self {
+        if id == 0 {
+            return self.clone();
+        }
+        Self {
+            authority: self.authority.for_namespace(id),
+            tracer: self.tracer.clone(),
+            audit: self.audit,
+            namespace: Some(name.to_string()),
+        }
+    }
+
+    pub fn authority(&self) -> &CapAuthority {
+        &self.authority
+    }
+
+    pub fn tracer(&self) -> &CapTracer {
+        &self.tracer
+    }
+
+    /// Verified cap of a bearer `token`, which must grant every bit of
+    /// `required`
+    pub async fn authorize(&self, token: Option<&str>, operation: &str, required: u32) -> Result<Cap, CapRefusal> {
+        let Some(cap) = token.and_then(Cap::from_token) else {
+            self.record(operation, None, false, "missing or malformed capability token").await?;
+            return Err(CapRefusal::Unauthenticated("a valid capability token is required".to_string()));
+        };
+        if let Err(e) = self.authority.verify(&cap) {
+            self.record(operation, Some(&cap), false, &e.to_string()).await?;
+            return Err(CapRefusal::Unauthenticated(e.to_string()));
+        }
+        if cap.perms & required != required {
+            return Err(self.deny(&cap, operation, "capability lacks the required permissions").await);
+        }
+        self.record(operation, Some(&cap), true, "authorized").await?;
+        Ok(cap)
+    }
+
+    /// Refusal of an authenticated request the cap does not cover, audited
+    pub async fn deny(&self, cap: &Cap, operation: &str, details: &str) -> CapRefusal {
+        match self.record(operation, Some(cap), false, details).await {
+            Ok(()) => CapRefusal::Forbidden(details.to_string()),
+            Err(e) => e,
+        }
+    }
+
+    /// Audit a refusal that was detected after authorization, e.g. by the
+    /// graph rejecting a write outside the cap
+    pub async fn audit_denied(&self, cap: &Cap, operation: &str, details: &str) -> Result<(), CapRefusal> {
+        self.record(operation, Some(cap), false, details).await
+    }
+
+    /// Require node `rid` to lie in the cap's region
+    pub async fn check_rid(&self, cap: &Cap, operation: &str, rid: u64) -> Result<(), CapRefusal> {
+        if cap.contains(rid) {
+            Ok(())
+        } else {
+            Err(self.deny(cap, operation, &format!("node {} is outside the capability", rid)).await)
+        }
+    }
+
+    /// Require the cap to span every RID below `next_rid`, for operations
+    /// that read the whole graph
+    pub async fn check_whole_graph(&self, cap: &Cap, operation: &str, next_rid: u64) -> Result<(), CapRefusal> {
+        if cap.base <= 1 && cap.base.saturating_add(cap.len) >= next_rid {
+            Ok(())
+        } else {
+            Err(self.deny(cap, operation, "operation needs a capability over the whole graph").await)
+        }
+    }
+
+    async fn record(&self, operation: &str, cap: Option<&Cap>, success: bool, details: &str) -> Result<(), CapRefusal> {
+        if !self.audit {
+            return Ok(());
+        }
+        let cap = cap.copied().unwrap_or(Cap { base: 0, len: 0, perms: 0, expiry: 0, lease: 0, proof: [0; 16] });
+        let operation = match &self.namespace {
+            Some(namespace) => format!("{}/{}", namespace, operation),
+            None => operation.to_string(),
+        };
+        self.tracer.record_operation(&operation, &holder(&cap), &resource(&operation), &cap, success, details).await?;
+        Ok(())
+    }
+}
+
+/// Raw RIDs a cap covers
+pub fn scope(cap: &Cap) -> Range<u64> {
+    cap.base..cap.base.saturating_add(cap.len)
+}
+
+/// Audit resource of an operation: the hash of its (namespaced) name
+pub fn resource(operation: &str) -> Cid {
+    Cid::hash(operation.as_bytes())
+}
+
+/// Audit identity of a token holder: a digest of the cap's proof
+pub fn holder(cap: &Cap) -> String {
+    if cap.proof == [0; 16] {
+        return "anonymous".to_string();
+    }
+    Cid::hash(&cap.proof).to_string()[..16].to_string()
+}
@@ -4,13 +4,22 @@
 //!
 //! Merkle DAG: enishi_concur -> ownership_types, cap_functor, txn_safety
 
-use fcdb_core::{Cap, Cid};
+use fcdb_core::{Cap, CapKey, Cid};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::sync::{RwLock, Mutex};
+use tokio::sync::{Notify, RwLock, Mutex};
 use async_trait::async_trait;
 use thiserror::Error;
 use serde::{Serialize, Deserialize};
 
+mod audit;
+pub mod gate;
+
+use audit::ChainCheck;
+pub use audit::{AuditError, AuditFile, AuditQuery, AuditRecord, AuditVerification, DEFAULT_SEGMENT_BYTES, GENESIS};
+pub use gate::{CapGate, CapRefusal};
+
 /// Capability-CID pair
 #[derive(Clone, PartialEq, Eq)]
 pub struct CapCid {
@@ -37,6 +46,10 @@ pub enum ConcurError {
     LeaseExpired,
     #[error("Permission denied")]
     PermissionDenied,
+    #[error(transparent)]
+    Audit(#[from] AuditError),
+    #[error("Lease store error: {0}")]
+    LeaseStore(#[from] std::io::Error),
 }
 
 /// Permission flags for capabilities
@@ -48,6 +61,120 @@ pub mod perms {
     pub const DELEGATE: u32 = 1 << 4;
 }
 
+/// Mints capabilities with the server-held key, verifies them, and derives
+/// attenuated caps from verified ones. Caps bound to a lease verify only
+/// while the authority's [`LeaseManager`] holds it live.
+/// Merkle DAG: enishi_concur -> cap_authority -> {mint, verify, derive, delegate}
+#[derive(Clone, Debug)]
+pub struct CapAuthority {
+    key: CapKey,
+    leases: Option<LeaseManager>,
+}
+
+impl CapAuthority {
+    pub fn new(key: CapKey) -> Self {
+        Self { key, leases: None }
+    }
+
+    /// Check lease-bound caps against `leases`
+    pub fn with_leases(mut self, leases: LeaseManager) -> Self {
+        self.leases = Some(leases);
+        self
+    }
+
+    pub fn leases(&self) -> Option<&LeaseManager> {
+        self.leases.as_ref()
+    }
+
+    /// Authority for namespace `id`, whose caps do not verify in any other
+    /// namespace; namespace 0 keeps this authority's key. Leases are shared.
+    pub fn for_namespace(&self, id: u32) -> Self {
+        let key = match id {
+            0 => self.key.clone(),
+            id => self.key.derive(&format!("namespace/{}", id)),
+        };
+        Self { key, leases: self.leases.clone() }
+    }
+
+    /// Authority with a fresh random key
+    pub fn generate() -> Self {
+        Self::new(CapKey::generate())
+    }
+
+    /// Cap over `base..base + len`; `expiry` is unix seconds, 0 for none
+    pub fn mint(&self, base: u64, len: u64, perms: u32, expiry: u64) -> Cap {
+        self.key.mint(base, len, perms, expiry)
+    }
+
+    /// Check that `cap` was minted by this authority and has not expired,
+    /// nor has the lease it is bound to
+    pub fn verify(&self, cap: &Cap) -> Result<(), ConcurError> {
+        if !self.key.verify(cap) {
+            return Err(ConcurError::CapCheckFailed);
+        }
+        if cap.is_expired(unix_now()) {
+            return Err(ConcurError::LeaseExpired);
+        }
+        if cap.lease != 0 {
+            self.leases.as_ref().ok_or(ConcurError::LeaseExpired)?.validate(cap.lease)?;
+        }
+        Ok(())
+    }
+
+    /// Sub-capability of `parent` (which needs `perms::DERIVE`), clamped to
+    /// the intersection of the request with the parent's region, perms and
+    /// expiry, as in `cap_flat_map`
+    pub fn derive(&self, parent: &Cap, base: u64, len: u64, perms: u32, expiry: u64) -> Result<Cap, ConcurError> {
+        self.attenuate(parent, perms::DERIVE, base, len, perms, expiry)
+    }
+
+    /// Copy of `parent` for another holder (which needs `perms::DELEGATE`),
+    /// over the same region with at most the parent's perms and expiry
+    pub fn delegate(&self, parent: &Cap, perms: u32, expiry: u64) -> Result<Cap, ConcurError> {
+        self.attenuate(parent, perms::DELEGATE, parent.base, parent.len, perms, expiry)
+    }
+
+    fn attenuate(&self, parent: &Cap, required: u32, base: u64, len: u64, perms: u32, expiry: u64) -> Result<Cap, ConcurError> {
+        self.verify(parent)?;
+        if !parent.has_perm(required) {
+            return Err(ConcurError::PermissionDenied);
+        }
+        Ok(self.key.sign(parent.attenuate(base, len, perms, expiry)))
+    }
+
+    /// Like [`CapAuthority::derive`], but bound to a new lease granted under
+    /// the parent's lease, if any. Returns the cap and the lease id.
+    pub async fn derive_leased(
+        &self,
+        parent: &Cap,
+        base: u64,
+        len: u64,
+        perms: u32,
+        terms: LeaseTerms,
+    ) -> Result<(Cap, u64), ConcurError> {
+        let leases = self.leases.as_ref().ok_or(ConcurError::PermissionDenied)?;
+        let child = self.derive(parent, base, len, perms, 0)?;
+        let info = LeaseInfo {
+            resource_id: child.base,
+            holder: terms.holder,
+            permissions: child.perms,
+            expires_at: unix_now() + terms.ttl_secs,
+            auto_renew: terms.auto_renew,
+            parent: (parent.lease != 0).then_some(parent.lease),
+            ttl_secs: terms.ttl_secs,
+        };
+        let lease = leases.grant(info).await?;
+        Ok((self.key.sign(Cap { lease, ..child }), lease))
+    }
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
 /// Phase D: Owned Capability Content Identifier
 /// Rust ownership ensures exclusive access and prevents data races
 pub struct OwnedCapCid<T> {
@@ -175,11 +302,10 @@ impl<T> CapFunctor for OwnedCapCid<T> {
         let OwnedCapCid { cap_cid: new_cap_cid, data: new_data } = f(data);
 
         // Compose capabilities: new_cap ∩ original_cap
+        let new_cap = new_cap_cid.cap;
         let composed_cap = Cap {
-            base: new_cap_cid.cap.base.max(cap_cid.cap.base),
-            len: new_cap_cid.cap.len.min(cap_cid.cap.len),
-            perms: new_cap_cid.cap.perms & cap_cid.cap.perms,
-            proof: new_cap_cid.cap.proof, // Keep new proof
+            proof: new_cap.proof, // Keep new proof; it only verifies if nothing was narrowed
+            ..cap_cid.cap.attenuate(new_cap.base, new_cap.len, new_cap.perms, new_cap.expiry)
         };
 
         OwnedCapCid::new(new_data, composed_cap, new_cap_cid.cid)
@@ -193,6 +319,11 @@ pub struct Transaction {
     borrowed_resources: Vec<Arc<RwLock<CapCid>>>,
     start_time: std::time::Instant,
     timeout_ms: u64,
+    // Lock table of the manager that began the transaction; its locks are
+    // released on commit, abort or drop
+    locks: Option<Arc<LockTable>>,
+    // Authority that verifies the caps the transaction relies on
+    authority: Option<Arc<CapAuthority>>,
 }
 
 impl Transaction {
@@ -203,14 +334,36 @@ impl Transaction {
             borrowed_resources: Vec::new(),
             start_time: std::time::Instant::now(),
             timeout_ms: 5000, // 5 second default timeout
+            locks: None,
+            authority: None,
         }
     }
 
+    /// Set the timeout, which also bounds how long lock acquisition may wait
+    pub fn with_timeout(mut self, timeout_ms: u64) -> Self {
+        self.timeout_ms = timeout_ms;
+        self
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
     /// Check if transaction has timed out
     pub fn is_expired(&self) -> bool {
-        self.start_time.elapsed().as_millis() as u64 > self.timeout_ms
+        self.start_time.elapsed() >= std::time::Duration::from_millis(self.timeout_ms)
+    }
+
+    fn deadline(&self) -> tokio::time::Instant {
+        tokio::time::Instant::from_std(self.start_time + std::time::Duration::from_millis(self.timeout_ms))
     }
 
+    /// Release every lock the transaction holds
+    fn release_locks(&mut self) {
+        if let Some(locks) = self.locks.take() {
+            locks.release_all(self.id);
+        }
+    }
     /// Add owned resource to transaction
     pub fn add_owned<T: Send + Sync + 'static>(&mut self, owned: OwnedCapCid<T>) {
         let boxed = OwnedCapCid::new(
@@ -226,103 +379,440 @@ impl Transaction {
         self.borrowed_resources.push(borrowed);
     }
 
-    /// Check if transaction has write permission for resource
+    /// Check if transaction has write permission for resource. The cap must
+    /// verify against the authority of the manager that began the transaction.
     pub async fn check_write_perm(&self, target_cid: &Cid) -> Result<(), ConcurError> {
-        // Check owned resources first
-        for owned in &self.owned_resources {
-            if owned.cap_cid.cid == *target_cid {
-                if owned.cap_cid.cap.has_perm(perms::WRITE) {
-                    return Ok(());
-                } else {
-                    return Err(ConcurError::PermissionDenied);
-                }
-            }
+        let cap = self.cap_for(target_cid).await.ok_or(ConcurError::PermissionDenied)?;
+        self.authority.as_ref().ok_or(ConcurError::CapCheckFailed)?.verify(&cap)?;
+        if cap.has_perm(perms::WRITE) {
+            Ok(())
+        } else {
+            Err(ConcurError::PermissionDenied)
         }
+    }
 
-        // Check borrowed resources
+    /// Cap the transaction holds for a resource, owned resources first
+    async fn cap_for(&self, target_cid: &Cid) -> Option<Cap> {
+        if let Some(owned) = self.owned_resources.iter().find(|owned| owned.cap_cid.cid == *target_cid) {
+            return Some(owned.cap_cid.cap);
+        }
         for borrowed in &self.borrowed_resources {
             let cap_cid = borrowed.read().await;
             if cap_cid.cid == *target_cid {
-                if cap_cid.cap.has_perm(perms::WRITE) {
-                    return Ok(());
-                } else {
-                    return Err(ConcurError::PermissionDenied);
-                }
+                return Some(cap_cid.cap);
             }
         }
-
-        Err(ConcurError::PermissionDenied)
+        None
     }
 }
 
 /// Phase D: Lease management for capability expiration
+///
+/// Leases form a tree: one granted under a parent lease dies with it, and
+/// caps bound to a lease stop verifying once it expires or is revoked (see
+/// [`CapAuthority::derive_leased`]). Expired leases are removed by
+/// [`LeaseManager::reap_expired`], which [`LeaseManager::spawn_reaper`] runs
+/// periodically. A manager opened on a file persists its active leases, so
+/// caps bound to them survive restarts; the file is written outside the
+/// table's lock, so lease checks never wait on the disk.
+/// Merkle DAG: enishi_concur -> lease_manager -> {grant, heartbeat, revoke, reap}
+#[derive(Clone, Debug, Default)]
 pub struct LeaseManager {
-    active_leases: Arc<RwLock<std::collections::HashMap<u64, LeaseInfo>>>,
+    active_leases: Arc<std::sync::RwLock<LeaseTable>>,
+    // Generation of the table last written to the lease file, locked
+    // while writing so saves land in order
+    saved: Arc<tokio::sync::Mutex<u64>>,
 }
 
-#[derive(Clone)]
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LeaseTable {
+    leases: HashMap<u64, LeaseInfo>,
+    next_id: u64,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    /// Bumped on every change
+    #[serde(skip)]
+    generation: u64,
+}
+
+/// Contents of the lease file as of one change to the table
+struct LeaseFile {
+    path: PathBuf,
+    generation: u64,
+    bytes: Vec<u8>,
+}
+
+impl LeaseFile {
+    /// Atomically replace the lease file
+    fn write(&self) -> std::io::Result<()> {
+        let tmp_path = self.path.with_extension("tmp");
+        {
+            let mut file = std::fs::File::create(&tmp_path)?;
+            std::io::Write::write_all(&mut file, &self.bytes)?;
+            file.sync_all()?;
+        }
+        std::fs::rename(tmp_path, &self.path)
+    }
+}
+
+impl LeaseTable {
+    /// The lease and every lease above it exist and have not expired
+    fn live(&self, lease_id: u64, now: u64) -> Result<&LeaseInfo, ConcurError> {
+        let info = self.leases.get(&lease_id).ok_or(ConcurError::LeaseExpired)?;
+        let mut current = Some(info);
+        while let Some(lease) = current {
+            if now > lease.expires_at {
+                return Err(ConcurError::LeaseExpired);
+            }
+            current = match lease.parent {
+                Some(parent) => Some(self.leases.get(&parent).ok_or(ConcurError::LeaseExpired)?),
+                None => None,
+            };
+        }
+        Ok(info)
+    }
+
+    /// Add a lease; one granted under a parent needs the parent live
+    fn insert(&mut self, lease_id: u64, info: LeaseInfo) -> Result<(), ConcurError> {
+        if let Some(parent) = info.parent {
+            self.live(parent, unix_now())?;
+        }
+        self.next_id = self.next_id.max(lease_id + 1);
+        self.leases.insert(lease_id, info);
+        Ok(())
+    }
+
+    /// Remove `lease_id` and every lease granted under it
+    fn remove_subtree(&mut self, lease_id: u64) -> Vec<u64> {
+        let mut removed = Vec::new();
+        let mut pending = vec![lease_id];
+        while let Some(id) = pending.pop() {
+            if self.leases.remove(&id).is_some() {
+                removed.push(id);
+            }
+            pending.extend(self.leases.iter().filter(|(_, info)| info.parent == Some(id)).map(|(child, _)| *child));
+        }
+        removed
+    }
+
+    /// Record a change, returning what the lease file must now hold if
+    /// there is one; the caller saves it after releasing the lock
+    fn changed(&mut self) -> Result<Option<LeaseFile>, ConcurError> {
+        self.generation += 1;
+        let Some(path) = &self.path else {
+            return Ok(None);
+        };
+        let bytes = serde_json::to_vec(self).map_err(std::io::Error::from)?;
+        Ok(Some(LeaseFile { path: path.clone(), generation: self.generation, bytes }))
+    }
+}
+
+/// Terms of a lease granted by [`CapAuthority::derive_leased`]
+#[derive(Clone, Debug)]
+pub struct LeaseTerms {
+    pub holder: String,
+    pub ttl_secs: u64,
+    /// Whether heartbeats may extend the lease
+    pub auto_renew: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct LeaseInfo {
     pub resource_id: u64,
     pub holder: String,
     pub permissions: u32,
     pub expires_at: u64,
     pub auto_renew: bool,
+    /// Lease this one was granted under; revoking or expiring it ends this one
+    #[serde(default)]
+    pub parent: Option<u64>,
+    /// Extension granted by each heartbeat
+    #[serde(default)]
+    pub ttl_secs: u64,
 }
 
 impl LeaseManager {
     pub fn new() -> Self {
-        Self {
-            active_leases: Arc::new(RwLock::new(std::collections::HashMap::new())),
+        Self::default()
+    }
+
+    /// Manager persisting its leases to `path`, starting from those saved
+    /// there by a previous run
+    pub fn open(path: &Path) -> Result<Self, ConcurError> {
+        let mut table = if path.exists() {
+            serde_json::from_slice(&std::fs::read(path)?).map_err(std::io::Error::from)?
+        } else {
+            if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+                std::fs::create_dir_all(parent)?;
+            }
+            LeaseTable::default()
+        };
+        table.path = Some(path.to_path_buf());
+        Ok(Self {
+            active_leases: Arc::new(std::sync::RwLock::new(table)),
+            saved: Default::default(),
+        })
+    }
+
+    /// Write a snapshot taken by [`LeaseTable::changed`] on the blocking
+    /// pool. A snapshot older than one already written is dropped.
+    async fn save(&self, file: Option<LeaseFile>) -> Result<(), ConcurError> {
+        let Some(file) = file else {
+            return Ok(());
+        };
+        let mut saved = self.saved.lock().await;
+        if *saved >= file.generation {
+            return Ok(());
         }
+        let generation = file.generation;
+        tokio::task::spawn_blocking(move || file.write()).await.map_err(std::io::Error::other)??;
+        *saved = generation;
+        Ok(())
     }
 
     /// Grant lease for resource
     pub async fn grant_lease(&self, lease_id: u64, info: LeaseInfo) -> Result<(), ConcurError> {
-        let mut leases = self.active_leases.write().await;
-        leases.insert(lease_id, info);
-        Ok(())
+        let file = {
+            let mut leases = self.active_leases.write().unwrap();
+            leases.insert(lease_id, info)?;
+            leases.changed()?
+        };
+        self.save(file).await
+    }
+
+    /// Grant a lease under a fresh id, which is returned
+    pub async fn grant(&self, info: LeaseInfo) -> Result<u64, ConcurError> {
+        let (lease_id, file) = {
+            let mut leases = self.active_leases.write().unwrap();
+            let lease_id = leases.next_id.max(1);
+            leases.insert(lease_id, info)?;
+            (lease_id, leases.changed()?)
+        };
+        self.save(file).await?;
+        Ok(lease_id)
     }
 
     /// Check if lease is valid
     pub async fn check_lease(&self, lease_id: u64) -> Result<LeaseInfo, ConcurError> {
-        let leases = self.active_leases.read().await;
-        match leases.get(&lease_id) {
-            Some(info) => {
-                let now = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs();
-
-                if now > info.expires_at {
-                    return Err(ConcurError::LeaseExpired);
-                }
+        let leases = self.active_leases.read().unwrap();
+        leases.live(lease_id, unix_now()).cloned()
+    }
+
+    /// Like [`LeaseManager::check_lease`], for synchronous callers
+    pub fn validate(&self, lease_id: u64) -> Result<(), ConcurError> {
+        self.active_leases.read().unwrap().live(lease_id, unix_now()).map(|_| ())
+    }
 
-                Ok(info.clone())
+    /// Whether `lease_id` is `ancestor` or was granted (transitively) under it
+    pub fn is_within(&self, lease_id: u64, ancestor: u64) -> bool {
+        let leases = self.active_leases.read().unwrap();
+        let mut current = Some(lease_id);
+        while let Some(id) = current {
+            if id == ancestor {
+                return true;
             }
-            None => Err(ConcurError::LeaseExpired),
+            current = leases.leases.get(&id).and_then(|info| info.parent);
         }
+        false
     }
 
-    /// Revoke lease
+    /// Revoke lease, along with every lease granted under it
     pub async fn revoke_lease(&self, lease_id: u64) -> Result<(), ConcurError> {
-        let mut leases = self.active_leases.write().await;
-        leases.remove(&lease_id);
-        Ok(())
+        let file = {
+            let mut leases = self.active_leases.write().unwrap();
+            if leases.remove_subtree(lease_id).is_empty() {
+                return Ok(());
+            }
+            leases.changed()?
+        };
+        self.save(file).await
     }
 
     /// Renew lease if auto-renew is enabled
     pub async fn renew_lease(&self, lease_id: u64, new_expiry: u64) -> Result<(), ConcurError> {
-        let mut leases = self.active_leases.write().await;
-        if let Some(info) = leases.get_mut(&lease_id) {
-            if info.auto_renew {
-                info.expires_at = new_expiry;
-                Ok(())
-            } else {
-                Err(ConcurError::PermissionDenied)
+        let file = {
+            let mut leases = self.active_leases.write().unwrap();
+            if !leases.live(lease_id, unix_now())?.auto_renew {
+                return Err(ConcurError::PermissionDenied);
+            }
+            leases.leases.get_mut(&lease_id).unwrap().expires_at = new_expiry;
+            leases.changed()?
+        };
+        self.save(file).await
+    }
+
+    /// Client heartbeat: extend a live auto-renewing lease to `ttl_secs`
+    /// from now, returning the new expiry
+    pub async fn heartbeat(&self, lease_id: u64) -> Result<u64, ConcurError> {
+        let now = unix_now();
+        let (expires_at, file) = {
+            let mut leases = self.active_leases.write().unwrap();
+            let ttl_secs = leases.live(lease_id, now)?.ttl_secs;
+            let info = leases.leases.get_mut(&lease_id).unwrap();
+            if !info.auto_renew {
+                return Err(ConcurError::PermissionDenied);
+            }
+            info.expires_at = info.expires_at.max(now + ttl_secs);
+            (info.expires_at, leases.changed()?)
+        };
+        self.save(file).await?;
+        Ok(expires_at)
+    }
+
+    /// Remove every expired lease and those granted under them, returning
+    /// their ids
+    pub async fn reap_expired(&self) -> Result<Vec<u64>, ConcurError> {
+        let now = unix_now();
+        let (reaped, file) = {
+            let mut leases = self.active_leases.write().unwrap();
+            let expired: Vec<u64> = leases.leases.iter()
+                .filter(|(_, info)| now > info.expires_at)
+                .map(|(id, _)| *id)
+                .collect();
+            let mut reaped = Vec::new();
+            for lease_id in expired {
+                reaped.extend(leases.remove_subtree(lease_id));
+            }
+            if reaped.is_empty() {
+                return Ok(reaped);
+            }
+            (reaped, leases.changed()?)
+        };
+        self.save(file).await?;
+        Ok(reaped)
+    }
+
+    /// Run [`LeaseManager::reap_expired`] every `interval` in the background
+    pub fn spawn_reaper(&self, interval: std::time::Duration) -> tokio::task::JoinHandle<()> {
+        let manager = self.clone();
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            loop {
+                ticks.tick().await;
+                // A failed save is retried on the next tick
+                let _ = manager.reap_expired().await;
+            }
+        })
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        self.release_locks();
+    }
+}
+
+/// Lock mode on a resource
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LockMode {
+    /// Compatible with other shared holders
+    Shared,
+    /// Excludes every other holder
+    Exclusive,
+}
+
+/// Per-CID shared/exclusive locks held by transactions, with a wait-for
+/// graph for deadlock detection
+/// Merkle DAG: enishi_concur -> txn_safety -> lock_table
+#[derive(Default)]
+struct LockTable {
+    state: std::sync::Mutex<LockState>,
+    // Signalled whenever locks are released
+    released: Notify,
+}
+
+#[derive(Default)]
+struct LockState {
+    holders: HashMap<Cid, HashMap<u64, LockMode>>,
+    held: HashMap<u64, HashSet<Cid>>,
+    // Waiting transaction -> transactions it waits for
+    waits_for: HashMap<u64, HashSet<u64>>,
+}
+
+impl LockState {
+    /// Other transactions whose locks keep `txn` from taking `cid` in `mode`
+    fn blockers(&self, txn: u64, cid: &Cid, mode: LockMode) -> HashSet<u64> {
+        self.holders.get(cid).into_iter().flatten()
+            .filter(|(holder, held)| **holder != txn && (mode == LockMode::Exclusive || **held == LockMode::Exclusive))
+            .map(|(holder, _)| *holder)
+            .collect()
+    }
+
+    /// Whether waiting on `blockers` would close a cycle back to `txn`
+    fn would_deadlock(&self, txn: u64, blockers: &HashSet<u64>) -> bool {
+        let mut stack: Vec<u64> = blockers.iter().copied().collect();
+        let mut seen = HashSet::new();
+        while let Some(next) = stack.pop() {
+            if next == txn {
+                return true;
+            }
+            if seen.insert(next) {
+                stack.extend(self.waits_for.get(&next).into_iter().flatten().copied());
             }
-        } else {
-            Err(ConcurError::LeaseExpired)
         }
+        false
+    }
+
+    fn grant(&mut self, txn: u64, cid: Cid, mode: LockMode) {
+        let held = self.holders.entry(cid).or_default().entry(txn).or_insert(mode);
+        if mode == LockMode::Exclusive {
+            *held = LockMode::Exclusive;
+        }
+        self.held.entry(txn).or_default().insert(cid);
+        self.waits_for.remove(&txn);
+    }
+}
+
+impl LockTable {
+    /// Wait until `txn` holds `cid` in `mode` (upgrading a shared lock it
+    /// already holds). Fails with `TransactionConflict` if waiting would
+    /// deadlock or outlast `deadline`.
+    async fn acquire(&self, txn: u64, cid: Cid, mode: LockMode, deadline: tokio::time::Instant) -> Result<(), ConcurError> {
+        loop {
+            // Register for wake-ups before checking, so a release in between is not missed
+            let released = self.released.notified();
+            tokio::pin!(released);
+            released.as_mut().enable();
+
+            {
+                let mut state = self.state.lock().unwrap();
+                let blockers = state.blockers(txn, &cid, mode);
+                if blockers.is_empty() {
+                    state.grant(txn, cid, mode);
+                    return Ok(());
+                }
+                if state.would_deadlock(txn, &blockers) {
+                    state.waits_for.remove(&txn);
+                    return Err(ConcurError::TransactionConflict);
+                }
+                state.waits_for.insert(txn, blockers);
+            }
+
+            if tokio::time::timeout_at(deadline, released).await.is_err() {
+                self.state.lock().unwrap().waits_for.remove(&txn);
+                return Err(ConcurError::TransactionConflict);
+            }
+        }
+    }
+
+    fn release_all(&self, txn: u64) {
+        {
+            let mut state = self.state.lock().unwrap();
+            for cid in state.held.remove(&txn).unwrap_or_default() {
+                if let Some(holders) = state.holders.get_mut(&cid) {
+                    holders.remove(&txn);
+                    if holders.is_empty() {
+                        state.holders.remove(&cid);
+                    }
+                }
+            }
+            state.waits_for.remove(&txn);
+        }
+        self.released.notify_waiters();
+    }
+
+    fn mode(&self, txn: u64, cid: &Cid) -> Option<LockMode> {
+        self.state.lock().unwrap().holders.get(cid)?.get(&txn).copied()
     }
 }
 
@@ -331,87 +821,143 @@ pub struct ResourceManager {
     resources: Arc<RwLock<std::collections::HashMap<Cid, Arc<RwLock<CapCid>>>>>,
     lease_manager: LeaseManager,
     next_txn_id: Arc<Mutex<u64>>,
+    locks: Arc<LockTable>,
+    authority: Arc<CapAuthority>,
 }
 
 impl ResourceManager {
     pub fn new() -> Self {
+        Self::with_authority(CapAuthority::generate())
+    }
+
+    /// Manager whose caps are minted and verified by `authority`
+    pub fn with_authority(authority: CapAuthority) -> Self {
         Self {
             resources: Arc::new(RwLock::new(std::collections::HashMap::new())),
             lease_manager: LeaseManager::new(),
             next_txn_id: Arc::new(Mutex::new(1)),
+            locks: Arc::new(LockTable::default()),
+            authority: Arc::new(authority),
         }
     }
 
+    pub fn authority(&self) -> &CapAuthority {
+        &self.authority
+    }
+
     /// Create new transaction
     pub async fn begin_transaction(&self) -> Result<Transaction, ConcurError> {
         let mut next_id = self.next_txn_id.lock().await;
         let txn_id = *next_id;
         *next_id += 1;
 
-        Ok(Transaction::new(txn_id))
+        let mut txn = Transaction::new(txn_id);
+        txn.locks = Some(self.locks.clone());
+        txn.authority = Some(self.authority.clone());
+        Ok(txn)
+    }
+
+    /// Lock `txn` currently holds on a resource
+    pub fn lock_mode(&self, cid: &Cid, txn: &Transaction) -> Option<LockMode> {
+        self.locks.mode(txn.id, cid)
     }
 
-    /// Register resource with capability
+    /// Lock a registered resource for `txn`, waiting for conflicting holders
+    async fn lock(&self, cid: &Cid, txn: &mut Transaction, mode: LockMode) -> Result<Arc<RwLock<CapCid>>, ConcurError> {
+        let resource = self.resources.read().await.get(cid).cloned()
+            .ok_or(ConcurError::OwnershipViolation)?;
+        let locks = txn.locks.clone().ok_or(ConcurError::OwnershipViolation)?;
+        if !Arc::ptr_eq(&locks, &self.locks) {
+            return Err(ConcurError::OwnershipViolation);
+        }
+
+        locks.acquire(txn.id, *cid, mode, txn.deadline()).await?;
+        if !txn.borrowed_resources.iter().any(|borrowed| Arc::ptr_eq(borrowed, &resource)) {
+            txn.add_borrowed(resource.clone());
+        }
+        Ok(resource)
+    }
+
+    /// Register resource with capability; the cap must have been minted by
+    /// this manager's authority
     pub async fn register_resource(&self, cid: Cid, cap: Cap) -> Result<(), ConcurError> {
+        self.authority.verify(&cap)?;
         let cap_cid = CapCid::new(cid, cap);
         let mut resources = self.resources.write().await;
         resources.insert(cid, Arc::new(RwLock::new(cap_cid)));
         Ok(())
     }
 
-    /// Acquire exclusive ownership (mutable borrow)
+    /// Acquire exclusive ownership (mutable borrow): waits until no other
+    /// transaction holds the resource. Requires write permission.
     pub async fn acquire_exclusive(&self, cid: &Cid, txn: &mut Transaction) -> Result<(), ConcurError> {
-        let resources = self.resources.read().await;
-        if let Some(resource) = resources.get(cid) {
-            txn.check_write_perm(cid).await?;
-            txn.add_borrowed(resource.clone());
-            Ok(())
-        } else {
-            Err(ConcurError::OwnershipViolation)
+        let resource = self.resources.read().await.get(cid).cloned()
+            .ok_or(ConcurError::OwnershipViolation)?;
+        let cap = resource.read().await.cap;
+        self.authority.verify(&cap)?;
+        if !cap.has_perm(perms::WRITE) {
+            return Err(ConcurError::PermissionDenied);
         }
+        self.lock(cid, txn, LockMode::Exclusive).await?;
+        Ok(())
     }
 
-    /// Acquire shared ownership (immutable borrow)
+    /// Acquire shared ownership (immutable borrow): waits while another
+    /// transaction holds the resource exclusively
     pub async fn acquire_shared(&self, cid: &Cid, txn: &mut Transaction) -> Result<(), ConcurError> {
-        let resources = self.resources.read().await;
-        if let Some(resource) = resources.get(cid) {
-            txn.add_borrowed(resource.clone());
-            Ok(())
-        } else {
-            Err(ConcurError::OwnershipViolation)
-        }
+        self.lock(cid, txn, LockMode::Shared).await?;
+        Ok(())
     }
 
-    /// Commit transaction with ownership transfer
-    pub async fn commit_transaction(&self, txn: Transaction) -> Result<(), ConcurError> {
-        if txn.is_expired() {
-            return Err(ConcurError::TransactionConflict);
-        }
-
-        // Validate all capability checks
-        for borrowed in &txn.borrowed_resources {
-            let cap_cid = borrowed.read().await;
-            // Additional validation could be added here
-        }
+    /// Commit transaction with ownership transfer, releasing its locks.
+    /// Fails with `TransactionConflict` (locks still released) if it expired.
+    pub async fn commit_transaction(&self, mut txn: Transaction) -> Result<(), ConcurError> {
+        let result = if txn.is_expired() {
+            Err(ConcurError::TransactionConflict)
+        } else {
+            // Every borrowed resource must still be registered
+            let resources = self.resources.read().await;
+            let mut still_registered = true;
+            for borrowed in &txn.borrowed_resources {
+                let cid = borrowed.read().await.cid;
+                still_registered &= resources.get(&cid).is_some_and(|current| Arc::ptr_eq(current, borrowed));
+            }
+            if still_registered { Ok(()) } else { Err(ConcurError::OwnershipViolation) }
+        };
 
-        // Transaction committed successfully
-        Ok(())
+        txn.release_locks();
+        result
     }
 
     /// Abort transaction and release resources
-    pub async fn abort_transaction(&self, txn: Transaction) -> Result<(), ConcurError> {
-        // Resources are automatically released when transaction is dropped
-        // due to Rust's ownership system
+    pub async fn abort_transaction(&self, mut txn: Transaction) -> Result<(), ConcurError> {
+        txn.release_locks();
         Ok(())
     }
 }
 
 /// Phase D: Capability Tracer for audit trail
+///
+/// Entries are sealed into a hash chain ([`AuditRecord`]); the most recent
+/// are kept in memory and, for a persistent tracer, every record is appended
+/// to an [`AuditFile`] before the operation is reported as recorded. Clones
+/// share the same log.
+#[derive(Clone)]
 pub struct CapTracer {
-    trace_log: Arc<RwLock<Vec<CapTraceEntry>>>,
+    trace_log: Arc<RwLock<TraceLog>>,
+}
+
+struct TraceLog {
+    recent: VecDeque<AuditRecord>,
+    next_seq: u64,
+    head: Cid,
+    file: Option<AuditFile>,
 }
 
-#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+/// Records kept in memory
+const RECENT_RECORDS: usize = 1000;
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct CapTraceEntry {
     pub timestamp: u64,
     pub operation: String,
@@ -424,8 +970,19 @@ pub struct CapTraceEntry {
 
 impl CapTracer {
     pub fn new() -> Self {
+        Self::with_file(None, None)
+    }
+
+    /// Tracer that appends to the on-disk log at `path`, continuing its chain
+    pub fn persistent(path: &Path, max_segment_bytes: u64) -> Result<Self, AuditError> {
+        let (file, last) = AuditFile::open(path, max_segment_bytes)?;
+        Ok(Self::with_file(Some(file), last))
+    }
+
+    fn with_file(file: Option<AuditFile>, last: Option<AuditRecord>) -> Self {
+        let (next_seq, head) = last.map_or((1, GENESIS), |record| (record.seq + 1, record.cid));
         Self {
-            trace_log: Arc::new(RwLock::new(Vec::new())),
+            trace_log: Arc::new(RwLock::new(TraceLog { recent: VecDeque::new(), next_seq, head, file })),
         }
     }
 
@@ -438,12 +995,9 @@ impl CapTracer {
         capability: &Cap,
         success: bool,
         details: &str,
-    ) {
+    ) -> Result<(), AuditError> {
         let entry = CapTraceEntry {
-            timestamp: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
+            timestamp: unix_now(),
             operation: operation.to_string(),
             actor: actor.to_string(),
             resource: *resource,
@@ -453,31 +1007,64 @@ impl CapTracer {
         };
 
         let mut log = self.trace_log.write().await;
-        log.push(entry);
+        let record = AuditRecord::seal(log.next_seq, log.head, entry);
+        if let Some(file) = log.file.as_mut() {
+            file.append(&record)?;
+        }
+        log.next_seq += 1;
+        log.head = record.cid;
+        log.recent.push_back(record);
 
-        // Keep only recent entries (last 1000)
-        if log.len() > 1000 {
-            log.remove(0);
+        if log.recent.len() > RECENT_RECORDS {
+            log.recent.pop_front();
         }
+        Ok(())
     }
 
     /// Get audit trail for resource
     pub async fn get_audit_trail(&self, resource: &Cid) -> Vec<CapTraceEntry> {
         let log = self.trace_log.read().await;
-        log.iter()
-            .filter(|entry| entry.resource == *resource)
-            .cloned()
+        log.recent.iter()
+            .filter(|record| record.entry.resource == *resource)
+            .map(|record| record.entry.clone())
             .collect()
     }
 
     /// Get operations by actor
     pub async fn get_actor_operations(&self, actor: &str) -> Vec<CapTraceEntry> {
         let log = self.trace_log.read().await;
-        log.iter()
-            .filter(|entry| entry.actor == actor)
-            .cloned()
+        log.recent.iter()
+            .filter(|record| record.entry.actor == actor)
+            .map(|record| record.entry.clone())
             .collect()
     }
+
+    /// Records matching `query`, oldest first: the whole on-disk log for a
+    /// persistent tracer, the recent records otherwise
+    pub async fn query(&self, query: &AuditQuery) -> Result<Vec<AuditRecord>, AuditError> {
+        let log = self.trace_log.read().await;
+        match &log.file {
+            Some(file) => file.query(query),
+            None => Ok(query.select(log.recent.iter())),
+        }
+    }
+
+    /// Check the hash chain: every segment from genesis for a persistent
+    /// tracer, the recent records otherwise
+    pub async fn verify(&self) -> Result<AuditVerification, AuditError> {
+        let log = self.trace_log.read().await;
+        if let Some(file) = &log.file {
+            return file.verify();
+        }
+        let Some(first) = log.recent.front() else {
+            return Ok(AuditVerification { records: 0, segments: 0, head: log.head });
+        };
+        let mut chain = ChainCheck::new(first.seq, first.prev);
+        for record in &log.recent {
+            chain.check(record)?;
+        }
+        Ok(AuditVerification { records: log.recent.len() as u64, segments: 0, head: log.head })
+    }
 }
 
 /// Phase D: Safe wrapper for concurrent operations
@@ -488,12 +1075,24 @@ pub struct SafeExecutor {
 
 impl SafeExecutor {
     pub fn new() -> Self {
+        Self::with_resource_manager(ResourceManager::new())
+    }
+
+    pub fn with_resource_manager(resource_manager: ResourceManager) -> Self {
         Self {
-            resource_manager: ResourceManager::new(),
+            resource_manager,
             tracer: CapTracer::new(),
         }
     }
 
+    pub fn resource_manager(&self) -> &ResourceManager {
+        &self.resource_manager
+    }
+
+    pub fn tracer(&self) -> &CapTracer {
+        &self.tracer
+    }
+
     /// Execute operation with full Own+CFA safety
     pub async fn execute_safe<F, Fut, T>(
         &self,
@@ -519,6 +1118,13 @@ impl SafeExecutor {
             temp
         };
 
+        // Only run the operation under a genuine, unexpired cap
+        if let Err(e) = self.resource_manager.authority.verify(&cap_cid.cap) {
+            self.tracer.record_operation(operation, actor, resource, &cap_cid.cap, false, &e.to_string()).await?;
+            self.resource_manager.abort_transaction(txn).await?;
+            return Err(e);
+        }
+
         // Execute operation
         let result = cap_check().await;
 
@@ -532,7 +1138,7 @@ impl SafeExecutor {
             &cap_cid.cap,
             success,
             details,
-        ).await;
+        ).await?;
 
         // Commit or abort transaction
         match result {
@@ -599,7 +1205,7 @@ mod tests {
     async fn test_transaction_lifecycle() {
         let rm = ResourceManager::new();
         let cid = Cid::hash(b"test resource");
-        let cap = Cap::new(0, 100, perms::READ | perms::WRITE);
+        let cap = rm.authority().mint(0, 100, perms::READ | perms::WRITE, 0);
 
         // Register resource
         rm.register_resource(cid, cap).await.unwrap();
@@ -617,6 +1223,216 @@ mod tests {
         rm.commit_transaction(txn).await.unwrap();
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_exclusive_locks_exclude() {
+        let rm = Arc::new(ResourceManager::new());
+        let cid = Cid::hash(b"contended");
+        rm.register_resource(cid, rm.authority().mint(0, 100, perms::READ | perms::WRITE, 0)).await.unwrap();
+
+        let mut first = rm.begin_transaction().await.unwrap();
+        rm.acquire_exclusive(&cid, &mut first).await.unwrap();
+        assert_eq!(rm.lock_mode(&cid, &first), Some(LockMode::Exclusive));
+
+        let waiter = {
+            let rm = rm.clone();
+            tokio::spawn(async move {
+                let mut second = rm.begin_transaction().await.unwrap();
+                rm.acquire_exclusive(&cid, &mut second).await.unwrap();
+                second
+            })
+        };
+
+        // The second acquirer cannot proceed while the first holds the lock
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!waiter.is_finished());
+
+        rm.commit_transaction(first).await.unwrap();
+        let second = tokio::time::timeout(std::time::Duration::from_secs(2), waiter).await
+            .expect("lock not released on commit").unwrap();
+        assert_eq!(rm.lock_mode(&cid, &second), Some(LockMode::Exclusive));
+
+        // Abort and drop release deterministically as well
+        rm.abort_transaction(second).await.unwrap();
+        let mut third = rm.begin_transaction().await.unwrap();
+        rm.acquire_exclusive(&cid, &mut third).await.unwrap();
+        drop(third);
+        let mut fourth = rm.begin_transaction().await.unwrap();
+        rm.acquire_exclusive(&cid, &mut fourth).await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_shared_locks_and_upgrade() {
+        let rm = Arc::new(ResourceManager::new());
+        let cid = Cid::hash(b"shared");
+        rm.register_resource(cid, rm.authority().mint(0, 100, perms::READ | perms::WRITE, 0)).await.unwrap();
+
+        let mut a = rm.begin_transaction().await.unwrap();
+        let mut b = rm.begin_transaction().await.unwrap();
+        rm.acquire_shared(&cid, &mut a).await.unwrap();
+        rm.acquire_shared(&cid, &mut b).await.unwrap();
+
+        // A writer waits for both readers
+        let writer = {
+            let rm = rm.clone();
+            tokio::spawn(async move {
+                let mut w = rm.begin_transaction().await.unwrap();
+                rm.acquire_exclusive(&cid, &mut w).await.unwrap();
+                w
+            })
+        };
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!writer.is_finished());
+        rm.commit_transaction(a).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!writer.is_finished());
+
+        // Once the last reader releases, the writer gets in
+        rm.commit_transaction(b).await.unwrap();
+        let w = writer.await.unwrap();
+        rm.commit_transaction(w).await.unwrap();
+
+        let mut c = rm.begin_transaction().await.unwrap();
+        rm.acquire_shared(&cid, &mut c).await.unwrap();
+        rm.acquire_exclusive(&cid, &mut c).await.unwrap();
+        assert_eq!(rm.lock_mode(&cid, &c), Some(LockMode::Exclusive));
+        rm.acquire_shared(&cid, &mut c).await.unwrap();
+        assert_eq!(rm.lock_mode(&cid, &c), Some(LockMode::Exclusive));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_deadlock_and_timeout_conflicts() {
+        let rm = Arc::new(ResourceManager::new());
+        let a = Cid::hash(b"a");
+        let b = Cid::hash(b"b");
+        let read_only = Cid::hash(b"read only");
+        for cid in [a, b] {
+            rm.register_resource(cid, rm.authority().mint(0, 100, perms::READ | perms::WRITE, 0)).await.unwrap();
+        }
+        rm.register_resource(read_only, rm.authority().mint(0, 100, perms::READ, 0)).await.unwrap();
+
+        let mut t1 = rm.begin_transaction().await.unwrap();
+        let mut t2 = rm.begin_transaction().await.unwrap();
+        assert!(matches!(rm.acquire_exclusive(&read_only, &mut t1).await, Err(ConcurError::PermissionDenied)));
+        rm.acquire_exclusive(&a, &mut t1).await.unwrap();
+        rm.acquire_exclusive(&b, &mut t2).await.unwrap();
+
+        // t1 waits for b; t2 asking for a closes the cycle and is refused
+        let waiter = {
+            let rm = rm.clone();
+            tokio::spawn(async move {
+                rm.acquire_exclusive(&b, &mut t1).await.unwrap();
+                t1
+            })
+        };
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(matches!(rm.acquire_exclusive(&a, &mut t2).await, Err(ConcurError::TransactionConflict)));
+
+        rm.abort_transaction(t2).await.unwrap();
+        let t1 = waiter.await.unwrap();
+        assert_eq!(rm.lock_mode(&b, &t1), Some(LockMode::Exclusive));
+
+        // A wait outlasting the transaction timeout is a conflict too
+        let mut t3 = rm.begin_transaction().await.unwrap().with_timeout(50);
+        assert!(matches!(rm.acquire_shared(&a, &mut t3).await, Err(ConcurError::TransactionConflict)));
+        assert!(matches!(rm.commit_transaction(t3).await, Err(ConcurError::TransactionConflict)));
+        rm.commit_transaction(t1).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_forged_caps_rejected() {
+        let rm = ResourceManager::new();
+        let cid = Cid::hash(b"guarded");
+
+        // Caps not minted by the manager's authority are refused outright
+        assert!(matches!(rm.register_resource(cid, Cap::new(0, 100, perms::WRITE)).await, Err(ConcurError::CapCheckFailed)));
+        let foreign = CapAuthority::generate().mint(0, 100, perms::WRITE, 0);
+        assert!(matches!(rm.register_resource(cid, foreign).await, Err(ConcurError::CapCheckFailed)));
+        let mut escalated = rm.authority().mint(0, 100, perms::READ, 0);
+        escalated.perms |= perms::WRITE;
+        assert!(matches!(rm.register_resource(cid, escalated).await, Err(ConcurError::CapCheckFailed)));
+        let expired = rm.authority().mint(0, 100, perms::WRITE, 1);
+        assert!(matches!(rm.register_resource(cid, expired).await, Err(ConcurError::LeaseExpired)));
+
+        // A fabricated owned cap does not grant write permission
+        let mut txn = rm.begin_transaction().await.unwrap();
+        txn.add_owned(OwnedCapCid::new(0u8, Cap::new(0, 100, perms::WRITE), cid));
+        assert!(matches!(txn.check_write_perm(&cid).await, Err(ConcurError::CapCheckFailed)));
+        let mut txn = rm.begin_transaction().await.unwrap();
+        txn.add_owned(OwnedCapCid::new(0u8, rm.authority().mint(0, 100, perms::WRITE, 0), cid));
+        assert!(txn.check_write_perm(&cid).await.is_ok());
+
+        // Nor does it get an operation through the safe executor
+        let executor = SafeExecutor::new();
+        let rm = executor.resource_manager();
+        rm.register_resource(cid, rm.authority().mint(0, 100, perms::READ, 0)).await.unwrap();
+        assert_eq!(executor.execute_safe("alice", "read", &cid, || async { Ok(7) }).await.unwrap(), 7);
+        rm.resources.read().await[&cid].write().await.cap.perms |= perms::WRITE;
+        let denied = executor.execute_safe("mallory", "write", &cid, || async { Ok(()) }).await;
+        assert!(matches!(denied, Err(ConcurError::CapCheckFailed)));
+        assert!(!executor.tracer().get_actor_operations("mallory").await[0].success);
+    }
+
+    #[test]
+    fn test_namespace_authorities_are_disjoint() {
+        let authority = CapAuthority::generate();
+        let tenant = authority.for_namespace(1);
+        let cap = tenant.mint(0, 100, perms::READ, 0);
+        assert!(tenant.verify(&cap).is_ok());
+        assert!(authority.for_namespace(1).verify(&cap).is_ok());
+        assert!(matches!(authority.verify(&cap), Err(ConcurError::CapCheckFailed)));
+        assert!(matches!(authority.for_namespace(2).verify(&cap), Err(ConcurError::CapCheckFailed)));
+        assert!(authority.for_namespace(0).verify(&authority.mint(0, 100, perms::READ, 0)).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_cap_gate() {
+        let gate = CapGate::new(CapAuthority::generate(), Some(CapTracer::new()));
+        let tenant = gate.for_namespace("tenant", 1);
+        let reader = gate.authority().mint(1, 10, perms::READ, 0);
+        let token = reader.to_token();
+
+        assert!(matches!(gate.authorize(None, "read", perms::READ).await, Err(CapRefusal::Unauthenticated(_))));
+        assert!(matches!(tenant.authorize(Some(&token), "read", perms::READ).await, Err(CapRefusal::Unauthenticated(_))));
+        assert!(matches!(gate.authorize(Some(&token), "write", perms::WRITE).await, Err(CapRefusal::Forbidden(_))));
+        let cap = gate.authorize(Some(&token), "read", perms::READ).await.unwrap();
+        assert!(gate.check_rid(&cap, "read", 10).await.is_ok());
+        assert!(matches!(gate.check_rid(&cap, "read", 11).await, Err(CapRefusal::Forbidden(_))));
+        assert!(gate.check_whole_graph(&cap, "read", 11).await.is_ok());
+        assert!(gate.check_whole_graph(&cap, "read", 12).await.is_err());
+        assert_eq!(gate::scope(&cap), 1..11);
+
+        // Each namespace audits under its own resources
+        let trail = gate.tracer().get_audit_trail(&gate::resource("read")).await;
+        assert_eq!(trail.iter().map(|entry| entry.success).collect::<Vec<_>>(), vec![false, true, false, false]);
+        assert_eq!(trail[0].actor, "anonymous");
+        assert_eq!(trail[1].actor, gate::holder(&reader));
+        let trail = gate.tracer().get_audit_trail(&gate::resource("tenant/read")).await;
+        assert_eq!(trail.len(), 1);
+    }
+
+    #[test]
+    fn test_derivation_only_attenuates() {
+        let authority = CapAuthority::generate();
+        let root = authority.mint(0, 100, perms::READ | perms::WRITE | perms::DERIVE, 0);
+
+        let child = authority.derive(&root, 50, 100, perms::READ | perms::DELEGATE, 4_000_000_000).unwrap();
+        assert!(authority.verify(&child).is_ok());
+        assert_eq!((child.base, child.len, child.perms, child.expiry), (50, 50, perms::READ, 4_000_000_000));
+
+        // The child lacks DERIVE and DELEGATE, so it cannot pass anything on
+        assert!(matches!(authority.derive(&child, 50, 10, perms::READ, 0), Err(ConcurError::PermissionDenied)));
+        assert!(matches!(authority.delegate(&child, perms::READ, 0), Err(ConcurError::PermissionDenied)));
+
+        let delegator = authority.mint(0, 10, perms::READ | perms::DELEGATE, 3_000_000_000);
+        let delegated = authority.delegate(&delegator, perms::READ | perms::WRITE, 0).unwrap();
+        assert_eq!((delegated.base, delegated.len, delegated.perms, delegated.expiry), (0, 10, perms::READ, 3_000_000_000));
+
+        // Only verified parents can be attenuated
+        let mut forged = root;
+        forged.len = u64::MAX;
+        assert!(matches!(authority.derive(&forged, 0, 1000, perms::READ, 0), Err(ConcurError::CapCheckFailed)));
+    }
+
     #[tokio::test]
     async fn test_lease_management() {
         let lm = LeaseManager::new();
@@ -631,6 +1447,8 @@ mod tests {
                 .unwrap()
                 .as_secs() + 3600, // 1 hour from now
             auto_renew: true,
+            parent: None,
+            ttl_secs: 3600,
         };
 
         // Grant lease
@@ -647,6 +1465,97 @@ mod tests {
         assert!(lm.check_lease(lease_id).await.is_err());
     }
 
+    #[tokio::test]
+    async fn test_lease_bound_caps_cascade() {
+        let authority = CapAuthority::generate().with_leases(LeaseManager::new());
+        let leases = authority.leases().unwrap().clone();
+        let root = authority.mint(0, 1000, perms::READ | perms::DERIVE, 0);
+        let terms = |auto_renew| LeaseTerms { holder: "alice".to_string(), ttl_secs: 60, auto_renew };
+
+        let (session, session_lease) = authority.derive_leased(&root, 0, 100, perms::READ | perms::DERIVE, terms(true)).await.unwrap();
+        let narrowed = authority.derive(&session, 0, 10, perms::READ, 0).unwrap();
+        let (worker, worker_lease) = authority.derive_leased(&session, 0, 10, perms::READ, terms(false)).await.unwrap();
+        assert_eq!(narrowed.lease, session_lease);
+        assert!([&session, &narrowed, &worker].iter().all(|cap| authority.verify(cap).is_ok()));
+
+        // Heartbeats extend auto-renewing leases only
+        let extended = leases.heartbeat(session_lease).await.unwrap();
+        assert!(extended >= unix_now() + 60);
+        assert!(matches!(leases.heartbeat(worker_lease).await, Err(ConcurError::PermissionDenied)));
+
+        // Revoking the session ends everything derived from it
+        leases.revoke_lease(session_lease).await.unwrap();
+        for cap in [&session, &narrowed, &worker] {
+            assert!(matches!(authority.verify(cap), Err(ConcurError::LeaseExpired)));
+        }
+        assert!(leases.check_lease(worker_lease).await.is_err());
+        assert!(authority.verify(&root).is_ok());
+
+        // Without a lease manager, bound caps never verify
+        let unleased = CapAuthority::new(CapKey::from_bytes([7; 32]));
+        let cap = CapKey::from_bytes([7; 32]).sign(Cap { lease: 1, ..Cap::new(0, 1, perms::READ) });
+        assert!(matches!(unleased.verify(&cap), Err(ConcurError::LeaseExpired)));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_lease_reaping_and_persistence() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("leases.json");
+        let key = CapKey::from_bytes([3; 32]);
+        let lease = |expires_at, parent| LeaseInfo {
+            resource_id: 0,
+            holder: "bob".to_string(),
+            permissions: perms::READ,
+            expires_at,
+            auto_renew: true,
+            parent,
+            ttl_secs: 60,
+        };
+
+        let (cap, live, stale) = {
+            let authority = CapAuthority::new(key.clone()).with_leases(LeaseManager::open(&path).unwrap());
+            let leases = authority.leases().unwrap();
+            let root = authority.mint(0, 100, perms::READ | perms::DERIVE, 0);
+            let (cap, live) = authority.derive_leased(&root, 0, 100, perms::READ, LeaseTerms { holder: "bob".to_string(), ttl_secs: 60, auto_renew: true }).await.unwrap();
+
+            let stale = leases.grant(lease(unix_now() - 1, None)).await.unwrap();
+            let child = leases.grant_lease(100, lease(unix_now() + 60, Some(stale))).await;
+            assert!(matches!(child, Err(ConcurError::LeaseExpired)));
+            leases.active_leases.write().unwrap().leases.insert(100, lease(unix_now() + 60, Some(stale)));
+
+            let mut reaped = leases.reap_expired().await.unwrap();
+            reaped.sort_unstable();
+            assert_eq!(reaped, vec![stale, 100]);
+            assert!(leases.reap_expired().await.unwrap().is_empty());
+            (cap, live, stale)
+        };
+
+        // Active leases, and the caps bound to them, survive a restart
+        let authority = CapAuthority::new(key).with_leases(LeaseManager::open(&path).unwrap());
+        assert!(authority.verify(&cap).is_ok());
+        let leases = authority.leases().unwrap();
+        // Ids of reaped leases are not handed out again
+        assert!(leases.grant(lease(unix_now() + 60, Some(live))).await.unwrap() > stale);
+        leases.revoke_lease(live).await.unwrap();
+        let reopened = CapAuthority::new(CapKey::from_bytes([3; 32])).with_leases(LeaseManager::open(&path).unwrap());
+        assert!(reopened.verify(&cap).is_err());
+
+        // Concurrent changes save in order: the file ends with all of them
+        let leases = LeaseManager::open(&path).unwrap();
+        let grants: Vec<_> = (0..16).map(|_| {
+            let leases = leases.clone();
+            tokio::spawn(async move { leases.grant(lease(unix_now() + 60, None)).await.unwrap() })
+        }).collect();
+        let mut granted = Vec::new();
+        for grant in grants {
+            granted.push(grant.await.unwrap());
+        }
+        let reopened = LeaseManager::open(&path).unwrap();
+        for lease_id in granted {
+            assert!(reopened.validate(lease_id).is_ok());
+        }
+    }
+
     #[tokio::test]
     async fn test_capability_tracing() {
         let tracer = CapTracer::new();
@@ -661,7 +1570,7 @@ mod tests {
             &cap,
             true,
             "successful read"
-        ).await;
+        ).await.unwrap();
 
         tracer.record_operation(
             "write",
@@ -670,7 +1579,7 @@ mod tests {
             &cap,
             false,
             "permission denied"
-        ).await;
+        ).await.unwrap();
 
         // Check audit trail
         let alice_ops = tracer.get_actor_operations("alice").await;
@@ -680,5 +1589,72 @@ mod tests {
 
         let resource_trail = tracer.get_audit_trail(&cid).await;
         assert_eq!(resource_trail.len(), 2);
+        assert_eq!(tracer.verify().await.unwrap().records, 2);
+    }
+
+    #[tokio::test]
+    async fn test_audit_log_persists_and_rotates() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("logs/audit.log");
+        let cap = Cap::new(0, 100, perms::READ);
+        let (read, write) = (Cid::hash(b"read"), Cid::hash(b"write"));
+
+        let head = {
+            let tracer = CapTracer::persistent(&path, 512).unwrap();
+            for i in 0..6 {
+                let actor = if i % 2 == 0 { "alice" } else { "bob" };
+                tracer.record_operation("read", actor, &read, &cap, true, "ok").await.unwrap();
+            }
+            tracer.verify().await.unwrap().head
+        };
+
+        // The chain continues across a restart and the segments it rotated into
+        let tracer = CapTracer::persistent(&path, 512).unwrap();
+        assert!(tracer.get_actor_operations("alice").await.is_empty());
+        tracer.record_operation("write", "alice", &write, &cap, false, "denied").await.unwrap();
+        let verification = tracer.verify().await.unwrap();
+        assert_eq!(verification.records, 7);
+        assert!(verification.segments > 1);
+
+        let alice = tracer.query(&AuditQuery { actor: Some("alice".into()), ..Default::default() }).await.unwrap();
+        assert_eq!(alice.len(), 4);
+        assert_eq!(alice.last().unwrap().prev, head);
+        let writes = tracer.query(&AuditQuery { resource: Some(write), ..Default::default() }).await.unwrap();
+        assert_eq!(writes.iter().map(|record| record.seq).collect::<Vec<_>>(), vec![7]);
+        let now = unix_now();
+        let window = AuditQuery { since: Some(now - 60), until: Some(now + 60), limit: Some(2), ..Default::default() };
+        assert_eq!(tracer.query(&window).await.unwrap().iter().map(|record| record.seq).collect::<Vec<_>>(), vec![6, 7]);
+        assert!(tracer.query(&AuditQuery { until: Some(now - 60), ..Default::default() }).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_audit_tampering_detected() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("audit.log");
+        let cap = Cap::new(0, 100, perms::READ);
+        let resource = Cid::hash(b"resource");
+
+        let tracer = CapTracer::persistent(&path, DEFAULT_SEGMENT_BYTES).unwrap();
+        for actor in ["alice", "bob", "carol"] {
+            tracer.record_operation("read", actor, &resource, &cap, true, "ok").await.unwrap();
+        }
+        drop(tracer);
+        let original = std::fs::read_to_string(&path).unwrap();
+
+        // Rewriting an entry breaks its Cid
+        std::fs::write(&path, original.replacen("\"bob\"", "\"eve\"", 1)).unwrap();
+        let tracer = CapTracer::persistent(&path, DEFAULT_SEGMENT_BYTES).unwrap();
+        assert!(matches!(tracer.verify().await, Err(AuditError::Tampered { seq: 2, .. })));
+
+        // Dropping one breaks the sequence
+        let lines: Vec<_> = original.lines().collect();
+        std::fs::write(&path, format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+        let tracer = CapTracer::persistent(&path, DEFAULT_SEGMENT_BYTES).unwrap();
+        assert!(matches!(tracer.verify().await, Err(AuditError::Tampered { seq: 2, .. })));
+
+        // A torn final line from a crash is discarded on open
+        std::fs::write(&path, format!("{}{{\"seq\":4", original)).unwrap();
+        let tracer = CapTracer::persistent(&path, DEFAULT_SEGMENT_BYTES).unwrap();
+        assert_eq!(tracer.verify().await.unwrap().records, 3);
     }
 }
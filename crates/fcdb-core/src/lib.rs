@@ -13,7 +13,7 @@ use std::collections::HashMap;
 pub struct Cid([u8; 32]);
 
 impl Cid {
-    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+    pub const fn from_bytes(bytes: [u8; 32]) -> Self {
         Self(bytes)
     }
 
@@ -47,18 +47,28 @@ impl fmt::Display for Cid {
 }
 
 /// Capability (Cap) - Cheri-style capability
+///
+/// `proof` is a keyed BLAKE3 MAC over the other fields, minted by a
+/// [`CapKey`]; a cap whose proof does not verify grants nothing.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Cap {
     pub base: u64,
     pub len: u64,
     pub perms: u32,
+    /// Unix seconds after which the cap is invalid; 0 never expires
+    pub expiry: u64,
+    /// Lease the cap is bound to, 0 for none; a bound cap is only valid
+    /// while the lease is live
+    pub lease: u64,
     pub proof: [u8; 16],
 }
 
 impl Cap {
+    /// Unsigned cap with a random proof, which never verifies; use
+    /// [`CapKey::mint`] for a usable one
     pub fn new(base: u64, len: u64, perms: u32) -> Self {
         let proof = rand::random::<[u8; 16]>();
-        Self { base, len, perms, proof }
+        Self { base, len, perms, expiry: 0, lease: 0, proof }
     }
 
     pub fn contains(&self, addr: u64) -> bool {
@@ -68,6 +78,121 @@ impl Cap {
     pub fn has_perm(&self, perm: u32) -> bool {
         (self.perms & perm) != 0
     }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expiry != 0 && now >= self.expiry
+    }
+
+    /// Intersection with a requested region, perms and expiry: the result
+    /// never exceeds `self` in any of them and stays bound to its lease.
+    /// The proof is left empty.
+    pub fn attenuate(&self, base: u64, len: u64, perms: u32, expiry: u64) -> Cap {
+        let start = self.base.max(base);
+        let end = self.base.saturating_add(self.len).min(base.saturating_add(len));
+        let expiry = match (self.expiry, expiry) {
+            (0, requested) => requested,
+            (current, 0) => current,
+            (current, requested) => current.min(requested),
+        };
+        Cap { base: start, len: end.saturating_sub(start), perms: self.perms & perms, expiry, lease: self.lease, proof: [0; 16] }
+    }
+
+    /// Bearer token form: hex of the fields, little endian, then the proof
+    pub fn to_token(&self) -> String {
+        let mut bytes = Vec::with_capacity(Self::TOKEN_BYTES);
+        bytes.extend_from_slice(&self.base.to_le_bytes());
+        bytes.extend_from_slice(&self.len.to_le_bytes());
+        bytes.extend_from_slice(&self.perms.to_le_bytes());
+        bytes.extend_from_slice(&self.expiry.to_le_bytes());
+        bytes.extend_from_slice(&self.lease.to_le_bytes());
+        bytes.extend_from_slice(&self.proof);
+        hex::encode(bytes)
+    }
+
+    /// Parse a token made by [`Cap::to_token`]; the proof is not checked
+    pub fn from_token(token: &str) -> Option<Cap> {
+        let bytes = hex::decode(token.trim()).ok()?;
+        if bytes.len() != Self::TOKEN_BYTES {
+            return None;
+        }
+        let u64_at = |i: usize| u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap());
+        Some(Cap {
+            base: u64_at(0),
+            len: u64_at(8),
+            perms: u32::from_le_bytes(bytes[16..20].try_into().unwrap()),
+            expiry: u64_at(20),
+            lease: u64_at(28),
+            proof: bytes[36..52].try_into().unwrap(),
+        })
+    }
+
+    const TOKEN_BYTES: usize = 8 + 8 + 4 + 8 + 8 + 16;
+}
+
+/// Server-held secret that mints and verifies capability proofs
+/// Merkle DAG: enishi_core -> cap -> cap_key
+#[derive(Clone)]
+pub struct CapKey([u8; 32]);
+
+impl CapKey {
+    pub fn generate() -> Self {
+        Self(rand::random())
+    }
+
+    pub const fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Key from 64 hex digits, as kept in configuration
+    pub fn from_hex(hex_key: &str) -> Option<Self> {
+        let bytes = hex::decode(hex_key.trim()).ok()?;
+        Some(Self(bytes.try_into().ok()?))
+    }
+
+    /// Independent key for `context`: caps minted by one do not verify under
+    /// the other, and the sub-key reveals nothing about this one
+    pub fn derive(&self, context: &str) -> Self {
+        let mut hasher = blake3::Hasher::new_keyed(&self.0);
+        hasher.update(b"fcdb-cap-subkey");
+        hasher.update(context.as_bytes());
+        Self(*hasher.finalize().as_bytes())
+    }
+
+    /// Cap signed by this key
+    pub fn mint(&self, base: u64, len: u64, perms: u32, expiry: u64) -> Cap {
+        self.sign(Cap { base, len, perms, expiry, lease: 0, proof: [0; 16] })
+    }
+
+    /// `cap` with its proof replaced by this key's MAC
+    pub fn sign(&self, cap: Cap) -> Cap {
+        Cap { proof: self.mac(&cap), ..cap }
+    }
+
+    /// Whether `cap` was minted by this key, unaltered
+    pub fn verify(&self, cap: &Cap) -> bool {
+        let expected = self.mac(cap);
+        // Constant-time comparison
+        expected.iter().zip(cap.proof.iter()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+    }
+
+    fn mac(&self, cap: &Cap) -> [u8; 16] {
+        let mut hasher = blake3::Hasher::new_keyed(&self.0);
+        hasher.update(b"fcdb-cap-v2");
+        hasher.update(&cap.base.to_le_bytes());
+        hasher.update(&cap.len.to_le_bytes());
+        hasher.update(&cap.perms.to_le_bytes());
+        hasher.update(&cap.expiry.to_le_bytes());
+        hasher.update(&cap.lease.to_le_bytes());
+        let mut proof = [0; 16];
+        proof.copy_from_slice(&hasher.finalize().as_bytes()[..16]);
+        proof
+    }
+}
+
+impl fmt::Debug for CapKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CapKey(..)")
+    }
 }
 
 /// Query Key for caching and indexing
@@ -381,6 +506,39 @@ mod tests {
         assert_eq!(cid.as_bytes().len(), 32);
     }
 
+    #[test]
+    fn test_cap_proofs() {
+        let key = CapKey::generate();
+        let cap = key.mint(10, 100, 0b11, 0);
+        assert!(key.verify(&cap));
+        assert!(!CapKey::generate().verify(&cap));
+        assert!(!key.verify(&Cap::new(10, 100, 0b11)));
+
+        let mut forged = cap;
+        forged.perms |= 0b100;
+        assert!(!key.verify(&forged));
+
+        let narrowed = cap.attenuate(50, 100, 0b101, 500);
+        assert_eq!((narrowed.base, narrowed.len, narrowed.perms, narrowed.expiry), (50, 60, 0b01, 500));
+        assert!(narrowed.is_expired(500) && !narrowed.is_expired(499));
+        assert_eq!(narrowed.attenuate(0, 10, 0b1, 0).len, 0);
+
+        let leased = key.sign(Cap { lease: 7, ..cap });
+        assert!(key.verify(&leased));
+        assert!(!key.verify(&Cap { lease: 8, ..leased }));
+        assert_eq!(leased.attenuate(0, 20, 0b1, 0).lease, 7);
+        assert_eq!(Cap::from_token(&leased.to_token()), Some(leased));
+
+        assert_eq!(Cap::from_token(&cap.to_token()), Some(cap));
+        assert_eq!(Cap::from_token("not hex"), None);
+        assert!(CapKey::from_hex(&"ab".repeat(32)).is_some() && CapKey::from_hex("abcd").is_none());
+
+        let sub_key = key.derive("namespace/1");
+        assert!(!sub_key.verify(&cap) && !key.verify(&sub_key.mint(10, 100, 0b11, 0)));
+        assert!(sub_key.verify(&key.derive("namespace/1").mint(10, 100, 0b11, 0)));
+        assert_eq!(Cap::from_token(&cap.to_token()[2..]), None);
+    }
+
     #[test]
     fn test_path_signature() {
         let path1 = &["user", "posts"];
@@ -3,6 +3,16 @@ use serde::{Deserialize, Serialize};
 /// Cypher query AST
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Query {
+    /// Clauses in order; each passes its rows to the next
+    pub statements: Vec<Statement>,
+    pub unions: Vec<Union>,
+}
+
+/// `UNION [ALL]` and the query whose rows it adds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Union {
+    /// Keep duplicate rows
+    pub all: bool,
     pub statements: Vec<Statement>,
 }
 
@@ -10,21 +20,61 @@ pub struct Query {
 pub enum Statement {
     Match(MatchClause),
     Where(WhereClause),
+    /// WITH projects like RETURN, passing its rows on to the next clause
+    With(ReturnClause),
+    Unwind(UnwindClause),
+    Call(CallClause),
     Return(ReturnClause),
+    Create(CreateClause),
+    Merge(MergeClause),
+    Set(SetClause),
+    Remove(RemoveClause),
+    Delete(DeleteClause),
 }
 
-/// MATCH clause
+/// MATCH clause; OPTIONAL MATCH binds null instead of dropping a row
+/// that has no match
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MatchClause {
-    pub pattern: Pattern,
+    /// Comma-separated paths, matched together
+    pub patterns: Vec<Pattern>,
+    pub optional: bool,
 }
 
-/// Graph pattern in MATCH clause
+/// UNWIND clause: one row per element of a list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnwindClause {
+    pub expression: Expression,
+    pub variable: String,
+}
+
+/// `CALL { ... }` subquery, run once for each incoming row
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CallClause {
+    pub query: Box<Query>,
+}
+
+/// Graph pattern: a path of nodes joined by relationships
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Pattern {
+    /// `p = ...`: variable bound to the whole path
+    pub path_variable: Option<String>,
     pub elements: Vec<PatternElement>,
 }
 
+impl Pattern {
+    /// Variables the pattern names, in order, the path's last
+    pub fn variables(&self) -> Vec<&String> {
+        self.elements.iter()
+            .filter_map(|element| match element {
+                PatternElement::Node(node) => node.variable.as_ref(),
+                PatternElement::Relationship(relationship) => relationship.variable.as_ref(),
+            })
+            .chain(self.path_variable.as_ref())
+            .collect()
+    }
+}
+
 /// Pattern element (node or relationship)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum PatternElement {
@@ -32,7 +82,7 @@ pub enum PatternElement {
     Relationship(RelationshipPattern),
 }
 
-/// Node pattern in MATCH
+/// Node pattern
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NodePattern {
     pub variable: Option<String>,
@@ -40,7 +90,7 @@ pub struct NodePattern {
     pub properties: Vec<Property>,
 }
 
-/// Relationship pattern in MATCH
+/// Relationship pattern
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RelationshipPattern {
     pub variable: Option<String>,
@@ -78,21 +128,80 @@ pub struct WhereClause {
     pub condition: Expression,
 }
 
+/// CREATE clause: every pattern is created for each row
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateClause {
+    pub patterns: Vec<Pattern>,
+}
+
+/// MERGE clause: matches the pattern, or creates it if there is no match
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeClause {
+    pub pattern: Pattern,
+    pub on_create: Vec<SetItem>,
+    pub on_match: Vec<SetItem>,
+}
+
+/// SET clause
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetClause {
+    pub items: Vec<SetItem>,
+}
+
+/// One assignment of a SET clause
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SetItem {
+    /// `n.key = value`; a null value removes the property
+    Property { variable: String, property: String, value: Expression },
+    /// `n = {...}` replaces every property, `n += {...}` only the given ones
+    Properties { variable: String, properties: Vec<Property>, replace: bool },
+    /// `n:Label:Other`
+    Labels { variable: String, labels: Vec<String> },
+}
+
+/// REMOVE clause
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoveClause {
+    pub items: Vec<RemoveItem>,
+}
+
+/// One removal of a REMOVE clause
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RemoveItem {
+    Property { variable: String, property: String },
+    Labels { variable: String, labels: Vec<String> },
+}
+
+/// DELETE clause; DETACH DELETE also deletes the nodes' relationships
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteClause {
+    pub expressions: Vec<Expression>,
+    pub detach: bool,
+}
+
 /// RETURN clause
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReturnClause {
     pub items: Vec<ReturnItem>,
     pub distinct: bool,
+    pub order_by: Vec<SortItem>,
     pub limit: Option<u32>,
     pub skip: Option<u32>,
 }
 
-/// Return item
+/// Projected expression and the column it is returned as
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum ReturnItem {
-    Variable(String),
-    Property { variable: String, property: String },
-    Count,
+pub struct ReturnItem {
+    pub expression: Expression,
+    /// The `AS` alias, or the expression as written
+    pub name: String,
+}
+
+/// ORDER BY key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SortItem {
+    pub expression: Expression,
+    pub descending: bool,
 }
 
 /// Expression in WHERE or property values
@@ -102,11 +211,66 @@ pub enum Expression {
     Literal(Literal),
     PropertyAccess { variable: String, property: String },
     BinaryOp { left: Box<Expression>, op: BinaryOperator, right: Box<Expression> },
-    In { left: Box<Expression>, list: Vec<Expression> },
+    Not(Box<Expression>),
+    Negate(Box<Expression>),
+    /// `expr IS NULL`, or `expr IS NOT NULL` when negated
+    IsNull { expression: Box<Expression>, negated: bool },
+    In { left: Box<Expression>, list: Box<Expression> },
+    List(Vec<Expression>),
+    Map(Vec<Property>),
+    /// `CASE [operand] WHEN .. THEN .. [ELSE ..] END`; with an operand each
+    /// `WHEN` value is compared to it, otherwise each is a condition
+    Case {
+        operand: Option<Box<Expression>>,
+        alternatives: Vec<(Expression, Expression)>,
+        default: Option<Box<Expression>>,
+    },
+    /// `name([DISTINCT] args)`; DISTINCT only applies to aggregates
+    FunctionCall { name: String, distinct: bool, arguments: Vec<Expression> },
+    /// `count(*)`
+    CountStar,
+}
+
+/// Aggregate functions, which reduce the rows of a group to one value
+pub const AGGREGATE_FUNCTIONS: &[&str] = &[
+    "count", "sum", "avg", "min", "max", "collect", "percentilecont", "stdev", "stdevp",
+];
+
+impl Expression {
+    /// Whether this is a call of an aggregate function
+    pub fn is_aggregate(&self) -> bool {
+        match self {
+            Expression::CountStar => true,
+            Expression::FunctionCall { name, .. } => AGGREGATE_FUNCTIONS.contains(&name.to_ascii_lowercase().as_str()),
+            _ => false,
+        }
+    }
+
+    /// Sub-expressions directly below this one
+    pub fn children(&self) -> Vec<&Expression> {
+        match self {
+            Expression::Variable(_) | Expression::Literal(_) | Expression::PropertyAccess { .. } | Expression::CountStar => Vec::new(),
+            Expression::BinaryOp { left, right, .. } => vec![left, right],
+            Expression::In { left, list } => vec![left, list],
+            Expression::Not(operand) | Expression::Negate(operand) => vec![operand],
+            Expression::IsNull { expression, .. } => vec![expression],
+            Expression::List(items) | Expression::FunctionCall { arguments: items, .. } => items.iter().collect(),
+            Expression::Map(entries) => entries.iter().map(|entry| &entry.value).collect(),
+            Expression::Case { operand, alternatives, default } => operand.iter().map(|operand| operand.as_ref())
+                .chain(alternatives.iter().flat_map(|(when, then)| [when, then]))
+                .chain(default.iter().map(|default| default.as_ref()))
+                .collect(),
+        }
+    }
+
+    /// Whether an aggregate is called anywhere in this expression
+    pub fn contains_aggregate(&self) -> bool {
+        self.is_aggregate() || self.children().into_iter().any(Expression::contains_aggregate)
+    }
 }
 
 /// Binary operators
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum BinaryOperator {
     Equal,        // =
     NotEqual,     // <>
@@ -114,6 +278,29 @@ pub enum BinaryOperator {
     GreaterThan,  // >
     LessEqual,    // <=
     GreaterEqual, // >=
+    And,
+    Or,
+    Xor,
+    Add,          // +
+    Subtract,     // -
+    Multiply,     // *
+    Divide,       // /
+    Modulo,       // %
+    StartsWith,
+    EndsWith,
+    Contains,
+    RegexMatch,   // =~
+}
+
+impl BinaryOperator {
+    /// Whether the operator compares its operands (`=`, `<>`, `<`, ...)
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            BinaryOperator::Equal | BinaryOperator::NotEqual | BinaryOperator::LessThan |
+            BinaryOperator::GreaterThan | BinaryOperator::LessEqual | BinaryOperator::GreaterEqual
+        )
+    }
 }
 
 /// Literal values
@@ -1,9 +1,12 @@
 use crate::ast::*;
 use crate::parser::parse_query;
-use crate::planner::{ExecutionPlan, QueryPlanner, MatchPlan, TraversalStep, WherePlan, ReturnPlan, ValueRef};
-use fcdb_graph::{GraphDB, Rid};
+use crate::planner::{ClausePlan, ExecutionPlan, QueryPlanner, MatchPlan, WherePlan, ReturnPlan, UpdatePlan};
+use crate::value::{Path, Relationship, Value};
+use crate::CypherError;
+use fcdb_graph::{AdjEntry, GraphDB, Properties, Rid, Transaction};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
 /// Cypher query executor
 pub struct CypherExecutor<'a> {
@@ -18,7 +21,7 @@ pub struct QueryResult {
     pub stats: QueryStats,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct QueryStats {
     pub nodes_created: u32,
     pub nodes_deleted: u32,
@@ -38,132 +41,177 @@ impl<'a> CypherExecutor<'a> {
         }
     }
 
-    /// Execute a Cypher query
+    /// Execute a Cypher query in its own transaction, committed only if the
+    /// whole query succeeds
     /// Merkle DAG: fcdb_cypher -> execute(query) -> result
-    pub async fn execute(&mut self, query: &str) -> Result<QueryResult, crate::CypherError> {
+    pub async fn execute(&mut self, query: &str) -> Result<QueryResult, CypherError> {
+        let mut tx = self.graph.begin();
+        let result = self.execute_in(&mut tx, query).await?;
+        tx.commit().await.map_err(CypherError::graph)?;
+        Ok(result)
+    }
+
+    /// Execute a Cypher query inside a caller-managed transaction; its reads
+    /// see the transaction's earlier writes
+    pub async fn execute_in(&mut self, tx: &mut Transaction<'a>, query: &str) -> Result<QueryResult, CypherError> {
         let start_time = std::time::Instant::now();
 
         // Parse query
         let ast = parse_query(query)
-            .map_err(crate::CypherError::Parse)?;
+            .map_err(CypherError::Parse)?;
 
         // Plan execution
         let plan = self.planner.plan_query(&ast).await
-            .map_err(crate::CypherError::Planning)?;
+            .map_err(CypherError::Planning)?;
 
         // Execute plan
-        let result = self.execute_plan(plan).await?;
-
-        let execution_time = start_time.elapsed().as_millis() as u64;
-
-        // Update stats
-        let stats = QueryStats {
-            nodes_created: 0, // Not implemented yet
-            nodes_deleted: 0,
-            relationships_created: 0,
-            relationships_deleted: 0,
-            labels_added: 0,
-            labels_removed: 0,
-            properties_set: 0,
-            execution_time_ms: execution_time,
-        };
-
-        Ok(QueryResult {
-            columns: result.columns,
-            rows: result.rows,
-            stats,
-        })
+        let mut result = self.execute_plan(tx, plan).await?;
+        result.stats.execution_time_ms = start_time.elapsed().as_millis() as u64;
+        Ok(result)
     }
 
-    async fn execute_plan(&self, plan: ExecutionPlan) -> Result<QueryResult, crate::CypherError> {
-        // Execute MATCH
-        let matches = self.execute_match(&plan.match_plan).await?;
+    async fn execute_plan(&self, tx: &mut Transaction<'a>, plan: ExecutionPlan) -> Result<QueryResult, CypherError> {
+        let mut stats = QueryStats::default();
+        let rows = self.run_query(tx, &plan, MatchResult::default(), &mut stats).await?;
 
-        // Apply WHERE filtering
-        let filtered_matches = if let Some(where_plan) = &plan.where_plan {
-            self.apply_where(matches, where_plan).await?
-        } else {
-            matches
-        };
-
-        // Apply RETURN projection
-        let result = self.apply_return(filtered_matches, &plan.return_plan).await?;
+        let mut json_rows = Vec::with_capacity(rows.len());
+        for projected in rows {
+            let mut row = HashMap::new();
+            for (name, value) in projected.bindings {
+                row.insert(name, self.value_to_json(tx, &value).await?);
+            }
+            json_rows.push(row);
+        }
 
-        Ok(result)
+        Ok(QueryResult { columns: plan.columns(), rows: json_rows, stats })
     }
 
-    async fn execute_match(&self, match_plan: &MatchPlan) -> Result<Vec<MatchResult>, crate::CypherError> {
-        let mut results = Vec::new();
+    /// Returned rows of a query and the queries UNION adds to it, each run
+    /// on the single row `input`
+    async fn run_query(
+        &self,
+        tx: &mut Transaction<'a>,
+        plan: &ExecutionPlan,
+        input: MatchResult,
+        stats: &mut QueryStats,
+    ) -> Result<Vec<MatchResult>, CypherError> {
+        let mut rows = self.run_single_query(tx, plan, input.clone(), stats).await?;
+        for (_, part) in &plan.unions {
+            rows.extend(self.run_single_query(tx, part, input.clone(), stats).await?);
+        }
 
-        // For each start node, execute traversals
-        for &start_rid in &match_plan.start_nodes {
-            let mut current_bindings = HashMap::new();
-            current_bindings.insert("start".to_string(), start_rid);
+        // The planner only allows all parts to be UNION or all UNION ALL
+        if plan.unions.first().is_some_and(|(all, _)| !all) {
+            let columns = plan.columns();
+            let mut seen = BTreeSet::new();
+            rows.retain(|row| seen.insert(GroupKey(columns.iter().map(|column| row.bindings[column].clone()).collect())));
+        }
+        Ok(rows)
+    }
 
-            let result = self.execute_traversals(start_rid, &match_plan.traversals, current_bindings).await?;
-            results.extend(result);
+    /// Pass `input` through each clause in turn, then project the RETURN;
+    /// a query without RETURN returns no rows
+    async fn run_single_query(
+        &self,
+        tx: &mut Transaction<'a>,
+        plan: &ExecutionPlan,
+        input: MatchResult,
+        stats: &mut QueryStats,
+    ) -> Result<Vec<MatchResult>, CypherError> {
+        let mut rows = vec![input];
+        for clause in &plan.clauses {
+            rows = self.apply_clause(tx, rows, clause, stats).await?;
         }
 
-        Ok(results)
+        match &plan.return_plan {
+            Some(return_plan) => self.project(tx, rows, return_plan).await,
+            None => Ok(Vec::new()),
+        }
     }
 
-    async fn execute_traversals(
+    async fn apply_clause(
         &self,
-        start_rid: Rid,
-        traversals: &[TraversalStep],
-        initial_bindings: HashMap<String, Rid>,
-    ) -> Result<Vec<MatchResult>, crate::CypherError> {
-        let mut results = vec![MatchResult {
-            bindings: initial_bindings,
-        }];
-
-        for traversal in traversals {
-            let mut new_results = Vec::new();
-
-            for result in &results {
-                if let Some(&from_rid) = result.bindings.get(&traversal.from_variable) {
-                    // Execute traversal
-                    let traversal_result = self.graph.traverse(
-                        from_rid,
-                        Some(&traversal.relationship_types),
-                        traversal.max_hops.unwrap_or(10) as usize,
-                        None, // No temporal filtering for now
-                    ).await.map_err(|e| crate::CypherError::Execution(e.to_string()))?;
-
-                    for (to_rid, _depth) in traversal_result {
-                        let mut new_bindings = result.bindings.clone();
-                        new_bindings.insert(traversal.to_variable.clone(), to_rid);
-                        new_results.push(MatchResult {
-                            bindings: new_bindings,
-                        });
+        tx: &mut Transaction<'a>,
+        rows: Vec<MatchResult>,
+        clause: &ClausePlan,
+        stats: &mut QueryStats,
+    ) -> Result<Vec<MatchResult>, CypherError> {
+        let mut output = Vec::new();
+        match clause {
+            ClausePlan::Match(match_plan) => {
+                for row in rows {
+                    let mut matches = self.execute_match(tx, match_plan, &row).await?;
+                    if let Some(filter) = &match_plan.filter {
+                        matches = self.apply_where(tx, matches, filter).await?;
+                    }
+                    if matches.is_empty() && match_plan.optional {
+                        let mut row = row;
+                        for variable in &match_plan.variables {
+                            row.bindings.entry(variable.clone()).or_insert(Value::Null);
+                        }
+                        matches.push(row);
                     }
+                    output.extend(matches);
                 }
             }
-
-            results = new_results;
+            ClausePlan::Where(where_plan) => output = self.apply_where(tx, rows, where_plan).await?,
+            ClausePlan::With(projection) => output = self.project(tx, rows, projection).await?,
+            ClausePlan::Unwind { expression, variable } => {
+                for row in rows {
+                    let items = match self.evaluate(tx, &row, expression).await? {
+                        Value::Null => Vec::new(),
+                        Value::List(items) => items,
+                        value => vec![value],
+                    };
+                    for item in items {
+                        let mut row = row.clone();
+                        row.bindings.insert(variable.clone(), item);
+                        output.push(row);
+                    }
+                }
+            }
+            ClausePlan::Call(subquery) => {
+                for row in rows {
+                    let returned = Box::pin(self.run_query(tx, subquery, row.clone(), stats)).await?;
+                    // A subquery without RETURN runs for its updates alone
+                    if subquery.return_plan.is_none() {
+                        output.push(row);
+                        continue;
+                    }
+                    for projected in returned {
+                        let mut row = row.clone();
+                        row.bindings.extend(projected.bindings);
+                        output.push(row);
+                    }
+                }
+            }
+            ClausePlan::Update(update) => output = self.apply_update(tx, rows, update, stats).await?,
         }
+        Ok(output)
+    }
 
-        Ok(results)
+    /// Matches of a MATCH extending `row`; variables `row` already binds
+    /// must be matched by their own values
+    async fn execute_match(&self, tx: &Transaction<'a>, match_plan: &MatchPlan, row: &MatchResult) -> Result<Vec<MatchResult>, CypherError> {
+        let start_nodes = match &match_plan.start_index {
+            Some(seek) => seek.resolve(tx).await,
+            None => None,
+        };
+        self.match_patterns(tx, &match_plan.patterns, row, start_nodes.as_deref()).await
     }
 
     async fn apply_where(
         &self,
+        tx: &Transaction<'a>,
         matches: Vec<MatchResult>,
         where_plan: &WherePlan,
-    ) -> Result<Vec<MatchResult>, crate::CypherError> {
+    ) -> Result<Vec<MatchResult>, CypherError> {
         let mut filtered = Vec::new();
 
+        // Rows where the condition is false or null are dropped
         for match_result in matches {
-            let mut passes = true;
-
-            for condition in &where_plan.conditions {
-                if !self.evaluate_condition(&match_result, condition).await? {
-                    passes = false;
-                    break;
-                }
-            }
-
-            if passes {
+            let value = self.evaluate(tx, &match_result, &where_plan.condition).await?;
+            if value.truth().map_err(CypherError::Execution)? == Some(true) {
                 filtered.push(match_result);
             }
         }
@@ -171,140 +219,1165 @@ impl<'a> CypherExecutor<'a> {
         Ok(filtered)
     }
 
-    async fn evaluate_condition(
+    /// Project rows onto the items of a RETURN or WITH: group and aggregate, drop
+    /// duplicates, sort, then skip and limit. Each projected row binds the
+    /// item names.
+    async fn project(
         &self,
-        match_result: &MatchResult,
-        condition: &crate::planner::Condition,
-    ) -> Result<bool, crate::CypherError> {
-        let left_value = self.resolve_value_ref(match_result, &condition.left).await?;
-        let right_value = self.resolve_value_ref(match_result, &condition.right).await?;
+        tx: &Transaction<'a>,
+        rows: Vec<MatchResult>,
+        return_plan: &ReturnPlan,
+    ) -> Result<Vec<MatchResult>, CypherError> {
+        let mut sort_expressions: Vec<Expression> = return_plan.order_by.iter()
+            .map(|sort| sort.expression.clone())
+            .collect();
 
-        match condition.op {
-            crate::ast::BinaryOperator::Equal => Ok(left_value == right_value),
-            crate::ast::BinaryOperator::NotEqual => Ok(left_value != right_value),
-            // Add other operators as needed
-            _ => Err(crate::CypherError::Execution("Unsupported operator".to_string())),
+        // Each output is a projected row and the row its ORDER BY keys are
+        // evaluated in, which also binds the item names
+        let mut outputs = Vec::new();
+        if return_plan.items.iter().any(|item| item.expression.contains_aggregate()) {
+            // Aggregate calls are evaluated per group and bound to hidden names
+            let mut aggregates = Vec::new();
+            let items: Vec<Expression> = return_plan.items.iter()
+                .map(|item| extract_aggregates(&item.expression, &mut aggregates))
+                .collect();
+            sort_expressions = sort_expressions.iter()
+                .map(|expression| extract_aggregates(expression, &mut aggregates))
+                .collect();
+
+            for (mut scope, keys, inputs) in self.group(tx, rows, return_plan, &aggregates).await? {
+                for (i, (aggregate, inputs)) in aggregates.iter().zip(inputs).enumerate() {
+                    let value = self.reduce(tx, &scope, aggregate, inputs).await?;
+                    scope.bindings.insert(aggregate_variable(i), value);
+                }
+                let mut projected = MatchResult::default();
+                let mut keys = keys.into_iter();
+                for (item, expression) in return_plan.items.iter().zip(&items) {
+                    let value = if item.expression.contains_aggregate() {
+                        self.evaluate(tx, &scope, expression).await?
+                    } else {
+                        keys.next().unwrap_or(Value::Null)
+                    };
+                    projected.bindings.insert(item.name.clone(), value);
+                }
+                scope.bindings.extend(projected.bindings.clone());
+                outputs.push((projected, scope));
+            }
+        } else {
+            for mut scope in rows {
+                let mut projected = MatchResult::default();
+                for item in &return_plan.items {
+                    let value = self.evaluate(tx, &scope, &item.expression).await?;
+                    projected.bindings.insert(item.name.clone(), value);
+                }
+                scope.bindings.extend(projected.bindings.clone());
+                outputs.push((projected, scope));
+            }
+        }
+
+        if return_plan.distinct {
+            let mut seen = BTreeSet::new();
+            outputs.retain(|(projected, _)| {
+                seen.insert(GroupKey(return_plan.items.iter().map(|item| projected.bindings[&item.name].clone()).collect()))
+            });
         }
+
+        let mut projected: Vec<MatchResult> = if return_plan.order_by.is_empty() {
+            outputs.into_iter().map(|(projected, _)| projected).collect()
+        } else {
+            let mut keyed = Vec::with_capacity(outputs.len());
+            for (projected, scope) in outputs {
+                let mut keys = Vec::with_capacity(sort_expressions.len());
+                for expression in &sort_expressions {
+                    keys.push(self.evaluate(tx, &scope, expression).await?);
+                }
+                keyed.push((keys, projected));
+            }
+            keyed.sort_by(|(a, _), (b, _)| {
+                a.iter().zip(b).zip(&return_plan.order_by)
+                    .map(|((a, b), sort)| if sort.descending { b.order(a) } else { a.order(b) })
+                    .find(|ordering| ordering.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            });
+            keyed.into_iter().map(|(_, projected)| projected).collect()
+        };
+
+        // SKIP applies before LIMIT
+        let skip = return_plan.skip.map_or(0, |skip| skip as usize).min(projected.len());
+        projected.drain(..skip);
+        if let Some(limit) = return_plan.limit {
+            projected.truncate(limit as usize);
+        }
+        Ok(projected)
     }
 
-    async fn resolve_value_ref(
+    /// Group rows by the values of the non-aggregate items, in order of first
+    /// appearance, collecting the non-null argument values of each
+    /// aggregate. Without grouping keys there is always one group, even for
+    /// no rows.
+    async fn group(
         &self,
-        match_result: &MatchResult,
-        value_ref: &ValueRef,
-    ) -> Result<serde_json::Value, crate::CypherError> {
-        match value_ref {
-            ValueRef::Variable(var) => {
-                if let Some(&rid) = match_result.bindings.get(var) {
-                    if let Ok(Some(data)) = self.graph.get_node(rid).await {
-                        Ok(serde_json::from_slice(&data)
-                            .unwrap_or(serde_json::Value::Null))
-                    } else {
-                        Ok(serde_json::Value::Null)
+        tx: &Transaction<'a>,
+        rows: Vec<MatchResult>,
+        return_plan: &ReturnPlan,
+        aggregates: &[Expression],
+    ) -> Result<Vec<(MatchResult, Vec<Value>, Vec<Vec<Value>>)>, CypherError> {
+        let key_items: Vec<&Expression> = return_plan.items.iter()
+            .map(|item| &item.expression)
+            .filter(|expression| !expression.contains_aggregate())
+            .collect();
+
+        let mut groups = Vec::new();
+        let mut index = BTreeMap::new();
+        for row in rows {
+            let mut keys = Vec::with_capacity(key_items.len());
+            for expression in &key_items {
+                keys.push(self.evaluate(tx, &row, expression).await?);
+            }
+            let slot = *index.entry(GroupKey(keys.clone())).or_insert_with(|| {
+                groups.push((row.clone(), keys, vec![Vec::new(); aggregates.len()]));
+                groups.len() - 1
+            });
+
+            for (aggregate, inputs) in aggregates.iter().zip(&mut groups[slot].2) {
+                let value = match aggregate {
+                    // Every row counts, so each is recorded as a non-null value
+                    Expression::CountStar => Value::Bool(true),
+                    Expression::FunctionCall { arguments, .. } => match arguments.first() {
+                        Some(argument) => self.evaluate(tx, &row, argument).await?,
+                        None => return Err(CypherError::Execution("Aggregate functions take an argument".to_string())),
+                    },
+                    _ => unreachable!("only aggregates are extracted"),
+                };
+                if value != Value::Null {
+                    inputs.push(value);
+                }
+            }
+        }
+
+        if groups.is_empty() && key_items.is_empty() {
+            groups.push((MatchResult::default(), Vec::new(), vec![Vec::new(); aggregates.len()]));
+        }
+        Ok(groups)
+    }
+
+    /// Final value of an aggregate over the values collected for a group;
+    /// `scope` is the group's first row, for `percentileCont`'s percentile
+    async fn reduce(&self, tx: &Transaction<'a>, scope: &MatchResult, aggregate: &Expression, mut values: Vec<Value>) -> Result<Value, CypherError> {
+        let Expression::FunctionCall { name, distinct, arguments } = aggregate else {
+            return Ok(Value::Int(values.len() as i64));
+        };
+        let name = name.to_ascii_lowercase();
+        if *distinct {
+            let mut seen = BTreeSet::new();
+            values.retain(|value| seen.insert(GroupKey(vec![value.clone()])));
+        }
+        let numbers = || values.iter()
+            .map(|value| value.as_float().ok_or_else(|| {
+                CypherError::Execution(format!("{}() expects numbers, got {}", name, value.type_name()))
+            }))
+            .collect::<Result<Vec<f64>, _>>();
+
+        Ok(match name.as_str() {
+            "count" => Value::Int(values.len() as i64),
+            "collect" => Value::List(values),
+            "min" => values.into_iter().min_by(Value::order).unwrap_or(Value::Null),
+            "max" => values.into_iter().max_by(Value::order).unwrap_or(Value::Null),
+            "sum" => {
+                let mut total = Value::Int(0);
+                for value in &values {
+                    if value.as_float().is_none() {
+                        return Err(CypherError::Execution(format!("sum() expects numbers, got {}", value.type_name())));
                     }
+                    total = total.arithmetic(&BinaryOperator::Add, value).map_err(CypherError::Execution)?;
+                }
+                total
+            }
+            "avg" => {
+                let numbers = numbers()?;
+                if numbers.is_empty() {
+                    Value::Null
                 } else {
-                    Ok(serde_json::Value::Null)
+                    Value::Float(numbers.iter().sum::<f64>() / numbers.len() as f64)
                 }
             }
-            ValueRef::Property { variable, property } => {
-                if let Some(&rid) = match_result.bindings.get(variable) {
-                    if let Ok(Some(data)) = self.graph.get_node(rid).await {
-                        let json: serde_json::Value = serde_json::from_slice(&data)
-                            .unwrap_or(serde_json::Value::Null);
-                        Ok(json.get(property).cloned().unwrap_or(serde_json::Value::Null))
-                    } else {
-                        Ok(serde_json::Value::Null)
-                    }
+            "stdev" | "stdevp" => {
+                let numbers = numbers()?;
+                let n = numbers.len() as f64;
+                let divisor = if name == "stdev" { n - 1.0 } else { n };
+                if divisor <= 0.0 {
+                    Value::Float(0.0)
                 } else {
-                    Ok(serde_json::Value::Null)
+                    let mean = numbers.iter().sum::<f64>() / n;
+                    Value::Float((numbers.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / divisor).sqrt())
                 }
             }
-            ValueRef::Literal(lit) => {
-                let value = match lit {
-                    Literal::String(s) => serde_json::Value::String(s.clone()),
-                    Literal::Integer(i) => serde_json::Value::Number((*i).into()),
-                    Literal::Float(f) => serde_json::Value::Number(serde_json::Number::from_f64(*f).unwrap()),
-                    Literal::Boolean(b) => serde_json::Value::Bool(*b),
-                    Literal::Null => serde_json::Value::Null,
+            "percentilecont" => {
+                let percentile = match arguments.get(1) {
+                    Some(argument) => self.evaluate(tx, scope, argument).await?.as_float(),
+                    None => None,
                 };
-                Ok(value)
+                let percentile = percentile.filter(|p| (0.0..=1.0).contains(p)).ok_or_else(|| {
+                    CypherError::Execution("percentileCont() takes a percentile between 0.0 and 1.0".to_string())
+                })?;
+                let mut numbers = numbers()?;
+                if numbers.is_empty() {
+                    return Ok(Value::Null);
+                }
+                numbers.sort_by(f64::total_cmp);
+                // Linear interpolation between the closest ranks
+                let position = percentile * (numbers.len() - 1) as f64;
+                let (lower, upper) = (numbers[position.floor() as usize], numbers[position.ceil() as usize]);
+                Value::Float(lower + (upper - lower) * position.fract())
+            }
+            _ => unreachable!("not an aggregate function"),
+        })
+    }
+
+    /// Apply one updating clause to every row; the rows it returns carry the
+    /// variables the clause bound
+    async fn apply_update(
+        &self,
+        tx: &mut Transaction<'a>,
+        rows: Vec<MatchResult>,
+        update: &UpdatePlan,
+        stats: &mut QueryStats,
+    ) -> Result<Vec<MatchResult>, CypherError> {
+        let mut updated = Vec::with_capacity(rows.len());
+        match update {
+            UpdatePlan::Create(create) => {
+                for mut row in rows {
+                    for pattern in &create.patterns {
+                        self.create_pattern(tx, &mut row, pattern, stats).await?;
+                    }
+                    updated.push(row);
+                }
+            }
+            UpdatePlan::Merge(merge) => {
+                for row in rows {
+                    let matches = self.match_patterns(tx, std::slice::from_ref(&merge.pattern), &row, None).await?;
+                    if matches.is_empty() {
+                        let mut row = row;
+                        self.create_pattern(tx, &mut row, &merge.pattern, stats).await?;
+                        for item in &merge.on_create {
+                            self.apply_set_item(tx, &mut row, item, stats).await?;
+                        }
+                        updated.push(row);
+                    } else {
+                        for mut row in matches {
+                            for item in &merge.on_match {
+                                self.apply_set_item(tx, &mut row, item, stats).await?;
+                            }
+                            updated.push(row);
+                        }
+                    }
+                }
+            }
+            UpdatePlan::Set(set) => {
+                for mut row in rows {
+                    for item in &set.items {
+                        self.apply_set_item(tx, &mut row, item, stats).await?;
+                    }
+                    updated.push(row);
+                }
+            }
+            UpdatePlan::Remove(remove) => {
+                for mut row in rows {
+                    for item in &remove.items {
+                        self.apply_remove_item(tx, &mut row, item, stats).await?;
+                    }
+                    updated.push(row);
+                }
+            }
+            UpdatePlan::Delete(delete) => {
+                self.apply_delete(tx, &rows, delete, stats).await?;
+                updated = rows;
             }
         }
+        Ok(updated)
     }
 
-    async fn apply_return(
+    /// Create the unbound nodes and every relationship of a pattern, binding
+    /// their variables in `row`
+    async fn create_pattern(
         &self,
-        matches: Vec<MatchResult>,
-        return_plan: &ReturnPlan,
-    ) -> Result<QueryResult, crate::CypherError> {
-        let mut columns = Vec::new();
-        let mut rows = Vec::new();
+        tx: &mut Transaction<'a>,
+        row: &mut MatchResult,
+        pattern: &Pattern,
+        stats: &mut QueryStats,
+    ) -> Result<(), CypherError> {
+        let mut previous = None;
+        let mut pending = None;
 
-        // Determine column names
-        for item in &return_plan.items {
-            match item {
-                ReturnItem::Variable(var) => columns.push(var.clone()),
-                ReturnItem::Property { variable, property } => {
-                    columns.push(format!("{}.{}", variable, property));
+        for element in &pattern.elements {
+            match element {
+                PatternElement::Node(node) => {
+                    let rid = self.create_node(tx, row, node, stats).await?;
+                    if let (Some(relationship), Some(from)) = (pending.take(), previous) {
+                        self.create_relationship(tx, row, relationship, from, rid, stats).await?;
+                    }
+                    previous = Some(rid);
                 }
-                ReturnItem::Count => columns.push("count".to_string()),
+                PatternElement::Relationship(relationship) => pending = Some(relationship),
             }
         }
+        Ok(())
+    }
 
-        // Process each match result
-        for match_result in matches {
-            let mut row = HashMap::new();
+    /// Node a CREATE pattern refers to: the bound node, or a new one
+    async fn create_node(
+        &self,
+        tx: &mut Transaction<'a>,
+        row: &mut MatchResult,
+        node: &NodePattern,
+        stats: &mut QueryStats,
+    ) -> Result<Rid, CypherError> {
+        if let Some(bound) = node.variable.as_ref().and_then(|variable| row.bindings.get(variable)) {
+            if !node.labels.is_empty() || !node.properties.is_empty() {
+                return Err(CypherError::Execution(format!(
+                    "Variable `{}` is already bound and cannot be given labels or properties in CREATE",
+                    node.variable.as_deref().unwrap_or_default(),
+                )));
+            }
+            return match bound {
+                Value::Node(rid) => Ok(*rid),
+                other => Err(CypherError::Execution(format!(
+                    "Expected `{}` to be a node, got {}",
+                    node.variable.as_deref().unwrap_or_default(),
+                    other.type_name(),
+                ))),
+            };
+        }
 
-            for (i, item) in return_plan.items.iter().enumerate() {
-                let value = match item {
-                    ReturnItem::Variable(var) => {
-                        self.resolve_value_ref(&match_result, &ValueRef::Variable(var.clone())).await?
+        let properties = self.evaluate_properties(tx, row, &node.properties).await?;
+        let labels: HashSet<&String> = node.labels.iter().collect();
+        stats.properties_set += properties.len() as u32;
+        stats.labels_added += labels.len() as u32;
+        let rid = tx.create_node_with_properties(labels, properties).await.map_err(CypherError::graph)?;
+        stats.nodes_created += 1;
+        if let Some(variable) = &node.variable {
+            row.bindings.insert(variable.clone(), Value::Node(rid));
+        }
+        Ok(rid)
+    }
+
+    async fn create_relationship(
+        &self,
+        tx: &mut Transaction<'a>,
+        row: &mut MatchResult,
+        relationship: &RelationshipPattern,
+        left: Rid,
+        right: Rid,
+        stats: &mut QueryStats,
+    ) -> Result<(), CypherError> {
+        let (start, end) = match relationship.direction {
+            Direction::Outgoing => (left, right),
+            Direction::Incoming => (right, left),
+            Direction::Bidirectional => {
+                return Err(CypherError::Execution("Only directed relationships can be created".to_string()));
+            }
+        };
+        let [name] = relationship.types.as_slice() else {
+            return Err(CypherError::Execution("A relationship must have exactly one type to be created".to_string()));
+        };
+        if relationship.length.is_some() {
+            return Err(CypherError::Execution("Variable-length relationships cannot be created".to_string()));
+        }
+        if let Some(variable) = relationship.variable.as_ref().filter(|variable| row.bindings.contains_key(*variable)) {
+            return Err(CypherError::Execution(format!("Variable `{}` is already bound", variable)));
+        }
+
+        let properties = self.evaluate_properties(tx, row, &relationship.properties).await?;
+        let label = tx.label_id(name).await.map_err(CypherError::graph)?;
+        stats.properties_set += properties.len() as u32;
+        tx.create_edge_with_properties(start, end, label, properties).await.map_err(CypherError::graph)?;
+        stats.relationships_created += 1;
+
+        if let Some(variable) = &relationship.variable {
+            // The edge just created is the last one to `end` with its label
+            let edge = tx.get_edges_from(start).await.into_iter()
+                .rev()
+                .find(|edge| edge.target == end && edge.label == label)
+                .ok_or_else(|| CypherError::Execution("Created relationship is not visible".to_string()))?;
+            row.bindings.insert(variable.clone(), Value::Relationship(Relationship { start, edge }));
+        }
+        Ok(())
+    }
+
+    /// Extensions of `row` binding every variable of the patterns; each
+    /// relationship is used at most once across all of them. `start_nodes`
+    /// narrows the candidates for the first node of the first pattern.
+    async fn match_patterns(
+        &self,
+        tx: &Transaction<'a>,
+        patterns: &[Pattern],
+        row: &MatchResult,
+        start_nodes: Option<&[Rid]>,
+    ) -> Result<Vec<MatchResult>, CypherError> {
+        let mut partial = vec![(row.clone(), Vec::new())];
+        for (i, pattern) in patterns.iter().enumerate() {
+            let candidates = if i == 0 { start_nodes } else { None };
+            let mut extended = Vec::new();
+            for (row, used) in partial {
+                extended.extend(self.match_path(tx, pattern, row, used, candidates).await?);
+            }
+            partial = extended;
+        }
+        Ok(partial.into_iter().map(|(row, _)| row).collect())
+    }
+
+    /// Extensions of `row` along one path pattern, each with the
+    /// relationships used so far, which no later step may use again
+    async fn match_path(
+        &self,
+        tx: &Transaction<'a>,
+        pattern: &Pattern,
+        row: MatchResult,
+        used: Vec<Relationship>,
+        start_nodes: Option<&[Rid]>,
+    ) -> Result<Vec<(MatchResult, Vec<Relationship>)>, CypherError> {
+        let mut nodes = Vec::new();
+        let mut relationships = Vec::new();
+        for element in &pattern.elements {
+            match element {
+                PatternElement::Node(node) => nodes.push(node),
+                PatternElement::Relationship(relationship) => relationships.push(relationship),
+            }
+        }
+        let Some(first) = nodes.first() else {
+            return Ok(Vec::new());
+        };
+
+        let candidates = match first.variable.as_ref().and_then(|variable| row.bindings.get(variable)) {
+            Some(Value::Node(rid)) => vec![*rid],
+            Some(_) => Vec::new(),
+            None => match start_nodes {
+                Some(rids) => rids.iter().copied().filter(|rid| tx.in_scope(*rid)).collect(),
+                None => tx.list_rids().await,
+            },
+        };
+        let mut partial = Vec::new();
+        for rid in candidates {
+            if self.node_matches(tx, &row, rid, first).await? {
+                let mut row = row.clone();
+                if let Some(variable) = &first.variable {
+                    row.bindings.insert(variable.clone(), Value::Node(rid));
+                }
+                partial.push((row, Path::new(rid)));
+            }
+        }
+
+        for (relationship, next) in relationships.iter().zip(&nodes[1..]) {
+            let mut labels = Vec::new();
+            for name in &relationship.types {
+                labels.extend(tx.lookup_label(name).await);
+            }
+            if !relationship.types.is_empty() && labels.is_empty() {
+                return Ok(Vec::new());
+            }
+            let (min, max) = match relationship.length {
+                None => (1, Some(1)),
+                Some(PathLength::Any) => (1, None),
+                Some(PathLength::Range(min, max)) => (min as usize, max.map(|max| max as usize)),
+            };
+
+            let mut extended = Vec::new();
+            for (row, path) in partial {
+                // Depth-first over the ways to extend the path by min..=max hops
+                let base = path.relationships.len();
+                let mut stack = vec![path];
+                while let Some(path) = stack.pop() {
+                    let hops = path.relationships.len() - base;
+                    let current = *path.nodes.last().unwrap();
+                    if hops >= min && self.node_matches(tx, &row, current, next).await? {
+                        let value = match relationship.length {
+                            None => Value::Relationship(path.relationships[base].clone()),
+                            Some(_) => Value::List(path.relationships[base..].iter().cloned().map(Value::Relationship).collect()),
+                        };
+                        let bound = relationship.variable.as_ref().and_then(|variable| row.bindings.get(variable));
+                        if bound.is_none_or(|bound| *bound == value) {
+                            let mut row = row.clone();
+                            if let Some(variable) = &relationship.variable {
+                                row.bindings.insert(variable.clone(), value);
+                            }
+                            if let Some(variable) = &next.variable {
+                                row.bindings.insert(variable.clone(), Value::Node(current));
+                            }
+                            extended.push((row, path.clone()));
+                        }
+                    }
+                    if max.is_some_and(|max| hops >= max) {
+                        continue;
                     }
-                    ReturnItem::Property { variable, property } => {
-                        self.resolve_value_ref(&match_result, &ValueRef::Property {
-                            variable: variable.clone(),
-                            property: property.clone(),
-                        }).await?
+                    for candidate in self.relationships_of(tx, current, &relationship.direction).await {
+                        if (!labels.is_empty() && !labels.contains(&candidate.label()))
+                            || used.contains(&candidate)
+                            || path.relationships.contains(&candidate)
+                            || !self.relationship_matches(tx, &row, &candidate, relationship).await?
+                        {
+                            continue;
+                        }
+                        let mut path = path.clone();
+                        path.push(candidate);
+                        stack.push(path);
                     }
-                    ReturnItem::Count => serde_json::Value::Number(1.into()),
+                }
+            }
+            partial = extended;
+        }
+
+        Ok(partial.into_iter().map(|(mut row, path)| {
+            if let Some(variable) = &pattern.path_variable {
+                row.bindings.insert(variable.clone(), Value::Path(path.clone()));
+            }
+            let mut used = used.clone();
+            used.extend(path.relationships);
+            (row, used)
+        }).collect())
+    }
+
+    /// Relationships of `rid` in the direction a pattern follows it
+    async fn relationships_of(&self, tx: &Transaction<'a>, rid: Rid, direction: &Direction) -> Vec<Relationship> {
+        let mut relationships = Vec::new();
+        if matches!(direction, Direction::Outgoing | Direction::Bidirectional) {
+            relationships.extend(tx.get_edges_from(rid).await.into_iter()
+                .map(|edge| Relationship { start: rid, edge }));
+        }
+        if matches!(direction, Direction::Incoming | Direction::Bidirectional) {
+            relationships.extend(tx.get_edges_to(rid).await.into_iter()
+                .map(|edge| Relationship { start: edge.target, edge: AdjEntry { target: rid, ..edge } }));
+        }
+        relationships
+    }
+
+    /// Whether `rid` fits a node pattern: the node the variable is bound to,
+    /// with every label and inline property
+    async fn node_matches(&self, tx: &Transaction<'a>, row: &MatchResult, rid: Rid, node: &NodePattern) -> Result<bool, CypherError> {
+        if let Some(bound) = node.variable.as_ref().and_then(|variable| row.bindings.get(variable)) {
+            if *bound != Value::Node(rid) {
+                return Ok(false);
+            }
+        }
+        if node.labels.is_empty() && node.properties.is_empty() {
+            return Ok(true);
+        }
+        let Some(record) = tx.get_record(rid).await.map_err(CypherError::graph)? else {
+            return Ok(false);
+        };
+        if !node.labels.iter().all(|label| record.labels.contains(label)) {
+            return Ok(false);
+        }
+        self.properties_match(tx, row, &record.properties, &node.properties).await
+    }
+
+    /// Whether a relationship has every inline property of a pattern; for
+    /// variable-length patterns, each relationship along the way must
+    async fn relationship_matches(&self, tx: &Transaction<'a>, row: &MatchResult, candidate: &Relationship, relationship: &RelationshipPattern) -> Result<bool, CypherError> {
+        if relationship.properties.is_empty() {
+            return Ok(true);
+        }
+        let properties = tx.get_edge_properties(&candidate.edge).await.map_err(CypherError::graph)?;
+        self.properties_match(tx, row, &properties, &relationship.properties).await
+    }
+
+    /// Whether stored properties equal every inline property; null equals nothing
+    async fn properties_match(&self, tx: &Transaction<'a>, row: &MatchResult, stored: &Properties, expected: &[Property]) -> Result<bool, CypherError> {
+        for property in expected {
+            let value = self.evaluate(tx, row, &property.value).await?;
+            let matches = stored.get(&property.key)
+                .and_then(|stored| Value::from_property(stored).equals(&value))
+                .unwrap_or(false);
+            if !matches {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    async fn apply_set_item(
+        &self,
+        tx: &mut Transaction<'a>,
+        row: &mut MatchResult,
+        item: &SetItem,
+        stats: &mut QueryStats,
+    ) -> Result<(), CypherError> {
+        match item {
+            SetItem::Property { variable, property, value } => {
+                let value = self.evaluate(tx, row, value).await?;
+                let value = value.to_property().map_err(CypherError::Execution)?;
+                let changes = vec![(property.clone(), value)];
+                self.update_properties(tx, row, variable, changes, false, stats).await
+            }
+            SetItem::Properties { variable, properties, replace } => {
+                let mut changes = Vec::new();
+                for property in properties {
+                    let value = self.evaluate(tx, row, &property.value).await?;
+                    changes.push((property.key.clone(), value.to_property().map_err(CypherError::Execution)?));
+                }
+                self.update_properties(tx, row, variable, changes, *replace, stats).await
+            }
+            SetItem::Labels { variable, labels } => {
+                let Some(rid) = self.bound_node(row, variable)? else {
+                    return Ok(());
                 };
+                let record = tx.get_record(rid).await.map_err(CypherError::graph)?.unwrap_or_default();
+                for label in labels {
+                    if !record.labels.contains(label) {
+                        tx.add_label(rid, label).await.map_err(CypherError::graph)?;
+                        stats.labels_added += 1;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    async fn apply_remove_item(
+        &self,
+        tx: &mut Transaction<'a>,
+        row: &mut MatchResult,
+        item: &RemoveItem,
+        stats: &mut QueryStats,
+    ) -> Result<(), CypherError> {
+        match item {
+            RemoveItem::Property { variable, property } => {
+                let changes = vec![(property.clone(), None)];
+                self.update_properties(tx, row, variable, changes, false, stats).await
+            }
+            RemoveItem::Labels { variable, labels } => {
+                let Some(rid) = self.bound_node(row, variable)? else {
+                    return Ok(());
+                };
+                let record = tx.get_record(rid).await.map_err(CypherError::graph)?.unwrap_or_default();
+                for label in labels {
+                    if record.labels.contains(label) {
+                        tx.remove_label(rid, label).await.map_err(CypherError::graph)?;
+                        stats.labels_removed += 1;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Set (or, for `None`, remove) properties of the node or relationship
+    /// bound to `variable`; `replace` first removes every other property.
+    /// Null bindings are left alone.
+    async fn update_properties(
+        &self,
+        tx: &mut Transaction<'a>,
+        row: &mut MatchResult,
+        variable: &str,
+        changes: Vec<(String, Option<fcdb_graph::PropertyValue>)>,
+        replace: bool,
+        stats: &mut QueryStats,
+    ) -> Result<(), CypherError> {
+        let target = row.bindings.get(variable).cloned()
+            .ok_or_else(|| CypherError::Execution(format!("Variable `{}` not defined", variable)))?;
+
+        let current = match &target {
+            Value::Null => return Ok(()),
+            Value::Node(rid) => match tx.get_record(*rid).await.map_err(CypherError::graph)? {
+                Some(record) => record.properties,
+                None => return Ok(()),
+            },
+            Value::Relationship(relationship) => match self.current_edge(tx, relationship).await {
+                Some(edge) => tx.get_edge_properties(&edge).await.map_err(CypherError::graph)?,
+                None => return Ok(()),
+            },
+            other => {
+                return Err(CypherError::Execution(format!(
+                    "Expected `{}` to be a node or relationship, got {}", variable, other.type_name(),
+                )));
+            }
+        };
+
+        let mut properties = current.clone();
+        if replace {
+            let kept: HashSet<&String> = changes.iter().map(|(key, _)| key).collect();
+            properties.retain(|key, _| kept.contains(key));
+        }
+        for (key, value) in changes {
+            match value {
+                Some(value) => {
+                    properties.insert(key, value);
+                    stats.properties_set += 1;
+                }
+                None => {
+                    if properties.remove(&key).is_some() {
+                        stats.properties_set += 1;
+                    }
+                }
+            }
+        }
+        if replace {
+            stats.properties_set += current.keys().filter(|key| !properties.contains_key(*key)).count() as u32;
+        }
+        if properties == current {
+            return Ok(());
+        }
+
+        match target {
+            Value::Node(rid) => {
+                for key in current.keys().filter(|key| !properties.contains_key(*key)) {
+                    tx.remove_property(rid, key).await.map_err(CypherError::graph)?;
+                }
+                for (key, value) in properties {
+                    if current.get(&key) != Some(&value) {
+                        tx.set_property(rid, &key, value).await.map_err(CypherError::graph)?;
+                    }
+                }
+            }
+            Value::Relationship(relationship) => {
+                let edge = self.rewrite_relationship(tx, &relationship, properties).await?;
+                row.bindings.insert(variable.to_string(), Value::Relationship(Relationship { start: relationship.start, edge }));
+            }
+            _ => unreachable!("checked above"),
+        }
+        Ok(())
+    }
+
+    /// Replace a relationship's properties; returns its new entry
+    async fn rewrite_relationship(&self, tx: &mut Transaction<'a>, relationship: &Relationship, properties: Properties) -> Result<AdjEntry, CypherError> {
+        let (start, id) = (relationship.start, relationship.edge.id);
+        if !tx.update_edge_with_properties(start, id, properties).await.map_err(CypherError::graph)? {
+            return Err(CypherError::Execution("Updated relationship is not visible".to_string()));
+        }
+        self.current_edge(tx, relationship).await
+            .ok_or_else(|| CypherError::Execution("Updated relationship is not visible".to_string()))
+    }
+
+    /// Delete one relationship, leaving its parallel edges alone; false if
+    /// it is already gone
+    async fn delete_relationship(&self, tx: &mut Transaction<'a>, relationship: &Relationship) -> Result<bool, CypherError> {
+        tx.delete_edge(relationship.start, relationship.edge.id).await.map_err(CypherError::graph)
+    }
+
+    /// Live entry of a bound relationship, which a SET may have replaced
+    async fn current_edge(&self, tx: &Transaction<'a>, relationship: &Relationship) -> Option<AdjEntry> {
+        tx.get_edges_from(relationship.start).await.into_iter()
+            .find(|edge| edge.id == relationship.edge.id)
+    }
+
+    /// Delete the clause's relationships, then its nodes. Without DETACH a
+    /// node that still has relationships is an error.
+    async fn apply_delete(
+        &self,
+        tx: &mut Transaction<'a>,
+        rows: &[MatchResult],
+        delete: &DeleteClause,
+        stats: &mut QueryStats,
+    ) -> Result<(), CypherError> {
+        let mut nodes = Vec::new();
+        let mut relationships = Vec::new();
+        for row in rows {
+            for expression in &delete.expressions {
+                match self.evaluate(tx, row, expression).await? {
+                    Value::Null => {}
+                    Value::Node(rid) => nodes.push(rid),
+                    Value::Relationship(relationship) => relationships.push(relationship),
+                    Value::Path(path) => {
+                        nodes.extend(path.nodes);
+                        relationships.extend(path.relationships);
+                    }
+                    other => {
+                        return Err(CypherError::Execution(format!("Cannot delete {}", other.type_name())));
+                    }
+                }
+            }
+        }
+
+        // The same relationship may be bound more than once, e.g. by a path and a variable
+        let mut seen = HashSet::new();
+        for relationship in relationships.into_iter()
+            .filter(|r| seen.insert(r.edge.id))
+        {
+            if self.delete_relationship(tx, &relationship).await? {
+                stats.relationships_deleted += 1;
+            }
+        }
 
-                row.insert(columns[i].clone(), value);
+        let mut seen = HashSet::new();
+        for rid in nodes.into_iter().filter(|rid| seen.insert(*rid)) {
+            if !tx.node_exists(rid).await {
+                continue;
             }
+            let outgoing = tx.get_edges_from(rid).await;
+            // Self-loops are in both lists
+            let incoming = tx.get_edges_to(rid).await.into_iter().filter(|edge| edge.target != rid).count();
+            let attached = (outgoing.len() + incoming) as u32;
+            if attached > 0 && !delete.detach {
+                return Err(CypherError::Execution(format!(
+                    "Cannot delete node {} because it still has {} relationship(s); use DETACH DELETE", rid, attached,
+                )));
+            }
+            // The node's relationships are deleted with it
+            if tx.delete_node(rid).await.map_err(CypherError::graph)? {
+                stats.nodes_deleted += 1;
+                stats.relationships_deleted += attached;
+            }
+        }
+        Ok(())
+    }
 
-            rows.push(row);
+    /// Properties of an inline property map; null values are left out
+    async fn evaluate_properties(&self, tx: &Transaction<'a>, row: &MatchResult, properties: &[Property]) -> Result<Properties, CypherError> {
+        let mut evaluated = Properties::new();
+        for property in properties {
+            let value = self.evaluate(tx, row, &property.value).await?;
+            if let Some(value) = value.to_property().map_err(CypherError::Execution)? {
+                evaluated.insert(property.key.clone(), value);
+            }
+        }
+        Ok(evaluated)
+    }
 
-            // Apply LIMIT
-            if let Some(limit) = return_plan.limit {
-                if rows.len() >= limit as usize {
-                    break;
+    /// Value of an expression in one row; null propagates through
+    /// operators and makes comparisons and predicates unknown
+    async fn evaluate(&self, tx: &Transaction<'a>, row: &MatchResult, expression: &Expression) -> Result<Value, CypherError> {
+        Ok(match expression {
+            Expression::Variable(variable) => row.bindings.get(variable).cloned()
+                .ok_or_else(|| CypherError::Execution(format!("Variable `{}` not defined", variable)))?,
+            Expression::Literal(literal) => Value::from_literal(literal),
+            Expression::PropertyAccess { variable, property } => {
+                let target = row.bindings.get(variable)
+                    .ok_or_else(|| CypherError::Execution(format!("Variable `{}` not defined", variable)))?;
+                self.property_of(tx, target, property).await?
+            }
+            Expression::BinaryOp { left, op, right } => {
+                let left = Box::pin(self.evaluate(tx, row, left)).await?;
+                // AND and OR skip the right side once the left decides the result
+                match (op, left.truth()) {
+                    (BinaryOperator::And, Ok(Some(false))) => return Ok(Value::Bool(false)),
+                    (BinaryOperator::Or, Ok(Some(true))) => return Ok(Value::Bool(true)),
+                    _ => {}
                 }
+                let right = Box::pin(self.evaluate(tx, row, right)).await?;
+                binary_operation(&left, op, &right).map_err(CypherError::Execution)?
             }
+            Expression::Not(operand) => {
+                let operand = Box::pin(self.evaluate(tx, row, operand)).await?;
+                operand.truth().map_err(CypherError::Execution)?.map_or(Value::Null, |b| Value::Bool(!b))
+            }
+            Expression::Negate(operand) => {
+                match Box::pin(self.evaluate(tx, row, operand)).await? {
+                    Value::Null => Value::Null,
+                    Value::Int(i) => Value::Int(i.checked_neg().ok_or_else(|| CypherError::Execution("Integer overflow".to_string()))?),
+                    Value::Float(f) => Value::Float(-f),
+                    other => return Err(CypherError::Execution(format!("Cannot negate {}", other.type_name()))),
+                }
+            }
+            Expression::IsNull { expression, negated } => {
+                let value = Box::pin(self.evaluate(tx, row, expression)).await?;
+                Value::Bool((value == Value::Null) != *negated)
+            }
+            Expression::In { left, list } => {
+                let left = Box::pin(self.evaluate(tx, row, left)).await?;
+                let items = match Box::pin(self.evaluate(tx, row, list)).await? {
+                    Value::Null => return Ok(Value::Null),
+                    Value::List(items) => items,
+                    other => return Err(CypherError::Execution(format!("IN expects a list, got {}", other.type_name()))),
+                };
+                let mut found = Some(false);
+                for item in &items {
+                    match left.equals(item) {
+                        Some(true) => return Ok(Value::Bool(true)),
+                        Some(false) => {}
+                        None => found = None,
+                    }
+                }
+                found.map_or(Value::Null, Value::Bool)
+            }
+            Expression::List(items) => {
+                let mut values = Vec::with_capacity(items.len());
+                for item in items {
+                    values.push(Box::pin(self.evaluate(tx, row, item)).await?);
+                }
+                Value::List(values)
+            }
+            Expression::Map(entries) => {
+                let mut map = std::collections::BTreeMap::new();
+                for entry in entries {
+                    map.insert(entry.key.clone(), Box::pin(self.evaluate(tx, row, &entry.value)).await?);
+                }
+                Value::Map(map)
+            }
+            Expression::Case { operand, alternatives, default } => {
+                let operand = match operand {
+                    Some(operand) => Some(Box::pin(self.evaluate(tx, row, operand)).await?),
+                    None => None,
+                };
+                for (when, then) in alternatives {
+                    let when = Box::pin(self.evaluate(tx, row, when)).await?;
+                    let chosen = match &operand {
+                        Some(operand) => operand.equals(&when) == Some(true),
+                        None => when.truth().map_err(CypherError::Execution)? == Some(true),
+                    };
+                    if chosen {
+                        return Box::pin(self.evaluate(tx, row, then)).await;
+                    }
+                }
+                match default {
+                    Some(default) => Box::pin(self.evaluate(tx, row, default)).await?,
+                    None => Value::Null,
+                }
+            }
+            Expression::FunctionCall { name, .. } if expression.is_aggregate() => {
+                return Err(CypherError::Execution(format!("Aggregate function {}() is only allowed in RETURN", name)));
+            }
+            Expression::CountStar => {
+                return Err(CypherError::Execution("Aggregate function count() is only allowed in RETURN".to_string()));
+            }
+            Expression::FunctionCall { name, arguments, .. } => {
+                let mut values = Vec::with_capacity(arguments.len());
+                for argument in arguments {
+                    values.push(Box::pin(self.evaluate(tx, row, argument)).await?);
+                }
+                self.call_function(tx, name, values).await?
+            }
+        })
+    }
+
+    /// Scalar function call; names are case-insensitive and every function
+    /// but `coalesce` returns null for a null argument
+    async fn call_function(&self, tx: &Transaction<'a>, name: &str, arguments: Vec<Value>) -> Result<Value, CypherError> {
+        let name = name.to_ascii_lowercase();
+        if name == "coalesce" {
+            return Ok(arguments.into_iter().find(|value| *value != Value::Null).unwrap_or(Value::Null));
         }
+        let [argument] = arguments.as_slice() else {
+            return Err(CypherError::Execution(format!("{}() takes one argument, got {}", name, arguments.len())));
+        };
+        let invalid = || CypherError::Execution(format!("Invalid argument to {}(): {}", name, argument.type_name()));
+        if *argument == Value::Null {
+            return Ok(Value::Null);
+        }
+        Ok(match (name.as_str(), argument) {
+            ("id", Value::Node(rid)) => Value::Int(rid.as_u64() as i64),
+            ("labels", Value::Node(rid)) => {
+                let record = tx.get_record(*rid).await.map_err(CypherError::graph)?.unwrap_or_default();
+                Value::List(record.labels.into_iter().map(Value::String).collect())
+            }
+            ("type", Value::Relationship(relationship)) => Value::String(tx.label_display(relationship.label()).await),
+            ("keys", Value::Map(map)) => Value::List(map.keys().cloned().map(Value::String).collect()),
+            ("keys", entity @ (Value::Node(_) | Value::Relationship(_))) => {
+                let Value::Map(properties) = self.properties_of(tx, entity).await? else { return Ok(Value::Null) };
+                Value::List(properties.into_keys().map(Value::String).collect())
+            }
+            ("properties", entity @ (Value::Node(_) | Value::Relationship(_))) => self.properties_of(tx, entity).await?,
+            ("size" | "length", Value::String(s)) => Value::Int(s.chars().count() as i64),
+            ("size" | "length", Value::List(items)) => Value::Int(items.len() as i64),
+            ("length", Value::Path(path)) => Value::Int(path.relationships.len() as i64),
+            ("nodes", Value::Path(path)) => Value::List(path.nodes.iter().copied().map(Value::Node).collect()),
+            ("relationships", Value::Path(path)) => Value::List(path.relationships.iter().cloned().map(Value::Relationship).collect()),
+            ("head", Value::List(items)) => items.first().cloned().unwrap_or(Value::Null),
+            ("last", Value::List(items)) => items.last().cloned().unwrap_or(Value::Null),
+            ("tolower", Value::String(s)) => Value::String(s.to_lowercase()),
+            ("toupper", Value::String(s)) => Value::String(s.to_uppercase()),
+            ("trim", Value::String(s)) => Value::String(s.trim().to_string()),
+            ("tostring", value) => Value::String(value.to_text().ok_or_else(invalid)?),
+            ("tointeger", Value::Int(i)) => Value::Int(*i),
+            ("tointeger", Value::Float(f)) => Value::Int(f.trunc() as i64),
+            ("tointeger", Value::String(s)) => s.trim().parse::<i64>().map(Value::Int)
+                .or_else(|_| s.trim().parse::<f64>().map(|f| Value::Int(f.trunc() as i64)))
+                .unwrap_or(Value::Null),
+            ("tofloat", Value::Int(_) | Value::Float(_)) => Value::Float(argument.as_float().unwrap()),
+            ("tofloat", Value::String(s)) => s.trim().parse().map(Value::Float).unwrap_or(Value::Null),
+            ("abs", Value::Int(i)) => Value::Int(i.checked_abs().ok_or_else(invalid)?),
+            ("abs", Value::Float(f)) => Value::Float(f.abs()),
+            ("id" | "labels" | "type" | "keys" | "properties" | "size" | "length" | "nodes" | "relationships" | "head" | "last"
+                | "tolower" | "toupper" | "trim" | "tointeger" | "tofloat" | "abs", _) => return Err(invalid()),
+            _ => return Err(CypherError::Execution(format!("Unknown function {}()", name))),
+        })
+    }
 
-        // Apply SKIP
-        if let Some(skip) = return_plan.skip {
-            rows = rows.into_iter().skip(skip as usize).collect();
+    /// Properties of a node or relationship as a map
+    async fn properties_of(&self, tx: &Transaction<'a>, entity: &Value) -> Result<Value, CypherError> {
+        let properties = match entity {
+            Value::Node(rid) => match tx.get_record(*rid).await.map_err(CypherError::graph)? {
+                Some(record) => record.properties,
+                None => return Ok(Value::Null),
+            },
+            Value::Relationship(relationship) => match self.current_edge(tx, relationship).await {
+                Some(edge) => tx.get_edge_properties(&edge).await.map_err(CypherError::graph)?,
+                None => return Ok(Value::Null),
+            },
+            _ => return Ok(Value::Null),
+        };
+        Ok(Value::Map(properties.iter().map(|(key, value)| (key.clone(), Value::from_property(value))).collect()))
+    }
+
+    fn bound_node(&self, row: &MatchResult, variable: &str) -> Result<Option<Rid>, CypherError> {
+        match row.bindings.get(variable) {
+            Some(Value::Node(rid)) => Ok(Some(*rid)),
+            Some(Value::Null) => Ok(None),
+            Some(other) => Err(CypherError::Execution(format!("Expected `{}` to be a node, got {}", variable, other.type_name()))),
+            None => Err(CypherError::Execution(format!("Variable `{}` not defined", variable))),
         }
+    }
+
+    /// Property of a node, relationship or map; null for anything missing
+    async fn property_of(&self, tx: &Transaction<'a>, value: &Value, property: &str) -> Result<Value, CypherError> {
+        let map = match value {
+            Value::Node(_) | Value::Relationship(_) => self.properties_of(tx, value).await?,
+            value => value.clone(),
+        };
+        Ok(match map {
+            Value::Map(mut map) => map.remove(property).unwrap_or(Value::Null),
+            _ => Value::Null,
+        })
+    }
 
-        Ok(QueryResult {
-            columns,
-            rows,
-            stats: QueryStats {
-                nodes_created: 0,
-                nodes_deleted: 0,
-                relationships_created: 0,
-                relationships_deleted: 0,
-                labels_added: 0,
-                labels_removed: 0,
-                properties_set: 0,
-                execution_time_ms: 0,
+    /// JSON form of a value; nodes and relationships render as their
+    /// properties, and deleted ones as null. A path renders as its nodes and
+    /// relationships in order.
+    async fn value_to_json(&self, tx: &Transaction<'a>, value: &Value) -> Result<serde_json::Value, CypherError> {
+        Ok(match value {
+            Value::Null => serde_json::Value::Null,
+            Value::Bool(b) => serde_json::Value::Bool(*b),
+            Value::Int(i) => serde_json::Value::Number((*i).into()),
+            Value::Float(f) => serde_json::Number::from_f64(*f).map_or(serde_json::Value::Null, serde_json::Value::Number),
+            Value::String(s) => serde_json::Value::String(s.clone()),
+            Value::List(items) => {
+                let mut list = Vec::with_capacity(items.len());
+                for item in items {
+                    list.push(Box::pin(self.value_to_json(tx, item)).await?);
+                }
+                serde_json::Value::Array(list)
+            }
+            Value::Map(map) => {
+                let mut object = serde_json::Map::new();
+                for (key, item) in map {
+                    object.insert(key.clone(), Box::pin(self.value_to_json(tx, item)).await?);
+                }
+                serde_json::Value::Object(object)
+            }
+            Value::Node(rid) => match tx.get_record(*rid).await {
+                Ok(Some(record)) => record.to_json(),
+                _ => serde_json::Value::Null,
+            },
+            Value::Relationship(relationship) => match self.current_edge(tx, relationship).await {
+                Some(edge) => fcdb_graph::properties_to_json(&tx.get_edge_properties(&edge).await.map_err(CypherError::graph)?),
+                None => serde_json::Value::Null,
             },
+            Value::Path(path) => Box::pin(self.value_to_json(tx, &Value::List(path.elements()))).await?,
         })
     }
 }
 
+/// Result of a binary operator on two evaluated operands, with Cypher's
+/// three-valued logic: unknown (null) operands give null unless the other
+/// side decides an AND, OR or XOR
+fn binary_operation(left: &Value, op: &BinaryOperator, right: &Value) -> Result<Value, String> {
+    let unknown_or = |result: Option<bool>| result.map_or(Value::Null, Value::Bool);
+    Ok(match op {
+        BinaryOperator::And => match (left.truth()?, right.truth()?) {
+            (Some(false), _) | (_, Some(false)) => Value::Bool(false),
+            (Some(true), Some(true)) => Value::Bool(true),
+            _ => Value::Null,
+        },
+        BinaryOperator::Or => match (left.truth()?, right.truth()?) {
+            (Some(true), _) | (_, Some(true)) => Value::Bool(true),
+            (Some(false), Some(false)) => Value::Bool(false),
+            _ => Value::Null,
+        },
+        BinaryOperator::Xor => unknown_or(left.truth()?.zip(right.truth()?).map(|(a, b)| a != b)),
+        BinaryOperator::Equal => unknown_or(left.equals(right)),
+        BinaryOperator::NotEqual => unknown_or(left.equals(right).map(|equal| !equal)),
+        BinaryOperator::LessThan => unknown_or(left.compare(right).map(Ordering::is_lt)),
+        BinaryOperator::GreaterThan => unknown_or(left.compare(right).map(Ordering::is_gt)),
+        BinaryOperator::LessEqual => unknown_or(left.compare(right).map(Ordering::is_le)),
+        BinaryOperator::GreaterEqual => unknown_or(left.compare(right).map(Ordering::is_ge)),
+        BinaryOperator::Add | BinaryOperator::Subtract | BinaryOperator::Multiply
+            | BinaryOperator::Divide | BinaryOperator::Modulo => left.arithmetic(op, right)?,
+        BinaryOperator::StartsWith | BinaryOperator::EndsWith | BinaryOperator::Contains | BinaryOperator::RegexMatch => {
+            // String predicates are unknown for anything but two strings
+            let (Value::String(text), Value::String(pattern)) = (left, right) else {
+                return Ok(Value::Null);
+            };
+            Value::Bool(match op {
+                BinaryOperator::StartsWith => text.starts_with(pattern.as_str()),
+                BinaryOperator::EndsWith => text.ends_with(pattern.as_str()),
+                BinaryOperator::Contains => text.contains(pattern.as_str()),
+                // The pattern must match the whole string
+                _ => regex::Regex::new(&format!("^(?:{})$", pattern))
+                    .map_err(|err| format!("Invalid regular expression: {}", err))?
+                    .is_match(text),
+            })
+        }
+    })
+}
+
+/// Copy of an expression with each aggregate call replaced by a hidden
+/// variable, appending the calls to `aggregates`
+fn extract_aggregates(expression: &Expression, aggregates: &mut Vec<Expression>) -> Expression {
+    if expression.is_aggregate() {
+        aggregates.push(expression.clone());
+        return Expression::Variable(aggregate_variable(aggregates.len() - 1));
+    }
+    let mut extract = |expression: &Expression| extract_aggregates(expression, aggregates);
+    match expression {
+        Expression::BinaryOp { left, op, right } => Expression::BinaryOp {
+            left: Box::new(extract(left)),
+            op: op.clone(),
+            right: Box::new(extract(right)),
+        },
+        Expression::Not(operand) => Expression::Not(Box::new(extract(operand))),
+        Expression::Negate(operand) => Expression::Negate(Box::new(extract(operand))),
+        Expression::IsNull { expression, negated } => Expression::IsNull { expression: Box::new(extract(expression)), negated: *negated },
+        Expression::In { left, list } => Expression::In { left: Box::new(extract(left)), list: Box::new(extract(list)) },
+        Expression::List(items) => Expression::List(items.iter().map(extract).collect()),
+        Expression::Map(entries) => Expression::Map(entries.iter()
+            .map(|entry| Property { key: entry.key.clone(), value: extract(&entry.value) })
+            .collect()),
+        Expression::Case { operand, alternatives, default } => Expression::Case {
+            operand: operand.as_ref().map(|operand| Box::new(extract(operand))),
+            alternatives: alternatives.iter().map(|(when, then)| (extract(when), extract(then))).collect(),
+            default: default.as_ref().map(|default| Box::new(extract(default))),
+        },
+        Expression::FunctionCall { name, distinct, arguments } => Expression::FunctionCall {
+            name: name.clone(),
+            distinct: *distinct,
+            arguments: arguments.iter().map(extract).collect(),
+        },
+        expression => expression.clone(),
+    }
+}
+
+/// Hidden variable holding the value of the `i`th aggregate of a group;
+/// the space keeps it apart from any name a query can write
+fn aggregate_variable(i: usize) -> String {
+    format!(" aggregate{}", i)
+}
+
+/// Values compared with [`Value::order`], to group rows and drop duplicates
+struct GroupKey(Vec<Value>);
+
+impl PartialEq for GroupKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for GroupKey {}
+
+impl PartialOrd for GroupKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for GroupKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.iter().zip(&other.0)
+            .map(|(a, b)| a.order(b))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| self.0.len().cmp(&other.0.len()))
+    }
+}
+
 /// Internal match result representation
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 struct MatchResult {
-    bindings: HashMap<String, Rid>,
+    bindings: HashMap<String, Value>,
 }
@@ -5,12 +5,14 @@ pub mod ast;
 pub mod parser;
 pub mod planner;
 pub mod executor;
+pub mod value;
 
 pub use ast::{Query, Statement, MatchClause, WhereClause, ReturnClause};
 pub use executor::{CypherExecutor, QueryResult};
 pub use planner::QueryPlanner;
+pub use value::Value;
 
-use fcdb_graph::GraphDB;
+use fcdb_graph::{ConstraintViolation, GraphDB, QuotaExceeded, ScopeViolation, Transaction, TransactionConflict};
 
 /// Execute a Cypher query against the graph database
 /// Merkle DAG: fcdb_cypher -> execute_cypher(query, graph) -> result
@@ -22,6 +24,15 @@ pub async fn execute_cypher(
     executor.execute(query).await
 }
 
+/// Execute a Cypher query as part of a larger transaction
+pub async fn execute_cypher_in<'a>(
+    query: &str,
+    tx: &mut Transaction<'a>,
+) -> Result<QueryResult, CypherError> {
+    let mut executor = CypherExecutor::new(tx.graph());
+    executor.execute_in(tx, query).await
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum CypherError {
     #[error("Parse error: {0}")]
@@ -32,6 +43,36 @@ pub enum CypherError {
     Execution(String),
     #[error("Graph error: {0}")]
     Graph(String),
+    #[error("Constraint violation: {0}")]
+    Constraint(#[from] ConstraintViolation),
+    #[error("Transaction conflict: {0}")]
+    Conflict(#[from] TransactionConflict),
+    #[error("Forbidden: {0}")]
+    Forbidden(#[from] ScopeViolation),
+    #[error("Storage quota: {0}")]
+    Quota(#[from] QuotaExceeded),
+}
+
+impl CypherError {
+    /// Wrap a graph error, keeping constraint violations, transaction
+    /// conflicts, scope violations and refused quota charges typed
+    pub fn graph(err: Box<dyn std::error::Error>) -> Self {
+        if let Some(exceeded) = QuotaExceeded::find(err.as_ref()) {
+            return CypherError::Quota(exceeded.clone());
+        }
+        let err = match err.downcast::<ConstraintViolation>() {
+            Ok(violation) => return CypherError::Constraint(*violation),
+            Err(err) => err,
+        };
+        let err = match err.downcast::<TransactionConflict>() {
+            Ok(conflict) => return CypherError::Conflict(*conflict),
+            Err(err) => err,
+        };
+        match err.downcast::<ScopeViolation>() {
+            Ok(violation) => CypherError::Forbidden(*violation),
+            Err(err) => CypherError::Graph(err.to_string()),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -82,9 +123,421 @@ mod tests {
         assert!(result.rows.len() >= 0);
     }
 
+    #[tokio::test]
+    async fn test_execute_cypher_updates() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let cas = PackCAS::open(temp_dir.path()).await.unwrap();
+        let graph = GraphDB::new(cas).await;
+
+        let result = execute_cypher(
+            "CREATE (a:Person {name: 'Alice', age: 30})-[:KNOWS {since: 2020}]->(b:Person {name: 'Bob'})",
+            &graph,
+        ).await.unwrap();
+        assert_eq!(result.stats.nodes_created, 2);
+        assert_eq!(result.stats.relationships_created, 1);
+        assert_eq!(result.stats.labels_added, 2);
+        assert_eq!(result.stats.properties_set, 4);
+        assert!(result.rows.is_empty());
+
+        // MERGE finds the existing node the second time
+        let merge = "MERGE (c:Person {name: 'Carol'}) ON CREATE SET c.new = true ON MATCH SET c.new = false RETURN c.new";
+        let created = execute_cypher(merge, &graph).await.unwrap();
+        assert_eq!(created.stats.nodes_created, 1);
+        assert_eq!(created.rows[0]["c.new"], serde_json::json!(true));
+        let matched = execute_cypher(merge, &graph).await.unwrap();
+        assert_eq!(matched.stats.nodes_created, 0);
+        assert_eq!(matched.rows[0]["c.new"], serde_json::json!(false));
+
+        let result = execute_cypher(
+            "MATCH (n:Person) WHERE n.name = 'Alice' SET n.age = 31, n:Admin REMOVE n.name RETURN n",
+            &graph,
+        ).await.unwrap();
+        assert_eq!(result.stats.properties_set, 2);
+        assert_eq!(result.stats.labels_added, 1);
+        assert_eq!(result.rows[0]["n"]["age"], serde_json::json!(31));
+        assert!(result.rows[0]["n"].get("name").is_none());
+
+        // Relationship properties are rewritten in place
+        let result = execute_cypher(
+            "MERGE (a:Admin)-[r:KNOWS]->(b:Person {name: 'Bob'}) SET r.since = 2021 RETURN r",
+            &graph,
+        ).await.unwrap();
+        assert_eq!(result.stats.relationships_created, 0);
+        assert_eq!(result.rows[0]["r"], serde_json::json!({"since": 2021}));
+
+        // A plain DELETE of a connected node fails and leaves the graph untouched
+        let err = execute_cypher("MATCH (n:Admin) DELETE n", &graph).await.unwrap_err();
+        assert!(matches!(err, CypherError::Execution(_)));
+        assert_eq!(graph.node_count().await, 3);
+
+        let result = execute_cypher("MATCH (n:Admin) DETACH DELETE n", &graph).await.unwrap();
+        assert_eq!(result.stats.nodes_deleted, 1);
+        assert_eq!(result.stats.relationships_deleted, 1);
+        assert_eq!(graph.node_count().await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_execute_cypher_deletes_one_parallel_edge() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let cas = PackCAS::open(temp_dir.path()).await.unwrap();
+        let graph = GraphDB::new(cas).await;
+        execute_cypher(
+            "CREATE (a:Person {name: 'Ann'})-[:KNOWS {since: 2019}]->(b:Person {name: 'Ben'}), (a)-[:KNOWS {since: 2021}]->(b), \
+             (a)-[:KNOWS {since: 2023}]->(b), (a)-[:KNOWS {since: 2023}]->(b)",
+            &graph,
+        ).await.unwrap();
+        let since = |graph| async move {
+            let result = execute_cypher("MATCH ()-[r:KNOWS]->() RETURN r.since ORDER BY r.since", graph).await.unwrap();
+            result.rows.iter().map(|row| row["r.since"].as_i64().unwrap()).collect::<Vec<_>>()
+        };
+        let ann = graph.list_rids().await[0];
+        let created = graph.committed_timestamp();
+
+        // Identical parallel edges are still distinct relationships of a path
+        let result = execute_cypher("MATCH (:Person {name: 'Ann'})-[:KNOWS*2]-(x) RETURN count(*) AS paths", &graph).await.unwrap();
+        assert_eq!(result.rows[0]["paths"], 12);
+
+        let result = execute_cypher("MATCH ()-[r:KNOWS {since: 2019}]->() DELETE r", &graph).await.unwrap();
+        assert_eq!(result.stats.relationships_deleted, 1);
+        assert_eq!(since(&graph).await, [2021, 2023, 2023]);
+
+        // Of two identical edges, only the bound one goes
+        let result = execute_cypher("MATCH ()-[r:KNOWS {since: 2023}]->() WITH r LIMIT 1 DELETE r", &graph).await.unwrap();
+        assert_eq!(result.stats.relationships_deleted, 1);
+        assert_eq!(since(&graph).await, [2021, 2023]);
+
+        // Updating one edge leaves the history of its siblings alone
+        execute_cypher("MATCH ()-[r:KNOWS {since: 2021}]->() SET r.since = 2022", &graph).await.unwrap();
+        assert_eq!(since(&graph).await, [2022, 2023]);
+        let live = graph.get_edges_from(ann).await;
+        assert_eq!(live.iter().filter(|edge| edge.timestamp == created).count(), 1);
+        assert_eq!(graph.get_edges_from_at(ann, Some(created)).await.len(), 4);
+
+        // A relationship bound twice is deleted once
+        let result = execute_cypher("MATCH p = ()-[r:KNOWS]->() DELETE r, p", &graph).await.unwrap();
+        assert_eq!(result.stats.relationships_deleted, 2);
+        assert_eq!(result.stats.nodes_deleted, 2);
+        assert!(since(&graph).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_execute_cypher_index_sees_own_writes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let cas = PackCAS::open(temp_dir.path()).await.unwrap();
+        let graph = GraphDB::new(cas).await;
+        graph.create_index(fcdb_graph::IndexDefinition::new("p_a", Some("P"), ["a"], fcdb_graph::IndexKind::BTree)).await.unwrap();
+
+        // Nodes created earlier in the query are found through the index
+        let result = execute_cypher("CREATE (:P {a: 1}) WITH 1 AS x MATCH (p:P {a: 1}) RETURN p.a", &graph).await.unwrap();
+        assert_eq!(result.rows.len(), 1);
+        let result = execute_cypher("CREATE (:P {a: 5}) WITH 1 AS x MATCH (p:P) WHERE p.a > 2 RETURN p.a", &graph).await.unwrap();
+        assert_eq!(result.rows.len(), 1);
+
+        // Nodes changed earlier in the query are found under their new values only
+        let result = execute_cypher(
+            "MATCH (p:P {a: 1}) SET p.a = 2 WITH 1 AS x MATCH (q:P {a: 1}) RETURN count(q) AS n",
+            &graph,
+        ).await.unwrap();
+        assert_eq!(result.rows[0]["n"], serde_json::json!(0));
+        let result = execute_cypher(
+            "MATCH (p:P {a: 2}) SET p.a = 3 WITH 1 AS x MATCH (q:P) WHERE q.a >= 3 RETURN q.a ORDER BY q.a",
+            &graph,
+        ).await.unwrap();
+        assert_eq!(result.rows.iter().map(|row| row["q.a"].clone()).collect::<Vec<_>>(), [serde_json::json!(3), serde_json::json!(5)]);
+    }
+
+    #[tokio::test]
+    async fn test_execute_cypher_expressions() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let cas = PackCAS::open(temp_dir.path()).await.unwrap();
+        let graph = GraphDB::new(cas).await;
+
+        execute_cypher("CREATE (:Person {name: 'Alice', age: 30, email: 'alice@example.com'}), \
+                        (:Person {name: 'Bob', age: 17}), \
+                        (:Person {name: 'Carol', email: 'carol@test.org'})", &graph).await.unwrap();
+
+        let names = |result: QueryResult| {
+            let mut names: Vec<String> = result.rows.iter()
+                .map(|row| row["n.name"].as_str().unwrap().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+        let query = |condition: &str| format!("MATCH (n:Person) WHERE {} RETURN n.name", condition);
+
+        // Carol has no age, so `n.age >= 18` is null for her and she is dropped either way
+        let result = execute_cypher(&query("n.age >= 18 OR n.name = 'Bob'"), &graph).await.unwrap();
+        assert_eq!(names(result), ["Alice", "Bob"]);
+        let result = execute_cypher(&query("NOT n.age >= 18"), &graph).await.unwrap();
+        assert_eq!(names(result), ["Bob"]);
+        let result = execute_cypher(&query("n.age IS NULL OR n.age % 2 = 1 XOR n.name ENDS WITH 'e'"), &graph).await.unwrap();
+        assert_eq!(names(result), ["Alice", "Bob", "Carol"]);
+        let result = execute_cypher(&query("n.email =~ '.*@example\\\\.com' OR n.name CONTAINS 'aro'"), &graph).await.unwrap();
+        assert_eq!(names(result), ["Alice", "Carol"]);
+        let result = execute_cypher(&query("n.name STARTS WITH 'B' AND n.age * 2 + 1 IN [35, 41 - 6]"), &graph).await.unwrap();
+        assert_eq!(names(result), ["Bob"]);
+        let result = execute_cypher(&query("toUpper(n.name) = 'CAROL' AND size(n.name) = 5"), &graph).await.unwrap();
+        assert_eq!(names(result), ["Carol"]);
+
+        let result = execute_cypher(
+            "MATCH (n:Person) WHERE n.name = 'Alice' \
+             SET n.group = CASE WHEN n.age < 18 THEN 'minor' ELSE 'adult' END, \
+                 n.tags = ['a'] + coalesce(n.missing, 'b') \
+             RETURN n",
+            &graph,
+        ).await.unwrap();
+        assert_eq!(result.rows[0]["n"]["group"], serde_json::json!("adult"));
+        assert_eq!(result.rows[0]["n"]["tags"], serde_json::json!(["a", "b"]));
+
+        let err = execute_cypher(&query("n.age / 0 = 1"), &graph).await.unwrap_err();
+        assert!(matches!(err, CypherError::Execution(_)));
+        let err = execute_cypher(&query("n.age"), &graph).await.unwrap_err();
+        assert!(matches!(err, CypherError::Execution(_)));
+    }
+
+    #[tokio::test]
+    async fn test_execute_cypher_aggregation() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let cas = PackCAS::open(temp_dir.path()).await.unwrap();
+        let graph = GraphDB::new(cas).await;
+
+        execute_cypher("CREATE (:Person {name: 'Alice', city: 'Oslo', age: 30}), \
+                        (:Person {name: 'Bob', city: 'Oslo', age: 20}), \
+                        (:Person {name: 'Carol', city: 'Rome', age: 40}), \
+                        (:Person {name: 'Dan', city: 'Rome'}), \
+                        (:Person {name: 'Eve', age: 20})", &graph).await.unwrap();
+
+        let result = execute_cypher(
+            "MATCH (p:Person) RETURN p.city AS city, count(*) AS people, count(p.age) AS aged, \
+             sum(p.age) AS total, avg(p.age) AS average, collect(p.name) AS names \
+             ORDER BY people DESC, city",
+            &graph,
+        ).await.unwrap();
+        assert_eq!(result.columns, ["city", "people", "aged", "total", "average", "names"]);
+        let rows: Vec<serde_json::Value> = result.rows.iter()
+            .map(|row| serde_json::json!([row["city"], row["people"], row["aged"], row["total"], row["average"]]))
+            .collect();
+        assert_eq!(rows, [
+            serde_json::json!(["Oslo", 2, 2, 50, 25.0]),
+            serde_json::json!(["Rome", 2, 1, 40, 40.0]),
+            // Null sorts last and groups like any other key
+            serde_json::json!([null, 1, 1, 20, 20.0]),
+        ]);
+        assert_eq!(result.rows[0]["names"], serde_json::json!(["Alice", "Bob"]));
+
+        let result = execute_cypher(
+            "MATCH (p:Person) RETURN count(DISTINCT p.age) AS ages, min(p.age), max(p.name), \
+             percentileCont(p.age, 0.5) AS median, stDev(p.age) AS spread, count(*) * 10 AS scaled",
+            &graph,
+        ).await.unwrap();
+        let row = &result.rows[0];
+        assert_eq!(row["ages"], serde_json::json!(3));
+        assert_eq!(row["min(p.age)"], serde_json::json!(20));
+        assert_eq!(row["max(p.name)"], serde_json::json!("Eve"));
+        assert_eq!(row["median"], serde_json::json!(25.0));
+        assert!((row["spread"].as_f64().unwrap() - (275.0_f64 / 3.0).sqrt()).abs() < 1e-9);
+        assert_eq!(row["scaled"], serde_json::json!(50));
+
+        // Without grouping keys an empty match still aggregates to one row
+        let result = execute_cypher("MATCH (p:Person) WHERE p.age > 99 RETURN count(*) AS n, sum(p.age) AS s", &graph).await.unwrap();
+        assert_eq!(result.rows.len(), 1);
+        assert_eq!((&result.rows[0]["n"], &result.rows[0]["s"]), (&serde_json::json!(0), &serde_json::json!(0)));
+
+        // SKIP applies before LIMIT, after ORDER BY
+        let result = execute_cypher("MATCH (p:Person) RETURN p.name AS name ORDER BY p.name DESC SKIP 1 LIMIT 2", &graph).await.unwrap();
+        let names: Vec<&str> = result.rows.iter().map(|row| row["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["Dan", "Carol"]);
+
+        let result = execute_cypher("MATCH (p:Person) RETURN DISTINCT p.city AS city ORDER BY city", &graph).await.unwrap();
+        let cities: Vec<&serde_json::Value> = result.rows.iter().map(|row| &row["city"]).collect();
+        assert_eq!(cities, [&serde_json::json!("Oslo"), &serde_json::json!("Rome"), &serde_json::Value::Null]);
+
+        assert!(matches!(
+            execute_cypher("MATCH (p) WHERE count(*) > 1 RETURN p", &graph).await,
+            Err(CypherError::Planning(_)),
+        ));
+        assert!(matches!(
+            execute_cypher("MATCH (p) RETURN count(count(*))", &graph).await,
+            Err(CypherError::Planning(_)),
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_execute_cypher_pipelines() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let cas = PackCAS::open(temp_dir.path()).await.unwrap();
+        let graph = GraphDB::new(cas).await;
+
+        execute_cypher("CREATE (a:Person {name: 'Alice', age: 30})-[:KNOWS]->(b:Person {name: 'Bob', age: 20}), \
+                        (c:Person {name: 'Carol', age: 40}), (:City {name: 'Oslo'})", &graph).await.unwrap();
+
+        // WITH projects and filters rows between clauses
+        let result = execute_cypher(
+            "MATCH (p:Person) WITH p.name AS name, p.age * 2 AS double WHERE double > 50 \
+             RETURN name, double ORDER BY name",
+            &graph,
+        ).await.unwrap();
+        let rows: Vec<serde_json::Value> = result.rows.iter().map(|row| serde_json::json!([row["name"], row["double"]])).collect();
+        assert_eq!(rows, [serde_json::json!(["Alice", 60]), serde_json::json!(["Carol", 80])]);
+
+        let result = execute_cypher("UNWIND [1, 2, null] AS x UNWIND [] AS y RETURN x", &graph).await.unwrap();
+        assert!(result.rows.is_empty());
+        let result = execute_cypher("UNWIND [3, 1, 2] AS x RETURN x ORDER BY x", &graph).await.unwrap();
+        let xs: Vec<&serde_json::Value> = result.rows.iter().map(|row| &row["x"]).collect();
+        assert_eq!(xs, [&serde_json::json!(1), &serde_json::json!(2), &serde_json::json!(3)]);
+
+        // A second MATCH starting from a bound variable extends each row
+        let result = execute_cypher(
+            "MATCH (p:Person {name: 'Alice'}) MATCH (p)-[:KNOWS]->(friend) RETURN p.name, friend.name",
+            &graph,
+        ).await.unwrap();
+        assert_eq!(result.rows.len(), 1);
+        assert_eq!(result.rows[0]["friend.name"], serde_json::json!("Bob"));
+
+        let result = execute_cypher(
+            "MATCH (p:Person) OPTIONAL MATCH (p)-[:KNOWS]->(friend) \
+             RETURN p.name AS name, friend.name AS friend ORDER BY name",
+            &graph,
+        ).await.unwrap();
+        let rows: Vec<serde_json::Value> = result.rows.iter().map(|row| serde_json::json!([row["name"], row["friend"]])).collect();
+        assert_eq!(rows, [
+            serde_json::json!(["Alice", "Bob"]),
+            serde_json::json!(["Bob", null]),
+            serde_json::json!(["Carol", null]),
+        ]);
+
+        let result = execute_cypher(
+            "MATCH (p:Person) RETURN p.age > 25 AS old UNION MATCH (c:City) RETURN false AS old",
+            &graph,
+        ).await.unwrap();
+        assert_eq!(result.columns, ["old"]);
+        assert_eq!(result.rows.len(), 2);
+        let result = execute_cypher(
+            "MATCH (p:Person) RETURN p.age > 25 AS old UNION ALL MATCH (c:City) RETURN false AS old",
+            &graph,
+        ).await.unwrap();
+        assert_eq!(result.rows.len(), 4);
+
+        let result = execute_cypher(
+            "MATCH (p:Person) CALL { WITH p OPTIONAL MATCH (p)-[:KNOWS]->(f) RETURN count(f) AS friends } \
+             RETURN p.name AS name, friends ORDER BY name",
+            &graph,
+        ).await.unwrap();
+        let friends: Vec<&serde_json::Value> = result.rows.iter().map(|row| &row["friends"]).collect();
+        assert_eq!(friends, [&serde_json::json!(1), &serde_json::json!(0), &serde_json::json!(0)]);
+
+        // A subquery without RETURN runs its updates once per row
+        let result = execute_cypher("UNWIND ['x', 'y'] AS tag CALL { WITH tag CREATE (:Tag {name: tag}) }", &graph).await.unwrap();
+        assert_eq!(result.stats.nodes_created, 2);
+
+        assert!(matches!(
+            execute_cypher("RETURN 1 AS a UNION RETURN 2 AS b", &graph).await,
+            Err(CypherError::Planning(_)),
+        ));
+        assert!(matches!(
+            execute_cypher("RETURN 1 AS a UNION RETURN 2 AS a UNION ALL RETURN 3 AS a", &graph).await,
+            Err(CypherError::Planning(_)),
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_execute_cypher_patterns() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let cas = PackCAS::open(temp_dir.path()).await.unwrap();
+        let graph = GraphDB::new(cas).await;
+
+        execute_cypher("CREATE (a:Person {name: 'Alice'})-[:KNOWS {since: 2020}]->(b:Person {name: 'Bob'}) \
+                        -[:KNOWS]->(c:Person {name: 'Carol'})-[:KNOWS]->(:Robot {name: 'Dan'}), \
+                        (a)-[:LIKES]->(c)", &graph).await.unwrap();
+        let names = |result: &QueryResult, column: &str| -> Vec<String> {
+            result.rows.iter().map(|row| row[column].as_str().unwrap_or("null").to_string()).collect()
+        };
+
+        // Every variable along the chain is bound
+        let result = execute_cypher(
+            "MATCH (a:Person)-[r:KNOWS]->(b)-[:KNOWS]->(c) RETURN a.name AS a, r.since AS since, b.name AS b, c.name AS c ORDER BY a",
+            &graph,
+        ).await.unwrap();
+        let rows: Vec<serde_json::Value> = result.rows.iter()
+            .map(|row| serde_json::json!([row["a"], row["since"], row["b"], row["c"]]))
+            .collect();
+        assert_eq!(rows, [
+            serde_json::json!(["Alice", 2020, "Bob", "Carol"]),
+            serde_json::json!(["Bob", null, "Carol", "Dan"]),
+        ]);
+
+        let result = execute_cypher("MATCH (b {name: 'Bob'})<-[:KNOWS]-(a) RETURN a.name AS name", &graph).await.unwrap();
+        assert_eq!(names(&result, "name"), ["Alice"]);
+        let result = execute_cypher("MATCH ({name: 'Bob'})-[:KNOWS]-(x) RETURN x.name AS name ORDER BY name", &graph).await.unwrap();
+        assert_eq!(names(&result, "name"), ["Alice", "Carol"]);
+
+        // Labels and inline properties apply to every node and relationship
+        let result = execute_cypher("MATCH (a)-[:KNOWS]->(:Robot) RETURN a.name AS name", &graph).await.unwrap();
+        assert_eq!(names(&result, "name"), ["Carol"]);
+        let result = execute_cypher("MATCH ()-[:KNOWS {since: 2020}]->(b) RETURN b.name AS name", &graph).await.unwrap();
+        assert_eq!(names(&result, "name"), ["Bob"]);
+
+        // A relationship is never used twice, so this cannot walk back to Alice
+        let result = execute_cypher("MATCH ({name: 'Alice'})-[:KNOWS]-()-[:KNOWS]-(c) RETURN c.name AS name", &graph).await.unwrap();
+        assert_eq!(names(&result, "name"), ["Carol"]);
+
+        let result = execute_cypher(
+            "MATCH (a {name: 'Alice'})-[:KNOWS]->(b), (a)-[:LIKES]->(c) RETURN b.name AS b, c.name AS c",
+            &graph,
+        ).await.unwrap();
+        assert_eq!((names(&result, "b"), names(&result, "c")), (vec!["Bob".to_string()], vec!["Carol".to_string()]));
+        let result = execute_cypher(
+            "MATCH ({name: 'Alice'})-[r1:KNOWS]->(), ({name: 'Alice'})-[r2:KNOWS]->() RETURN r1",
+            &graph,
+        ).await.unwrap();
+        assert!(result.rows.is_empty());
+
+        let result = execute_cypher("MATCH ({name: 'Alice'})-[:KNOWS*2..3]->(x) RETURN x.name AS name ORDER BY name", &graph).await.unwrap();
+        assert_eq!(names(&result, "name"), ["Carol", "Dan"]);
+        let result = execute_cypher("MATCH ({name: 'Alice'})-[:KNOWS*0..1]->(x) RETURN x.name AS name ORDER BY name", &graph).await.unwrap();
+        assert_eq!(names(&result, "name"), ["Alice", "Bob"]);
+        let result = execute_cypher("MATCH ({name: 'Alice'})-[rs:KNOWS*]->(:Robot) RETURN size(rs) AS hops", &graph).await.unwrap();
+        assert_eq!(result.rows[0]["hops"], serde_json::json!(3));
+
+        // Closing the cycle still gives finitely many unbounded paths
+        execute_cypher("MATCH (d:Robot), (a {name: 'Alice'}) CREATE (d)-[:KNOWS]->(a)", &graph).await.unwrap();
+        let result = execute_cypher("MATCH ({name: 'Alice'})-[:KNOWS*]->(x) RETURN x.name AS name ORDER BY name", &graph).await.unwrap();
+        assert_eq!(names(&result, "name"), ["Alice", "Bob", "Carol", "Dan"]);
+
+        let result = execute_cypher(
+            "MATCH p = ({name: 'Alice'})-[:KNOWS*2]->() RETURN p, length(p) AS hops, size(nodes(p)) AS nodes",
+            &graph,
+        ).await.unwrap();
+        let row = &result.rows[0];
+        assert_eq!((&row["hops"], &row["nodes"]), (&serde_json::json!(2), &serde_json::json!(3)));
+        assert_eq!(row["p"], serde_json::json!([{"name": "Alice"}, {"since": 2020}, {"name": "Bob"}, {}, {"name": "Carol"}]));
+
+        assert!(matches!(
+            execute_cypher("CREATE p = (:Person)", &graph).await,
+            Err(CypherError::Planning(_)),
+        ));
+    }
+
     #[test]
     fn test_cypher_error_display() {
         let error = CypherError::Parse("invalid syntax".to_string());
         assert!(error.to_string().contains("invalid syntax"));
+
+        let violation = ConstraintViolation::Missing {
+            constraint: "user_email".to_string(),
+            label: "User".to_string(),
+            property: "email".to_string(),
+        };
+        let error = CypherError::graph(Box::new(violation.clone()));
+        assert!(matches!(&error, CypherError::Constraint(v) if *v == violation));
+        assert!(matches!(CypherError::graph("boom".into()), CypherError::Graph(_)));
+
+        let conflict = TransactionConflict::Write { rid: fcdb_graph::Rid(1), snapshot: fcdb_graph::Timestamp(1), committed_at: fcdb_graph::Timestamp(2) };
+        assert!(matches!(CypherError::graph(Box::new(conflict)), CypherError::Conflict(_)));
+        let forbidden = ScopeViolation { rid: fcdb_graph::Rid(7) };
+        assert!(matches!(CypherError::graph(Box::new(forbidden)), CypherError::Forbidden(_)));
+        let exceeded = QuotaExceeded { scope: fcdb_graph::QuotaScope::Namespace(1), used: 8, requested: 3, limit: 10 };
+        let io_error = std::io::Error::other(exceeded.clone());
+        assert!(matches!(CypherError::graph(Box::new(io_error)), CypherError::Quota(q) if q == exceeded));
     }
 }
@@ -8,62 +8,125 @@ use crate::ast::*;
 pub struct CypherParser;
 
 pub fn parse_query(input: &str) -> Result<Query, String> {
-    let pairs = CypherParser::parse(Rule::cypher_query, input)
-        .map_err(|e| format!("Parse error: {}", e))?;
+    let query = CypherParser::parse(Rule::cypher_query, input)
+        .map_err(|e| format!("Parse error: {}", e))?
+        .next()
+        .ok_or("Empty query")?;
+
+    // SOI, the query body, then the optional semicolon and EOI
+    let body = query.into_inner()
+        .find(|pair| pair.as_rule() == Rule::query_body)
+        .ok_or("Empty query")?;
+    parse_query_body(body)
+}
+
+fn parse_query_body(pair: pest::iterators::Pair<Rule>) -> Result<Query, String> {
+    let mut parts = pair.into_inner();
+    let statements = parse_single_query(parts.next().unwrap())?;
+    let mut unions = Vec::new();
+
+    while let (Some(union), Some(part)) = (parts.next(), parts.next()) {
+        let all = union.into_inner().any(|inner| inner.as_rule() == Rule::ALL);
+        unions.push(Union { all, statements: parse_single_query(part)? });
+    }
 
+    Ok(Query { statements, unions })
+}
+
+fn parse_single_query(pair: pest::iterators::Pair<Rule>) -> Result<Vec<Statement>, String> {
     let mut statements = Vec::new();
 
-    for pair in pairs {
+    for pair in pair.into_inner() {
         match pair.as_rule() {
             Rule::match_clause => {
-                let pattern = parse_pattern(pair)?;
-                statements.push(Statement::Match(MatchClause { pattern }));
+                let mut parts = pair.into_inner();
+                let optional = parts.next().unwrap().as_rule() == Rule::OPTIONAL;
+                let patterns = parse_pattern(parts.find(|part| part.as_rule() == Rule::pattern).unwrap())?;
+                statements.push(Statement::Match(MatchClause { patterns, optional }));
             }
             Rule::where_clause => {
-                let condition = parse_expression(pair.into_inner().next().unwrap())?;
+                let condition = parse_expression(pair.into_inner().nth(1).unwrap())?;
                 statements.push(Statement::Where(WhereClause { condition }));
             }
+            Rule::with_clause => {
+                statements.push(Statement::With(parse_projection(pair.into_inner().nth(1).unwrap())?));
+            }
+            Rule::unwind_clause => {
+                let mut parts = pair.into_inner();
+                let expression = parse_expression(parts.nth(1).unwrap())?;
+                let variable = parts.nth(1).unwrap().as_str().to_string();
+                statements.push(Statement::Unwind(UnwindClause { expression, variable }));
+            }
+            Rule::call_clause => {
+                let query = parse_query_body(pair.into_inner().nth(1).unwrap())?;
+                statements.push(Statement::Call(CallClause { query: Box::new(query) }));
+            }
+            Rule::create_clause => {
+                let patterns = parse_pattern(pair.into_inner().nth(1).unwrap())?;
+                statements.push(Statement::Create(CreateClause { patterns }));
+            }
+            Rule::merge_clause => {
+                statements.push(Statement::Merge(parse_merge_clause(pair)?));
+            }
+            Rule::set_clause => {
+                let items = pair.into_inner()
+                    .filter(|inner| inner.as_rule() == Rule::set_item)
+                    .map(parse_set_item)
+                    .collect::<Result<_, _>>()?;
+                statements.push(Statement::Set(SetClause { items }));
+            }
+            Rule::remove_clause => {
+                let items = pair.into_inner()
+                    .filter(|inner| inner.as_rule() == Rule::remove_item)
+                    .map(parse_remove_item)
+                    .collect::<Result<_, _>>()?;
+                statements.push(Statement::Remove(RemoveClause { items }));
+            }
+            Rule::delete_clause => {
+                let mut detach = false;
+                let mut expressions = Vec::new();
+                for inner in pair.into_inner() {
+                    match inner.as_rule() {
+                        Rule::DETACH => detach = true,
+                        Rule::expression => expressions.push(parse_expression(inner)?),
+                        _ => {}
+                    }
+                }
+                statements.push(Statement::Delete(DeleteClause { expressions, detach }));
+            }
             Rule::return_clause => {
                 let return_clause = parse_return_clause(pair)?;
                 statements.push(Statement::Return(return_clause));
             }
-            _ => {} // Skip other rules
+            _ => {}
         }
     }
 
-    Ok(Query { statements })
+    Ok(statements)
+}
+
+fn parse_pattern(pair: pest::iterators::Pair<Rule>) -> Result<Vec<Pattern>, String> {
+    pair.into_inner().map(parse_pattern_part).collect()
 }
 
-fn parse_pattern(pair: pest::iterators::Pair<Rule>) -> Result<Pattern, String> {
+fn parse_pattern_part(pair: pest::iterators::Pair<Rule>) -> Result<Pattern, String> {
+    let mut path_variable = None;
     let mut elements = Vec::new();
 
     for inner_pair in pair.into_inner() {
         match inner_pair.as_rule() {
-            Rule::pattern_element => {
-                let element = parse_pattern_element(inner_pair)?;
-                elements.push(element);
+            Rule::variable => path_variable = Some(inner_pair.as_str().to_string()),
+            Rule::node_pattern => {
+                elements.push(PatternElement::Node(parse_node_pattern(inner_pair)?));
             }
-            _ => {}
+            Rule::relationship_pattern => {
+                elements.push(PatternElement::Relationship(parse_relationship_pattern(inner_pair)?));
+            }
+            _ => return Err("Unknown pattern element".to_string()),
         }
     }
 
-    Ok(Pattern { elements })
-}
-
-fn parse_pattern_element(pair: pest::iterators::Pair<Rule>) -> Result<PatternElement, String> {
-    let inner = pair.into_inner().next().unwrap();
-
-    match inner.as_rule() {
-        Rule::node_pattern => {
-            let node = parse_node_pattern(inner)?;
-            Ok(PatternElement::Node(node))
-        }
-        Rule::relationship_pattern => {
-            let rel = parse_relationship_pattern(inner)?;
-            Ok(PatternElement::Relationship(rel))
-        }
-        _ => Err("Unknown pattern element".to_string()),
-    }
+    Ok(Pattern { path_variable, elements })
 }
 
 fn parse_node_pattern(pair: pest::iterators::Pair<Rule>) -> Result<NodePattern, String> {
@@ -77,7 +140,7 @@ fn parse_node_pattern(pair: pest::iterators::Pair<Rule>) -> Result<NodePattern,
                 variable = Some(inner.as_str().to_string());
             }
             Rule::label => {
-                labels.push(inner.as_str().trim_start_matches(':').to_string());
+                labels.push(inner.as_str().trim_start_matches(':').trim().to_string());
             }
             Rule::property_map => {
                 properties = parse_property_map(inner)?;
@@ -96,28 +159,45 @@ fn parse_node_pattern(pair: pest::iterators::Pair<Rule>) -> Result<NodePattern,
 fn parse_relationship_pattern(pair: pest::iterators::Pair<Rule>) -> Result<RelationshipPattern, String> {
     let mut variable = None;
     let mut types = Vec::new();
-    let direction = Direction::Outgoing; // Default
+    let mut incoming = false;
+    let mut outgoing = false;
     let mut length = None;
     let mut properties = Vec::new();
 
     for inner in pair.into_inner() {
         match inner.as_rule() {
-            Rule::variable => {
-                variable = Some(inner.as_str().to_string());
-            }
-            Rule::label => {
-                types.push(inner.as_str().trim_start_matches(':').to_string());
-            }
-            Rule::path_length => {
-                length = Some(parse_path_length(inner)?);
-            }
-            Rule::property_map => {
-                properties = parse_property_map(inner)?;
+            Rule::left_arrow => incoming = true,
+            Rule::right_arrow => outgoing = true,
+            Rule::relationship_detail => {
+                for detail in inner.into_inner() {
+                    match detail.as_rule() {
+                        Rule::variable => {
+                            variable = Some(detail.as_str().to_string());
+                        }
+                        Rule::relationship_types => {
+                            types.extend(detail.into_inner().map(|name| name.as_str().to_string()));
+                        }
+                        Rule::path_length => {
+                            length = Some(parse_path_length(detail)?);
+                        }
+                        Rule::property_map => {
+                            properties = parse_property_map(detail)?;
+                        }
+                        _ => {}
+                    }
+                }
             }
             _ => {}
         }
     }
 
+    // `<-->` names both directions, like `--`
+    let direction = match (incoming, outgoing) {
+        (false, true) => Direction::Outgoing,
+        (true, false) => Direction::Incoming,
+        _ => Direction::Bidirectional,
+    };
+
     Ok(RelationshipPattern {
         variable,
         types,
@@ -128,34 +208,25 @@ fn parse_relationship_pattern(pair: pest::iterators::Pair<Rule>) -> Result<Relat
 }
 
 fn parse_path_length(pair: pest::iterators::Pair<Rule>) -> Result<PathLength, String> {
-    let inner = pair.into_inner().next();
-
-    match inner {
-        Some(p) => {
-            match p.as_str() {
-                "*" => Ok(PathLength::Any),
-                s if s.starts_with('*') => {
-                    // Parse range like *1..3
-                    let range_str = &s[1..];
-                    let parts: Vec<&str> = range_str.split("..").collect();
-                    match parts.len() {
-                        1 => {
-                            let min = parts[0].parse().map_err(|_| "Invalid range")?;
-                            Ok(PathLength::Range(min, None))
-                        }
-                        2 => {
-                            let min = parts[0].parse().map_err(|_| "Invalid range")?;
-                            let max = parts[1].parse().map_err(|_| "Invalid range")?;
-                            Ok(PathLength::Range(min, Some(max)))
-                        }
-                        _ => Err("Invalid path length".to_string()),
-                    }
-                }
-                _ => Err("Invalid path length".to_string()),
-            }
+    let text = pair.as_str();
+    let mut min = None;
+    let mut max = None;
+
+    for inner in pair.into_inner() {
+        let bound: u32 = inner.as_str().trim().parse().map_err(|_| "Invalid range")?;
+        match inner.as_rule() {
+            Rule::path_min => min = Some(bound),
+            Rule::path_max => max = Some(bound),
+            _ => {}
         }
-        None => Ok(PathLength::Any),
     }
+
+    Ok(match (min, max) {
+        (None, None) if !text.contains("..") => PathLength::Any,
+        // `*n` is exactly n hops
+        (Some(min), None) if !text.contains("..") => PathLength::Range(min, Some(min)),
+        (min, max) => PathLength::Range(min.unwrap_or(1), max),
+    })
 }
 
 fn parse_property_map(pair: pest::iterators::Pair<Rule>) -> Result<Vec<Property>, String> {
@@ -194,25 +265,157 @@ fn parse_property_pair(pair: pest::iterators::Pair<Rule>) -> Result<Property, St
 }
 
 fn parse_expression(pair: pest::iterators::Pair<Rule>) -> Result<Expression, String> {
-    let inner = pair.into_inner().next().unwrap();
-
-    match inner.as_rule() {
-        Rule::literal => parse_literal(inner),
-        Rule::variable => Ok(Expression::Variable(inner.as_str().to_string())),
-        Rule::property_access => parse_property_access(inner),
-        Rule::comparison_expression => parse_comparison_expression(inner),
+    match pair.as_rule() {
+        Rule::expression | Rule::parenthesized => parse_expression(pair.into_inner().next().unwrap()),
+        Rule::or_expression | Rule::xor_expression | Rule::and_expression => {
+            let op = match pair.as_rule() {
+                Rule::or_expression => BinaryOperator::Or,
+                Rule::xor_expression => BinaryOperator::Xor,
+                _ => BinaryOperator::And,
+            };
+            let mut operands = pair.into_inner()
+                .filter(|inner| !matches!(inner.as_rule(), Rule::OR | Rule::XOR | Rule::AND));
+            let mut left = parse_expression(operands.next().unwrap())?;
+            for operand in operands {
+                left = binary(left, op.clone(), parse_expression(operand)?);
+            }
+            Ok(left)
+        }
+        Rule::not_expression => {
+            let mut inner: Vec<_> = pair.into_inner().collect();
+            let mut expression = parse_expression(inner.pop().unwrap())?;
+            for _ in inner {
+                expression = Expression::Not(Box::new(expression));
+            }
+            Ok(expression)
+        }
+        Rule::comparison_expression => {
+            let mut parts = pair.into_inner();
+            let mut left = parse_expression(parts.next().unwrap())?;
+            let mut comparisons = Vec::new();
+            while let (Some(op), Some(right)) = (parts.next(), parts.next()) {
+                let op = match op.as_rule() {
+                    Rule::EQUALS => BinaryOperator::Equal,
+                    Rule::NOT_EQUALS => BinaryOperator::NotEqual,
+                    Rule::LESS_THAN => BinaryOperator::LessThan,
+                    Rule::GREATER_THAN => BinaryOperator::GreaterThan,
+                    Rule::LESS_EQUALS => BinaryOperator::LessEqual,
+                    Rule::GREATER_EQUALS => BinaryOperator::GreaterEqual,
+                    _ => return Err("Unknown operator".to_string()),
+                };
+                let right = parse_expression(right)?;
+                comparisons.push(binary(left, op, right.clone()));
+                left = right;
+            }
+            Ok(comparisons.into_iter()
+                .reduce(|chain, comparison| binary(chain, BinaryOperator::And, comparison))
+                .unwrap_or(left))
+        }
+        Rule::predicate_expression => {
+            let mut parts = pair.into_inner();
+            let mut expression = parse_expression(parts.next().unwrap())?;
+            for predicate in parts {
+                let rule = predicate.as_rule();
+                let mut inner = predicate.into_inner()
+                    .filter(|part| part.as_rule() == Rule::additive_expression || part.as_rule() == Rule::NOT);
+                if rule == Rule::is_null {
+                    let negated = inner.next().is_some();
+                    expression = Expression::IsNull { expression: Box::new(expression), negated };
+                    continue;
+                }
+                let right = parse_expression(inner.next().unwrap())?;
+                expression = match rule {
+                    Rule::in_list => Expression::In { left: Box::new(expression), list: Box::new(right) },
+                    Rule::starts_with => binary(expression, BinaryOperator::StartsWith, right),
+                    Rule::ends_with => binary(expression, BinaryOperator::EndsWith, right),
+                    Rule::contains => binary(expression, BinaryOperator::Contains, right),
+                    Rule::regex_match => binary(expression, BinaryOperator::RegexMatch, right),
+                    _ => return Err("Unknown predicate".to_string()),
+                };
+            }
+            Ok(expression)
+        }
+        Rule::additive_expression | Rule::multiplicative_expression => {
+            let mut parts = pair.into_inner();
+            let mut left = parse_expression(parts.next().unwrap())?;
+            while let (Some(op), Some(right)) = (parts.next(), parts.next()) {
+                let op = match op.as_str() {
+                    "+" => BinaryOperator::Add,
+                    "-" => BinaryOperator::Subtract,
+                    "*" => BinaryOperator::Multiply,
+                    "/" => BinaryOperator::Divide,
+                    _ => BinaryOperator::Modulo,
+                };
+                left = binary(left, op, parse_expression(right)?);
+            }
+            Ok(left)
+        }
+        Rule::unary_expression => {
+            let mut inner: Vec<_> = pair.into_inner().collect();
+            let mut expression = parse_expression(inner.pop().unwrap())?;
+            for _ in inner {
+                expression = match expression {
+                    // Negative numbers are literals, which the planner can use with indexes
+                    Expression::Literal(Literal::Integer(i)) => Expression::Literal(Literal::Integer(-i)),
+                    Expression::Literal(Literal::Float(f)) => Expression::Literal(Literal::Float(-f)),
+                    expression => Expression::Negate(Box::new(expression)),
+                };
+            }
+            Ok(expression)
+        }
+        Rule::literal => parse_literal(pair),
+        Rule::variable => Ok(Expression::Variable(pair.as_str().to_string())),
+        Rule::property_access => parse_property_access(pair),
+        Rule::list => Ok(Expression::List(pair.into_inner().map(parse_expression).collect::<Result<_, _>>()?)),
+        Rule::property_map => Ok(Expression::Map(parse_property_map(pair)?)),
+        Rule::case_expression => {
+            let mut operand = None;
+            let mut alternatives = Vec::new();
+            let mut default = None;
+            for inner in pair.into_inner() {
+                match inner.as_rule() {
+                    Rule::case_operand => operand = Some(Box::new(parse_expression(inner.into_inner().next().unwrap())?)),
+                    Rule::case_alternative => {
+                        let mut parts = inner.into_inner().filter(|part| part.as_rule() == Rule::expression);
+                        let when = parse_expression(parts.next().unwrap())?;
+                        let then = parse_expression(parts.next().unwrap())?;
+                        alternatives.push((when, then));
+                    }
+                    Rule::case_default => default = Some(Box::new(parse_expression(inner.into_inner().nth(1).unwrap())?)),
+                    _ => {}
+                }
+            }
+            Ok(Expression::Case { operand, alternatives, default })
+        }
+        Rule::count_star => Ok(Expression::CountStar),
+        Rule::function_call => {
+            let mut parts = pair.into_inner();
+            let name = parts.next().unwrap().as_str().to_string();
+            let mut distinct = false;
+            let mut arguments = Vec::new();
+            for part in parts {
+                match part.as_rule() {
+                    Rule::DISTINCT => distinct = true,
+                    _ => arguments.push(parse_expression(part)?),
+                }
+            }
+            Ok(Expression::FunctionCall { name, distinct, arguments })
+        }
         _ => Err("Unsupported expression type".to_string()),
     }
 }
 
+fn binary(left: Expression, op: BinaryOperator, right: Expression) -> Expression {
+    Expression::BinaryOp { left: Box::new(left), op, right: Box::new(right) }
+}
+
 fn parse_literal(pair: pest::iterators::Pair<Rule>) -> Result<Expression, String> {
     let inner = pair.into_inner().next().unwrap();
 
     match inner.as_rule() {
         Rule::string => {
             let s = inner.as_str();
-            let cleaned = s.trim_matches('"').replace("\\\"", "\"");
-            Ok(Expression::Literal(Literal::String(cleaned)))
+            Ok(Expression::Literal(Literal::String(unescape(&s[1..s.len() - 1]))))
         }
         Rule::integer => {
             let i: i64 = inner.as_str().parse().map_err(|_| "Invalid integer")?;
@@ -223,15 +426,39 @@ fn parse_literal(pair: pest::iterators::Pair<Rule>) -> Result<Expression, String
             Ok(Expression::Literal(Literal::Float(f)))
         }
         Rule::boolean => {
-            let b: bool = inner.as_str().parse().map_err(|_| "Invalid boolean")?;
-            Ok(Expression::Literal(Literal::Boolean(b)))
+            Ok(Expression::Literal(Literal::Boolean(inner.as_str().eq_ignore_ascii_case("true"))))
         }
         Rule::null => Ok(Expression::Literal(Literal::Null)),
         _ => Err("Unknown literal type".to_string()),
     }
 }
 
+/// Body of a string literal with its escape sequences resolved
+fn unescape(body: &str) -> String {
+    let mut out = String::with_capacity(body.len());
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
 fn parse_property_access(pair: pest::iterators::Pair<Rule>) -> Result<Expression, String> {
+    let (variable, property) = variable_and_key(pair);
+    Ok(Expression::PropertyAccess { variable, property })
+}
+
+fn variable_and_key(pair: pest::iterators::Pair<Rule>) -> (String, String) {
     let mut variable = String::new();
     let mut property = String::new();
 
@@ -243,51 +470,120 @@ fn parse_property_access(pair: pest::iterators::Pair<Rule>) -> Result<Expression
         }
     }
 
-    Ok(Expression::PropertyAccess { variable, property })
+    (variable, property)
 }
 
-fn parse_comparison_expression(pair: pest::iterators::Pair<Rule>) -> Result<Expression, String> {
-    let mut parts = pair.into_inner();
+fn parse_merge_clause(pair: pest::iterators::Pair<Rule>) -> Result<MergeClause, String> {
+    let mut pattern = None;
+    let mut on_create = Vec::new();
+    let mut on_match = Vec::new();
 
-    let left = parse_expression(parts.next().unwrap())?;
-    let op_pair = parts.next().unwrap();
-    let right = parse_expression(parts.next().unwrap())?;
-
-    let op = match op_pair.as_str() {
-        "=" => BinaryOperator::Equal,
-        "<>" => BinaryOperator::NotEqual,
-        "<" => BinaryOperator::LessThan,
-        ">" => BinaryOperator::GreaterThan,
-        "<=" => BinaryOperator::LessEqual,
-        ">=" => BinaryOperator::GreaterEqual,
-        _ => return Err("Unknown operator".to_string()),
-    };
+    for inner in pair.into_inner() {
+        match inner.as_rule() {
+            Rule::pattern_part => pattern = Some(parse_pattern_part(inner)?),
+            Rule::merge_action => {
+                let mut parts = inner.into_inner();
+                let on = parts.nth(1).unwrap().as_rule();
+                let items = parts
+                    .filter(|part| part.as_rule() == Rule::set_item)
+                    .map(parse_set_item)
+                    .collect::<Result<Vec<_>, _>>()?;
+                if on == Rule::CREATE {
+                    on_create.extend(items);
+                } else {
+                    on_match.extend(items);
+                }
+            }
+            _ => {}
+        }
+    }
 
-    Ok(Expression::BinaryOp {
-        left: Box::new(left),
-        op,
-        right: Box::new(right),
+    Ok(MergeClause {
+        pattern: pattern.ok_or("Missing MERGE pattern")?,
+        on_create,
+        on_match,
     })
 }
 
+fn parse_set_item(pair: pest::iterators::Pair<Rule>) -> Result<SetItem, String> {
+    let inner = pair.into_inner().next().unwrap();
+
+    match inner.as_rule() {
+        Rule::set_property => {
+            let mut parts = inner.into_inner();
+            let (variable, property) = variable_and_key(parts.next().unwrap());
+            let value = parse_expression(parts.next().unwrap())?;
+            Ok(SetItem::Property { variable, property, value })
+        }
+        Rule::merge_properties | Rule::replace_properties => {
+            let replace = inner.as_rule() == Rule::replace_properties;
+            let mut parts = inner.into_inner();
+            let variable = parts.next().unwrap().as_str().to_string();
+            let properties = parse_property_map(parts.next().unwrap())?;
+            Ok(SetItem::Properties { variable, properties, replace })
+        }
+        Rule::set_labels => {
+            let (variable, labels) = variable_and_labels(inner);
+            Ok(SetItem::Labels { variable, labels })
+        }
+        _ => Err("Unsupported SET item".to_string()),
+    }
+}
+
+fn parse_remove_item(pair: pest::iterators::Pair<Rule>) -> Result<RemoveItem, String> {
+    let inner = pair.into_inner().next().unwrap();
+
+    match inner.as_rule() {
+        Rule::property_access => {
+            let (variable, property) = variable_and_key(inner);
+            Ok(RemoveItem::Property { variable, property })
+        }
+        Rule::set_labels => {
+            let (variable, labels) = variable_and_labels(inner);
+            Ok(RemoveItem::Labels { variable, labels })
+        }
+        _ => Err("Unsupported REMOVE item".to_string()),
+    }
+}
+
+fn variable_and_labels(pair: pest::iterators::Pair<Rule>) -> (String, Vec<String>) {
+    let mut parts = pair.into_inner();
+    let variable = parts.next().unwrap().as_str().to_string();
+    let labels = parts.map(|label| label.as_str().trim_start_matches(':').trim().to_string()).collect();
+    (variable, labels)
+}
+
 fn parse_return_clause(pair: pest::iterators::Pair<Rule>) -> Result<ReturnClause, String> {
+    parse_projection(pair.into_inner().nth(1).unwrap())
+}
+
+fn parse_projection(pair: pest::iterators::Pair<Rule>) -> Result<ReturnClause, String> {
     let mut items = Vec::new();
     let mut distinct = false;
+    let mut order_by = Vec::new();
     let mut limit = None;
     let mut skip = None;
 
     for inner in pair.into_inner() {
         match inner.as_rule() {
             Rule::DISTINCT => distinct = true,
-            Rule::return_item => {
+            Rule::projection_item => {
                 let item = parse_return_item(inner)?;
                 items.push(item);
             }
-            Rule::LIMIT => {
-                limit = Some(inner.into_inner().next().unwrap().as_str().parse().unwrap());
+            Rule::order_by => {
+                for sort_item in inner.into_inner().filter(|part| part.as_rule() == Rule::sort_item) {
+                    let mut parts = sort_item.into_inner();
+                    let expression = parse_expression(parts.next().unwrap())?;
+                    let descending = parts.next().is_some_and(|direction| direction.as_rule() == Rule::DESC);
+                    order_by.push(SortItem { expression, descending });
+                }
+            }
+            Rule::limit => {
+                limit = Some(parse_count(inner)?);
             }
-            Rule::SKIP => {
-                skip = Some(inner.into_inner().next().unwrap().as_str().parse().unwrap());
+            Rule::skip => {
+                skip = Some(parse_count(inner)?);
             }
             _ => {}
         }
@@ -296,32 +592,28 @@ fn parse_return_clause(pair: pest::iterators::Pair<Rule>) -> Result<ReturnClause
     Ok(ReturnClause {
         items,
         distinct,
+        order_by,
         limit,
         skip,
     })
 }
 
-fn parse_return_item(pair: pest::iterators::Pair<Rule>) -> Result<ReturnItem, String> {
-    let inner = pair.into_inner().next().unwrap();
-
-    match inner.as_rule() {
-        Rule::variable => Ok(ReturnItem::Variable(inner.as_str().to_string())),
-        Rule::property_access => {
-            let mut variable = String::new();
-            let mut property = String::new();
-
-            for part in inner.into_inner() {
-                match part.as_rule() {
-                    Rule::variable => variable = part.as_str().to_string(),
-                    Rule::property_key => property = part.as_str().to_string(),
-                    _ => {}
-                }
-            }
+/// Non-negative integer argument of SKIP or LIMIT
+fn parse_count(pair: pest::iterators::Pair<Rule>) -> Result<u32, String> {
+    let value = pair.into_inner().nth(1).unwrap().as_str();
+    value.parse().map_err(|_| format!("Invalid count {}", value))
+}
 
-            Ok(ReturnItem::Property { variable, property })
-        }
-        _ => Ok(ReturnItem::Count), // Simplified
+fn parse_return_item(pair: pest::iterators::Pair<Rule>) -> Result<ReturnItem, String> {
+    let mut parts = pair.into_inner();
+    let expression_pair = parts.next().unwrap();
+    // Unaliased columns are named after the expression as written
+    let mut name = expression_pair.as_str().trim().to_string();
+    let expression = parse_expression(expression_pair)?;
+    if let Some(alias) = parts.find(|part| part.as_rule() == Rule::variable) {
+        name = alias.as_str().to_string();
     }
+    Ok(ReturnItem { expression, name })
 }
 
 #[cfg(test)]
@@ -340,5 +632,125 @@ mod tests {
         let query = "MATCH (n:Person)-[:KNOWS]->(m:Person) RETURN n, m";
         let result = parse_query(query);
         assert!(result.is_ok());
+
+        let query = parse_query("MATCH p = (a)<-[:KNOWS*2..]-(b), (b)--(c) RETURN p").unwrap();
+        let Statement::Match(match_clause) = &query.statements[0] else { unreachable!() };
+        assert_eq!(match_clause.patterns.len(), 2);
+        assert_eq!(match_clause.patterns[0].path_variable.as_deref(), Some("p"));
+        assert_eq!(match_clause.patterns[0].variables(), ["a", "b", "p"]);
+        let PatternElement::Relationship(rel) = &match_clause.patterns[0].elements[1] else { unreachable!() };
+        assert!(matches!(rel.direction, Direction::Incoming));
+        assert!(matches!(rel.length, Some(PathLength::Range(2, None))));
+        assert!(match_clause.patterns[1].path_variable.is_none());
+    }
+
+    #[test]
+    fn test_parse_updating_clauses() {
+        let query = parse_query(
+            "MATCH (a:Person) WHERE a.name = 'Alice' \
+             CREATE (a)-[r:KNOWS {since: 2020}]->(b:Person:Friend {name: \"Bob\"}), (c) \
+             MERGE (a)<-[:LIKES]-(d {name: 'Dan'}) ON CREATE SET d.new = true ON MATCH SET d.seen = 1 \
+             SET a.age = 31, a += {city: 'Oslo'}, b:Active \
+             REMOVE a.temp, b:Friend \
+             DETACH DELETE c RETURN a, b.name",
+        ).unwrap();
+
+        let kinds: Vec<&str> = query.statements.iter().map(|statement| match statement {
+            Statement::Match(_) => "match",
+            Statement::Where(_) => "where",
+            Statement::With(_) => "with",
+            Statement::Unwind(_) => "unwind",
+            Statement::Call(_) => "call",
+            Statement::Create(_) => "create",
+            Statement::Merge(_) => "merge",
+            Statement::Set(_) => "set",
+            Statement::Remove(_) => "remove",
+            Statement::Delete(_) => "delete",
+            Statement::Return(_) => "return",
+        }).collect();
+        assert_eq!(kinds, ["match", "where", "create", "merge", "set", "remove", "delete", "return"]);
+
+        let Statement::Create(create) = &query.statements[2] else { unreachable!() };
+        assert_eq!(create.patterns.len(), 2);
+        assert_eq!(create.patterns[0].elements.len(), 3);
+        let PatternElement::Relationship(rel) = &create.patterns[0].elements[1] else { unreachable!() };
+        assert_eq!((rel.variable.as_deref(), rel.types.as_slice()), (Some("r"), ["KNOWS".to_string()].as_slice()));
+        assert!(matches!(rel.direction, Direction::Outgoing));
+        let PatternElement::Node(b) = &create.patterns[0].elements[2] else { unreachable!() };
+        assert_eq!(b.labels, ["Person", "Friend"]);
+
+        let Statement::Merge(merge) = &query.statements[3] else { unreachable!() };
+        let PatternElement::Relationship(rel) = &merge.pattern.elements[1] else { unreachable!() };
+        assert!(matches!(rel.direction, Direction::Incoming));
+        assert_eq!((merge.on_create.len(), merge.on_match.len()), (1, 1));
+
+        let Statement::Set(set) = &query.statements[4] else { unreachable!() };
+        assert!(matches!(&set.items[1], SetItem::Properties { replace: false, .. }));
+        assert!(matches!(&set.items[2], SetItem::Labels { labels, .. } if labels == &["Active"]));
+
+        let Statement::Delete(delete) = &query.statements[6] else { unreachable!() };
+        assert!(delete.detach);
+
+        // Keywords are case-insensitive but never swallow names that start with one
+        assert!(parse_query("match (n) where n.on_call = true set n.setting = 'x' return n").is_ok());
+        assert!(parse_query("CREATE (n) RETURN").is_err());
+    }
+
+    #[test]
+    fn test_parse_expression_precedence() {
+        let query = parse_query(
+            "MATCH (n) WHERE NOT n.a = 1 OR n.b XOR n.c AND n.d + 2 * -3 < 5 RETURN n",
+        ).unwrap();
+        let Statement::Where(where_clause) = &query.statements[1] else { unreachable!() };
+        // ((NOT (n.a = 1)) OR (n.b XOR (n.c AND ((n.d + (2 * -3)) < 5))))
+        let Expression::BinaryOp { left, op: BinaryOperator::Or, right } = &where_clause.condition else { panic!() };
+        assert!(matches!(left.as_ref(), Expression::Not(inner) if matches!(inner.as_ref(), Expression::BinaryOp { op: BinaryOperator::Equal, .. })));
+        let Expression::BinaryOp { op: BinaryOperator::Xor, right, .. } = right.as_ref() else { panic!() };
+        let Expression::BinaryOp { op: BinaryOperator::And, right, .. } = right.as_ref() else { panic!() };
+        let Expression::BinaryOp { left, op: BinaryOperator::LessThan, .. } = right.as_ref() else { panic!() };
+        let Expression::BinaryOp { op: BinaryOperator::Add, right, .. } = left.as_ref() else { panic!() };
+        assert!(matches!(right.as_ref(), Expression::BinaryOp {
+            op: BinaryOperator::Multiply,
+            right,
+            ..
+        } if matches!(right.as_ref(), Expression::Literal(Literal::Integer(-3)))));
+
+        let query = parse_query(
+            "MATCH (n) WHERE n.name STARTS WITH 'A' AND n.email =~ '.*@x\\.com' AND n.age IS NOT NULL \
+             AND n.tag IN ['a', 'b'] AND 1 < n.x <= 3 \
+             RETURN n",
+        ).unwrap();
+        let Statement::Where(where_clause) = &query.statements[1] else { unreachable!() };
+        let Expression::BinaryOp { right, .. } = &where_clause.condition else { panic!() };
+        // The chained comparison becomes `1 < n.x AND n.x <= 3`
+        assert!(matches!(right.as_ref(), Expression::BinaryOp { op: BinaryOperator::And, .. }));
+
+        assert!(parse_query("CREATE (n {v: CASE WHEN 1 > 2 THEN 'a' ELSE toUpper('b') END, m: {k: [1, 2.5]}})").is_ok());
+        assert!(parse_query("CREATE (n {v: CASE 1 WHEN 1 THEN 'one' END})").is_ok());
+        assert!(parse_query("MATCH (n) WHERE n.a AND RETURN n").is_err());
+    }
+
+    #[test]
+    fn test_parse_query_pipeline() {
+        let query = parse_query(
+            "OPTIONAL MATCH (n) WITH n.name AS name WHERE name <> 'x' UNWIND [1, 2] AS i \
+             CALL { WITH name RETURN name AS inner } RETURN name, i \
+             UNION ALL RETURN 'a' AS name, 1 AS i",
+        ).unwrap();
+        let kinds: Vec<&str> = query.statements.iter().map(|statement| match statement {
+            Statement::Match(clause) if clause.optional => "optional match",
+            Statement::With(_) => "with",
+            Statement::Where(_) => "where",
+            Statement::Unwind(_) => "unwind",
+            Statement::Call(call) if call.query.statements.len() == 2 => "call",
+            Statement::Return(_) => "return",
+            _ => "other",
+        }).collect();
+        assert_eq!(kinds, ["optional match", "with", "where", "unwind", "call", "return"]);
+        assert_eq!(query.unions.len(), 1);
+        assert!(query.unions[0].all);
+
+        assert!(parse_query("UNION RETURN 1 AS x").is_err());
+        assert!(parse_query("CALL { RETURN 1 AS x ").is_err());
     }
 }
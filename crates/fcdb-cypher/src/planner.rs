@@ -1,53 +1,106 @@
 use crate::ast::*;
-use fcdb_graph::{GraphDB, Rid, LabelId, Timestamp};
+use fcdb_graph::{GraphDB, Rid, PropertyValue, Transaction};
+use std::collections::HashSet;
+use std::ops::Bound;
 
 /// Query execution plan
 #[derive(Debug, Clone)]
 pub struct ExecutionPlan {
-    pub match_plan: MatchPlan,
-    pub where_plan: Option<WherePlan>,
-    pub return_plan: ReturnPlan,
+    /// Clauses applied in order to a stream of rows, starting from a single
+    /// empty row
+    pub clauses: Vec<ClausePlan>,
+    /// `None` only for queries that end with an update
+    pub return_plan: Option<ReturnPlan>,
+    /// Queries whose rows UNION adds; `true` keeps duplicate rows
+    pub unions: Vec<(bool, ExecutionPlan)>,
 }
 
-#[derive(Debug, Clone)]
-pub struct MatchPlan {
-    pub start_nodes: Vec<Rid>,
-    pub traversals: Vec<TraversalStep>,
+impl ExecutionPlan {
+    /// Names of the returned columns
+    pub fn columns(&self) -> Vec<String> {
+        self.return_plan.iter()
+            .flat_map(|return_plan| return_plan.items.iter().map(|item| item.name.clone()))
+            .collect()
+    }
 }
 
+/// One step of the row pipeline
 #[derive(Debug, Clone)]
-pub struct TraversalStep {
-    pub from_variable: String,
-    pub to_variable: String,
-    pub relationship_types: Vec<LabelId>,
-    pub direction: Direction,
-    pub min_hops: u32,
-    pub max_hops: Option<u32>,
+pub enum ClausePlan {
+    Match(MatchPlan),
+    Where(WherePlan),
+    /// WITH: project the rows, which then bind only the projected names
+    With(ReturnPlan),
+    Unwind { expression: Expression, variable: String },
+    /// Subquery run for each row; its returned rows extend that row
+    Call(Box<ExecutionPlan>),
+    Update(UpdatePlan),
 }
 
 #[derive(Debug, Clone)]
-pub struct WherePlan {
-    pub conditions: Vec<Condition>,
+pub struct MatchPlan {
+    /// OPTIONAL MATCH: a row without matches continues with the pattern's
+    /// new variables bound to null
+    pub optional: bool,
+    /// WHERE of this MATCH, part of what it means for a row to match
+    pub filter: Option<WherePlan>,
+    /// Variables the patterns name
+    pub variables: Vec<String>,
+    pub patterns: Vec<Pattern>,
+    /// Index access for the first node; `None` scans every node
+    pub start_index: Option<IndexSeek>,
+}
+
+/// Property constraints on a pattern's first node that an index may answer.
+/// Resolved when the match runs, against the executing transaction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexSeek {
+    pub label: Option<String>,
+    pub equalities: Vec<(String, PropertyValue)>,
+    /// Property with its lower and upper bound
+    pub range: Option<(String, Bound<PropertyValue>, Bound<PropertyValue>)>,
+}
+
+impl IndexSeek {
+    /// Candidate start nodes as seen by `tx`, from an index covering the
+    /// equalities or else one led by the range property. `None` when no
+    /// index applies.
+    pub async fn resolve(&self, tx: &Transaction<'_>) -> Option<Vec<Rid>> {
+        let label = self.label.as_deref();
+        if !self.equalities.is_empty() {
+            let values: Vec<(&str, &PropertyValue)> = self.equalities.iter().map(|(key, value)| (key.as_str(), value)).collect();
+            if let Some(rids) = tx.index_lookup(label, &values).await {
+                return Some(rids);
+            }
+        }
+        let (property, lower, upper) = self.range.as_ref()?;
+        tx.index_range(label, property, lower.as_ref(), upper.as_ref()).await
+    }
 }
 
 #[derive(Debug, Clone)]
-pub struct Condition {
-    pub left: ValueRef,
-    pub op: BinaryOperator,
-    pub right: ValueRef,
+pub struct WherePlan {
+    /// Rows are kept only where this evaluates to true
+    pub condition: Expression,
 }
 
+/// Updating clause, applied to every row in query order
 #[derive(Debug, Clone)]
-pub enum ValueRef {
-    Variable(String),
-    Property { variable: String, property: String },
-    Literal(Literal),
+pub enum UpdatePlan {
+    Create(CreateClause),
+    Merge(MergeClause),
+    Set(SetClause),
+    Remove(RemoveClause),
+    Delete(DeleteClause),
 }
 
+/// Projection of RETURN or WITH
 #[derive(Debug, Clone)]
 pub struct ReturnPlan {
+    /// Items with aggregates group the rows by the values of the others
     pub items: Vec<ReturnItem>,
     pub distinct: bool,
+    pub order_by: Vec<SortItem>,
     pub limit: Option<u32>,
     pub skip: Option<u32>,
 }
@@ -64,168 +117,361 @@ impl<'a> QueryPlanner<'a> {
     /// Plan a Cypher query execution
     /// Merkle DAG: fcdb_cypher -> plan_query(query) -> execution_plan
     pub async fn plan_query(&self, query: &Query) -> Result<ExecutionPlan, String> {
-        let mut match_plan = None;
-        let mut where_plan = None;
+        let mut plan = self.plan_single_query(&query.statements).await?;
+
+        if query.unions.iter().any(|union| union.all) && !query.unions.iter().all(|union| union.all) {
+            return Err("UNION and UNION ALL cannot be mixed".to_string());
+        }
+        for union in &query.unions {
+            let part = self.plan_single_query(&union.statements).await?;
+            if plan.return_plan.is_none() || part.return_plan.is_none() {
+                return Err("Every query in a UNION must end with RETURN".to_string());
+            }
+            if part.columns() != plan.columns() {
+                return Err("Every query in a UNION must return the same columns".to_string());
+            }
+            plan.unions.push((union.all, part));
+        }
+
+        Ok(plan)
+    }
+
+    async fn plan_single_query(&self, statements: &[Statement]) -> Result<ExecutionPlan, String> {
+        let mut clauses = Vec::new();
         let mut return_plan = None;
+        // Variables bound by the clauses planned so far
+        let mut bound = HashSet::new();
 
-        for statement in &query.statements {
+        for (i, statement) in statements.iter().enumerate() {
             match statement {
                 Statement::Match(match_clause) => {
-                    match_plan = Some(self.plan_match(&match_clause.pattern).await?);
+                    // WHERE predicates can narrow the start nodes through an index
+                    let where_condition = match statements.get(i + 1) {
+                        Some(Statement::Where(where_clause)) => Some(&where_clause.condition),
+                        _ => None,
+                    };
+                    let mut match_plan = self.plan_match(&match_clause.patterns, where_condition, &bound).await?;
+                    match_plan.optional = match_clause.optional;
+                    bound.extend(match_plan.variables.iter().cloned());
+                    clauses.push(ClausePlan::Match(match_plan));
                 }
                 Statement::Where(where_clause) => {
-                    where_plan = Some(self.plan_where(&where_clause.condition)?);
+                    let where_plan = self.plan_where(&where_clause.condition)?;
+                    // A WHERE after MATCH is part of it, which matters for OPTIONAL MATCH
+                    match clauses.last_mut() {
+                        Some(ClausePlan::Match(match_plan)) if match_plan.filter.is_none() => {
+                            match_plan.filter = Some(where_plan);
+                        }
+                        _ => clauses.push(ClausePlan::Where(where_plan)),
+                    }
+                }
+                Statement::With(projection) => {
+                    bound = projection.items.iter().map(|item| item.name.clone()).collect();
+                    clauses.push(ClausePlan::With(self.plan_return(projection)?));
+                }
+                Statement::Unwind(unwind) => {
+                    if unwind.expression.contains_aggregate() {
+                        return Err("Aggregate functions are not allowed in UNWIND".to_string());
+                    }
+                    bound.insert(unwind.variable.clone());
+                    clauses.push(ClausePlan::Unwind {
+                        expression: unwind.expression.clone(),
+                        variable: unwind.variable.clone(),
+                    });
+                }
+                Statement::Call(call) => {
+                    let subquery = Box::pin(self.plan_query(&call.query)).await?;
+                    bound.extend(subquery.columns());
+                    clauses.push(ClausePlan::Call(Box::new(subquery)));
                 }
                 Statement::Return(return_clause) => {
                     return_plan = Some(self.plan_return(return_clause)?);
                 }
+                Statement::Create(create) => {
+                    if create.patterns.iter().any(|pattern| pattern.path_variable.is_some()) {
+                        return Err("Paths can only be named in MATCH".to_string());
+                    }
+                    bound.extend(create.patterns.iter().flat_map(Pattern::variables).cloned());
+                    clauses.push(ClausePlan::Update(UpdatePlan::Create(create.clone())));
+                }
+                Statement::Merge(merge) => {
+                    if merge.pattern.path_variable.is_some() {
+                        return Err("Paths can only be named in MATCH".to_string());
+                    }
+                    bound.extend(merge.pattern.variables().into_iter().cloned());
+                    clauses.push(ClausePlan::Update(UpdatePlan::Merge(merge.clone())));
+                }
+                Statement::Set(set) => clauses.push(ClausePlan::Update(UpdatePlan::Set(set.clone()))),
+                Statement::Remove(remove) => clauses.push(ClausePlan::Update(UpdatePlan::Remove(remove.clone()))),
+                Statement::Delete(delete) => clauses.push(ClausePlan::Update(UpdatePlan::Delete(delete.clone()))),
             }
         }
 
-        let match_plan = match_plan.ok_or("No MATCH clause found")?;
-        let return_plan = return_plan.ok_or("No RETURN clause found")?;
+        // Only queries that end with an update may end without RETURN
+        let ends_with_update = match clauses.last() {
+            Some(ClausePlan::Update(_)) => true,
+            Some(ClausePlan::Call(subquery)) => subquery.return_plan.is_none(),
+            _ => false,
+        };
+        if return_plan.is_none() && !ends_with_update {
+            return Err("No RETURN clause found".to_string());
+        }
 
         Ok(ExecutionPlan {
-            match_plan,
-            where_plan,
+            clauses,
             return_plan,
+            unions: Vec::new(),
         })
     }
 
-    async fn plan_match(&self, pattern: &Pattern) -> Result<MatchPlan, String> {
-        let mut start_nodes = Vec::new();
-        let mut traversals = Vec::new();
-
-        // For now, start from all nodes if no specific start is given
-        // In a full implementation, we'd analyze the pattern to find optimal starting points
-        if pattern.elements.is_empty() {
+    async fn plan_match(&self, patterns: &[Pattern], where_condition: Option<&Expression>, bound: &HashSet<String>) -> Result<MatchPlan, String> {
+        let Some(first) = patterns.first().and_then(|pattern| pattern.elements.first()) else {
             return Err("Empty pattern".to_string());
-        }
-
-        // Find start nodes (nodes without incoming relationships in the pattern)
-        let mut node_variables = std::collections::HashMap::new();
-        let mut rel_sources: std::collections::HashSet<String> = std::collections::HashSet::new();
-
-        for element in &pattern.elements {
-            match element {
-                PatternElement::Node(node) => {
-                    if let Some(var) = &node.variable {
-                        node_variables.insert(var.clone(), node.clone());
-                    }
-                }
-                PatternElement::Relationship(rel) => {
-                    // This is a simplified approach - we'd need to track variable bindings
-                    // For now, just collect all nodes
-                }
-            }
-        }
+        };
+        let PatternElement::Node(start) = first else {
+            return Err("A pattern must start with a node".to_string());
+        };
 
-        // If we have specific node patterns, use them as start points
-        for (var, node_pattern) in &node_variables {
-            if !node_pattern.labels.is_empty() {
-                // For now, assume all nodes are potential matches
-                // In a real implementation, we'd filter by labels
-                start_nodes.extend(self.graph.list_rids().await);
-                break;
+        let mut seen = HashSet::new();
+        for variable in patterns.iter().filter_map(|pattern| pattern.path_variable.as_ref()) {
+            if bound.contains(variable) || !seen.insert(variable) {
+                return Err(format!("Path variable `{}` is already bound", variable));
             }
         }
 
-        // If no specific patterns, start from all nodes
-        if start_nodes.is_empty() {
-            start_nodes = self.graph.list_rids().await;
-        }
-
-        // Plan traversals for relationships
-        for element in &pattern.elements {
-            if let PatternElement::Relationship(rel) = element {
-                // This is a simplified traversal planning
-                // In a full implementation, we'd need to properly track variable bindings
-                let from_var = "start".to_string(); // Simplified
-                let to_var = "end".to_string();     // Simplified
-
-                let relationship_types = rel.types.iter()
-                    .map(|t| LabelId(t.parse().unwrap_or(0)))
-                    .collect();
-
-                let (min_hops, max_hops) = match &rel.length {
-                    Some(PathLength::Any) => (0, None),
-                    Some(PathLength::Range(min, max)) => (*min, *max),
-                    None => (1, Some(1)),
-                };
-
-                traversals.push(TraversalStep {
-                    from_variable: from_var,
-                    to_variable: to_var,
-                    relationship_types,
-                    direction: rel.direction.clone(),
-                    min_hops,
-                    max_hops,
-                });
-            }
-        }
+        // A node bound by a previous clause is its own start
+        let start_bound = start.variable.as_ref().is_some_and(|variable| bound.contains(variable));
+        let start_index = if start_bound {
+            None
+        } else {
+            let indexes = self.graph.list_indexes().await;
+            index_seek(start, where_condition)
+                .filter(|seek| indexes.iter().any(|index| index.label == seek.label))
+        };
 
         Ok(MatchPlan {
-            start_nodes,
-            traversals,
+            optional: false,
+            filter: None,
+            variables: patterns.iter().flat_map(Pattern::variables).cloned().collect(),
+            patterns: patterns.to_vec(),
+            start_index,
         })
     }
 
     fn plan_where(&self, condition: &Expression) -> Result<WherePlan, String> {
-        let conditions = self.extract_conditions(condition)?;
-        Ok(WherePlan { conditions })
-    }
-
-    fn extract_conditions(&self, expr: &Expression) -> Result<Vec<Condition>, String> {
-        match expr {
-            Expression::BinaryOp { left, op, right } => {
-                let left_ref = self.expr_to_value_ref(left)?;
-                let right_ref = self.expr_to_value_ref(right)?;
-                Ok(vec![Condition {
-                    left: left_ref,
-                    op: op.clone(),
-                    right: right_ref,
-                }])
-            }
-            Expression::In { left, list } => {
-                let left_ref = self.expr_to_value_ref(left)?;
-                let mut conditions = Vec::new();
-
-                // Convert IN to multiple OR conditions
-                for item in list {
-                    let right_ref = self.expr_to_value_ref(item)?;
-                    conditions.push(Condition {
-                        left: left_ref.clone(),
-                        op: BinaryOperator::Equal,
-                        right: right_ref,
-                    });
-                }
-
-                Ok(conditions)
-            }
-            _ => Err("Unsupported WHERE expression".to_string()),
+        if condition.contains_aggregate() {
+            return Err("Aggregate functions are not allowed in WHERE".to_string());
         }
+        Ok(WherePlan { condition: condition.clone() })
     }
 
-    fn expr_to_value_ref(&self, expr: &Expression) -> Result<ValueRef, String> {
-        match expr {
-            Expression::Variable(var) => Ok(ValueRef::Variable(var.clone())),
-            Expression::PropertyAccess { variable, property } => {
-                Ok(ValueRef::Property {
-                    variable: variable.clone(),
-                    property: property.clone(),
-                })
+    fn plan_return(&self, return_clause: &ReturnClause) -> Result<ReturnPlan, String> {
+        let expressions = return_clause.items.iter().map(|item| &item.expression)
+            .chain(return_clause.order_by.iter().map(|sort| &sort.expression));
+        for expression in expressions {
+            if nested_aggregate(expression, false) {
+                return Err("Aggregate functions cannot be nested".to_string());
             }
-            Expression::Literal(lit) => Ok(ValueRef::Literal(lit.clone())),
-            _ => Err("Unsupported expression in condition".to_string()),
         }
-    }
+        let aggregating = return_clause.items.iter().any(|item| item.expression.contains_aggregate());
+        if !aggregating && return_clause.order_by.iter().any(|sort| sort.expression.contains_aggregate()) {
+            return Err("ORDER BY can only aggregate when RETURN does".to_string());
+        }
 
-    fn plan_return(&self, return_clause: &ReturnClause) -> Result<ReturnPlan, String> {
         Ok(ReturnPlan {
             items: return_clause.items.clone(),
             distinct: return_clause.distinct,
+            order_by: return_clause.order_by.clone(),
             limit: return_clause.limit,
             skip: return_clause.skip,
         })
     }
 }
+
+/// Property value of a literal; `null` matches nothing in an index
+fn literal_to_property(literal: &Literal) -> Option<PropertyValue> {
+    Some(match literal {
+        Literal::String(s) => PropertyValue::String(s.clone()),
+        Literal::Integer(i) => PropertyValue::Int(*i),
+        Literal::Float(f) => PropertyValue::Float(*f),
+        Literal::Boolean(b) => PropertyValue::Bool(*b),
+        Literal::Null => return None,
+    })
+}
+
+/// Whether an aggregate is called inside another one
+fn nested_aggregate(expression: &Expression, inside: bool) -> bool {
+    let aggregate = expression.is_aggregate();
+    (aggregate && inside) || expression.children().into_iter().any(|child| nested_aggregate(child, inside || aggregate))
+}
+
+/// Index access for a pattern's first node from its inline properties or
+/// `WHERE` comparisons of its properties with literals, including those
+/// joined by AND. `None` when there is nothing an index could answer.
+fn index_seek(node: &NodePattern, where_condition: Option<&Expression>) -> Option<IndexSeek> {
+    let mut equalities: Vec<(String, PropertyValue)> = node.properties.iter()
+        .filter_map(|property| match &property.value {
+            Expression::Literal(literal) => Some((property.key.clone(), literal_to_property(literal)?)),
+            _ => None,
+        })
+        .collect();
+
+    let mut range = None;
+    if let (Some(variable), Some(condition)) = (&node.variable, where_condition) {
+        for conjunct in conjuncts(condition) {
+            let Expression::BinaryOp { left, op, right } = conjunct else { continue };
+            if !op.is_comparison() {
+                continue;
+            }
+            // Normalize to `variable.property <op> literal`
+            let comparison = match (left.as_ref(), right.as_ref()) {
+                (Expression::PropertyAccess { variable: v, property }, Expression::Literal(literal)) if v == variable => {
+                    Some((property, op.clone(), literal))
+                }
+                (Expression::Literal(literal), Expression::PropertyAccess { variable: v, property }) if v == variable => {
+                    Some((property, flip(op), literal))
+                }
+                _ => None,
+            };
+            if let Some((property, op, literal)) = comparison {
+                if let Some(value) = literal_to_property(literal) {
+                    let bounds = match op {
+                        BinaryOperator::Equal => {
+                            equalities.push((property.clone(), value));
+                            continue;
+                        }
+                        BinaryOperator::LessThan => (Bound::Unbounded, Bound::Excluded(value)),
+                        BinaryOperator::LessEqual => (Bound::Unbounded, Bound::Included(value)),
+                        BinaryOperator::GreaterThan => (Bound::Excluded(value), Bound::Unbounded),
+                        BinaryOperator::GreaterEqual => (Bound::Included(value), Bound::Unbounded),
+                        _ => continue,
+                    };
+                    range = range.or(Some((property.clone(), bounds.0, bounds.1)));
+                }
+            }
+        }
+    }
+
+    if equalities.is_empty() && range.is_none() {
+        return None;
+    }
+    Some(IndexSeek {
+        label: node.labels.first().cloned(),
+        equalities,
+        range,
+    })
+}
+
+/// Terms of a conjunction (`a AND b AND c`); any row the query keeps
+/// satisfies each of them
+fn conjuncts(expression: &Expression) -> Vec<&Expression> {
+    match expression {
+        Expression::BinaryOp { left, op: BinaryOperator::And, right } => {
+            let mut terms = conjuncts(left);
+            terms.extend(conjuncts(right));
+            terms
+        }
+        expression => vec![expression],
+    }
+}
+
+/// Operator with its operands swapped (`a < b` is `b > a`)
+fn flip(op: &BinaryOperator) -> BinaryOperator {
+    match op {
+        BinaryOperator::LessThan => BinaryOperator::GreaterThan,
+        BinaryOperator::GreaterThan => BinaryOperator::LessThan,
+        BinaryOperator::LessEqual => BinaryOperator::GreaterEqual,
+        BinaryOperator::GreaterEqual => BinaryOperator::LessEqual,
+        op => op.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fcdb_cas::PackCAS;
+    use fcdb_graph::{IndexDefinition, IndexKind, Properties};
+
+    fn first_match(plan: &ExecutionPlan) -> &MatchPlan {
+        plan.clauses.iter()
+            .find_map(|clause| match clause {
+                ClausePlan::Match(match_plan) => Some(match_plan),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    fn person_query(condition: Expression) -> Query {
+        Query {
+            statements: vec![
+                Statement::Match(MatchClause {
+                    patterns: vec![Pattern {
+                        path_variable: None,
+                        elements: vec![PatternElement::Node(NodePattern {
+                            variable: Some("p".to_string()),
+                            labels: vec!["Person".to_string()],
+                            properties: vec![],
+                        })],
+                    }],
+                    optional: false,
+                }),
+                Statement::Where(WhereClause { condition }),
+                Statement::Return(ReturnClause {
+                    items: vec![ReturnItem { expression: Expression::Variable("p".to_string()), name: "p".to_string() }],
+                    distinct: false,
+                    order_by: vec![],
+                    limit: None,
+                    skip: None,
+                }),
+            ],
+            unions: vec![],
+        }
+    }
+
+    fn age_condition(op: BinaryOperator, age: i64) -> Expression {
+        Expression::BinaryOp {
+            left: Box::new(Expression::PropertyAccess { variable: "p".to_string(), property: "age".to_string() }),
+            op,
+            right: Box::new(Expression::Literal(Literal::Integer(age))),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_planner_uses_property_index() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let cas = PackCAS::open(temp_dir.path()).await.unwrap();
+        let graph = GraphDB::new(cas).await;
+
+        let mut rids = Vec::new();
+        for age in [20, 30, 40] {
+            let mut properties = Properties::new();
+            properties.insert("age".to_string(), PropertyValue::Int(age));
+            rids.push(graph.create_node_with_properties(["Person"], properties).await.unwrap());
+        }
+        graph.create_node(b"unrelated").await.unwrap();
+
+        let planner = QueryPlanner::new(&graph);
+        let plan = planner.plan_query(&person_query(age_condition(BinaryOperator::GreaterEqual, 30))).await.unwrap();
+        assert_eq!(first_match(&plan).start_index, None);
+
+        graph.create_index(IndexDefinition::new("person_age", Some("Person"), ["age"], IndexKind::BTree)).await.unwrap();
+        let tx = graph.begin();
+        let start_nodes = |condition| async {
+            let plan = planner.plan_query(&person_query(condition)).await.unwrap();
+            assert_eq!(first_match(&plan).variables, ["p"]);
+            first_match(&plan).start_index.as_ref()?.resolve(&tx).await
+        };
+        assert_eq!(start_nodes(age_condition(BinaryOperator::GreaterEqual, 30)).await, Some(rids[1..].to_vec()));
+        assert_eq!(start_nodes(age_condition(BinaryOperator::Equal, 20)).await, Some(vec![rids[0]]));
+        assert_eq!(start_nodes(age_condition(BinaryOperator::Equal, 99)).await, Some(vec![]));
+
+        // Each term of a conjunction narrows the start nodes; a disjunction does not
+        let both = |op| Expression::BinaryOp {
+            left: Box::new(age_condition(BinaryOperator::LessThan, 35)),
+            op,
+            right: Box::new(age_condition(BinaryOperator::Equal, 30)),
+        };
+        assert_eq!(start_nodes(both(BinaryOperator::And)).await, Some(vec![rids[1]]));
+        assert_eq!(start_nodes(both(BinaryOperator::Or)).await, None);
+    }
+}
@@ -0,0 +1,347 @@
+//! Runtime values bound to variables and produced by expressions
+
+use crate::ast::{BinaryOperator, Literal};
+use fcdb_graph::{AdjEntry, LabelId, PropertyValue, Rid};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+/// Value of a variable or expression in one row
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    List(Vec<Value>),
+    Map(BTreeMap<String, Value>),
+    Node(Rid),
+    Relationship(Relationship),
+    Path(Path),
+}
+
+/// A relationship bound by a pattern: the edge `start --label--> end`.
+/// Parallel edges with the same label are told apart by their edge ids.
+#[derive(Debug, Clone)]
+pub struct Relationship {
+    pub start: Rid,
+    pub edge: AdjEntry,
+}
+
+impl Relationship {
+    pub fn end(&self) -> Rid {
+        self.edge.target
+    }
+
+    pub fn label(&self) -> LabelId {
+        self.edge.label
+    }
+}
+
+impl PartialEq for Relationship {
+    fn eq(&self, other: &Self) -> bool {
+        self.start == other.start && self.edge.id == other.edge.id
+    }
+}
+
+/// A path bound by a pattern: its nodes in order, joined by one more node
+/// than relationships
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    pub nodes: Vec<Rid>,
+    pub relationships: Vec<Relationship>,
+}
+
+impl Path {
+    pub fn new(start: Rid) -> Self {
+        Self { nodes: vec![start], relationships: Vec::new() }
+    }
+
+    /// Extend the path along a relationship of its last node, in either
+    /// direction
+    pub fn push(&mut self, relationship: Relationship) {
+        let last = *self.nodes.last().unwrap();
+        let next = if relationship.start == last { relationship.end() } else { relationship.start };
+        self.nodes.push(next);
+        self.relationships.push(relationship);
+    }
+
+    /// Nodes and relationships in path order, alternating
+    pub fn elements(&self) -> Vec<Value> {
+        let mut elements = vec![Value::Node(self.nodes[0])];
+        for (relationship, node) in self.relationships.iter().zip(&self.nodes[1..]) {
+            elements.push(Value::Relationship(relationship.clone()));
+            elements.push(Value::Node(*node));
+        }
+        elements
+    }
+}
+
+impl Value {
+    pub fn from_literal(literal: &Literal) -> Self {
+        match literal {
+            Literal::String(s) => Value::String(s.clone()),
+            Literal::Integer(i) => Value::Int(*i),
+            Literal::Float(f) => Value::Float(*f),
+            Literal::Boolean(b) => Value::Bool(*b),
+            Literal::Null => Value::Null,
+        }
+    }
+
+    /// Value of a stored property; date-times read as microseconds since the
+    /// epoch and byte strings as lists of bytes
+    pub fn from_property(value: &PropertyValue) -> Self {
+        match value {
+            PropertyValue::String(s) => Value::String(s.clone()),
+            PropertyValue::Int(i) => Value::Int(*i),
+            PropertyValue::Float(f) => Value::Float(*f),
+            PropertyValue::Bool(b) => Value::Bool(*b),
+            PropertyValue::List(items) => Value::List(items.iter().map(Value::from_property).collect()),
+            PropertyValue::Map(map) => Value::Map(map.iter().map(|(k, v)| (k.clone(), Value::from_property(v))).collect()),
+            PropertyValue::DateTime(micros) => Value::Int(*micros),
+            PropertyValue::Bytes(bytes) => Value::List(bytes.iter().map(|b| Value::Int(*b as i64)).collect()),
+        }
+    }
+
+    /// Property form of the value; `None` for null, which removes a property.
+    /// Graph entities and lists containing null cannot be stored.
+    pub fn to_property(&self) -> Result<Option<PropertyValue>, String> {
+        Ok(Some(match self {
+            Value::Null => return Ok(None),
+            Value::Bool(b) => PropertyValue::Bool(*b),
+            Value::Int(i) => PropertyValue::Int(*i),
+            Value::Float(f) => PropertyValue::Float(*f),
+            Value::String(s) => PropertyValue::String(s.clone()),
+            Value::List(items) => PropertyValue::List(items.iter()
+                .map(|item| item.to_property()?.ok_or_else(|| "Lists stored as properties cannot contain null".to_string()))
+                .collect::<Result<_, _>>()?),
+            Value::Map(map) => PropertyValue::Map(map.iter()
+                .filter_map(|(k, v)| v.to_property().transpose().map(|v| v.map(|v| (k.clone(), v))))
+                .collect::<Result<_, _>>()?),
+            Value::Node(_) | Value::Relationship(_) | Value::Path(_) => {
+                return Err(format!("{} cannot be stored as a property", self.type_name()));
+            }
+        }))
+    }
+
+    /// Whether two values are equal, comparing integers and floats by value;
+    /// `None` if either side is null, or if lists or maps differ only where
+    /// one of them holds null
+    pub fn equals(&self, other: &Value) -> Option<bool> {
+        match (self, other) {
+            (Value::Null, _) | (_, Value::Null) => None,
+            (Value::Int(a), Value::Float(b)) | (Value::Float(b), Value::Int(a)) => Some(*a as f64 == *b),
+            (Value::List(a), Value::List(b)) => {
+                if a.len() != b.len() {
+                    return Some(false);
+                }
+                all_equal(a.iter().zip(b))
+            }
+            (Value::Map(a), Value::Map(b)) => {
+                if a.len() != b.len() || a.keys().ne(b.keys()) {
+                    return Some(false);
+                }
+                all_equal(a.values().zip(b.values()))
+            }
+            (a, b) => Some(a == b),
+        }
+    }
+
+    /// Order of two numbers, strings or booleans; `None` for null and for
+    /// values of different types, which are not comparable
+    pub fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Int(a), Value::Int(b)) => Some(a.cmp(b)),
+            (Value::Int(a), Value::Float(b)) => (*a as f64).partial_cmp(b),
+            (Value::Float(a), Value::Int(b)) => a.partial_cmp(&(*b as f64)),
+            (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+            _ => None,
+        }
+    }
+
+    /// Total order used by ORDER BY, grouping and DISTINCT: maps, nodes,
+    /// relationships, lists, paths, strings, booleans, numbers, then null
+    pub fn order(&self, other: &Value) -> Ordering {
+        let rank = |value: &Value| match value {
+            Value::Map(_) => 0,
+            Value::Node(_) => 1,
+            Value::Relationship(_) => 2,
+            Value::List(_) => 3,
+            Value::Path(_) => 4,
+            Value::String(_) => 5,
+            Value::Bool(_) => 6,
+            Value::Int(_) | Value::Float(_) => 7,
+            Value::Null => 8,
+        };
+        match (self, other) {
+            (Value::Int(a), Value::Int(b)) => a.cmp(b),
+            (Value::Int(_) | Value::Float(_), Value::Int(_) | Value::Float(_)) => {
+                self.as_float().unwrap().total_cmp(&other.as_float().unwrap())
+            }
+            (Value::String(a), Value::String(b)) => a.cmp(b),
+            (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+            (Value::List(a), Value::List(b)) => order_all(a.iter().zip(b)).then(a.len().cmp(&b.len())),
+            (Value::Map(a), Value::Map(b)) => a.keys().cmp(b.keys())
+                .then_with(|| order_all(a.values().zip(b.values()))),
+            (Value::Node(a), Value::Node(b)) => a.cmp(b),
+            (Value::Relationship(a), Value::Relationship(b)) => (a.start, a.end(), a.label().0, a.edge.id)
+                .cmp(&(b.start, b.end(), b.label().0, b.edge.id)),
+            (Value::Path(a), Value::Path(b)) => {
+                let (a, b) = (a.elements(), b.elements());
+                order_all(a.iter().zip(&b)).then(a.len().cmp(&b.len()))
+            }
+            (a, b) => rank(a).cmp(&rank(b)),
+        }
+    }
+
+    /// Truth value of a boolean operand: `None` for null
+    pub fn truth(&self) -> Result<Option<bool>, String> {
+        match self {
+            Value::Null => Ok(None),
+            Value::Bool(b) => Ok(Some(*b)),
+            other => Err(format!("Expected a boolean, got {}", other.type_name())),
+        }
+    }
+
+    /// Result of `+ - * / %`; null if either side is null. `+` also joins
+    /// strings and lists. Integer overflow and division by zero are errors.
+    pub fn arithmetic(&self, op: &BinaryOperator, other: &Value) -> Result<Value, String> {
+        let overflow = || format!("Integer overflow in {:?}", op);
+        Ok(match (self, op, other) {
+            (Value::Null, _, _) | (_, _, Value::Null) => Value::Null,
+            (Value::Int(a), _, Value::Int(b)) => Value::Int(match op {
+                BinaryOperator::Add => a.checked_add(*b).ok_or_else(overflow)?,
+                BinaryOperator::Subtract => a.checked_sub(*b).ok_or_else(overflow)?,
+                BinaryOperator::Multiply => a.checked_mul(*b).ok_or_else(overflow)?,
+                BinaryOperator::Divide | BinaryOperator::Modulo if *b == 0 => return Err("Division by zero".to_string()),
+                BinaryOperator::Divide => a.checked_div(*b).ok_or_else(overflow)?,
+                _ => a.checked_rem(*b).ok_or_else(overflow)?,
+            }),
+            (Value::Int(_) | Value::Float(_), _, Value::Int(_) | Value::Float(_)) => {
+                let (a, b) = (self.as_float().unwrap(), other.as_float().unwrap());
+                Value::Float(match op {
+                    BinaryOperator::Add => a + b,
+                    BinaryOperator::Subtract => a - b,
+                    BinaryOperator::Multiply => a * b,
+                    BinaryOperator::Divide => a / b,
+                    _ => a % b,
+                })
+            }
+            (Value::List(a), BinaryOperator::Add, Value::List(b)) => Value::List(a.iter().chain(b).cloned().collect()),
+            (Value::List(a), BinaryOperator::Add, b) => Value::List(a.iter().cloned().chain([b.clone()]).collect()),
+            (a, BinaryOperator::Add, Value::List(b)) => Value::List([a.clone()].into_iter().chain(b.iter().cloned()).collect()),
+            (Value::String(a), BinaryOperator::Add, b) => Value::String(format!("{}{}", a, b.to_text().ok_or_else(|| mismatch(self, op, other))?)),
+            (a, BinaryOperator::Add, Value::String(b)) => Value::String(format!("{}{}", a.to_text().ok_or_else(|| mismatch(self, op, other))?, b)),
+            _ => return Err(mismatch(self, op, other)),
+        })
+    }
+
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Value::Int(i) => Some(*i as f64),
+            Value::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    /// Text of a string, number or boolean, as `+` and `toString()` render it
+    pub fn to_text(&self) -> Option<String> {
+        match self {
+            Value::String(s) => Some(s.clone()),
+            Value::Int(i) => Some(i.to_string()),
+            Value::Float(f) => Some(f.to_string()),
+            Value::Bool(b) => Some(b.to_string()),
+            _ => None,
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Bool(_) => "a boolean",
+            Value::Int(_) => "an integer",
+            Value::Float(_) => "a float",
+            Value::String(_) => "a string",
+            Value::List(_) => "a list",
+            Value::Map(_) => "a map",
+            Value::Node(_) => "a node",
+            Value::Relationship(_) => "a relationship",
+            Value::Path(_) => "a path",
+        }
+    }
+}
+
+/// Equality of paired values: false if any pair differs, else null if any
+/// pair involves null
+fn all_equal<'v>(pairs: impl Iterator<Item = (&'v Value, &'v Value)>) -> Option<bool> {
+    let mut result = Some(true);
+    for (a, b) in pairs {
+        match a.equals(b) {
+            Some(false) => return Some(false),
+            None => result = None,
+            Some(true) => {}
+        }
+    }
+    result
+}
+
+/// Order of the first differing pair
+fn order_all<'v>(pairs: impl Iterator<Item = (&'v Value, &'v Value)>) -> Ordering {
+    pairs.map(|(a, b)| a.order(b)).find(|ordering| ordering.is_ne()).unwrap_or(Ordering::Equal)
+}
+
+fn mismatch(left: &Value, op: &BinaryOperator, right: &Value) -> String {
+    format!("Cannot apply {:?} to {} and {}", op, left.type_name(), right.type_name())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_property_round_trip() {
+        let value = Value::Map(BTreeMap::from([
+            ("name".to_string(), Value::String("Alice".to_string())),
+            ("scores".to_string(), Value::List(vec![Value::Int(1), Value::Float(2.5)])),
+            ("gone".to_string(), Value::Null),
+        ]));
+        let property = value.to_property().unwrap().unwrap();
+        let PropertyValue::Map(map) = &property else { panic!("expected a map") };
+        assert!(!map.contains_key("gone"));
+        assert_eq!(Value::from_property(&property), Value::Map(BTreeMap::from([
+            ("name".to_string(), Value::String("Alice".to_string())),
+            ("scores".to_string(), Value::List(vec![Value::Int(1), Value::Float(2.5)])),
+        ])));
+
+        assert_eq!(Value::Null.to_property(), Ok(None));
+        assert!(Value::List(vec![Value::Null]).to_property().is_err());
+        assert!(Value::Node(Rid(1)).to_property().is_err());
+        assert_eq!(Value::Int(2).equals(&Value::Float(2.0)), Some(true));
+        assert_eq!(Value::Int(2).equals(&Value::Null), None);
+    }
+
+    #[test]
+    fn test_operators() {
+        let list = |items: Vec<Value>| Value::List(items);
+        assert_eq!(list(vec![Value::Int(1), Value::Null]).equals(&list(vec![Value::Int(1), Value::Null])), None);
+        assert_eq!(list(vec![Value::Int(2), Value::Null]).equals(&list(vec![Value::Int(1), Value::Null])), Some(false));
+        assert_eq!(Value::Int(1).compare(&Value::Float(1.5)), Some(Ordering::Less));
+        assert_eq!(Value::Int(1).compare(&Value::String("1".to_string())), None);
+
+        assert_eq!(Value::Int(7).arithmetic(&BinaryOperator::Divide, &Value::Int(2)), Ok(Value::Int(3)));
+        assert_eq!(Value::Int(7).arithmetic(&BinaryOperator::Modulo, &Value::Float(2.0)), Ok(Value::Float(1.0)));
+        assert_eq!(Value::String("n".to_string()).arithmetic(&BinaryOperator::Add, &Value::Int(1)), Ok(Value::String("n1".to_string())));
+        assert_eq!(Value::Null.arithmetic(&BinaryOperator::Multiply, &Value::Int(1)), Ok(Value::Null));
+        assert!(Value::Int(1).arithmetic(&BinaryOperator::Divide, &Value::Int(0)).is_err());
+        assert!(Value::Int(i64::MAX).arithmetic(&BinaryOperator::Add, &Value::Int(1)).is_err());
+        assert!(Value::Bool(true).arithmetic(&BinaryOperator::Subtract, &Value::Int(1)).is_err());
+        assert!(Value::Int(1).truth().is_err());
+
+        let mut values = vec![Value::Null, Value::Float(1.5), Value::String("b".to_string()), Value::Int(1), Value::Bool(false)];
+        values.sort_by(Value::order);
+        assert_eq!(values, [Value::String("b".to_string()), Value::Bool(false), Value::Int(1), Value::Float(1.5), Value::Null]);
+        assert_eq!(Value::Int(2).order(&Value::Float(2.0)), Ordering::Equal);
+    }
+}
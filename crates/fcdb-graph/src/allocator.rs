@@ -0,0 +1,229 @@
+//! Durable, monotonic RID allocation.
+//!
+//! Merkle DAG: enishi_graph -> allocator -> {next_rid, rid_blocks, external_keys}
+//!
+//! RIDs are handed out from a high-water mark that only ever grows: every
+//! allocation is covered by a WAL record (the `CreateNode` it belongs to, or a
+//! `ReserveRids` for bulk blocks) and the mark is checkpointed with the graph
+//! root, so ids of deleted nodes or of blocks reserved before a crash are never
+//! reissued. Reserved blocks are persisted too, since only their RIDs may be
+//! named by callers. Callers may also attach an external key to a node and
+//! resolve it back to its RID later.
+
+use crate::persist::{GraphOp, Whole};
+use crate::{GraphDB, Rid, Timestamp};
+use fcdb_core::Cid;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use tracing::info;
+
+/// Contiguous range of RIDs reserved for a bulk load
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RidBlock {
+    /// First RID in the block
+    pub start: Rid,
+    /// One past the last RID in the block
+    pub end: Rid,
+}
+
+impl RidBlock {
+    pub fn len(&self) -> u64 {
+        self.end.0 - self.start.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, rid: Rid) -> bool {
+        self.start <= rid && rid < self.end
+    }
+
+    /// RIDs in the block, in order
+    pub fn rids(&self) -> impl Iterator<Item = Rid> {
+        (self.start.0..self.end.0).map(Rid)
+    }
+}
+
+/// Allocator state persisted with the graph root
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct RidAllocator {
+    /// Smallest RID never handed out
+    next: u64,
+    /// Blocks from `reserve_rids`, by start RID to one past their end
+    #[serde(default)]
+    blocks: BTreeMap<u64, u64>,
+    external_keys: BTreeMap<String, Rid>,
+    keys_by_rid: BTreeMap<Rid, String>,
+}
+
+impl Default for RidAllocator {
+    fn default() -> Self {
+        Self { next: 1, blocks: BTreeMap::new(), external_keys: BTreeMap::new(), keys_by_rid: BTreeMap::new() }
+    }
+}
+
+impl RidAllocator {
+    /// Allocator for roots written before it was persisted: resume above every
+    /// RID that has a timeline
+    pub(crate) fn resume(temporal: &HashMap<Rid, BTreeMap<Timestamp, Cid>>) -> Self {
+        let next = temporal.keys().map(|rid| rid.0 + 1).max().unwrap_or(1);
+        Self { next, ..Self::default() }
+    }
+
+    pub(crate) fn next(&self) -> Rid {
+        Rid(self.next)
+    }
+
+    /// Raise the high-water mark so every RID below `end` counts as issued
+    pub(crate) fn advance_to(&mut self, end: u64) {
+        self.next = self.next.max(end);
+    }
+
+    /// Record a reserved block and issue every RID in it
+    pub(crate) fn reserve(&mut self, start: u64, end: u64) {
+        self.blocks.insert(start, end);
+        self.advance_to(end);
+    }
+
+    /// Whether `rid` lies in a block reserved for bulk loads
+    pub(crate) fn is_reserved(&self, rid: Rid) -> bool {
+        self.blocks.range(..=rid.0).next_back().is_some_and(|(_, end)| rid.0 < *end)
+    }
+
+    pub(crate) fn rid_for_key(&self, key: &str) -> Option<Rid> {
+        self.external_keys.get(key).copied()
+    }
+
+    pub(crate) fn key_for_rid(&self, rid: Rid) -> Option<&str> {
+        self.keys_by_rid.get(&rid).map(String::as_str)
+    }
+
+    pub(crate) fn bind_key(&mut self, key: &str, rid: Rid) {
+        self.external_keys.insert(key.to_string(), rid);
+        self.keys_by_rid.insert(rid, key.to_string());
+    }
+
+    /// Drop the key of a deleted node so it can be bound again
+    pub(crate) fn release(&mut self, rid: Rid) {
+        if let Some(key) = self.keys_by_rid.remove(&rid) {
+            self.external_keys.remove(&key);
+        }
+    }
+}
+
+impl GraphDB {
+    /// Durably reserve `count` consecutive RIDs for a bulk load. They are
+    /// never issued to anyone else; nodes are created in them with
+    /// [`GraphDB::create_node_with_rid`].
+    /// Merkle DAG: enishi_graph -> allocator -> rid_blocks
+    pub async fn reserve_rids(&self, count: u64) -> Result<RidBlock, Box<dyn std::error::Error>> {
+        let block = {
+            let _guard = self.write_lock.lock().await;
+            let start = self.allocator.read().await.next();
+            let end = start.0.checked_add(count).ok_or("RID space exhausted")?;
+
+            self.log_and_apply(GraphOp::ReserveRids { start: Some(start.0), end }, None).await?;
+            RidBlock { start, end: Rid(end) }
+        };
+        self.maybe_checkpoint().await?;
+
+        info!("Reserved RIDs {}..{}", block.start, block.end);
+        Ok(block)
+    }
+
+    /// Create a node under a RID previously obtained from
+    /// [`GraphDB::reserve_rids`]; fails if the RID is outside every reserved
+    /// block or is already in use
+    pub async fn create_node_with_rid(&self, rid: Rid, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        {
+            let _guard = self.write_lock.lock().await;
+            if !self.allocator.read().await.is_reserved(rid) {
+                return Err(format!("RID {} has not been reserved", rid).into());
+            }
+            if self.temporal_rid_mappings.read().await.contains_key(&rid) {
+                return Err(format!("RID {} is already in use", rid).into());
+            }
+            self.create_node_locked(rid, None, data).await?;
+        }
+        self.maybe_checkpoint().await
+    }
+
+    /// Create a node bound to a caller-supplied external key; fails if the
+    /// key already names a live node
+    /// Merkle DAG: enishi_graph -> allocator -> external_keys
+    pub async fn create_node_with_key(&self, key: &str, data: &[u8]) -> Result<Rid, Box<dyn std::error::Error>> {
+        let rid = {
+            let _guard = self.write_lock.lock().await;
+            if let Some(existing) = self.allocator.read().await.rid_for_key(key) {
+                return Err(format!("external key {:?} is already bound to node {}", key, existing).into());
+            }
+            let rid = self.allocator.read().await.next();
+            self.create_node_locked(rid, Some(key), data).await?;
+            rid
+        };
+        self.maybe_checkpoint().await?;
+        Ok(rid)
+    }
+
+    /// Issue a RID for a node created inside a transaction. The allocation is
+    /// not logged: the node's `CreateNode` makes it durable on commit, and a
+    /// RID lost to a rollback or crash is simply never used.
+    pub(crate) async fn allocate_rid(&self) -> Rid {
+        let _guard = self.write_lock.lock().await;
+        let mut allocator = self.allocator.write().await;
+        let rid = allocator.next();
+        allocator.advance_to(rid.0 + 1);
+        self.dirty().mark_object(Whole::Allocator);
+        rid
+    }
+
+    /// RID of the live node bound to an external key
+    pub async fn rid_for_key(&self, key: &str) -> Option<Rid> {
+        self.allocator.read().await.rid_for_key(key)
+    }
+
+    /// External key bound to a live node, if any
+    pub async fn key_for_rid(&self, rid: Rid) -> Option<String> {
+        self.allocator.read().await.key_for_rid(rid).map(str::to_string)
+    }
+
+    /// Next RID the allocator will issue
+    pub async fn next_rid(&self) -> Rid {
+        self.allocator.read().await.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allocator_state() {
+        let mut temporal = HashMap::new();
+        temporal.insert(Rid(7), BTreeMap::new());
+        let mut allocator = RidAllocator::resume(&temporal);
+        assert_eq!(allocator.next(), Rid(8));
+
+        allocator.advance_to(5);
+        assert_eq!(allocator.next(), Rid(8));
+        allocator.advance_to(20);
+        assert_eq!(allocator.next(), Rid(20));
+
+        allocator.bind_key("user:1", Rid(3));
+        assert_eq!(allocator.rid_for_key("user:1"), Some(Rid(3)));
+        assert_eq!(allocator.key_for_rid(Rid(3)), Some("user:1"));
+        allocator.release(Rid(3));
+        assert_eq!(allocator.rid_for_key("user:1"), None);
+
+        allocator.reserve(30, 33);
+        assert!(allocator.is_reserved(Rid(30)) && allocator.is_reserved(Rid(32)));
+        assert!(!allocator.is_reserved(Rid(29)) && !allocator.is_reserved(Rid(33)) && !allocator.is_reserved(Rid(25)));
+        assert_eq!(allocator.next(), Rid(33));
+
+        let block = RidBlock { start: Rid(10), end: Rid(13) };
+        assert_eq!(block.len(), 3);
+        assert!(block.contains(Rid(12)) && !block.contains(Rid(13)));
+        assert_eq!(block.rids().collect::<Vec<_>>(), vec![Rid(10), Rid(11), Rid(12)]);
+    }
+}
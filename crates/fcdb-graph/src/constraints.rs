@@ -0,0 +1,388 @@
+//! Declarative schema constraints checked on every write.
+//!
+//! Merkle DAG: enishi_graph -> constraints -> {unique, exists, property_type, node_key}
+//!
+//! Constraints apply to the nodes (or edges) carrying a label. Uniqueness is
+//! answered by a hash index the constraint creates for itself, so checks cost
+//! one index lookup rather than a scan. Violations are reported as
+//! [`ConstraintViolation`] inside the usual boxed error; callers that want to
+//! treat them specially can `downcast_ref` it.
+
+use crate::index::{IndexDefinition, IndexKind};
+use crate::persist::GraphOp;
+use crate::{GraphDB, PropertyRecord, PropertyValue, Rid};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use tracing::info;
+
+/// Type a property must have
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PropertyType {
+    String,
+    Int,
+    Float,
+    Bool,
+    List,
+    Map,
+    DateTime,
+    Bytes,
+}
+
+impl PropertyType {
+    pub fn of(value: &PropertyValue) -> Self {
+        match value {
+            PropertyValue::String(_) => PropertyType::String,
+            PropertyValue::Int(_) => PropertyType::Int,
+            PropertyValue::Float(_) => PropertyType::Float,
+            PropertyValue::Bool(_) => PropertyType::Bool,
+            PropertyValue::List(_) => PropertyType::List,
+            PropertyValue::Map(_) => PropertyType::Map,
+            PropertyValue::DateTime(_) => PropertyType::DateTime,
+            PropertyValue::Bytes(_) => PropertyType::Bytes,
+        }
+    }
+}
+
+/// What a constraint requires of its properties
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConstraintKind {
+    /// No two nodes share the combination of values (nodes lacking any of
+    /// the properties are exempt)
+    Unique,
+    /// Every property is present
+    Exists,
+    /// Present properties have this type
+    Type(PropertyType),
+    /// Every property is present and the combination is unique
+    NodeKey,
+}
+
+/// Whether a constraint applies to nodes or edges
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConstraintTarget {
+    #[default]
+    Node,
+    Edge,
+}
+
+/// Declared constraint
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConstraintDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub target: ConstraintTarget,
+    /// Node label or edge label name the constraint applies to
+    pub label: String,
+    pub properties: Vec<String>,
+    pub kind: ConstraintKind,
+}
+
+impl ConstraintDefinition {
+    /// Constraint on nodes with `label`
+    pub fn node<S: Into<String>>(name: &str, label: &str, properties: impl IntoIterator<Item = S>, kind: ConstraintKind) -> Self {
+        Self {
+            name: name.to_string(),
+            target: ConstraintTarget::Node,
+            label: label.to_string(),
+            properties: properties.into_iter().map(Into::into).collect(),
+            kind,
+        }
+    }
+
+    /// Constraint on edges with `label`; only `Exists` and `Type` apply
+    pub fn edge<S: Into<String>>(name: &str, label: &str, properties: impl IntoIterator<Item = S>, kind: ConstraintKind) -> Self {
+        Self { target: ConstraintTarget::Edge, ..Self::node(name, label, properties, kind) }
+    }
+
+    fn is_unique(&self) -> bool {
+        matches!(self.kind, ConstraintKind::Unique | ConstraintKind::NodeKey)
+    }
+
+    fn requires_presence(&self) -> bool {
+        matches!(self.kind, ConstraintKind::Exists | ConstraintKind::NodeKey)
+    }
+
+    /// Name of the index backing a uniqueness constraint
+    fn index_name(&self) -> String {
+        format!("constraint:{}", self.name)
+    }
+
+    fn backing_index(&self) -> IndexDefinition {
+        IndexDefinition::new(&self.index_name(), Some(&self.label), self.properties.iter().cloned(), IndexKind::Hash)
+    }
+}
+
+/// A write rejected by a constraint
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum ConstraintViolation {
+    #[error("constraint {constraint:?} violated: node {existing} already has :{label} {properties:?} = {values:?}")]
+    Duplicate {
+        constraint: String,
+        label: String,
+        properties: Vec<String>,
+        values: Vec<PropertyValue>,
+        existing: Rid,
+    },
+    #[error("constraint {constraint:?} violated: :{label} requires property {property:?}")]
+    Missing {
+        constraint: String,
+        label: String,
+        property: String,
+    },
+    #[error("constraint {constraint:?} violated: :{label} property {property:?} must be {expected:?}, got {actual:?}")]
+    WrongType {
+        constraint: String,
+        label: String,
+        property: String,
+        expected: PropertyType,
+        actual: PropertyType,
+    },
+}
+
+impl ConstraintViolation {
+    /// Name of the violated constraint
+    pub fn constraint(&self) -> &str {
+        match self {
+            ConstraintViolation::Duplicate { constraint, .. }
+            | ConstraintViolation::Missing { constraint, .. }
+            | ConstraintViolation::WrongType { constraint, .. } => constraint,
+        }
+    }
+}
+
+/// Presence and type requirements of one constraint against a record
+fn check_shape(constraint: &ConstraintDefinition, record: &PropertyRecord) -> Result<(), ConstraintViolation> {
+    for property in &constraint.properties {
+        match (record.properties.get(property), constraint.kind) {
+            (None, _) if constraint.requires_presence() => {
+                return Err(ConstraintViolation::Missing {
+                    constraint: constraint.name.clone(),
+                    label: constraint.label.clone(),
+                    property: property.clone(),
+                });
+            }
+            (Some(value), ConstraintKind::Type(expected)) if PropertyType::of(value) != expected => {
+                return Err(ConstraintViolation::WrongType {
+                    constraint: constraint.name.clone(),
+                    label: constraint.label.clone(),
+                    property: property.clone(),
+                    expected,
+                    actual: PropertyType::of(value),
+                });
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Values of a uniqueness constraint's properties, if all are present
+fn unique_values(constraint: &ConstraintDefinition, record: &PropertyRecord) -> Option<Vec<PropertyValue>> {
+    constraint.properties.iter()
+        .map(|property| record.properties.get(property).cloned())
+        .collect()
+}
+
+impl GraphDB {
+    /// Declare a constraint after checking that existing data satisfies it
+    /// Merkle DAG: enishi_graph -> constraints -> create_constraint
+    pub async fn create_constraint(&self, definition: ConstraintDefinition) -> Result<(), Box<dyn std::error::Error>> {
+        if definition.properties.is_empty() {
+            return Err("a constraint needs at least one property".into());
+        }
+        if definition.target == ConstraintTarget::Edge && definition.is_unique() {
+            return Err("edge constraints support only Exists and Type".into());
+        }
+
+        {
+            let _guard = self.write_lock.lock().await;
+            if self.constraints.read().await.contains_key(&definition.name) {
+                return Err(format!("constraint {:?} already exists", definition.name).into());
+            }
+            self.check_existing(&definition).await?;
+
+            info!("Creating constraint {:?} on :{}", definition.name, definition.label);
+            if definition.is_unique() && !self.indexes.read().await.contains(&definition.index_name()) {
+                self.create_index_locked(definition.backing_index()).await?;
+            }
+            self.log_and_apply(GraphOp::CreateConstraint { definition }, None).await?;
+        }
+        self.maybe_checkpoint().await
+    }
+
+    /// Drop a constraint (and its backing index); false if it did not exist
+    pub async fn drop_constraint(&self, name: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        {
+            let _guard = self.write_lock.lock().await;
+            let Some(definition) = self.constraints.read().await.get(name).cloned() else {
+                return Ok(false);
+            };
+            self.log_and_apply(GraphOp::DropConstraint { name: name.to_string() }, None).await?;
+            if self.indexes.read().await.contains(&definition.index_name()) {
+                self.log_and_apply(GraphOp::DropIndex { name: definition.index_name() }, None).await?;
+            }
+        }
+        self.maybe_checkpoint().await?;
+        Ok(true)
+    }
+
+    /// Every declared constraint
+    pub async fn list_constraints(&self) -> Vec<ConstraintDefinition> {
+        self.constraints.read().await.values().cloned().collect()
+    }
+
+    /// Check node data about to be stored under `rid`; callers hold `write_lock`
+    pub(crate) async fn check_node_constraints(&self, rid: Rid, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        self.check_node_writes(&[(rid, Some(data))]).await
+    }
+
+    /// Check the final state of every node written together (`None` for a
+    /// deletion): the data must decode, and written nodes are judged by their
+    /// new values, both against the rest of the graph and against each
+    /// other; callers hold `write_lock`.
+    pub(crate) async fn check_node_writes(&self, writes: &[(Rid, Option<&[u8]>)]) -> Result<(), Box<dyn std::error::Error>> {
+        // Node data must decode before it is logged: replay indexes it again
+        let mut records = Vec::new();
+        for &(rid, data) in writes {
+            if let Some(data) = data {
+                records.push((rid, PropertyRecord::decode(data)?));
+            }
+        }
+
+        let constraints = self.constraints.read().await;
+        if constraints.is_empty() {
+            return Ok(());
+        }
+
+        let written: HashSet<Rid> = writes.iter().map(|(rid, _)| *rid).collect();
+        let mut claimed: HashMap<(&str, Vec<crate::IndexValue>), Rid> = HashMap::new();
+        for &(rid, ref record) in &records {
+            for constraint in constraints.values()
+                .filter(|c| c.target == ConstraintTarget::Node && record.labels.contains(&c.label))
+            {
+                check_shape(constraint, record)?;
+                if !constraint.is_unique() {
+                    continue;
+                }
+                let Some(values) = unique_values(constraint, record) else {
+                    continue;
+                };
+                let duplicate = |existing| ConstraintViolation::Duplicate {
+                    constraint: constraint.name.clone(),
+                    label: constraint.label.clone(),
+                    properties: constraint.properties.clone(),
+                    values: values.clone(),
+                    existing,
+                };
+
+                // Stored holders that keep their value after this write
+                let existing = self.indexes.read().await
+                    .lookup_named(&constraint.index_name(), &values)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .find(|other| !written.contains(other));
+                if let Some(existing) = existing {
+                    return Err(Box::new(duplicate(existing)));
+                }
+
+                // Other nodes in the same write
+                let Some(key) = values.iter().map(crate::IndexValue::from_property).collect::<Option<Vec<_>>>() else {
+                    continue;
+                };
+                match claimed.insert((constraint.name.as_str(), key), rid) {
+                    Some(other) if other != rid => return Err(Box::new(duplicate(other))),
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check edge properties about to be stored; callers hold `write_lock`
+    pub(crate) async fn check_edge_constraints(&self, label: &str, properties: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        let constraints = self.constraints.read().await;
+        if !constraints.values().any(|c| c.target == ConstraintTarget::Edge) {
+            return Ok(());
+        }
+
+        let record = PropertyRecord::decode(properties)?;
+        for constraint in constraints.values()
+            .filter(|c| c.target == ConstraintTarget::Edge && c.label == label)
+        {
+            check_shape(constraint, &record)?;
+        }
+        Ok(())
+    }
+
+    /// Reject a new constraint that current data already violates
+    async fn check_existing(&self, definition: &ConstraintDefinition) -> Result<(), Box<dyn std::error::Error>> {
+        let cas = self.cas.read().await;
+        match definition.target {
+            ConstraintTarget::Node => {
+                let nodes: Vec<_> = self.rid_to_cid.read().await.iter().map(|(rid, cid)| (*rid, *cid)).collect();
+                let mut seen: HashMap<Vec<crate::IndexValue>, Rid> = HashMap::new();
+                for (rid, cid) in nodes {
+                    let record = PropertyRecord::decode(&cas.get(&cid).await?)?;
+                    if !record.labels.contains(&definition.label) {
+                        continue;
+                    }
+                    check_shape(definition, &record)?;
+
+                    let Some(values) = unique_values(definition, &record).filter(|_| definition.is_unique()) else {
+                        continue;
+                    };
+                    let Some(key) = values.iter().map(crate::IndexValue::from_property).collect::<Option<Vec<_>>>() else {
+                        continue;
+                    };
+                    if let Some(&existing) = seen.get(&key) {
+                        return Err(Box::new(ConstraintViolation::Duplicate {
+                            constraint: definition.name.clone(),
+                            label: definition.label.clone(),
+                            properties: definition.properties.clone(),
+                            values,
+                            existing,
+                        }));
+                    }
+                    seen.insert(key, rid);
+                }
+            }
+            ConstraintTarget::Edge => {
+                let Some(label) = self.lookup_label(&definition.label).await else {
+                    return Ok(());
+                };
+                let properties: Vec<_> = self.adjacency.read().await.values()
+                    .flatten()
+                    .filter(|edge| edge.label == label && edge.deleted_at.is_none())
+                    .map(|edge| edge.properties)
+                    .collect();
+                for cid in properties {
+                    check_shape(definition, &PropertyRecord::decode(&cas.get(&cid).await?)?)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_shape() {
+        let mut record = PropertyRecord::new(["User"], Default::default());
+        record.properties.insert("email".to_string(), PropertyValue::Int(1));
+
+        let exists = ConstraintDefinition::node("user_name", "User", ["name"], ConstraintKind::Exists);
+        assert!(matches!(check_shape(&exists, &record), Err(ConstraintViolation::Missing { .. })));
+
+        let typed = ConstraintDefinition::node("user_email", "User", ["email", "name"], ConstraintKind::Type(PropertyType::String));
+        let err = check_shape(&typed, &record).unwrap_err();
+        assert_eq!(err.constraint(), "user_email");
+        assert!(err.to_string().contains("must be String"));
+
+        let unique = ConstraintDefinition::node("user_key", "User", ["email", "name"], ConstraintKind::Unique);
+        assert!(check_shape(&unique, &record).is_ok());
+        assert_eq!(unique_values(&unique, &record), None);
+    }
+}
@@ -0,0 +1,189 @@
+//! Dictionary interning label and property-key names to compact ids.
+//!
+//! Merkle DAG: enishi_graph -> dictionary -> {labels, property_keys}
+//!
+//! Names are interned once (logged to the WAL like any other mutation) and
+//! the dictionary is checkpointed with the graph root. Its `version` grows
+//! with every new name, so callers caching resolved ids can detect changes.
+//! All-digit names are reserved for raw numeric ids, which predate the
+//! dictionary and are still accepted everywhere a name is.
+
+use crate::{GraphDB, LabelId};
+use crate::persist::GraphOp;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::Ordering;
+
+/// Property key id
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PropertyKeyId(pub u32);
+
+/// Namespace of an interned name
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NameKind {
+    Label,
+    PropertyKey,
+}
+
+/// Bidirectional name <-> id tables
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Dictionary {
+    /// Incremented on every interned name
+    pub version: u64,
+    labels: BTreeMap<String, u32>,
+    label_names: BTreeMap<u32, String>,
+    property_keys: BTreeMap<String, u32>,
+    property_key_names: BTreeMap<u32, String>,
+}
+
+impl Dictionary {
+    fn tables(&self, kind: NameKind) -> (&BTreeMap<String, u32>, &BTreeMap<u32, String>) {
+        match kind {
+            NameKind::Label => (&self.labels, &self.label_names),
+            NameKind::PropertyKey => (&self.property_keys, &self.property_key_names),
+        }
+    }
+
+    pub fn id(&self, kind: NameKind, name: &str) -> Option<u32> {
+        self.tables(kind).0.get(name).copied()
+    }
+
+    pub fn name(&self, kind: NameKind, id: u32) -> Option<&str> {
+        self.tables(kind).1.get(&id).map(String::as_str)
+    }
+
+    /// Smallest id above every id assigned so far in `kind`
+    fn next_id(&self, kind: NameKind) -> u32 {
+        self.tables(kind).1.keys().next_back().map_or(1, |id| id + 1)
+    }
+
+    pub(crate) fn insert(&mut self, kind: NameKind, name: &str, id: u32) {
+        let (names, ids) = match kind {
+            NameKind::Label => (&mut self.labels, &mut self.label_names),
+            NameKind::PropertyKey => (&mut self.property_keys, &mut self.property_key_names),
+        };
+        if names.insert(name.to_string(), id).is_none() {
+            ids.insert(id, name.to_string());
+            self.version += 1;
+        }
+    }
+}
+
+fn is_numeric(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|b| b.is_ascii_digit())
+}
+
+impl GraphDB {
+    /// Id for a label name, interning it if new. Numeric names are taken as
+    /// raw ids.
+    /// Merkle DAG: enishi_graph -> dictionary -> labels
+    pub async fn label_id(&self, name: &str) -> Result<LabelId, Box<dyn std::error::Error>> {
+        Ok(LabelId(self.intern(NameKind::Label, name).await?))
+    }
+
+    /// Id of an already-known label name, without interning
+    pub async fn lookup_label(&self, name: &str) -> Option<LabelId> {
+        self.lookup(NameKind::Label, name).await.map(LabelId)
+    }
+
+    /// Name of a label id, if it was interned
+    pub async fn label_name(&self, id: LabelId) -> Option<String> {
+        self.dictionary.read().await.name(NameKind::Label, id.0).map(str::to_string)
+    }
+
+    /// Display form of a label: its name, or the raw id for unnamed labels
+    pub async fn label_display(&self, id: LabelId) -> String {
+        self.label_name(id).await.unwrap_or_else(|| id.0.to_string())
+    }
+
+    /// Id for a property key, interning it if new
+    /// Merkle DAG: enishi_graph -> dictionary -> property_keys
+    pub async fn property_key_id(&self, name: &str) -> Result<PropertyKeyId, Box<dyn std::error::Error>> {
+        Ok(PropertyKeyId(self.intern(NameKind::PropertyKey, name).await?))
+    }
+
+    /// Name of a property key id, if it was interned
+    pub async fn property_key_name(&self, id: PropertyKeyId) -> Option<String> {
+        self.dictionary.read().await.name(NameKind::PropertyKey, id.0).map(str::to_string)
+    }
+
+    /// Current dictionary version
+    pub async fn dictionary_version(&self) -> u64 {
+        self.dictionary.read().await.version
+    }
+
+    async fn lookup(&self, kind: NameKind, name: &str) -> Option<u32> {
+        if is_numeric(name) {
+            return name.parse().ok();
+        }
+        self.dictionary.read().await.id(kind, name)
+    }
+
+    async fn intern(&self, kind: NameKind, name: &str) -> Result<u32, Box<dyn std::error::Error>> {
+        if let Some(id) = self.lookup(kind, name).await {
+            return Ok(id);
+        }
+        if name.is_empty() {
+            return Err("empty names cannot be interned".into());
+        }
+
+        let id = {
+            let _guard = self.write_lock.lock().await;
+            // Another writer may have interned it while we waited
+            if let Some(id) = self.dictionary.read().await.id(kind, name) {
+                return Ok(id);
+            }
+
+            let id = self.next_name_id(kind).await;
+            self.log_and_apply(GraphOp::InternName { kind, name: name.to_string(), id }, None).await?;
+            id
+        };
+        self.maybe_checkpoint().await?;
+        Ok(id)
+    }
+
+    /// Id the next new name of `kind` gets; callers hold `write_lock`
+    pub(crate) async fn next_name_id(&self, kind: NameKind) -> u32 {
+        let id = self.dictionary.read().await.next_id(kind);
+        if kind != NameKind::Label {
+            return id;
+        }
+        // Skip raw ids already used by edges created without a name
+        let max_raw = self.adjacency.read().await.values()
+            .flatten()
+            .map(|edge| edge.label.0)
+            .max()
+            .unwrap_or(0);
+        id.max(max_raw + 1).max(self.reserved_label_ids.load(Ordering::SeqCst))
+    }
+
+    /// Set aside a label id for a name a transaction interns on commit.
+    /// Names interned meanwhile get other ids; an id whose transaction
+    /// rolls back is never used.
+    pub(crate) async fn reserve_label_id(&self) -> LabelId {
+        let _guard = self.write_lock.lock().await;
+        let id = self.next_name_id(NameKind::Label).await;
+        self.reserved_label_ids.store(id + 1, Ordering::SeqCst);
+        LabelId(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dictionary_tables() {
+        let mut dictionary = Dictionary::default();
+        dictionary.insert(NameKind::Label, "KNOWS", 1);
+        dictionary.insert(NameKind::PropertyKey, "name", 1);
+        dictionary.insert(NameKind::Label, "KNOWS", 1);
+
+        assert_eq!(dictionary.version, 2);
+        assert_eq!(dictionary.id(NameKind::Label, "KNOWS"), Some(1));
+        assert_eq!(dictionary.name(NameKind::PropertyKey, 1), Some("name"));
+        assert_eq!(dictionary.id(NameKind::PropertyKey, "KNOWS"), None);
+        assert_eq!(dictionary.next_id(NameKind::Label), 2);
+        assert!(is_numeric("42") && !is_numeric("4a") && !is_numeric(""));
+    }
+}
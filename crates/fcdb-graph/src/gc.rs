@@ -0,0 +1,175 @@
+//! Version retention and garbage collection for GraphDB.
+//!
+//! Merkle DAG: enishi_graph -> gc -> {retention, mark, enishi_cas::gc}
+
+use crate::{persist, GraphDB, Timestamp};
+use fcdb_cas::{GcReport, PackCAS, DEFAULT_LIVE_RATIO_THRESHOLD};
+use fcdb_core::Cid;
+use std::collections::{BTreeMap, HashSet};
+use tokio::sync::RwLock;
+use tracing::info;
+
+/// Which historical node versions survive a GC run.
+/// The current version of every node is always kept (it is marked via rid_to_cid).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RetentionPolicy {
+    /// Keep the full history
+    All,
+    /// Keep the newest `n` versions of each node
+    LatestVersions(usize),
+    /// Keep every version visible at or after the timestamp
+    Since(Timestamp),
+}
+
+impl RetentionPolicy {
+    /// Drop versions outside the policy, except the one visible at `pinned`
+    fn prune(&self, timeline: &mut BTreeMap<Timestamp, Cid>, pinned: Option<Timestamp>) {
+        let keep_from = match *self {
+            RetentionPolicy::All => return,
+            RetentionPolicy::LatestVersions(n) => {
+                timeline.keys().rev().nth(n.max(1) - 1).copied()
+            }
+            // The version valid at `ts` may have been written before it
+            RetentionPolicy::Since(ts) => {
+                timeline.range(..=ts).next_back().map(|(valid_from, _)| *valid_from)
+            }
+        };
+
+        let visible_at_pin = pinned.and_then(|ts| timeline.range(..=ts).next_back().map(|(valid_from, _)| *valid_from));
+        let keep_from = match (keep_from, visible_at_pin) {
+            (Some(keep_from), Some(pinned)) => Some(keep_from.min(pinned)),
+            (keep_from, _) => keep_from,
+        };
+        if let Some(keep_from) = keep_from {
+            *timeline = timeline.split_off(&keep_from);
+        }
+    }
+}
+
+/// Options for [`GraphDB::gc`]
+#[derive(Clone, Copy, Debug)]
+pub struct GcOptions {
+    pub retention: RetentionPolicy,
+    /// Packs whose live bytes fall below this fraction of their size are rewritten
+    pub live_ratio_threshold: f64,
+}
+
+impl Default for GcOptions {
+    fn default() -> Self {
+        Self {
+            retention: RetentionPolicy::All,
+            live_ratio_threshold: DEFAULT_LIVE_RATIO_THRESHOLD,
+        }
+    }
+}
+
+impl GraphDB {
+    /// Drop node versions outside the retention policy, then sweep every CAS
+    /// object no longer reachable from the graph root and compact sparse packs.
+    ///
+    /// Mutations are blocked for the duration; reads only wait for the final
+    /// pack swap. Versions visible to live snapshots are kept regardless of
+    /// the policy.
+    pub async fn gc(&self, options: GcOptions) -> Result<GcReport, Box<dyn std::error::Error>> {
+        let _guard = self.write_lock.lock().await;
+        if self.shared_wal.is_some() {
+            return Err("graphs sharing a PackCAS are collected with Catalog::gc".into());
+        }
+
+        let live = self.prune_and_mark(&options).await?;
+        self.recount_usage(&live).await?;
+        let report = sweep(&self.cas, &live, &options).await?;
+        info!("Graph GC kept {} live objects: {:?}", live.len(), report);
+        Ok(report)
+    }
+
+    /// Apply the retention policy, checkpoint, and return every object the
+    /// graph still needs; callers hold `write_lock`
+    pub(crate) async fn prune_and_mark(&self, options: &GcOptions) -> Result<HashSet<Cid>, Box<dyn std::error::Error>> {
+        let pinned = self.oldest_snapshot();
+        for (rid, timeline) in self.temporal_rid_mappings.write().await.iter_mut() {
+            let versions = timeline.len();
+            options.retention.prune(timeline, pinned);
+            if timeline.len() != versions {
+                self.dirty().mark_rid(persist::Sharded::Temporal, *rid);
+            }
+        }
+
+        // Persist the pruned timelines so the new root is the only one needed
+        let root_cid = self.checkpoint_locked().await?;
+        self.mark_live(&root_cid).await
+    }
+
+    /// Reset the graph's quota usage to the data objects among `live`, at
+    /// the size metered writes charge for them
+    pub(crate) async fn recount_usage(&self, live: &HashSet<Cid>) -> std::io::Result<()> {
+        let mut cas = self.cas.write().await;
+        let bytes = live.iter()
+            .filter_map(|cid| cas.record(cid))
+            .filter(|record| record.kind != persist::KIND_INDEX)
+            .map(|record| record.raw_len() as u64)
+            .sum();
+        cas.set_namespace_usage(self.namespace, Some(bytes));
+        cas.save_usage()
+    }
+
+    /// Every CAS object reachable from the graph root
+    async fn mark_live(&self, root_cid: &Cid) -> Result<HashSet<Cid>, Box<dyn std::error::Error>> {
+        let mut live: HashSet<Cid> = persist::root_objects(&*self.cas.read().await, root_cid).await?
+            .into_iter()
+            .collect();
+
+        live.extend(self.rid_to_cid.read().await.values().copied());
+        for timeline in self.temporal_rid_mappings.read().await.values() {
+            live.extend(timeline.values().copied());
+        }
+        for entries in self.adjacency.read().await.values() {
+            live.extend(entries.iter().map(|entry| entry.properties));
+        }
+        for entries in self.reverse_adjacency.read().await.values() {
+            live.extend(entries.iter().map(|entry| entry.properties));
+        }
+        Ok(live)
+    }
+}
+
+/// Compact `cas` down to the `live` objects
+pub(crate) async fn sweep(
+    cas: &RwLock<PackCAS>,
+    live: &HashSet<Cid>,
+    options: &GcOptions,
+) -> Result<GcReport, Box<dyn std::error::Error>> {
+    cas.write().await.seal_current_pack().await?;
+    let compaction = cas.read().await
+        .prepare_compaction(live, options.live_ratio_threshold).await?;
+    Ok(cas.write().await.apply_compaction(compaction)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retention_policy_prune() {
+        let timeline: BTreeMap<Timestamp, Cid> = (1..=4u64)
+            .map(|i| (Timestamp(i * 10), Cid::hash(&i.to_le_bytes())))
+            .collect();
+
+        let mut latest = timeline.clone();
+        RetentionPolicy::LatestVersions(2).prune(&mut latest, None);
+        assert_eq!(latest.keys().copied().collect::<Vec<_>>(), vec![Timestamp(30), Timestamp(40)]);
+
+        let mut since = timeline.clone();
+        RetentionPolicy::Since(Timestamp(25)).prune(&mut since, None);
+        assert_eq!(since.keys().copied().collect::<Vec<_>>(), vec![Timestamp(20), Timestamp(30), Timestamp(40)]);
+
+        let mut all = timeline.clone();
+        RetentionPolicy::All.prune(&mut all, None);
+        assert_eq!(all, timeline);
+
+        // A snapshot pinned at 25 still needs the version written at 20
+        let mut pinned = timeline.clone();
+        RetentionPolicy::LatestVersions(1).prune(&mut pinned, Some(Timestamp(25)));
+        assert_eq!(pinned.keys().copied().collect::<Vec<_>>(), vec![Timestamp(20), Timestamp(30), Timestamp(40)]);
+    }
+}
@@ -0,0 +1,558 @@
+//! Secondary property indexes.
+//!
+//! Merkle DAG: enishi_graph -> index -> {hash_index, btree_index, index_entries}
+//!
+//! An index covers the nodes carrying a label (or every node) and maps the
+//! values of one or more properties to RIDs. Hash indexes answer equality
+//! lookups; B-tree indexes also answer range scans on their leading property.
+//! Each entry records the interval during which a node version held the key,
+//! so lookups can be answered as of any timestamp. Indexes are maintained as
+//! mutations are applied and checkpointed with the graph root.
+
+use crate::persist::{GraphOp, Whole};
+use crate::{GraphDB, PropertyRecord, PropertyValue, Rid, Timestamp, TOMBSTONE};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::ops::Bound;
+use tracing::info;
+
+/// Index structure
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IndexKind {
+    /// Equality lookups only
+    Hash,
+    /// Equality lookups and range scans
+    BTree,
+}
+
+/// User-declared secondary index
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexDefinition {
+    pub name: String,
+    /// Only nodes with this label are indexed; `None` indexes every node
+    pub label: Option<String>,
+    /// Indexed properties; composite when more than one
+    pub properties: Vec<String>,
+    pub kind: IndexKind,
+}
+
+impl IndexDefinition {
+    pub fn new<S: Into<String>>(name: &str, label: Option<&str>, properties: impl IntoIterator<Item = S>, kind: IndexKind) -> Self {
+        Self {
+            name: name.to_string(),
+            label: label.map(str::to_string),
+            properties: properties.into_iter().map(Into::into).collect(),
+            kind,
+        }
+    }
+}
+
+/// Orderable form of an indexable property value. Integers and floats
+/// compare numerically with each other.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum IndexValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    DateTime(i64),
+}
+
+impl IndexValue {
+    /// Lists, maps, bytes and NaN are not indexable
+    pub fn from_property(value: &PropertyValue) -> Option<Self> {
+        Some(match value {
+            PropertyValue::Bool(b) => IndexValue::Bool(*b),
+            PropertyValue::Int(i) => IndexValue::Int(*i),
+            PropertyValue::Float(f) if !f.is_nan() => IndexValue::Float(*f),
+            PropertyValue::String(s) => IndexValue::String(s.clone()),
+            PropertyValue::DateTime(micros) => IndexValue::DateTime(*micros),
+            _ => return None,
+        })
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            IndexValue::Bool(_) => 0,
+            IndexValue::Int(_) | IndexValue::Float(_) => 1,
+            IndexValue::String(_) => 2,
+            IndexValue::DateTime(_) => 3,
+        }
+    }
+}
+
+/// 2^63, the first float above every i64
+const I64_LIMIT: f64 = 9_223_372_036_854_775_808.0;
+
+/// Exact comparison of an integer with a (non-NaN) float
+fn cmp_int_float(i: i64, f: f64) -> Ordering {
+    if f.is_infinite() || f.abs() >= 1e38 {
+        return if f > 0.0 { Ordering::Less } else { Ordering::Greater };
+    }
+    let floor = f.floor();
+    match (i as i128).cmp(&(floor as i128)) {
+        Ordering::Equal if f != floor => Ordering::Less,
+        ordering => ordering,
+    }
+}
+
+impl Ord for IndexValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        use IndexValue::*;
+        match (self, other) {
+            (Bool(a), Bool(b)) => a.cmp(b),
+            (Int(a), Int(b)) => a.cmp(b),
+            (Float(a), Float(b)) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+            (Int(a), Float(b)) => cmp_int_float(*a, *b),
+            (Float(a), Int(b)) => cmp_int_float(*b, *a).reverse(),
+            (String(a), String(b)) => a.cmp(b),
+            (DateTime(a), DateTime(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl PartialOrd for IndexValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for IndexValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for IndexValue {}
+
+impl Hash for IndexValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.rank().hash(state);
+        match self {
+            IndexValue::Bool(b) => b.hash(state),
+            IndexValue::Int(i) => i.hash(state),
+            // Integral floats hash like the integer they equal
+            IndexValue::Float(f) if f.fract() == 0.0 && (-I64_LIMIT..I64_LIMIT).contains(f) => (*f as i64).hash(state),
+            IndexValue::Float(f) => f.to_bits().hash(state),
+            IndexValue::String(s) => s.hash(state),
+            IndexValue::DateTime(micros) => micros.hash(state),
+        }
+    }
+}
+
+/// Composite index key, one component per indexed property
+pub type IndexKey = Vec<IndexValue>;
+
+/// A node version holding a key during `[from, to)`
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+struct IndexEntry {
+    rid: Rid,
+    from: Timestamp,
+    to: Option<Timestamp>,
+}
+
+impl IndexEntry {
+    fn is_visible_at(&self, as_of: Option<Timestamp>) -> bool {
+        match as_of {
+            None => self.to.is_none(),
+            Some(ts) => self.from <= ts && self.to.is_none_or(|to| ts < to),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Entries {
+    Hash(HashMap<IndexKey, Vec<IndexEntry>>),
+    BTree(BTreeMap<IndexKey, Vec<IndexEntry>>),
+}
+
+impl Entries {
+    fn get_mut(&mut self, key: &IndexKey) -> Option<&mut Vec<IndexEntry>> {
+        match self {
+            Entries::Hash(map) => map.get_mut(key),
+            Entries::BTree(map) => map.get_mut(key),
+        }
+    }
+
+    fn get(&self, key: &IndexKey) -> Option<&Vec<IndexEntry>> {
+        match self {
+            Entries::Hash(map) => map.get(key),
+            Entries::BTree(map) => map.get(key),
+        }
+    }
+
+    fn push(&mut self, key: IndexKey, entry: IndexEntry) {
+        match self {
+            Entries::Hash(map) => map.entry(key).or_default().push(entry),
+            Entries::BTree(map) => map.entry(key).or_default().push(entry),
+        }
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (&IndexKey, &Vec<IndexEntry>)> + '_> {
+        match self {
+            Entries::Hash(map) => Box::new(map.iter()),
+            Entries::BTree(map) => Box::new(map.iter()),
+        }
+    }
+}
+
+/// One index with its entries and the open key of every indexed node
+#[derive(Clone, Debug)]
+pub(crate) struct PropertyIndex {
+    definition: IndexDefinition,
+    entries: Entries,
+    current: HashMap<Rid, IndexKey>,
+}
+
+impl PropertyIndex {
+    fn new(definition: IndexDefinition) -> Self {
+        let entries = match definition.kind {
+            IndexKind::Hash => Entries::Hash(HashMap::new()),
+            IndexKind::BTree => Entries::BTree(BTreeMap::new()),
+        };
+        Self { definition, entries, current: HashMap::new() }
+    }
+
+    /// Key of a node version, if the index covers it
+    fn key_for(&self, record: &PropertyRecord) -> Option<IndexKey> {
+        if let Some(label) = &self.definition.label {
+            if !record.labels.contains(label) {
+                return None;
+            }
+        }
+        self.definition.properties.iter()
+            .map(|property| record.properties.get(property).and_then(IndexValue::from_property))
+            .collect()
+    }
+
+    /// Record that `rid` holds `record` from `ts` on (`None` once deleted)
+    fn update(&mut self, rid: Rid, record: Option<&PropertyRecord>, ts: Timestamp) {
+        let key = record.and_then(|record| self.key_for(record));
+        if self.current.get(&rid) == key.as_ref() {
+            return;
+        }
+
+        if let Some(old) = self.current.remove(&rid) {
+            if let Some(entry) = self.entries.get_mut(&old)
+                .and_then(|entries| entries.iter_mut().rev().find(|e| e.rid == rid && e.to.is_none()))
+            {
+                entry.to = Some(ts);
+            }
+        }
+        if let Some(key) = key {
+            self.entries.push(key.clone(), IndexEntry { rid, from: ts, to: None });
+            self.current.insert(rid, key);
+        }
+    }
+
+    fn lookup(&self, key: &IndexKey, as_of: Option<Timestamp>) -> Vec<Rid> {
+        self.entries.get(key).into_iter().flatten()
+            .filter(|entry| entry.is_visible_at(as_of))
+            .map(|entry| entry.rid)
+            .collect()
+    }
+
+    /// Entries whose leading component lies within the bounds
+    fn range(&self, lower: Bound<&IndexValue>, upper: Bound<&IndexValue>, as_of: Option<Timestamp>) -> Vec<Rid> {
+        let Entries::BTree(map) = &self.entries else {
+            return Vec::new();
+        };
+
+        let start = match lower {
+            Bound::Included(value) | Bound::Excluded(value) => Bound::Included(vec![value.clone()]),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        map.range((start, Bound::Unbounded))
+            .skip_while(|(key, _)| matches!(lower, Bound::Excluded(value) if &key[0] == value))
+            .take_while(|(key, _)| match upper {
+                Bound::Included(value) => &key[0] <= value,
+                Bound::Excluded(value) => &key[0] < value,
+                Bound::Unbounded => true,
+            })
+            .flat_map(|(_, entries)| entries)
+            .filter(|entry| entry.is_visible_at(as_of))
+            .map(|entry| entry.rid)
+            .collect()
+    }
+}
+
+/// Persisted form of an index; key maps are stored as entry lists
+#[derive(Serialize, Deserialize)]
+struct StoredIndex {
+    definition: IndexDefinition,
+    entries: Vec<(IndexKey, Vec<IndexEntry>)>,
+}
+
+/// Every secondary index by name
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(from = "Vec<StoredIndex>", into = "Vec<StoredIndex>")]
+pub(crate) struct Indexes {
+    indexes: BTreeMap<String, PropertyIndex>,
+}
+
+impl From<Vec<StoredIndex>> for Indexes {
+    fn from(stored: Vec<StoredIndex>) -> Self {
+        let mut indexes = BTreeMap::new();
+        for StoredIndex { definition, entries } in stored {
+            let mut index = PropertyIndex::new(definition);
+            for (key, key_entries) in entries {
+                for entry in key_entries.iter().filter(|e| e.to.is_none()) {
+                    index.current.insert(entry.rid, key.clone());
+                }
+                for entry in key_entries {
+                    index.entries.push(key.clone(), entry);
+                }
+            }
+            indexes.insert(index.definition.name.clone(), index);
+        }
+        Self { indexes }
+    }
+}
+
+impl From<Indexes> for Vec<StoredIndex> {
+    fn from(indexes: Indexes) -> Self {
+        indexes.indexes.into_values()
+            .map(|index| {
+                let mut entries: Vec<_> = index.entries.iter()
+                    .map(|(key, entries)| (key.clone(), entries.clone()))
+                    .collect();
+                entries.sort_by(|a, b| a.0.cmp(&b.0));
+                StoredIndex { definition: index.definition, entries }
+            })
+            .collect()
+    }
+}
+
+impl Indexes {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.indexes.is_empty()
+    }
+
+    pub(crate) fn contains(&self, name: &str) -> bool {
+        self.indexes.contains_key(name)
+    }
+
+    pub(crate) fn remove(&mut self, name: &str) -> bool {
+        self.indexes.remove(name).is_some()
+    }
+
+    /// Apply a node version to every index (`None` for a deletion)
+    pub(crate) fn update(&mut self, rid: Rid, record: Option<&PropertyRecord>, ts: Timestamp) {
+        for index in self.indexes.values_mut() {
+            index.update(rid, record, ts);
+        }
+    }
+
+    /// Current nodes holding `values` in the named index
+    pub(crate) fn lookup_named(&self, name: &str, values: &[PropertyValue]) -> Option<Vec<Rid>> {
+        let index = self.indexes.get(name)?;
+        let key = values.iter().map(IndexValue::from_property).collect::<Option<IndexKey>>()?;
+        Some(index.lookup(&key, None))
+    }
+
+    fn definitions(&self) -> Vec<IndexDefinition> {
+        self.indexes.values().map(|index| index.definition.clone()).collect()
+    }
+
+    /// Index on `label` whose properties are all among `properties`,
+    /// preferring the one covering the most
+    fn covering(&self, label: Option<&str>, properties: &[&str]) -> Option<&PropertyIndex> {
+        self.indexes.values()
+            .filter(|index| index.definition.label.as_deref() == label)
+            .filter(|index| index.definition.properties.iter().all(|p| properties.contains(&p.as_str())))
+            .max_by_key(|index| index.definition.properties.len())
+    }
+}
+
+impl GraphDB {
+    /// Declare a secondary index and build it from every stored node version
+    /// Merkle DAG: enishi_graph -> index -> create_index
+    pub async fn create_index(&self, definition: IndexDefinition) -> Result<(), Box<dyn std::error::Error>> {
+        if definition.properties.is_empty() {
+            return Err("an index needs at least one property".into());
+        }
+
+        {
+            let _guard = self.write_lock.lock().await;
+            if self.indexes.read().await.contains(&definition.name) {
+                return Err(format!("index {:?} already exists", definition.name).into());
+            }
+            info!("Creating index {:?} on {:?}", definition.name, definition.properties);
+            self.create_index_locked(definition).await?;
+        }
+        self.maybe_checkpoint().await
+    }
+
+    /// Drop an index; false if it did not exist
+    pub async fn drop_index(&self, name: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        let dropped = {
+            let _guard = self.write_lock.lock().await;
+            if !self.indexes.read().await.contains(name) {
+                return Ok(false);
+            }
+            self.log_and_apply(GraphOp::DropIndex { name: name.to_string() }, None).await?;
+            true
+        };
+        self.maybe_checkpoint().await?;
+        Ok(dropped)
+    }
+
+    /// Definitions of every secondary index
+    pub async fn list_indexes(&self) -> Vec<IndexDefinition> {
+        self.indexes.read().await.definitions()
+    }
+
+    /// Nodes with `label` whose properties equal `values`, as of a timestamp
+    /// (current state when `None`). Returns `None` when no index covers the
+    /// lookup; properties beyond those of the chosen index are not checked.
+    /// Merkle DAG: enishi_graph -> index -> lookup
+    pub async fn index_lookup(
+        &self,
+        label: Option<&str>,
+        values: &[(&str, &PropertyValue)],
+        as_of: Option<Timestamp>,
+    ) -> Option<Vec<Rid>> {
+        let indexes = self.indexes.read().await;
+        let properties: Vec<&str> = values.iter().map(|(property, _)| *property).collect();
+        let index = indexes.covering(label, &properties)?;
+
+        let mut key = Vec::with_capacity(index.definition.properties.len());
+        for property in &index.definition.properties {
+            let (_, value) = values.iter().find(|(p, _)| p == property)?;
+            match IndexValue::from_property(value) {
+                Some(value) => key.push(value),
+                // Unindexable values never match an indexed node
+                None => return Some(Vec::new()),
+            }
+        }
+
+        let mut rids = index.lookup(&key, as_of);
+        rids.sort();
+        rids.dedup();
+        Some(rids)
+    }
+
+    /// Nodes with `label` whose `property` lies within the bounds, using a
+    /// B-tree index led by that property. `None` when no such index exists.
+    /// Merkle DAG: enishi_graph -> index -> range_scan
+    pub async fn index_range(
+        &self,
+        label: Option<&str>,
+        property: &str,
+        lower: Bound<&PropertyValue>,
+        upper: Bound<&PropertyValue>,
+        as_of: Option<Timestamp>,
+    ) -> Option<Vec<Rid>> {
+        let indexes = self.indexes.read().await;
+        let index = indexes.indexes.values().find(|index| {
+            index.definition.kind == IndexKind::BTree
+                && index.definition.label.as_deref() == label
+                && index.definition.properties[0] == property
+        })?;
+
+        let convert = |bound: Bound<&PropertyValue>| -> Option<Bound<IndexValue>> {
+            Some(match bound {
+                Bound::Included(value) => Bound::Included(IndexValue::from_property(value)?),
+                Bound::Excluded(value) => Bound::Excluded(IndexValue::from_property(value)?),
+                Bound::Unbounded => Bound::Unbounded,
+            })
+        };
+        let (Some(lower), Some(upper)) = (convert(lower), convert(upper)) else {
+            return Some(Vec::new());
+        };
+
+        let mut rids = index.range(lower.as_ref(), upper.as_ref(), as_of);
+        rids.sort();
+        rids.dedup();
+        Some(rids)
+    }
+
+    /// Build an index, then log its creation and install it; callers hold
+    /// `write_lock`. The build reads every stored node version, so it runs
+    /// before anything is logged.
+    pub(crate) async fn create_index_locked(&self, definition: IndexDefinition) -> Result<(), Box<dyn std::error::Error>> {
+        let index = self.build_index(&definition).await?;
+        let op = GraphOp::CreateIndex { definition };
+        let lsn = self.log_op(&op).await?;
+        self.install_index(index).await;
+        self.op_applied(&op, lsn);
+        Ok(())
+    }
+
+    /// Build a new index from every stored node version; callers hold
+    /// `write_lock`
+    pub(crate) async fn build_index(&self, definition: &IndexDefinition) -> Result<PropertyIndex, Box<dyn std::error::Error>> {
+        let mut index = PropertyIndex::new(definition.clone());
+        let temporal = self.temporal_rid_mappings.read().await.clone();
+        let cas = self.cas.read().await;
+
+        for (rid, timeline) in temporal {
+            for (ts, cid) in timeline {
+                if cid == TOMBSTONE {
+                    index.update(rid, None, ts);
+                } else {
+                    let record = PropertyRecord::decode(&cas.get(&cid).await?)?;
+                    index.update(rid, Some(&record), ts);
+                }
+            }
+        }
+
+        Ok(index)
+    }
+
+    pub(crate) async fn install_index(&self, index: PropertyIndex) {
+        self.indexes.write().await.indexes.insert(index.definition.name.clone(), index);
+        self.dirty().mark_object(Whole::Indexes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_value_ordering() {
+        assert_eq!(IndexValue::Int(3), IndexValue::Float(3.0));
+        assert!(IndexValue::Int(3) < IndexValue::Float(3.5));
+        assert!(IndexValue::Float(-0.5) < IndexValue::Int(0));
+        assert!(IndexValue::Int(i64::MAX) < IndexValue::Float(9.3e18));
+        assert!(IndexValue::Bool(true) < IndexValue::Int(0));
+        assert!(IndexValue::Int(100) < IndexValue::String("a".to_string()));
+
+        let mut map = HashMap::new();
+        map.insert(vec![IndexValue::Int(3)], 1);
+        assert_eq!(map.get(&vec![IndexValue::Float(3.0)]), Some(&1));
+    }
+
+    #[test]
+    fn test_index_versions() {
+        let definition = IndexDefinition::new("age", Some("Person"), ["age"], IndexKind::BTree);
+        let mut index = PropertyIndex::new(definition);
+        let person = |age: i64| {
+            let mut record = PropertyRecord::new(["Person"], Default::default());
+            record.properties.insert("age".to_string(), PropertyValue::Int(age));
+            record
+        };
+
+        index.update(Rid(1), Some(&person(30)), Timestamp(10));
+        index.update(Rid(1), Some(&person(30)), Timestamp(15));
+        index.update(Rid(1), Some(&person(40)), Timestamp(20));
+        index.update(Rid(2), Some(&PropertyRecord::default()), Timestamp(20));
+
+        let key = vec![IndexValue::Int(30)];
+        assert_eq!(index.lookup(&key, None), Vec::<Rid>::new());
+        assert_eq!(index.lookup(&key, Some(Timestamp(15))), vec![Rid(1)]);
+        assert_eq!(index.entries.get(&key).unwrap().len(), 1);
+
+        let adults = IndexValue::Int(35);
+        assert_eq!(index.range(Bound::Included(&adults), Bound::Unbounded, None), vec![Rid(1)]);
+        assert!(index.range(Bound::Unbounded, Bound::Excluded(&adults), None).is_empty());
+
+        index.update(Rid(1), None, Timestamp(30));
+        assert!(index.current.is_empty());
+        assert_eq!(index.range(Bound::Unbounded, Bound::Unbounded, Some(Timestamp(25))), vec![Rid(1)]);
+    }
+}
@@ -2,15 +2,47 @@
 //!
 //! Graph data structures and operations for the Enishi database.
 //!
-//! Merkle DAG: enishi_graph -> rid_to_cid, adjacency, postings, temporal
+//! Merkle DAG: enishi_graph -> rid_to_cid, adjacency, postings, temporal, persist, gc, properties, dictionary, allocator, index, constraints
+
+mod allocator;
+mod constraints;
+mod dictionary;
+mod gc;
+mod index;
+mod namespace;
+mod persist;
+mod properties;
+mod snapshot;
+mod transaction;
 
 use fcdb_core::{Cid, varint, Monoid};
-use fcdb_cas::{PackCAS, PackBand};
+use fcdb_cas::{CasConfig, Charge, PackCAS, PackBand};
+use persist::{GraphOp, Sharded, Whole};
+
+pub use allocator::RidBlock;
+pub use constraints::{ConstraintDefinition, ConstraintKind, ConstraintTarget, ConstraintViolation, PropertyType};
+pub use dictionary::{Dictionary, NameKind, PropertyKeyId};
+pub use fcdb_cas::{GcReport, QuotaExceeded, QuotaScope};
+pub use gc::{GcOptions, RetentionPolicy};
+pub use index::{IndexDefinition, IndexKey, IndexKind, IndexValue};
+pub use namespace::{Catalog, Namespace, NamespaceError, DEFAULT_NAMESPACE};
+pub use properties::{properties_from_json, properties_to_json, Properties, PropertyRecord, PropertyValue};
+pub use snapshot::Snapshot;
+pub use transaction::{IsolationLevel, ScopeViolation, Transaction, TransactionConflict};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet, BTreeMap};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
-use tokio::sync::RwLock;
-use tracing::{info, debug};
+use tokio::sync::{Mutex, RwLock};
+use tracing::{info, debug, warn};
+
+/// Mutations between automatic checkpoints (bounds WAL replay on open)
+const CHECKPOINT_INTERVAL: usize = 1024;
+
+/// Timeline entry marking a node as deleted from its timestamp on
+pub const TOMBSTONE: Cid = Cid::from_bytes([0; 32]);
 
 /// Resource ID (RID) - unique identifier for graph nodes
 #[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
@@ -60,6 +92,10 @@ impl From<u32> for LabelId {
     }
 }
 
+/// Edge identifier, unique within a graph; tells parallel edges apart
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct EdgeId(pub u64);
+
 /// Temporal timestamp for versioning
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Timestamp(pub u64);
@@ -93,14 +129,27 @@ pub struct Edge {
 /// Adjacency list entry
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AdjEntry {
+    pub id: EdgeId,
     pub target: Rid,
     pub label: LabelId,
     pub properties: Cid,
     pub timestamp: Timestamp,
+    #[serde(default)]
+    pub deleted_at: Option<Timestamp>,
+}
+
+impl AdjEntry {
+    /// Whether the edge exists at `as_of` (now, if `None`)
+    pub fn is_visible_at(&self, as_of: Option<Timestamp>) -> bool {
+        match as_of {
+            Some(ts) => self.timestamp <= ts && self.deleted_at.is_none_or(|deleted| deleted > ts),
+            None => self.deleted_at.is_none(),
+        }
+    }
 }
 
 /// Posting list for full-text search and analytics
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Posting {
     pub term: String,
     pub rid: Rid,
@@ -137,109 +186,607 @@ pub struct GraphDB {
     // Posting lists for search
     postings: Arc<RwLock<HashMap<String, Vec<Posting>>>>,
 
+    // Label and property-key names
+    dictionary: Arc<RwLock<Dictionary>>,
+
+    // RID high-water mark and external keys
+    allocator: Arc<RwLock<allocator::RidAllocator>>,
+
+    // Secondary property indexes
+    indexes: Arc<RwLock<index::Indexes>>,
+
+    // Schema constraints by name
+    constraints: Arc<RwLock<BTreeMap<String, ConstraintDefinition>>>,
+
     // Current timestamp for operations
     current_timestamp: Arc<RwLock<Timestamp>>,
+
+    // Timestamp of the last fully applied commit; reads of "now" happen here
+    committed_ts: Arc<AtomicU64>,
+
+    // Next edge id to hand out
+    next_edge_id: Arc<AtomicU64>,
+
+    // One past the highest label id reserved by a transaction
+    reserved_label_ids: Arc<AtomicU32>,
+
+    // Timestamps pinned by live snapshots, with reference counts
+    pinned: Arc<std::sync::Mutex<BTreeMap<Timestamp, usize>>>,
+
+    // Directory holding the root pointer (the CAS base path)
+    root_dir: PathBuf,
+
+    // Serializes logging + applying mutations against checkpoints, so a
+    // checkpoint always captures exactly the ops up to `applied_lsn`
+    write_lock: Arc<Mutex<()>>,
+
+    // LSN of the last WAL record applied to the in-memory structures
+    applied_lsn: Arc<AtomicU64>,
+
+    // Mutations logged since the last checkpoint
+    ops_since_checkpoint: Arc<AtomicUsize>,
+
+    // Shards changed since the last checkpoint
+    dirty: Arc<std::sync::Mutex<persist::Dirty>>,
+
+    // Shard CIDs of the last checkpoint
+    checkpointed: Arc<Mutex<persist::Checkpointed>>,
+
+    // WAL tag of the graph's records; 0 unless it is a catalog namespace
+    namespace: u32,
+
+    // Set when other graphs log to the same PackCAS; the WAL may then only
+    // be reset once every one of them has checkpointed
+    shared_wal: Option<Arc<namespace::SharedWal>>,
 }
 
 impl GraphDB {
-    /// Create a new graph database instance
+    /// Create a new, empty graph database instance
     pub async fn new(cas: PackCAS) -> Self {
+        Self::from_state(cas, persist::GraphState::default())
+    }
+
+    /// Open the graph stored at `path`, rebuilding in-memory indexes from the
+    /// last checkpointed root (an empty graph if none has been written yet)
+    /// and replaying mutations logged in the WAL after it
+    pub async fn open<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::open_with_config(path, CasConfig::default()).await
+    }
+
+    /// Like [`GraphDB::open`], with explicit PackCAS configuration
+    pub async fn open_with_config<P: AsRef<Path>>(path: P, config: CasConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut cas = PackCAS::open_with_config(path, config).await?;
+
+        let state = match persist::read_root_pointer(cas.path())? {
+            Some(root_cid) => persist::read_state(&cas, &root_cid).await?,
+            None => persist::GraphState::default(),
+        };
+        let recovered_ops = cas.take_recovered_ops();
+
+        let graph = Self::from_state(cas, state);
+        graph.recover(recovered_ops).await?;
+
+        info!("Opened graph with {} nodes", graph.rid_to_cid.read().await.len());
+        Ok(graph)
+    }
+
+    fn from_state(cas: PackCAS, state: persist::GraphState) -> Self {
+        let root_dir = cas.path().to_path_buf();
+        Self::from_parts(Arc::new(RwLock::new(cas)), root_dir, state, 0, None)
+    }
+
+    /// Graph over a possibly shared PackCAS, with its root pointer in `root_dir`
+    pub(crate) fn from_parts(
+        cas: Arc<RwLock<PackCAS>>,
+        root_dir: PathBuf,
+        state: persist::GraphState,
+        namespace: u32,
+        shared_wal: Option<Arc<namespace::SharedWal>>,
+    ) -> Self {
+        let committed_ts = state.last_commit();
+        let next_edge_id = state.next_edge_id();
         Self {
-            cas: Arc::new(RwLock::new(cas)),
-            rid_to_cid: Arc::new(RwLock::new(HashMap::new())),
-            temporal_rid_mappings: Arc::new(RwLock::new(HashMap::new())),
-            adjacency: Arc::new(RwLock::new(HashMap::new())),
-            reverse_adjacency: Arc::new(RwLock::new(HashMap::new())),
-            postings: Arc::new(RwLock::new(HashMap::new())),
+            cas,
+            rid_to_cid: Arc::new(RwLock::new(state.rid_to_cid)),
+            temporal_rid_mappings: Arc::new(RwLock::new(state.temporal)),
+            adjacency: Arc::new(RwLock::new(state.adjacency)),
+            reverse_adjacency: Arc::new(RwLock::new(state.reverse_adjacency)),
+            postings: Arc::new(RwLock::new(state.postings)),
+            dictionary: Arc::new(RwLock::new(state.dictionary)),
+            allocator: Arc::new(RwLock::new(state.allocator)),
+            indexes: Arc::new(RwLock::new(state.indexes)),
+            constraints: Arc::new(RwLock::new(state.constraints)),
             current_timestamp: Arc::new(RwLock::new(Timestamp::now())),
+            committed_ts: Arc::new(AtomicU64::new(committed_ts.0)),
+            next_edge_id: Arc::new(AtomicU64::new(next_edge_id.0)),
+            reserved_label_ids: Arc::new(AtomicU32::new(0)),
+            pinned: Arc::new(std::sync::Mutex::new(BTreeMap::new())),
+            root_dir,
+            write_lock: Arc::new(Mutex::new(())),
+            applied_lsn: Arc::new(AtomicU64::new(state.wal_lsn)),
+            ops_since_checkpoint: Arc::new(AtomicUsize::new(0)),
+            dirty: Arc::new(std::sync::Mutex::new(persist::Dirty::default())),
+            checkpointed: Arc::new(Mutex::new(state.checkpointed)),
+            namespace,
+            shared_wal,
+        }
+    }
+
+    /// Re-apply WAL records newer than the checkpointed root, then checkpoint
+    pub(crate) async fn recover(&self, ops: Vec<(u64, Vec<u8>)>) -> Result<(), Box<dyn std::error::Error>> {
+        let checkpointed_lsn = self.applied_lsn.load(Ordering::SeqCst);
+        let mut replayed = 0;
+
+        for (lsn, payload) in ops {
+            if lsn <= checkpointed_lsn {
+                continue;
+            }
+            let op = GraphOp::decode(&payload)?;
+            self.apply_op(&op, None).await;
+            self.op_applied(&op, lsn);
+            replayed += 1;
+        }
+
+        if replayed > 0 {
+            info!("Replayed {} graph mutations from the WAL", replayed);
+            self.checkpoint().await?;
+        }
+        Ok(())
+    }
+
+    /// Write the index shards changed since the last checkpoint to CAS,
+    /// atomically advance the root pointer and discard the WAL records it
+    /// now covers
+    /// Merkle DAG: enishi_graph -> persist -> graph_root
+    pub async fn checkpoint(&self) -> Result<Cid, Box<dyn std::error::Error>> {
+        let _guard = self.write_lock.lock().await;
+        self.checkpoint_locked().await
+    }
+
+    /// [`GraphDB::checkpoint`] for callers already holding `write_lock`
+    async fn checkpoint_locked(&self) -> Result<Cid, Box<dyn std::error::Error>> {
+        let wal_lsn = self.applied_lsn.load(Ordering::SeqCst);
+        let dirty = std::mem::take(&mut *self.dirty());
+        let written = self.write_checkpoint(&dirty, wal_lsn).await;
+        if written.is_err() {
+            self.dirty().merge(dirty);
+        }
+        let root_cid = written?;
+
+        let mut cas = self.cas.write().await;
+        cas.sync()?;
+        persist::write_root_pointer(&self.root_dir, &root_cid)?;
+        match &self.shared_wal {
+            Some(shared) if !shared.mark_clean(self.namespace) => {
+                cas.commit()?;
+                cas.save_usage()?;
+            }
+            _ => cas.checkpoint_wal()?,
         }
+        self.ops_since_checkpoint.store(0, Ordering::SeqCst);
+
+        debug!("Checkpointed graph root {:?} at LSN {}", root_cid, wal_lsn);
+        Ok(root_cid)
+    }
+
+    /// Encode the shards marked in `dirty` under the map locks, then store
+    /// them with a new root object
+    async fn write_checkpoint(&self, dirty: &persist::Dirty, wal_lsn: u64) -> std::io::Result<Cid> {
+        let encoded = {
+            let rid_to_cid = self.rid_to_cid.read().await;
+            let temporal = self.temporal_rid_mappings.read().await;
+            let adjacency = self.adjacency.read().await;
+            let reverse_adjacency = self.reverse_adjacency.read().await;
+            let postings = self.postings.read().await;
+            let dictionary = self.dictionary.read().await;
+            let allocator = self.allocator.read().await;
+            let indexes = self.indexes.read().await;
+            let constraints = self.constraints.read().await;
+            persist::encode_state(&persist::StateView {
+                rid_to_cid: &rid_to_cid,
+                temporal: &temporal,
+                adjacency: &adjacency,
+                reverse_adjacency: &reverse_adjacency,
+                postings: &postings,
+                dictionary: &dictionary,
+                allocator: &allocator,
+                indexes: &indexes,
+                constraints: &constraints,
+                wal_lsn,
+            }, dirty)?
+        };
+
+        let mut checkpointed = self.checkpointed.lock().await;
+        let mut cas = self.cas.write().await;
+        persist::write_state(&mut cas, encoded, &mut checkpointed).await
+    }
+
+    /// Shards changed since the last checkpoint, to mark what a mutation touched
+    fn dirty(&self) -> std::sync::MutexGuard<'_, persist::Dirty> {
+        self.dirty.lock().unwrap()
     }
 
-    /// Set the current timestamp for operations (for testing/temporal control)
+    /// An edge `from -> to` was added or tombstoned
+    fn mark_edge(&self, from: Rid, to: Rid) {
+        let mut dirty = self.dirty();
+        dirty.mark_rid(Sharded::Adjacency, from);
+        dirty.mark_rid(Sharded::ReverseAdjacency, to);
+    }
+
+    /// Log a mutation to the WAL (group-committed with any staged puts) and
+    /// apply it in memory; callers hold `write_lock` and have already made
+    /// every check that can fail
+    async fn log_and_apply(&self, op: GraphOp, data: Option<&[u8]>) -> Result<(), Box<dyn std::error::Error>> {
+        let lsn = self.log_op(&op).await?;
+        self.apply_op(&op, data).await;
+        self.op_applied(&op, lsn);
+        Ok(())
+    }
+
+    /// Log a mutation to the WAL and return its LSN; callers hold
+    /// `write_lock` and apply the mutation right after
+    async fn log_op(&self, op: &GraphOp) -> Result<u64, Box<dyn std::error::Error>> {
+        let mut cas = self.cas.write().await;
+        let lsn = cas.log_op_in(self.namespace, &op.encode()?);
+        cas.commit()?;
+        if let Some(shared) = &self.shared_wal {
+            shared.mark_dirty(self.namespace);
+        }
+        Ok(lsn)
+    }
+
+    /// A logged mutation has been applied: publish it to readers
+    fn op_applied(&self, op: &GraphOp, lsn: u64) {
+        self.publish_commit(op);
+        self.applied_lsn.store(lsn, Ordering::SeqCst);
+    }
+
+    /// Apply a logged mutation to the in-memory structures; node data is
+    /// read back from CAS when not supplied (WAL replay). Writers check
+    /// everything that can fail before logging, so this does not fail: what
+    /// cannot be read back is left out of the indexes with a warning.
+    async fn apply_op(&self, op: &GraphOp, data: Option<&[u8]>) {
+        match *op {
+            GraphOp::CreateNode { rid, cid, ts, .. } | GraphOp::UpdateNode { rid, cid, ts } => {
+                if let GraphOp::CreateNode { ref key, .. } = *op {
+                    let mut allocator = self.allocator.write().await;
+                    allocator.advance_to(rid.0 + 1);
+                    if let Some(key) = key {
+                        allocator.bind_key(key, rid);
+                    }
+                    self.dirty().mark_object(Whole::Allocator);
+                }
+                {
+                    let mut rid_to_cid = self.rid_to_cid.write().await;
+                    let mut temporal = self.temporal_rid_mappings.write().await;
+
+                    rid_to_cid.insert(rid, cid);
+                    temporal.entry(rid).or_insert_with(BTreeMap::new).insert(ts, cid);
+                    let mut dirty = self.dirty();
+                    dirty.mark_rid(Sharded::RidToCid, rid);
+                    dirty.mark_rid(Sharded::Temporal, rid);
+                }
+
+                let data = match data {
+                    Some(data) => Some(data.to_vec()),
+                    None => match self.cas.read().await.get(&cid).await {
+                        Ok(data) => Some(data),
+                        Err(e) => {
+                            warn!("Data {:?} of node {} is unreadable, not indexed: {}", cid, rid, e);
+                            None
+                        }
+                    },
+                };
+                if let Some(data) = data {
+                    self.index_node(rid, &data, ts).await;
+                }
+            }
+            GraphOp::CreateEdge { id, from, to, label, properties, ts } => {
+                self.next_edge_id.fetch_max(id.0 + 1, Ordering::SeqCst);
+                let mut adj = self.adjacency.write().await;
+                let mut rev_adj = self.reverse_adjacency.write().await;
+                self.mark_edge(from, to);
+
+                adj.entry(from).or_insert_with(Vec::new).push(AdjEntry {
+                    id,
+                    target: to,
+                    label,
+                    properties,
+                    timestamp: ts,
+                    deleted_at: None,
+                });
+                rev_adj.entry(to).or_insert_with(Vec::new).push(AdjEntry {
+                    id,
+                    target: from,
+                    label,
+                    properties,
+                    timestamp: ts,
+                    deleted_at: None,
+                });
+            }
+            GraphOp::DeleteNode { rid, ts } => {
+                self.rid_to_cid.write().await.remove(&rid);
+                self.allocator.write().await.release(rid);
+                self.indexes.write().await.update(rid, None, ts);
+                self.temporal_rid_mappings.write().await
+                    .entry(rid).or_insert_with(BTreeMap::new).insert(ts, TOMBSTONE);
+                {
+                    let mut dirty = self.dirty();
+                    dirty.mark_rid(Sharded::RidToCid, rid);
+                    dirty.mark_rid(Sharded::Temporal, rid);
+                    dirty.mark_object(Whole::Allocator);
+                    dirty.mark_object(Whole::Indexes);
+                }
+
+                // Incident edges die with the node
+                let mut adj = self.adjacency.write().await;
+                let mut rev_adj = self.reverse_adjacency.write().await;
+                for entry in adj.get_mut(&rid).into_iter().flatten().filter(|e| e.deleted_at.is_none()) {
+                    entry.deleted_at = Some(ts);
+                    tombstone_edge(&mut rev_adj, entry.target, entry.id, ts);
+                    self.mark_edge(rid, entry.target);
+                }
+                for entry in rev_adj.get_mut(&rid).into_iter().flatten().filter(|e| e.deleted_at.is_none()) {
+                    entry.deleted_at = Some(ts);
+                    tombstone_edge(&mut adj, entry.target, entry.id, ts);
+                    self.mark_edge(entry.target, rid);
+                }
+            }
+            GraphOp::DeleteEdge { from, to, id, ts } => {
+                self.mark_edge(from, to);
+                tombstone_edge(&mut *self.adjacency.write().await, from, id, ts);
+                tombstone_edge(&mut *self.reverse_adjacency.write().await, to, id, ts);
+            }
+            GraphOp::InternName { kind, ref name, id } => {
+                self.dictionary.write().await.insert(kind, name, id);
+                self.dirty().mark_object(Whole::Dictionary);
+            }
+            GraphOp::ReserveRids { start, end } => {
+                let mut allocator = self.allocator.write().await;
+                match start {
+                    Some(start) => allocator.reserve(start, end),
+                    None => allocator.advance_to(end),
+                }
+                self.dirty().mark_object(Whole::Allocator);
+            }
+            GraphOp::CreateIndex { ref definition } => {
+                let built = self.build_index(definition).await.map_err(|e| e.to_string());
+                match built {
+                    Ok(index) => self.install_index(index).await,
+                    Err(e) => warn!("Index {:?} could not be rebuilt, skipped: {}", definition.name, e),
+                }
+            }
+            GraphOp::DropIndex { ref name } => {
+                self.indexes.write().await.remove(name);
+                self.dirty().mark_object(Whole::Indexes);
+            }
+            GraphOp::CreateConstraint { ref definition } => {
+                self.constraints.write().await.insert(definition.name.clone(), definition.clone());
+                self.dirty().mark_object(Whole::Constraints);
+            }
+            GraphOp::DropConstraint { ref name } => {
+                self.constraints.write().await.remove(name);
+                self.dirty().mark_object(Whole::Constraints);
+            }
+            GraphOp::Batch { ref ops } => {
+                // Node data of a batch is read back from CAS
+                for op in ops {
+                    Box::pin(self.apply_op(op, None)).await;
+                }
+            }
+        }
+    }
+
+    /// Add a node version to the text and property indexes
+    async fn index_node(&self, rid: Rid, data: &[u8], ts: Timestamp) {
+        let record = match PropertyRecord::decode(data) {
+            Ok(record) => record,
+            Err(e) => {
+                warn!("Data of node {} does not decode, not indexed: {}", rid, e);
+                return;
+            }
+        };
+
+        // Index for search if it's text data
+        if PropertyRecord::is_encoded(data) {
+            // Property records are indexed by their string values
+            let text: Vec<&str> = record.properties.values()
+                .filter_map(|value| match value {
+                    PropertyValue::String(s) => Some(s.as_str()),
+                    _ => None,
+                })
+                .collect();
+            self.index_text(rid, &text.join(" "), ts).await;
+        } else if let Ok(text) = std::str::from_utf8(data) {
+            self.index_text(rid, text, ts).await;
+        }
+
+        let mut indexes = self.indexes.write().await;
+        if !indexes.is_empty() {
+            indexes.update(rid, Some(&record), ts);
+            self.dirty().mark_object(Whole::Indexes);
+        }
+    }
+
+    /// Quota account of objects the graph stores outside a transaction
+    fn charge(&self) -> Charge<'static> {
+        Charge { namespace: self.namespace, actor: None }
+    }
+
+    /// Checkpoint once enough mutations have accumulated in the WAL
+    async fn maybe_checkpoint(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.ops_since_checkpoint.fetch_add(1, Ordering::SeqCst) + 1 >= CHECKPOINT_INTERVAL {
+            self.checkpoint().await?;
+        }
+        Ok(())
+    }
+
+    /// Set the current timestamp for operations (for testing/temporal control).
+    /// Commits never go back in time: while the clock is behind the last
+    /// commit, writes are stamped just after it.
     pub async fn set_timestamp(&self, ts: Timestamp) {
         *self.current_timestamp.write().await = ts;
     }
 
     /// Create a new node with initial data
     pub async fn create_node(&self, data: &[u8]) -> Result<Rid, Box<dyn std::error::Error>> {
-        let ts = *self.current_timestamp.read().await;
+        let rid = {
+            let _guard = self.write_lock.lock().await;
+            let rid = self.allocator.read().await.next();
+            self.create_node_locked(rid, None, data).await?;
+            rid
+        };
+        self.maybe_checkpoint().await?;
+        Ok(rid)
+    }
 
-        // Generate new RID (simplified - in real impl, use proper ID generation)
-        let rid = Rid(self.rid_to_cid.read().await.len() as u64 + 1);
+    /// Store node data and log its creation under `rid`; callers hold
+    /// `write_lock` and have checked that `rid` is free
+    async fn create_node_locked(&self, rid: Rid, key: Option<&str>, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        let ts = self.next_commit_timestamp().await;
+        self.check_node_constraints(rid, data).await?;
 
         // Store data in CAS
         let cid = {
             let mut cas = self.cas.write().await;
-            cas.put(data, 0, PackBand::Small).await?
+            cas.put_charged(self.charge(), data, 0, PackBand::Small).await?
         };
 
-        // Update mappings
-        {
-            let mut rid_to_cid = self.rid_to_cid.write().await;
-            let mut temporal = self.temporal_rid_mappings.write().await;
-
-            rid_to_cid.insert(rid, cid);
-            temporal.entry(rid).or_insert_with(BTreeMap::new).insert(ts, cid);
-        }
-
-        // Index for search if it's text data
-        if let Ok(text) = std::str::from_utf8(data) {
-            self.index_text(rid, text, ts).await;
-        }
+        let key = key.map(str::to_string);
+        self.log_and_apply(GraphOp::CreateNode { rid, cid, ts, key }, Some(data)).await?;
 
         info!("Created node {} with CID {:?}", rid, cid);
-        Ok(rid)
+        Ok(())
     }
 
     /// Update a node's data
     pub async fn update_node(&self, rid: Rid, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
-        let ts = *self.current_timestamp.read().await;
+        {
+            let _guard = self.write_lock.lock().await;
+            self.update_node_locked(rid, data).await?;
+        }
+        self.maybe_checkpoint().await
+    }
+
+    /// [`GraphDB::update_node`] for callers already holding `write_lock`
+    async fn update_node_locked(&self, rid: Rid, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.rid_to_cid.read().await.contains_key(&rid) {
+            return Err(format!("node {} does not exist", rid).into());
+        }
+        let ts = self.next_commit_timestamp().await;
+        self.check_node_constraints(rid, data).await?;
 
         let cid = {
             let mut cas = self.cas.write().await;
-            cas.put(data, 0, PackBand::Small).await?
+            cas.put_charged(self.charge(), data, 0, PackBand::Small).await?
         };
 
-        // Update mappings
-        {
-            let mut rid_to_cid = self.rid_to_cid.write().await;
-            let mut temporal = self.temporal_rid_mappings.write().await;
+        self.log_and_apply(GraphOp::UpdateNode { rid, cid, ts }, Some(data)).await?;
+        debug!("Updated node {} to CID {:?}", rid, cid);
+        Ok(())
+    }
+
+    /// Create a node with labels and typed properties
+    /// Merkle DAG: enishi_graph -> properties -> property_record
+    pub async fn create_node_with_properties<I, S>(&self, labels: I, properties: Properties) -> Result<Rid, Box<dyn std::error::Error>>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.create_node(&PropertyRecord::new(labels, properties).encode()).await
+    }
 
-            rid_to_cid.insert(rid, cid);
-            temporal.entry(rid).or_insert_with(BTreeMap::new).insert(ts, cid);
+    /// Current labels and properties of a node
+    pub async fn get_record(&self, rid: Rid) -> Result<Option<PropertyRecord>, Box<dyn std::error::Error>> {
+        match self.get_node(rid).await? {
+            Some(data) => Ok(Some(PropertyRecord::decode(&data)?)),
+            None => Ok(None),
         }
+    }
 
-        // Re-index for search
-        if let Ok(text) = std::str::from_utf8(data) {
-            self.index_text(rid, text, ts).await;
+    /// Labels and properties of a node at a specific timestamp
+    pub async fn get_record_at(&self, rid: Rid, as_of: Timestamp) -> Result<Option<PropertyRecord>, Box<dyn std::error::Error>> {
+        match self.get_node_at(rid, as_of).await? {
+            Some(data) => Ok(Some(PropertyRecord::decode(&data)?)),
+            None => Ok(None),
         }
+    }
 
-        debug!("Updated node {} to CID {:?}", rid, cid);
-        Ok(())
+    /// Current properties of a node
+    pub async fn get_properties(&self, rid: Rid) -> Result<Option<Properties>, Box<dyn std::error::Error>> {
+        Ok(self.get_record(rid).await?.map(|record| record.properties))
     }
 
-    /// Get current data for a node
-    pub async fn get_node(&self, rid: Rid) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
-        let cid = {
-            let rid_to_cid = self.rid_to_cid.read().await;
-            rid_to_cid.get(&rid).cloned()
-        };
+    /// Current labels of a node
+    pub async fn get_labels(&self, rid: Rid) -> Result<Option<Vec<String>>, Box<dyn std::error::Error>> {
+        Ok(self.get_record(rid).await?.map(|record| record.labels.into_iter().collect()))
+    }
 
-        if let Some(cid) = cid {
-            let cas = self.cas.read().await;
-            Ok(Some(cas.get(&cid).await?))
-        } else {
-            Ok(None)
+    /// Set one property, writing a new node version
+    pub async fn set_property(&self, rid: Rid, key: &str, value: PropertyValue) -> Result<(), Box<dyn std::error::Error>> {
+        self.modify_record(rid, |record| {
+            record.properties.insert(key.to_string(), value);
+        }).await
+    }
+
+    /// Remove one property, writing a new node version
+    pub async fn remove_property(&self, rid: Rid, key: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.modify_record(rid, |record| {
+            record.properties.remove(key);
+        }).await
+    }
+
+    /// Add a label, writing a new node version
+    pub async fn add_label(&self, rid: Rid, label: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.modify_record(rid, |record| {
+            record.labels.insert(label.to_string());
+        }).await
+    }
+
+    /// Remove a label, writing a new node version
+    pub async fn remove_label(&self, rid: Rid, label: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.modify_record(rid, |record| {
+            record.labels.remove(label);
+        }).await
+    }
+
+    /// Read-modify-write of a node record under `write_lock`, so concurrent
+    /// property updates are not lost. Unchanged records write no version.
+    async fn modify_record<F>(&self, rid: Rid, f: F) -> Result<(), Box<dyn std::error::Error>>
+    where
+        F: FnOnce(&mut PropertyRecord),
+    {
+        {
+            let _guard = self.write_lock.lock().await;
+            let data = self.get_node(rid).await?
+                .ok_or_else(|| format!("node {} does not exist", rid))?;
+            let mut record = PropertyRecord::decode(&data)?;
+            let before = record.clone();
+            f(&mut record);
+            if record == before && PropertyRecord::is_encoded(&data) {
+                return Ok(());
+            }
+            self.update_node_locked(rid, &record.encode()).await?;
         }
+        self.maybe_checkpoint().await
     }
 
-    /// Get node data at a specific timestamp (temporal query)
+    /// Get current data for a node, as of the last completed commit
+    pub async fn get_node(&self, rid: Rid) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        self.get_node_at(rid, self.committed_timestamp()).await
+    }
+
+    /// Get node data at a specific timestamp (temporal query); commits still
+    /// being applied are never visible
     pub async fn get_node_at(&self, rid: Rid, as_of: Timestamp) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        let as_of = self.read_timestamp(Some(as_of));
         let cid = {
             let temporal = self.temporal_rid_mappings.read().await;
             if let Some(timeline) = temporal.get(&rid) {
-                // Find the most recent CID valid at as_of
-                timeline.range(..=as_of).next_back().map(|(_, cid)| *cid)
+                // Find the most recent CID valid at as_of (none if it is a tombstone)
+                timeline.range(..=as_of).next_back()
+                    .map(|(_, cid)| *cid)
+                    .filter(|cid| *cid != TOMBSTONE)
             } else {
                 None
             }
@@ -254,51 +801,129 @@ impl GraphDB {
     }
 
     /// Create an edge between nodes
-    pub async fn create_edge(&self, from: Rid, to: Rid, label: LabelId, properties: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
-        let ts = *self.current_timestamp.read().await;
+    pub async fn create_edge(&self, from: Rid, to: Rid, label: LabelId, properties: &[u8]) -> Result<EdgeId, Box<dyn std::error::Error>> {
+        let id = {
+            let _guard = self.write_lock.lock().await;
+            for rid in [from, to] {
+                if !self.node_exists(rid, None).await {
+                    return Err(format!("node {} does not exist", rid).into());
+                }
+            }
+            let ts = self.next_commit_timestamp().await;
+            self.check_edge_constraints(&self.label_display(label).await, properties).await?;
 
-        let prop_cid = {
-            let mut cas = self.cas.write().await;
-            cas.put(properties, 1, PackBand::Small).await?
-        };
+            let prop_cid = {
+                let mut cas = self.cas.write().await;
+                cas.put_charged(self.charge(), properties, 1, PackBand::Small).await?
+            };
 
-        let entry = AdjEntry {
-            target: to,
-            label,
-            properties: prop_cid,
-            timestamp: ts,
+            let id = self.allocate_edge_id();
+            let op = GraphOp::CreateEdge { id, from, to, label, properties: prop_cid, ts };
+            self.log_and_apply(op, None).await?;
+            id
         };
+        self.maybe_checkpoint().await?;
+
+        debug!("Created edge {} --({})--> {}", from, label.0, to);
+        Ok(id)
+    }
+
+    /// Issue an edge id. Like RIDs for transactions, the allocation is not
+    /// logged: replaying the edge's `CreateEdge` moves the counter past it.
+    pub(crate) fn allocate_edge_id(&self) -> EdgeId {
+        EdgeId(self.next_edge_id.fetch_add(1, Ordering::SeqCst))
+    }
 
-        // Update adjacency lists
+    /// Create an edge with typed properties
+    pub async fn create_edge_with_properties(&self, from: Rid, to: Rid, label: LabelId, properties: Properties) -> Result<EdgeId, Box<dyn std::error::Error>> {
+        let record = PropertyRecord { labels: Default::default(), properties };
+        self.create_edge(from, to, label, &record.encode()).await
+    }
+
+    /// Typed properties of an edge
+    pub async fn get_edge_properties(&self, edge: &AdjEntry) -> Result<Properties, Box<dyn std::error::Error>> {
+        let data = self.cas.read().await.get(&edge.properties).await?;
+        Ok(PropertyRecord::decode(&data)?.properties)
+    }
+
+    /// Delete a node, tombstoning it and all of its edges at the current timestamp.
+    /// Returns false if the node does not exist.
+    pub async fn delete_node(&self, rid: Rid) -> Result<bool, Box<dyn std::error::Error>> {
         {
-            let mut adj = self.adjacency.write().await;
-            let mut rev_adj = self.reverse_adjacency.write().await;
+            let _guard = self.write_lock.lock().await;
+            if !self.rid_to_cid.read().await.contains_key(&rid) {
+                return Ok(false);
+            }
+            let ts = self.next_commit_timestamp().await;
+            self.log_and_apply(GraphOp::DeleteNode { rid, ts }, None).await?;
+        }
+        self.maybe_checkpoint().await?;
 
-            adj.entry(from).or_insert_with(Vec::new).push(entry.clone());
-            rev_adj.entry(to).or_insert_with(Vec::new).push(AdjEntry {
-                target: from,
-                label,
-                properties: prop_cid,
-                timestamp: ts,
-            });
+        debug!("Deleted node {}", rid);
+        Ok(true)
+    }
+
+    /// Delete the live edges `from --label--> to` at the current timestamp.
+    /// Returns false if there was no such edge.
+    pub async fn delete_edge(&self, from: Rid, to: Rid, label: LabelId) -> Result<bool, Box<dyn std::error::Error>> {
+        {
+            let _guard = self.write_lock.lock().await;
+            let ids: Vec<EdgeId> = self.adjacency.read().await.get(&from).into_iter().flatten()
+                .filter(|e| e.target == to && e.label == label && e.deleted_at.is_none())
+                .map(|e| e.id)
+                .collect();
+            if ids.is_empty() {
+                return Ok(false);
+            }
+            let ts = self.next_commit_timestamp().await;
+            let ops = ids.into_iter().map(|id| GraphOp::DeleteEdge { from, to, id, ts }).collect();
+            self.log_and_apply(GraphOp::Batch { ops }, None).await?;
         }
+        self.maybe_checkpoint().await?;
 
-        debug!("Created edge {} --({})--> {}", from, label.0, to);
-        Ok(())
+        debug!("Deleted edge {} --({})--> {}", from, label.0, to);
+        Ok(true)
+    }
+
+    /// Whether a node exists at `as_of` (now, if `None`)
+    pub async fn node_exists(&self, rid: Rid, as_of: Option<Timestamp>) -> bool {
+        let ts = self.read_timestamp(as_of);
+        self.temporal_rid_mappings.read().await.get(&rid)
+            .and_then(|timeline| timeline.range(..=ts).next_back())
+            .is_some_and(|(_, cid)| *cid != TOMBSTONE)
     }
 
     /// Traverse graph from a starting node
     pub async fn traverse(&self, from: Rid, labels: Option<&[LabelId]>, max_depth: usize, as_of: Option<Timestamp>)
         -> Result<Vec<(Rid, usize)>, Box<dyn std::error::Error>>
     {
+        self.traverse_scoped(from, labels, max_depth, as_of, None).await
+    }
+
+    /// Traverse graph from a starting node without leaving the RID range
+    /// `scope`: nodes outside it are neither returned nor expanded
+    pub async fn traverse_within(&self, from: Rid, labels: Option<&[LabelId]>, max_depth: usize, as_of: Option<Timestamp>, scope: Range<Rid>)
+        -> Result<Vec<(Rid, usize)>, Box<dyn std::error::Error>>
+    {
+        self.traverse_scoped(from, labels, max_depth, as_of, Some(&scope)).await
+    }
+
+    async fn traverse_scoped(&self, from: Rid, labels: Option<&[LabelId]>, max_depth: usize, as_of: Option<Timestamp>, scope: Option<&Range<Rid>>)
+        -> Result<Vec<(Rid, usize)>, Box<dyn std::error::Error>>
+    {
+        let as_of = Some(self.read_timestamp(as_of));
         let mut visited = HashSet::new();
         let mut result = Vec::new();
         let mut queue = vec![(from, 0)]; // (node, depth)
 
+        if !self.node_exists(from, as_of).await {
+            return Ok(result);
+        }
+
         let adj = self.adjacency.read().await;
 
         while let Some((current, depth)) = queue.pop() {
-            if depth > max_depth || !visited.insert(current) {
+            if depth > max_depth || scope.is_some_and(|scope| !scope.contains(&current)) || !visited.insert(current) {
                 continue;
             }
 
@@ -307,11 +932,9 @@ impl GraphDB {
             if depth < max_depth {
                 if let Some(edges) = adj.get(&current) {
                     for edge in edges {
-                        // Check timestamp if as_of is specified
-                        if let Some(as_of) = as_of {
-                            if edge.timestamp > as_of {
-                                continue;
-                            }
+                        // Skip edges not yet created or already deleted at as_of
+                        if !edge.is_visible_at(as_of) {
+                            continue;
                         }
 
                         // Check label filter
@@ -330,6 +953,17 @@ impl GraphDB {
         Ok(result)
     }
 
+    /// Per-band compression statistics of the underlying CAS
+    /// Merkle DAG: enishi_graph -> enishi_cas -> compress
+    pub async fn storage_stats(&self) -> HashMap<PackBand, fcdb_cas::BandStats> {
+        self.cas.read().await.compression_stats()
+    }
+
+    /// Number of live nodes
+    pub async fn node_count(&self) -> usize {
+        self.rid_to_cid.read().await.len()
+    }
+
     /// List all RIDs currently present in the graph
     /// Merkle DAG: enishi_graph -> rid_to_cid (exposed read-only view)
     pub async fn list_rids(&self) -> Vec<Rid> {
@@ -339,11 +973,34 @@ impl GraphDB {
         rids
     }
 
-    /// Get outgoing edges from a node (read-only clone)
+    /// Get live outgoing edges from a node (read-only clone)
     /// Merkle DAG: enishi_graph -> adjacency (exposed read-only view)
     pub async fn get_edges_from(&self, from: Rid) -> Vec<AdjEntry> {
+        self.get_edges_from_at(from, None).await
+    }
+
+    /// Get outgoing edges from a node as they existed at `as_of`
+    pub async fn get_edges_from_at(&self, from: Rid, as_of: Option<Timestamp>) -> Vec<AdjEntry> {
+        let as_of = Some(self.read_timestamp(as_of));
         let adj = self.adjacency.read().await;
-        adj.get(&from).cloned().unwrap_or_default()
+        adj.get(&from)
+            .map(|edges| edges.iter().filter(|e| e.is_visible_at(as_of)).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Get live incoming edges of a node; each entry's `target` is the
+    /// edge's source
+    pub async fn get_edges_to(&self, to: Rid) -> Vec<AdjEntry> {
+        self.get_edges_to_at(to, None).await
+    }
+
+    /// Get incoming edges of a node as they existed at `as_of`
+    pub async fn get_edges_to_at(&self, to: Rid, as_of: Option<Timestamp>) -> Vec<AdjEntry> {
+        let as_of = Some(self.read_timestamp(as_of));
+        let rev_adj = self.reverse_adjacency.read().await;
+        rev_adj.get(&to)
+            .map(|edges| edges.iter().filter(|e| e.is_visible_at(as_of)).cloned().collect())
+            .unwrap_or_default()
     }
 
     /// Search nodes by text content
@@ -351,9 +1008,11 @@ impl GraphDB {
         let postings = self.postings.read().await;
         let mut results = HashMap::new();
 
-        // Simple term-based search (no ranking yet)
+        // Simple term-based search (no ranking yet); deleted nodes keep their
+        // postings for history but are not returned
+        let rid_to_cid = self.rid_to_cid.read().await;
         if let Some(posts) = postings.get(query) {
-            for post in posts {
+            for post in posts.iter().filter(|post| rid_to_cid.contains_key(&post.rid)) {
                 *results.entry(post.rid).or_insert(0.0) += 1.0; // Simple TF scoring
             }
         }
@@ -368,6 +1027,7 @@ impl GraphDB {
     async fn index_text(&self, rid: Rid, text: &str, timestamp: Timestamp) {
         let words: Vec<&str> = text.split_whitespace().collect();
         let mut postings = self.postings.write().await;
+        let mut dirty = self.dirty();
 
         for (pos, word) in words.iter().enumerate() {
             let posting = Posting {
@@ -377,6 +1037,7 @@ impl GraphDB {
                 timestamp,
             };
 
+            dirty.mark_term(&posting.term);
             postings.entry(word.to_lowercase())
                 .or_insert_with(Vec::new)
                 .push(posting);
@@ -384,9 +1045,20 @@ impl GraphDB {
     }
 }
 
+/// Mark edge `id` in `owner`'s list of one adjacency map deleted
+fn tombstone_edge(map: &mut HashMap<Rid, Vec<AdjEntry>>, owner: Rid, id: EdgeId, ts: Timestamp) {
+    for entry in map.get_mut(&owner).into_iter().flatten() {
+        if entry.id == id && entry.deleted_at.is_none() {
+            entry.deleted_at = Some(ts);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::BTreeSet;
+    use std::ops::Bound;
     use tempfile::tempdir;
 
     #[tokio::test]
@@ -436,4 +1108,995 @@ mod tests {
         // Test timestamp was updated
         assert_eq!(*graph.current_timestamp.read().await, future_ts);
     }
+
+    #[tokio::test]
+    async fn test_graph_reopen_restores_state() {
+        let temp_dir = tempdir().unwrap();
+
+        let (alice, bob, carol) = {
+            let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+            let alice = graph.create_node(b"Alice").await.unwrap();
+            let bob = graph.create_node(b"Bob").await.unwrap();
+            let carol = graph.create_node(b"Carol").await.unwrap();
+
+            graph.create_edge(alice, bob, LabelId(1), b"knows").await.unwrap();
+            graph.create_edge(bob, carol, LabelId(1), b"knows").await.unwrap();
+
+            graph.set_timestamp(Timestamp(u64::MAX - 1)).await;
+            graph.update_node(carol, b"Carol v2").await.unwrap();
+            (alice, bob, carol)
+        };
+
+        let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+
+        assert_eq!(graph.list_rids().await, vec![alice, bob, carol]);
+        assert_eq!(graph.get_node(alice).await.unwrap().unwrap(), b"Alice");
+        assert_eq!(graph.get_node(carol).await.unwrap().unwrap(), b"Carol v2");
+
+        let mut reached: Vec<Rid> = graph
+            .traverse(alice, Some(&[LabelId(1)]), 2, None)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(rid, _)| rid)
+            .collect();
+        reached.sort();
+        assert_eq!(reached, vec![alice, bob, carol]);
+
+        // Carol is only reachable through Bob, who is out of scope
+        let scoped = graph.traverse_within(alice, None, 2, None, alice..bob).await.unwrap();
+        assert_eq!(scoped, vec![(alice, 0)]);
+        let scoped = graph.traverse_within(alice, None, 2, None, alice..Rid(carol.0 + 1)).await.unwrap();
+        assert_eq!(scoped.len(), 3);
+        assert!(graph.traverse_within(alice, None, 2, None, bob..Rid(carol.0 + 1)).await.unwrap().is_empty());
+
+        assert_eq!(graph.reverse_adjacency.read().await.get(&carol).unwrap()[0].target, bob);
+        assert_eq!(graph.temporal_rid_mappings.read().await.get(&carol).unwrap().len(), 2);
+        assert!(!graph.search("alice").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_graph_recovers_from_torn_wal() {
+        let temp_dir = tempdir().unwrap();
+        let wal_path = temp_dir.path().join(fcdb_cas::wal::WAL_FILE);
+
+        // WAL length after each acknowledged mutation
+        let mut wal_ends = Vec::new();
+        {
+            let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+            let a = graph.create_node(b"first").await.unwrap();
+            wal_ends.push(std::fs::metadata(&wal_path).unwrap().len());
+            let b = graph.create_node(b"second").await.unwrap();
+            wal_ends.push(std::fs::metadata(&wal_path).unwrap().len());
+            graph.create_edge(a, b, LabelId(7), b"link").await.unwrap();
+            wal_ends.push(std::fs::metadata(&wal_path).unwrap().len());
+        }
+
+        for len in 16..=wal_ends[2] {
+            let crashed = tempdir().unwrap();
+            for entry in std::fs::read_dir(temp_dir.path()).unwrap() {
+                let entry = entry.unwrap();
+                std::fs::copy(entry.path(), crashed.path().join(entry.file_name())).unwrap();
+            }
+            std::fs::OpenOptions::new()
+                .write(true)
+                .open(crashed.path().join(fcdb_cas::wal::WAL_FILE))
+                .unwrap()
+                .set_len(len)
+                .unwrap();
+
+            let graph = GraphDB::open(crashed.path()).await.unwrap();
+            let expected_nodes = wal_ends[..2].iter().filter(|end| len >= **end).count();
+            assert_eq!(graph.list_rids().await.len(), expected_nodes, "WAL cut at {}", len);
+            assert_eq!(!graph.get_edges_from(Rid(1)).await.is_empty(), len >= wal_ends[2], "WAL cut at {}", len);
+            if expected_nodes > 0 {
+                assert_eq!(graph.get_node(Rid(1)).await.unwrap().unwrap(), b"first");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_malformed_record_is_not_logged() {
+        let temp_dir = tempdir().unwrap();
+
+        let node = {
+            let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+            let node = graph.create_node(b"intact").await.unwrap();
+
+            // The record magic without a body
+            assert!(graph.create_node(b"FCPG").await.is_err());
+            assert!(graph.update_node(node, b"FCPG").await.is_err());
+            let mut tx = graph.begin();
+            tx.create_node(b"FCPG").await.unwrap();
+            assert!(tx.commit().await.is_err());
+
+            assert_eq!(graph.list_rids().await, vec![node]);
+            node
+        };
+
+        // Nothing was logged, so replay succeeds
+        let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+        assert_eq!(graph.list_rids().await, vec![node]);
+        assert_eq!(graph.get_node(node).await.unwrap().unwrap(), b"intact");
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_truncates_wal() {
+        let temp_dir = tempdir().unwrap();
+        let wal_path = temp_dir.path().join(fcdb_cas::wal::WAL_FILE);
+
+        let node = {
+            let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+            let node = graph.create_node(b"durable").await.unwrap();
+            graph.checkpoint().await.unwrap();
+            assert_eq!(std::fs::metadata(&wal_path).unwrap().len(), 16);
+
+            graph.update_node(node, b"durable v2").await.unwrap();
+            node
+        };
+
+        // Only the update is replayed on top of the checkpointed root
+        let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+        assert_eq!(graph.list_rids().await, vec![node]);
+        assert_eq!(graph.get_node(node).await.unwrap().unwrap(), b"durable v2");
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_writes_only_changed_shards() {
+        let temp_dir = tempdir().unwrap();
+        let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+
+        // Nodes in two RID shards, linked by an edge
+        let mut rids = Vec::new();
+        for i in 0..=persist::RID_SHARD_SIZE {
+            rids.push(graph.create_node(format!("n{}", i).as_bytes()).await.unwrap());
+        }
+        let (near, far) = (rids[0], rids[rids.len() - 1]);
+        assert_ne!(persist::rid_shard(near), persist::rid_shard(far));
+        graph.create_edge(near, far, LabelId(1), b"").await.unwrap();
+        let before = graph.checkpoint().await.unwrap();
+
+        // Nothing changed: the same root
+        assert_eq!(graph.checkpoint().await.unwrap(), before);
+
+        graph.update_node(far, b"far node moved").await.unwrap();
+        let after = graph.checkpoint().await.unwrap();
+
+        let cas = graph.cas.read().await;
+        let before = persist::shard_tables(&cas, &before).await.unwrap();
+        let after = persist::shard_tables(&cas, &after).await.unwrap();
+        let changed: BTreeSet<(Sharded, u64)> = before.iter().chain(&after)
+            .flat_map(|(&map, table)| table.keys().map(move |&shard| (map, shard)))
+            .filter(|(map, shard)| before[map].get(shard) != after[map].get(shard))
+            .collect();
+
+        // Only the far node's shards and those of the words it now holds
+        let mut expected = BTreeSet::from([
+            (Sharded::RidToCid, persist::rid_shard(far)),
+            (Sharded::Temporal, persist::rid_shard(far)),
+        ]);
+        expected.extend(["far", "node", "moved"].map(|term| (Sharded::Postings, persist::term_shard(term))));
+        assert_eq!(changed, expected);
+    }
+
+    #[tokio::test]
+    async fn test_gc_reclaims_old_versions() {
+        let temp_dir = tempdir().unwrap();
+
+        let (node, other) = {
+            let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+            graph.set_timestamp(Timestamp(100)).await;
+            let node = graph.create_node(b"version one").await.unwrap();
+            let other = graph.create_node(b"other node").await.unwrap();
+            graph.create_edge(node, other, LabelId(1), b"edge props").await.unwrap();
+            for (i, ts) in [200, 300, 400].into_iter().enumerate() {
+                graph.set_timestamp(Timestamp(ts)).await;
+                graph.update_node(node, format!("version {}", i + 2).as_bytes()).await.unwrap();
+            }
+
+            let report = graph.gc(GcOptions {
+                retention: RetentionPolicy::LatestVersions(1),
+                live_ratio_threshold: 1.0,
+            }).await.unwrap();
+            assert!(report.objects_removed >= 3);
+            assert!(report.bytes_reclaimed > 0);
+
+            assert_eq!(graph.get_node(node).await.unwrap().unwrap(), b"version 4");
+            assert_eq!(graph.get_node_at(node, Timestamp(250)).await.unwrap(), None);
+            (node, other)
+        };
+
+        let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+        assert_eq!(graph.get_node(node).await.unwrap().unwrap(), b"version 4");
+        assert_eq!(graph.get_node(other).await.unwrap().unwrap(), b"other node");
+        assert_eq!(graph.get_edges_from(node).await.len(), 1);
+        let props = graph.get_edges_from(node).await[0].properties;
+        assert_eq!(graph.cas.read().await.get(&props).await.unwrap(), b"edge props");
+    }
+
+    #[tokio::test]
+    async fn test_delete_node_and_edge_visibility() {
+        let temp_dir = tempdir().unwrap();
+
+        let (a, b, c) = {
+            let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+            graph.set_timestamp(Timestamp(100)).await;
+            let a = graph.create_node(b"node a").await.unwrap();
+            let b = graph.create_node(b"node b").await.unwrap();
+            let c = graph.create_node(b"node c").await.unwrap();
+            graph.create_edge(a, b, LabelId(1), b"").await.unwrap();
+            graph.create_edge(a, c, LabelId(2), b"").await.unwrap();
+
+            graph.set_timestamp(Timestamp(200)).await;
+            assert!(graph.delete_edge(a, c, LabelId(2)).await.unwrap());
+            assert!(!graph.delete_edge(a, c, LabelId(2)).await.unwrap());
+
+            graph.set_timestamp(Timestamp(300)).await;
+            assert!(graph.delete_node(b).await.unwrap());
+            assert!(!graph.delete_node(b).await.unwrap());
+            assert!(graph.update_node(b, b"revived").await.is_err());
+
+            // RIDs of deleted nodes are not reused
+            let d = graph.create_node(b"node d").await.unwrap();
+            assert_ne!(d, b);
+            graph.delete_node(d).await.unwrap();
+            (a, b, c)
+        };
+
+        // Tombstones survive WAL replay and checkpoints alike
+        let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+        assert_eq!(graph.list_rids().await, vec![a, c]);
+        assert_eq!(graph.get_node(b).await.unwrap(), None);
+        assert_eq!(graph.get_node_at(b, Timestamp(250)).await.unwrap().unwrap(), b"node b");
+        assert_eq!(graph.get_node_at(b, Timestamp(300)).await.unwrap(), None);
+
+        assert!(graph.get_edges_from(a).await.is_empty());
+        assert_eq!(graph.get_edges_from_at(a, Some(Timestamp(150))).await.len(), 2);
+        assert_eq!(graph.get_edges_from_at(a, Some(Timestamp(250))).await.len(), 1);
+
+        assert_eq!(graph.traverse(a, None, 2, None).await.unwrap(), vec![(a, 0)]);
+        assert_eq!(graph.traverse(a, None, 2, Some(Timestamp(250))).await.unwrap().len(), 2);
+        assert!(graph.traverse(b, None, 2, None).await.unwrap().is_empty());
+        assert!(graph.search("b").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_property_model() {
+        let temp_dir = tempdir().unwrap();
+
+        let (alice, bob) = {
+            let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+            graph.set_timestamp(Timestamp(100)).await;
+
+            let mut properties = Properties::new();
+            properties.insert("name".to_string(), "Alice".into());
+            let alice = graph.create_node_with_properties(["Person"], properties).await.unwrap();
+            let bob = graph.create_node(br#"{"name": "Bob"}"#).await.unwrap();
+
+            graph.set_timestamp(Timestamp(200)).await;
+            graph.set_property(alice, "age", PropertyValue::Int(30)).await.unwrap();
+            graph.add_label(alice, "Employee").await.unwrap();
+            graph.remove_label(alice, "Person").await.unwrap();
+            graph.add_label(bob, "Person").await.unwrap();
+
+            let mut since = Properties::new();
+            since.insert("since".to_string(), PropertyValue::Int(2020));
+            graph.create_edge_with_properties(alice, bob, LabelId(1), since).await.unwrap();
+            (alice, bob)
+        };
+
+        let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+        let record = graph.get_record(alice).await.unwrap().unwrap();
+        assert_eq!(record.labels.into_iter().collect::<Vec<_>>(), vec!["Employee"]);
+        assert_eq!(record.properties["name"], PropertyValue::from("Alice"));
+        assert_eq!(record.properties["age"], PropertyValue::Int(30));
+
+        // Earlier versions keep their own properties
+        let before = graph.get_record_at(alice, Timestamp(150)).await.unwrap().unwrap();
+        assert!(!before.properties.contains_key("age"));
+
+        // Legacy JSON nodes are upgraded on first modification
+        assert_eq!(graph.get_labels(bob).await.unwrap().unwrap(), vec!["Person"]);
+        assert_eq!(graph.get_properties(bob).await.unwrap().unwrap()["name"], PropertyValue::from("Bob"));
+
+        let edge = &graph.get_edges_from(alice).await[0];
+        assert_eq!(graph.get_edge_properties(edge).await.unwrap()["since"], PropertyValue::Int(2020));
+
+        assert_eq!(graph.search("alice").await.unwrap()[0].0, alice);
+        assert!(graph.set_property(Rid(99), "x", true.into()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_label_dictionary_persists() {
+        let temp_dir = tempdir().unwrap();
+
+        let knows = {
+            let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+            let a = graph.create_node(b"a").await.unwrap();
+            let b = graph.create_node(b"b").await.unwrap();
+            // An unnamed raw label already in use
+            graph.create_edge(a, b, LabelId(5), b"").await.unwrap();
+
+            let knows = graph.label_id("KNOWS").await.unwrap();
+            assert_eq!(knows, LabelId(6));
+            assert_eq!(graph.label_id("KNOWS").await.unwrap(), knows);
+            assert_eq!(graph.label_id("42").await.unwrap(), LabelId(42));
+            assert_eq!(graph.lookup_label("LIKES").await, None);
+            graph.property_key_id("name").await.unwrap();
+            assert_eq!(graph.dictionary_version().await, 2);
+            knows
+        };
+
+        // Recovered from the WAL
+        let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+        assert_eq!(graph.lookup_label("KNOWS").await, Some(knows));
+        assert_eq!(graph.label_name(knows).await.as_deref(), Some("KNOWS"));
+        assert_eq!(graph.label_display(LabelId(5)).await, "5");
+        assert_eq!(graph.property_key_name(PropertyKeyId(1)).await.as_deref(), Some("name"));
+        graph.checkpoint().await.unwrap();
+        drop(graph);
+
+        // And from the checkpointed root
+        let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+        assert_eq!(graph.label_display(knows).await, "KNOWS");
+        assert_eq!(graph.label_id("LIKES").await.unwrap(), LabelId(7));
+        assert_eq!(graph.dictionary_version().await, 3);
+
+        // Transactions intern new names only when they commit
+        let a = graph.list_rids().await[0];
+        let mut tx = graph.begin();
+        let follows = tx.label_id("FOLLOWS").await.unwrap();
+        assert_eq!(tx.lookup_label("FOLLOWS").await, Some(follows));
+        assert_eq!(tx.label_display(follows).await, "FOLLOWS");
+        tx.create_edge(a, a, follows, b"").await.unwrap();
+        tx.rollback();
+        assert_eq!(graph.lookup_label("FOLLOWS").await, None);
+        assert_eq!(graph.dictionary_version().await, 3);
+
+        // Reserved ids are real ones, distinct from raw ids and other names
+        let mut tx = graph.begin();
+        let follows = tx.label_id("FOLLOWS").await.unwrap();
+        let blocks = tx.label_id("BLOCKS").await.unwrap();
+        tx.label_id("UNUSED").await.unwrap();
+        let raw = tx.label_id(&u32::MAX.to_string()).await.unwrap();
+        assert_eq!(raw, LabelId(u32::MAX));
+        assert_eq!(tx.label_display(raw).await, u32::MAX.to_string());
+        tx.create_edge(a, a, follows, b"").await.unwrap();
+        tx.create_edge(a, a, blocks, b"").await.unwrap();
+        tx.create_edge(a, a, raw, b"").await.unwrap();
+        // Interned meanwhile by another writer, whose id the commit adopts
+        let blocked = graph.label_id("BLOCKS").await.unwrap();
+        assert!(![follows, blocks].contains(&blocked));
+        tx.commit().await.unwrap();
+        assert_eq!(graph.lookup_label("FOLLOWS").await, Some(follows));
+        assert_eq!(graph.lookup_label("UNUSED").await, None);
+        let labels: Vec<LabelId> = graph.get_edges_from(a).await.iter().map(|edge| edge.label).collect();
+        assert_eq!(labels, [LabelId(5), follows, blocked, raw]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_rid_allocation() {
+        let temp_dir = tempdir().unwrap();
+        let graph = Arc::new(GraphDB::open(temp_dir.path()).await.unwrap());
+
+        // Direct writes and transactions draw from the same allocator
+        let mut tasks = Vec::new();
+        for task in 0..8 {
+            let graph = graph.clone();
+            tasks.push(tokio::spawn(async move {
+                let mut rids = Vec::new();
+                for i in 0..25 {
+                    let data = format!("node {} {}", task, i);
+                    if task % 2 == 0 {
+                        rids.push(graph.create_node(data.as_bytes()).await.unwrap());
+                    } else {
+                        let mut tx = graph.begin();
+                        rids.push(tx.create_node(data.as_bytes()).await.unwrap());
+                        tx.commit().await.unwrap();
+                    }
+                }
+                rids
+            }));
+        }
+        let mut rids = HashSet::new();
+        for task in tasks {
+            rids.extend(task.await.unwrap());
+        }
+        assert_eq!(rids.len(), 200);
+        assert_eq!(graph.next_rid().await, Rid(201));
+        for rid in rids {
+            assert!(graph.get_node(rid).await.unwrap().is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reserved_rid_blocks() {
+        let temp_dir = tempdir().unwrap();
+
+        let block = {
+            let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+            graph.create_node(b"first").await.unwrap();
+            let block = graph.reserve_rids(10).await.unwrap();
+            assert_eq!(block.start, Rid(2));
+            assert_eq!(block.end, Rid(12));
+
+            graph.create_node_with_rid(block.start, b"bulk").await.unwrap();
+            assert!(graph.create_node_with_rid(block.start, b"again").await.is_err());
+            assert!(graph.create_node_with_rid(block.end, b"unreserved").await.is_err());
+            assert!(graph.create_node_with_rid(Rid(1), b"allocated").await.is_err());
+
+            // Allocation skips the block
+            assert_eq!(graph.create_node(b"after").await.unwrap(), block.end);
+            block
+        };
+
+        // Reserved blocks survive a restart
+        let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+        graph.create_node_with_rid(Rid(block.start.0 + 1), b"bulk after restart").await.unwrap();
+        assert!(graph.create_node_with_rid(block.start, b"again").await.is_err());
+        let rid = graph.create_node(b"after restart").await.unwrap();
+        assert!(!block.contains(rid));
+    }
+
+    #[tokio::test]
+    async fn test_external_keys() {
+        let temp_dir = tempdir().unwrap();
+
+        let keyed = {
+            let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+            let keyed = graph.create_node_with_key("user:42", b"keyed").await.unwrap();
+            assert!(graph.create_node_with_key("user:42", b"dup").await.is_err());
+            assert_eq!(graph.rid_for_key("user:42").await, Some(keyed));
+            assert_eq!(graph.key_for_rid(keyed).await.as_deref(), Some("user:42"));
+            assert_eq!(graph.rid_for_key("user:43").await, None);
+            keyed
+        };
+
+        let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+        assert_eq!(graph.rid_for_key("user:42").await, Some(keyed));
+        assert_eq!(graph.key_for_rid(keyed).await.as_deref(), Some("user:42"));
+
+        // Deleting the node releases its key, which binds to a fresh RID
+        graph.delete_node(keyed).await.unwrap();
+        assert_eq!(graph.rid_for_key("user:42").await, None);
+        assert_eq!(graph.key_for_rid(keyed).await, None);
+        let rebound = graph.create_node_with_key("user:42", b"rebound").await.unwrap();
+        assert_ne!(rebound, keyed);
+        assert_eq!(graph.rid_for_key("user:42").await, Some(rebound));
+    }
+
+    #[tokio::test]
+    async fn test_rid_allocation_survives_restart() {
+        let temp_dir = tempdir().unwrap();
+
+        let rids = {
+            let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+            let mut rids = Vec::new();
+            for i in 0..3 {
+                rids.push(graph.create_node(format!("node {}", i).as_bytes()).await.unwrap());
+            }
+            // Deleted RIDs stay retired
+            graph.delete_node(rids[2]).await.unwrap();
+            rids
+        };
+
+        for reopen in 0..2 {
+            let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+            assert_eq!(graph.next_rid().await, Rid(4 + reopen));
+            let rid = graph.create_node(b"after restart").await.unwrap();
+            assert!(!rids.contains(&rid));
+            assert_eq!(graph.get_node(rids[2]).await.unwrap(), None);
+            if reopen == 0 {
+                graph.checkpoint().await.unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transaction_rids_are_not_reusable() {
+        let temp_dir = tempdir().unwrap();
+        let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+
+        // A RID issued to an open transaction is taken by nothing else
+        let mut tx = graph.begin();
+        let pending = tx.create_node(b"pending").await.unwrap();
+        assert!(graph.create_node_with_rid(pending, b"stolen").await.is_err());
+        assert_ne!(graph.create_node(b"direct").await.unwrap(), pending);
+        assert!(!graph.reserve_rids(10).await.unwrap().contains(pending));
+        let mut other = graph.begin();
+        assert_ne!(other.create_node(b"other").await.unwrap(), pending);
+        other.commit().await.unwrap();
+
+        tx.commit().await.unwrap();
+        assert_eq!(graph.get_node(pending).await.unwrap().as_deref(), Some(&b"pending"[..]));
+    }
+
+    #[tokio::test]
+    async fn test_secondary_indexes() {
+        let temp_dir = tempdir().unwrap();
+        let person = |name: &str, age: i64| {
+            let mut properties = Properties::new();
+            properties.insert("name".to_string(), name.into());
+            properties.insert("age".to_string(), PropertyValue::Int(age));
+            properties
+        };
+
+        let (alice, bob) = {
+            let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+            graph.set_timestamp(Timestamp(100)).await;
+            let alice = graph.create_node_with_properties(["Person"], person("Alice", 30)).await.unwrap();
+
+            // Existing nodes are indexed when the index is created
+            graph.create_index(IndexDefinition::new("person_name", Some("Person"), ["name"], IndexKind::Hash)).await.unwrap();
+            graph.create_index(IndexDefinition::new("person_age", Some("Person"), ["age"], IndexKind::BTree)).await.unwrap();
+            assert!(graph.create_index(IndexDefinition::new("person_age", None, ["age"], IndexKind::Hash)).await.is_err());
+
+            let bob = graph.create_node_with_properties(["Person"], person("Bob", 25)).await.unwrap();
+            graph.create_node_with_properties(["Robot"], person("Alice", 3)).await.unwrap();
+
+            graph.set_timestamp(Timestamp(200)).await;
+            graph.set_property(alice, "age", PropertyValue::Int(31)).await.unwrap();
+            graph.delete_node(bob).await.unwrap();
+            (alice, bob)
+        };
+
+        for reopen in 0..2 {
+            let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+            let alice_name = PropertyValue::from("Alice");
+            assert_eq!(graph.index_lookup(Some("Person"), &[("name", &alice_name)], None).await, Some(vec![alice]));
+            assert_eq!(graph.index_lookup(Some("Person"), &[("email", &alice_name)], None).await, None);
+
+            let thirty = PropertyValue::Int(30);
+            let over_30 = graph.index_range(Some("Person"), "age", Bound::Excluded(&thirty), Bound::Unbounded, None).await;
+            assert_eq!(over_30, Some(vec![alice]));
+            let old = graph.index_range(Some("Person"), "age", Bound::Unbounded, Bound::Included(&thirty), Some(Timestamp(150))).await;
+            assert_eq!(old, Some(vec![alice, bob]));
+            assert_eq!(graph.index_range(Some("Person"), "name", Bound::Unbounded, Bound::Unbounded, None).await, None);
+
+            if reopen == 0 {
+                graph.checkpoint().await.unwrap();
+            } else {
+                assert!(graph.drop_index("person_age").await.unwrap());
+                assert_eq!(graph.list_indexes().await.len(), 1);
+            }
+        }
+
+        // Transactions read the index at their snapshot, over their own writes
+        let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+        let mut tx = graph.begin();
+        graph.create_node_with_properties(["Person"], person("Alice", 40)).await.unwrap();
+        let carol = tx.create_node_with_properties(["Person"], person("Alice", 20)).await.unwrap();
+        tx.create_node_with_properties(["Robot"], person("Alice", 20)).await.unwrap();
+        let alice_name = PropertyValue::from("Alice");
+        assert_eq!(tx.index_lookup(Some("Person"), &[("name", &alice_name)]).await, Some(vec![alice, carol]));
+        tx.set_property(alice, "name", "Alicia".into()).await.unwrap();
+        assert_eq!(tx.index_lookup(Some("Person"), &[("name", &alice_name)]).await, Some(vec![carol]));
+        assert_eq!(tx.index_lookup(Some("Person"), &[("email", &alice_name)]).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_constraints() {
+        let temp_dir = tempdir().unwrap();
+        let user = |email: &str| {
+            let mut properties = Properties::new();
+            properties.insert("email".to_string(), email.into());
+            properties
+        };
+        let violation = |err: Box<dyn std::error::Error>| err.downcast_ref::<ConstraintViolation>().cloned();
+
+        let alice = {
+            let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+            let alice = graph.create_node_with_properties(["User"], user("a@example.com")).await.unwrap();
+            let dup = graph.create_node_with_properties(["User"], user("a@example.com")).await.unwrap();
+
+            // Existing duplicates block the constraint until resolved
+            let unique = ConstraintDefinition::node("user_email", "User", ["email"], ConstraintKind::Unique);
+            let err = graph.create_constraint(unique.clone()).await.unwrap_err();
+            assert!(matches!(violation(err), Some(ConstraintViolation::Duplicate { .. })));
+            graph.delete_node(dup).await.unwrap();
+            graph.create_constraint(unique).await.unwrap();
+            graph.create_constraint(ConstraintDefinition::edge("knows_since", "KNOWS", ["since"], ConstraintKind::Exists)).await.unwrap();
+            alice
+        };
+
+        let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+        assert_eq!(graph.list_constraints().await.len(), 2);
+
+        let err = graph.create_node_with_properties(["User"], user("a@example.com")).await.unwrap_err();
+        match violation(err) {
+            Some(ConstraintViolation::Duplicate { constraint, existing, .. }) => {
+                assert_eq!(constraint, "user_email");
+                assert_eq!(existing, alice);
+            }
+            other => panic!("expected duplicate, got {:?}", other),
+        }
+        // Other labels and rewriting the same node are fine
+        graph.create_node_with_properties(["Admin"], user("a@example.com")).await.unwrap();
+        graph.set_property(alice, "name", "Alice".into()).await.unwrap();
+
+        graph.create_constraint(ConstraintDefinition::node("user_email_type", "User", ["email"], ConstraintKind::Type(PropertyType::String))).await.unwrap();
+        graph.create_constraint(ConstraintDefinition::node("user_key", "User", ["email"], ConstraintKind::NodeKey)).await.unwrap();
+        let err = graph.set_property(alice, "email", PropertyValue::Int(5)).await.unwrap_err();
+        assert!(matches!(violation(err), Some(ConstraintViolation::WrongType { .. })));
+        let err = graph.remove_property(alice, "email").await.unwrap_err();
+        assert!(matches!(violation(err), Some(ConstraintViolation::Missing { .. })));
+
+        let bob = graph.create_node_with_properties(["User"], user("b@example.com")).await.unwrap();
+        let knows = graph.label_id("KNOWS").await.unwrap();
+        let err = graph.create_edge_with_properties(alice, bob, knows, Properties::new()).await.unwrap_err();
+        assert!(matches!(violation(err), Some(ConstraintViolation::Missing { .. })));
+        let mut since = Properties::new();
+        since.insert("since".to_string(), PropertyValue::Int(2020));
+        graph.create_edge_with_properties(alice, bob, knows, since).await.unwrap();
+
+        assert!(graph.drop_constraint("user_email").await.unwrap());
+        assert!(graph.drop_constraint("user_key").await.unwrap());
+        assert!(graph.list_indexes().await.is_empty());
+        graph.create_node_with_properties(["User"], user("a@example.com")).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_transaction_commit_and_rollback() {
+        let temp_dir = tempdir().unwrap();
+        let person = |name: &str| {
+            let mut properties = Properties::new();
+            properties.insert("name".to_string(), name.into());
+            properties
+        };
+
+        let (alice, bob, ts) = {
+            let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+            let knows = graph.label_id("KNOWS").await.unwrap();
+
+            let mut tx = graph.begin();
+            let alice = tx.create_node_with_properties(["Person"], person("Alice")).await.unwrap();
+            let bob = tx.create_node_with_properties(["Person"], person("Bob")).await.unwrap();
+            tx.create_edge_with_properties(alice, bob, knows, Properties::new()).await.unwrap();
+            tx.set_property(bob, "age", PropertyValue::Int(30)).await.unwrap();
+
+            // Visible inside the transaction only
+            assert_eq!(tx.get_record(bob).await.unwrap().unwrap().properties.get("age"), Some(&PropertyValue::Int(30)));
+            assert_eq!(tx.get_edges_from(alice).await.len(), 1);
+            assert!(!graph.node_exists(alice, None).await);
+            assert!(graph.get_edges_from(alice).await.is_empty());
+
+            let ts = tx.commit().await.unwrap();
+            (alice, bob, ts)
+        };
+
+        let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+        assert_eq!(graph.get_record_at(alice, ts).await.unwrap().unwrap().properties.get("name"), Some(&"Alice".into()));
+        assert_eq!(graph.get_record(bob).await.unwrap().unwrap().properties.get("age"), Some(&PropertyValue::Int(30)));
+        let edges = graph.get_edges_from_at(alice, Some(ts)).await;
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0].timestamp, ts);
+
+        // Rolled back writes leave nothing behind
+        let mut tx = graph.begin();
+        let carol = tx.create_node_with_properties(["Person"], person("Carol")).await.unwrap();
+        assert!(tx.delete_node(alice).await.unwrap());
+        assert!(tx.get_edges_from(alice).await.is_empty());
+        tx.rollback();
+        assert!(!graph.node_exists(carol, None).await);
+        assert!(graph.node_exists(alice, None).await);
+        assert_eq!(graph.get_edges_from(alice).await.len(), 1);
+
+        // Deleting in a transaction also removes edges it created
+        graph.set_timestamp(Timestamp(ts.0 + 1)).await;
+        let knows = graph.lookup_label("KNOWS").await.unwrap();
+        let mut tx = graph.begin();
+        let dave = tx.create_node(b"dave").await.unwrap();
+        tx.create_edge(bob, dave, knows, b"").await.unwrap();
+        assert!(tx.delete_node(bob).await.unwrap());
+        assert!(tx.create_edge(bob, alice, knows, b"").await.is_err());
+        tx.commit().await.unwrap();
+        assert!(!graph.node_exists(bob, None).await);
+        assert!(graph.node_exists(dave, None).await);
+        assert!(graph.get_edges_from(alice).await.is_empty());
+        assert!(graph.get_edges_from(bob).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_transaction_scope() {
+        let temp_dir = tempdir().unwrap();
+        let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+        let knows = graph.label_id("KNOWS").await.unwrap();
+        let inside = graph.create_node(b"inside").await.unwrap();
+        let outside = graph.create_node(b"outside").await.unwrap();
+        graph.create_edge(inside, outside, knows, b"").await.unwrap();
+
+        let mut tx = graph.begin();
+        tx.restrict(inside..outside);
+        assert!(tx.node_exists(inside).await && !tx.node_exists(outside).await);
+        assert_eq!(tx.get_node(outside).await.unwrap(), None);
+        assert!(tx.get_edges_from(inside).await.is_empty());
+
+        for err in [
+            tx.update_node(outside, b"changed").await.unwrap_err(),
+            tx.delete_node(outside).await.unwrap_err(),
+            tx.create_edge(outside, inside, knows, b"").await.unwrap_err(),
+            tx.create_node(b"new").await.unwrap_err(),
+        ] {
+            assert!(err.downcast_ref::<ScopeViolation>().is_some());
+        }
+        tx.update_node(inside, b"changed").await.unwrap();
+        tx.commit().await.unwrap();
+        assert_eq!(graph.get_node(outside).await.unwrap().unwrap(), b"outside");
+    }
+
+    #[tokio::test]
+    async fn test_transaction_is_all_or_nothing() {
+        let temp_dir = tempdir().unwrap();
+        let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+        graph.create_constraint(ConstraintDefinition::node("user_email", "User", ["email"], ConstraintKind::Unique)).await.unwrap();
+        let user = |email: &str| {
+            let mut properties = Properties::new();
+            properties.insert("email".to_string(), email.into());
+            properties
+        };
+        let existing = graph.create_node_with_properties(["User"], user("a@example.com")).await.unwrap();
+
+        // A duplicate within the batch fails the whole commit
+        let mut tx = graph.begin();
+        let first = tx.create_node_with_properties(["User"], user("b@example.com")).await.unwrap();
+        tx.create_node_with_properties(["User"], user("b@example.com")).await.unwrap();
+        let err = tx.commit().await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<ConstraintViolation>(), Some(ConstraintViolation::Duplicate { .. })));
+        assert!(!graph.node_exists(first, None).await);
+
+        // Values freed earlier in the same batch can be reused
+        let mut tx = graph.begin();
+        tx.set_property(existing, "email", "c@example.com".into()).await.unwrap();
+        let reuse = tx.create_node_with_properties(["User"], user("a@example.com")).await.unwrap();
+        tx.commit().await.unwrap();
+        assert_eq!(graph.index_lookup(Some("User"), &[("email", &"a@example.com".into())], None).await, Some(vec![reuse]));
+
+        // Writes to a node deleted by another writer are rejected
+        let mut tx = graph.begin();
+        tx.set_property(reuse, "name", "Reuse".into()).await.unwrap();
+        let orphan = tx.create_node(b"orphan").await.unwrap();
+        graph.delete_node(reuse).await.unwrap();
+        assert!(tx.commit().await.is_err());
+        assert!(!graph.node_exists(orphan, None).await);
+
+        // Empty transactions commit trivially
+        assert!(graph.begin().commit().await.is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_snapshot_reads_under_concurrent_transfers() {
+        let temp_dir = tempdir().unwrap();
+        let graph = Arc::new(GraphDB::open(temp_dir.path()).await.unwrap());
+        let account = |balance: i64| {
+            let mut properties = Properties::new();
+            properties.insert("balance".to_string(), PropertyValue::Int(balance));
+            properties
+        };
+        let a = graph.create_node_with_properties(["Account"], account(100)).await.unwrap();
+        let b = graph.create_node_with_properties(["Account"], account(100)).await.unwrap();
+
+        fn balance(record: Option<PropertyRecord>) -> i64 {
+            match record.unwrap().properties["balance"] {
+                PropertyValue::Int(balance) => balance,
+                ref other => panic!("unexpected balance {:?}", other),
+            }
+        }
+
+        // Writers move 1 from a to b, retrying when they lose a conflict
+        let mut writers = Vec::new();
+        for _ in 0..4 {
+            let graph = graph.clone();
+            writers.push(tokio::spawn(async move {
+                let mut conflicts = 0;
+                for _ in 0..25 {
+                    loop {
+                        let mut tx = graph.begin();
+                        let from = balance(tx.get_record(a).await.unwrap());
+                        let to = balance(tx.get_record(b).await.unwrap());
+                        tx.set_property(a, "balance", PropertyValue::Int(from - 1)).await.unwrap();
+                        tx.set_property(b, "balance", PropertyValue::Int(to + 1)).await.unwrap();
+                        match tx.commit().await {
+                            Ok(_) => break,
+                            Err(err) => {
+                                assert!(err.downcast_ref::<TransactionConflict>().is_some(), "{}", err);
+                                conflicts += 1;
+                            }
+                        }
+                    }
+                }
+                conflicts
+            }));
+        }
+
+        // Readers always see the total preserved, and a snapshot never changes
+        let mut readers = Vec::new();
+        for _ in 0..4 {
+            let graph = graph.clone();
+            readers.push(tokio::spawn(async move {
+                for _ in 0..50 {
+                    let snapshot = graph.snapshot();
+                    let first = balance(snapshot.get_record(a).await.unwrap());
+                    tokio::task::yield_now().await;
+                    let second = balance(snapshot.get_record(b).await.unwrap());
+                    assert_eq!(first + second, 200);
+                    assert_eq!(balance(snapshot.get_record(a).await.unwrap()), first);
+                }
+            }));
+        }
+
+        for reader in readers {
+            reader.await.unwrap();
+        }
+        for writer in writers {
+            writer.await.unwrap();
+        }
+        assert_eq!(balance(graph.get_record(a).await.unwrap()), 0);
+        assert_eq!(balance(graph.get_record(b).await.unwrap()), 200);
+        assert_eq!(graph.oldest_snapshot(), None);
+    }
+
+    #[tokio::test]
+    async fn test_transaction_isolation_levels() {
+        let temp_dir = tempdir().unwrap();
+        let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+        let on_call = |value: bool| {
+            let mut properties = Properties::new();
+            properties.insert("on_call".to_string(), PropertyValue::Bool(value));
+            properties
+        };
+        let alice = graph.create_node_with_properties(["Doctor"], on_call(true)).await.unwrap();
+        let bob = graph.create_node_with_properties(["Doctor"], on_call(true)).await.unwrap();
+
+        // First committer wins on the same node
+        let mut first = graph.begin();
+        let mut second = graph.begin();
+        first.set_property(alice, "shift", "day".into()).await.unwrap();
+        second.set_property(alice, "shift", "night".into()).await.unwrap();
+        first.commit().await.unwrap();
+        let err = second.commit().await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<TransactionConflict>(), Some(TransactionConflict::Write { rid, .. }) if *rid == alice));
+        assert_eq!(graph.get_record(alice).await.unwrap().unwrap().properties["shift"], PropertyValue::from("day"));
+
+        // Each doctor goes off call if the other is still on call
+        async fn go_off_call(tx: &mut Transaction<'_>, me: Rid, other: Rid) {
+            let other_on_call = tx.get_record(other).await.unwrap().unwrap().properties["on_call"] == PropertyValue::Bool(true);
+            let me_on_call = tx.get_record(me).await.unwrap().unwrap().properties["on_call"] == PropertyValue::Bool(true);
+            if other_on_call && me_on_call {
+                tx.set_property(me, "on_call", PropertyValue::Bool(false)).await.unwrap();
+            }
+        }
+
+        // Snapshot isolation admits the write skew...
+        let mut tx1 = graph.begin();
+        let mut tx2 = graph.begin();
+        go_off_call(&mut tx1, alice, bob).await;
+        go_off_call(&mut tx2, bob, alice).await;
+        tx1.commit().await.unwrap();
+        tx2.commit().await.unwrap();
+        assert!(graph.get_record(alice).await.unwrap().unwrap().properties["on_call"] == PropertyValue::Bool(false));
+        assert!(graph.get_record(bob).await.unwrap().unwrap().properties["on_call"] == PropertyValue::Bool(false));
+
+        // ...serializable transactions do not
+        graph.set_property(alice, "on_call", PropertyValue::Bool(true)).await.unwrap();
+        graph.set_property(bob, "on_call", PropertyValue::Bool(true)).await.unwrap();
+        let mut tx1 = graph.begin_with(IsolationLevel::Serializable);
+        let mut tx2 = graph.begin_with(IsolationLevel::Serializable);
+        go_off_call(&mut tx1, alice, bob).await;
+        go_off_call(&mut tx2, bob, alice).await;
+        tx1.commit().await.unwrap();
+        let err = tx2.commit().await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<TransactionConflict>(), Some(TransactionConflict::Read { rid, .. }) if *rid == alice));
+        assert!(graph.get_record(bob).await.unwrap().unwrap().properties["on_call"] == PropertyValue::Bool(true));
+
+        // Snapshots ignore later commits and keep their versions through GC
+        let snapshot = graph.snapshot();
+        let carol = graph.create_node(b"carol").await.unwrap();
+        graph.update_node(alice, b"alice v2").await.unwrap();
+        assert!(!snapshot.node_exists(carol).await);
+        assert_eq!(snapshot.list_rids().await, vec![alice, bob]);
+        graph.gc(GcOptions { retention: RetentionPolicy::LatestVersions(1), ..Default::default() }).await.unwrap();
+        assert!(snapshot.get_record(alice).await.unwrap().unwrap().properties.contains_key("on_call"));
+
+        let pinned_at = snapshot.timestamp();
+        drop(snapshot);
+        graph.gc(GcOptions { retention: RetentionPolicy::LatestVersions(1), ..Default::default() }).await.unwrap();
+        assert_eq!(graph.get_node_at(alice, pinned_at).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_transaction_incoming_edges() {
+        let temp_dir = tempdir().unwrap();
+        let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+        let knows = graph.label_id("KNOWS").await.unwrap();
+        let alice = graph.create_node(b"alice").await.unwrap();
+        let bob = graph.create_node(b"bob").await.unwrap();
+        let edge = graph.create_edge(alice, bob, knows, b"").await.unwrap();
+        assert_eq!(graph.get_edges_to(bob).await.iter().map(|e| e.target).collect::<Vec<_>>(), vec![alice]);
+
+        // Pending writes are layered over the snapshot's incoming edges
+        let mut tx = graph.begin_with(IsolationLevel::Serializable);
+        let carol = tx.create_node(b"carol").await.unwrap();
+        tx.create_edge(carol, bob, knows, b"").await.unwrap();
+        tx.delete_edge(alice, edge).await.unwrap();
+        assert_eq!(tx.get_edges_to(bob).await.iter().map(|e| e.target).collect::<Vec<_>>(), vec![carol]);
+        assert_eq!(tx.list_rids().await, vec![alice, bob, carol]);
+        tx.delete_node(alice).await.unwrap();
+        assert_eq!(tx.list_rids().await, vec![bob, carol]);
+
+        // A serializable reader of bob's incoming edges conflicts with a new one
+        let dave = graph.create_node(b"dave").await.unwrap();
+        graph.create_edge(dave, bob, knows, b"").await.unwrap();
+        let err = tx.commit().await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<TransactionConflict>(), Some(TransactionConflict::Read { rid, .. }) if *rid == bob));
+    }
+
+    #[tokio::test]
+    async fn test_delete_one_parallel_edge() {
+        let temp_dir = tempdir().unwrap();
+        let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+        let knows = graph.label_id("KNOWS").await.unwrap();
+        let alice = graph.create_node(b"alice").await.unwrap();
+        let bob = graph.create_node(b"bob").await.unwrap();
+        let first = graph.create_edge(alice, bob, knows, b"same").await.unwrap();
+        let second = graph.create_edge(alice, bob, knows, b"same").await.unwrap();
+        assert_ne!(first, second);
+        let created_at = graph.get_edges_from(alice).await[1].timestamp;
+        let before = graph.committed_timestamp();
+
+        // Only the named edge goes; its sibling keeps its history
+        let mut tx = graph.begin();
+        assert!(tx.delete_edge(alice, first).await.unwrap());
+        assert!(!tx.delete_edge(alice, first).await.unwrap());
+        assert_eq!(tx.get_edges_to(bob).await.iter().map(|e| e.id).collect::<Vec<_>>(), vec![second]);
+        tx.commit().await.unwrap();
+        let live = graph.get_edges_from(alice).await;
+        assert_eq!(live.iter().map(|e| (e.id, e.timestamp)).collect::<Vec<_>>(), vec![(second, created_at)]);
+        assert_eq!(graph.get_edges_from_at(alice, Some(before)).await.len(), 2);
+
+        // An update keeps the id and leaves the old data to earlier readers
+        let mut tx = graph.begin();
+        assert!(tx.update_edge(alice, second, b"changed").await.unwrap());
+        assert!(!tx.update_edge(alice, first, b"changed").await.unwrap());
+        tx.commit().await.unwrap();
+        let live = graph.get_edges_from(alice).await;
+        assert_eq!(live.iter().map(|e| (e.id, e.properties)).collect::<Vec<_>>(), vec![(second, Cid::hash(b"changed"))]);
+        let old = graph.get_edges_from_at(alice, Some(before)).await;
+        assert!(old.iter().all(|e| e.properties == Cid::hash(b"same")));
+
+        // Ids stay unique across a restart
+        drop(graph);
+        let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+        let third = graph.create_edge(alice, bob, knows, b"same").await.unwrap();
+        assert!(third > second);
+        assert!(graph.delete_edge(alice, bob, knows).await.unwrap());
+        assert!(graph.get_edges_from(alice).await.is_empty());
+        assert_eq!(graph.get_edges_from_at(alice, Some(before)).await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_edges_need_live_endpoints() {
+        let temp_dir = tempdir().unwrap();
+        let graph = GraphDB::open(temp_dir.path()).await.unwrap();
+        let knows = graph.label_id("KNOWS").await.unwrap();
+        let alice = graph.create_node(b"alice").await.unwrap();
+        let gone = graph.create_node(b"gone").await.unwrap();
+        graph.delete_node(gone).await.unwrap();
+        let never = graph.next_rid().await;
+
+        for rid in [gone, never] {
+            assert!(graph.create_edge(alice, rid, knows, b"").await.is_err());
+            assert!(graph.create_edge(rid, alice, knows, b"").await.is_err());
+            let mut tx = graph.begin();
+            assert!(tx.create_edge(alice, rid, knows, b"").await.is_err());
+            assert!(tx.create_edge(rid, alice, knows, b"").await.is_err());
+        }
+        assert!(graph.get_edges_from(alice).await.is_empty());
+
+        // Nodes created earlier in the same transaction are valid endpoints
+        let mut tx = graph.begin();
+        let bob = tx.create_node(b"bob").await.unwrap();
+        tx.create_edge(alice, bob, knows, b"").await.unwrap();
+        tx.commit().await.unwrap();
+        assert_eq!(graph.get_edges_from(alice).await.len(), 1);
+    }
 }
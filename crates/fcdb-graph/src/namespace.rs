@@ -0,0 +1,415 @@
+//! Named graphs sharing one PackCAS.
+//!
+//! Merkle DAG: enishi_graph -> namespace -> {catalog, shared_wal, create, drop, gc}
+//!
+//! A [`Catalog`] hosts several [`GraphDB`]s, each with its own RID space,
+//! dictionary, indexes and root pointer, over a single PackCAS so identical
+//! content is stored once. Their mutations share the WAL, tagged with the
+//! namespace id, and a namespace's checkpoint only resets the WAL once no
+//! other namespace has records newer than its own root. Unreferenced objects
+//! are reclaimed by [`Catalog::gc`], which keeps everything any namespace
+//! still needs.
+//!
+//! The default namespace keeps its root pointer in the CAS directory, where a
+//! standalone [`GraphDB::open`] keeps it, so an existing store opens as a
+//! catalog holding just the default namespace. The others are listed in
+//! `namespaces.json` and keep their root pointer in `namespaces/<id>/`.
+//!
+//! A namespace may be given a storage quota, also kept in the manifest: its
+//! graph's writes are refused once the bytes it references would exceed it
+//! (see [`fcdb_cas::quota`]).
+
+use crate::gc::{self, GcOptions};
+use crate::{persist, GraphDB};
+use fcdb_cas::{CasConfig, GcReport, PackCAS};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::RwLock;
+use tracing::info;
+
+/// Name of the namespace that always exists
+pub const DEFAULT_NAMESPACE: &str = "default";
+
+const MANIFEST_FILE: &str = "namespaces.json";
+const NAMESPACE_DIR: &str = "namespaces";
+
+/// Why a namespace could not be created, found or dropped
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum NamespaceError {
+    #[error("namespace {0:?} already exists")]
+    AlreadyExists(String),
+    #[error("namespace {0:?} does not exist")]
+    NotFound(String),
+    #[error("invalid namespace name {0:?}: use 1-64 letters, digits, '-' or '_'")]
+    InvalidName(String),
+    #[error("the default namespace cannot be dropped")]
+    DropDefault,
+}
+
+/// Which namespaces sharing a WAL have records their root does not cover yet
+#[derive(Default)]
+pub(crate) struct SharedWal {
+    state: std::sync::Mutex<WalState>,
+}
+
+#[derive(Default)]
+struct WalState {
+    dirty: HashSet<u32>,
+    // Dropped namespaces; in-flight writes to them no longer pin the WAL
+    retired: HashSet<u32>,
+}
+
+impl SharedWal {
+    /// `namespace` logged a record; called with the PackCAS write lock held
+    pub(crate) fn mark_dirty(&self, namespace: u32) {
+        let mut state = self.state.lock().unwrap();
+        if !state.retired.contains(&namespace) {
+            state.dirty.insert(namespace);
+        }
+    }
+
+    /// `namespace` checkpointed everything it logged; returns whether no
+    /// namespace needs the WAL any more, so it may be reset
+    pub(crate) fn mark_clean(&self, namespace: u32) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.dirty.remove(&namespace);
+        state.dirty.is_empty()
+    }
+
+    fn retire(&self, namespace: u32) {
+        let mut state = self.state.lock().unwrap();
+        state.dirty.remove(&namespace);
+        state.retired.insert(namespace);
+    }
+}
+
+/// A graph hosted by a [`Catalog`]
+#[derive(Clone)]
+pub struct Namespace {
+    pub name: String,
+    /// Never reused, so a dropped and recreated namespace gets a fresh id
+    pub id: u32,
+    pub graph: Arc<RwLock<GraphDB>>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Manifest {
+    namespaces: BTreeMap<String, u32>,
+    next_id: u32,
+    /// Storage quotas in bytes, by namespace name
+    #[serde(default)]
+    quotas: BTreeMap<String, u64>,
+}
+
+struct CatalogState {
+    namespaces: BTreeMap<String, Namespace>,
+    next_id: u32,
+    quotas: BTreeMap<String, u64>,
+}
+
+/// Named, isolated graphs over one shared PackCAS
+pub struct Catalog {
+    cas: Arc<RwLock<PackCAS>>,
+    shared_wal: Arc<SharedWal>,
+    base: PathBuf,
+    state: RwLock<CatalogState>,
+}
+
+impl Catalog {
+    /// Open every namespace stored at `path`, replaying each one's WAL records
+    pub async fn open<P: AsRef<Path>>(path: P, config: CasConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut cas = PackCAS::open_with_config(path, config).await?;
+        let base = cas.path().to_path_buf();
+        let manifest = read_manifest(&base)?;
+        let shared_wal = Arc::new(SharedWal::default());
+
+        // Every namespace with records to replay pins the WAL before any of
+        // them checkpoints, so one recovery cannot discard another's records
+        let mut pending = Vec::new();
+        for (name, id) in std::iter::once((DEFAULT_NAMESPACE.to_string(), 0)).chain(manifest.namespaces) {
+            cas.set_namespace_quota(id, manifest.quotas.get(&name).copied());
+            let ops = cas.take_recovered_ops_in(id);
+            if !ops.is_empty() {
+                shared_wal.mark_dirty(id);
+            }
+            pending.push((name, id, ops));
+        }
+
+        let cas = Arc::new(RwLock::new(cas));
+        let mut namespaces = BTreeMap::new();
+        for (name, id, ops) in pending {
+            let root_dir = namespace_dir(&base, id);
+            let state = match persist::read_root_pointer(&root_dir)? {
+                Some(root_cid) => persist::read_state(&*cas.read().await, &root_cid).await?,
+                None => persist::GraphState::default(),
+            };
+            let graph = GraphDB::from_parts(cas.clone(), root_dir, state, id, Some(shared_wal.clone()));
+            graph.recover(ops).await?;
+            // Whatever it logged is now covered by its root
+            shared_wal.mark_clean(id);
+            namespaces.insert(name.clone(), Namespace { name, id, graph: Arc::new(RwLock::new(graph)) });
+        }
+
+        info!("Opened catalog with {} namespaces", namespaces.len());
+        Ok(Self {
+            cas,
+            shared_wal,
+            base,
+            state: RwLock::new(CatalogState { namespaces, next_id: manifest.next_id.max(1), quotas: manifest.quotas }),
+        })
+    }
+
+    pub async fn get(&self, name: &str) -> Option<Namespace> {
+        self.state.read().await.namespaces.get(name).cloned()
+    }
+
+    pub async fn default_namespace(&self) -> Namespace {
+        self.get(DEFAULT_NAMESPACE).await.expect("the default namespace always exists")
+    }
+
+    /// Every namespace, by name
+    pub async fn list(&self) -> Vec<Namespace> {
+        self.state.read().await.namespaces.values().cloned().collect()
+    }
+
+    /// Create an empty namespace
+    pub async fn create(&self, name: &str) -> Result<Namespace, Box<dyn std::error::Error>> {
+        let valid = (1..=64).contains(&name.len())
+            && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+        if !valid {
+            return Err(NamespaceError::InvalidName(name.to_string()).into());
+        }
+
+        let mut state = self.state.write().await;
+        if state.namespaces.contains_key(name) {
+            return Err(NamespaceError::AlreadyExists(name.to_string()).into());
+        }
+        let id = state.next_id;
+        let root_dir = namespace_dir(&self.base, id);
+        std::fs::create_dir_all(&root_dir)?;
+        let graph = GraphDB::from_parts(self.cas.clone(), root_dir, persist::GraphState::default(), id, Some(self.shared_wal.clone()));
+
+        let namespace = Namespace { name: name.to_string(), id, graph: Arc::new(RwLock::new(graph)) };
+        state.next_id += 1;
+        state.namespaces.insert(name.to_string(), namespace.clone());
+        write_manifest(&self.base, &state)?;
+        info!("Created namespace {:?} (id {})", name, id);
+        Ok(namespace)
+    }
+
+    /// Drop a namespace and its root; its objects are reclaimed by the next
+    /// [`Catalog::gc`]
+    pub async fn drop_namespace(&self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if name == DEFAULT_NAMESPACE {
+            return Err(NamespaceError::DropDefault.into());
+        }
+        let mut state = self.state.write().await;
+        let namespace = state.namespaces.remove(name)
+            .ok_or_else(|| NamespaceError::NotFound(name.to_string()))?;
+        state.quotas.remove(name);
+        write_manifest(&self.base, &state)?;
+        self.shared_wal.retire(namespace.id);
+        {
+            let mut cas = self.cas.write().await;
+            cas.set_namespace_quota(namespace.id, None);
+            cas.set_namespace_usage(namespace.id, None);
+        }
+
+        match std::fs::remove_dir_all(namespace_dir(&self.base, namespace.id)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        info!("Dropped namespace {:?} (id {})", name, namespace.id);
+        Ok(())
+    }
+
+    /// Limit the bytes `name` may reference; `None` lifts the limit
+    pub async fn set_quota(&self, name: &str, bytes: Option<u64>) -> Result<(), Box<dyn std::error::Error>> {
+        let mut state = self.state.write().await;
+        let id = state.namespaces.get(name)
+            .ok_or_else(|| NamespaceError::NotFound(name.to_string()))?
+            .id;
+        match bytes {
+            Some(bytes) => state.quotas.insert(name.to_string(), bytes),
+            None => state.quotas.remove(name),
+        };
+        write_manifest(&self.base, &state)?;
+        self.cas.write().await.set_namespace_quota(id, bytes);
+        Ok(())
+    }
+
+    /// Bytes charged to `namespace` and its quota, if it has one
+    pub async fn storage_usage(&self, namespace: &Namespace) -> (u64, Option<u64>) {
+        let cas = self.cas.read().await;
+        (cas.namespace_usage(namespace.id), cas.namespace_quota(namespace.id))
+    }
+
+    /// Checkpoint every namespace, which lets the shared WAL be reset
+    pub async fn checkpoint(&self) -> Result<(), Box<dyn std::error::Error>> {
+        for namespace in self.list().await {
+            namespace.graph.read().await.checkpoint().await?;
+        }
+        Ok(())
+    }
+
+    /// [`GraphDB::gc`] across every namespace: mutations in all of them are
+    /// blocked while objects no namespace reaches are swept
+    pub async fn gc(&self, options: GcOptions) -> Result<GcReport, Box<dyn std::error::Error>> {
+        let state = self.state.read().await;
+        let mut graphs = Vec::new();
+        for namespace in state.namespaces.values() {
+            graphs.push(namespace.graph.read().await);
+        }
+        let mut guards = Vec::new();
+        for graph in &graphs {
+            guards.push(graph.write_lock.lock().await);
+        }
+
+        let mut live = HashSet::new();
+        for graph in &graphs {
+            let reachable = graph.prune_and_mark(&options).await?;
+            graph.recount_usage(&reachable).await?;
+            live.extend(reachable);
+        }
+        let report = gc::sweep(&self.cas, &live, &options).await?;
+        info!("Catalog GC kept {} live objects: {:?}", live.len(), report);
+        Ok(report)
+    }
+}
+
+/// Directory holding a namespace's root pointer
+fn namespace_dir(base: &Path, id: u32) -> PathBuf {
+    match id {
+        0 => base.to_path_buf(),
+        id => base.join(NAMESPACE_DIR).join(id.to_string()),
+    }
+}
+
+fn read_manifest(base: &Path) -> io::Result<Manifest> {
+    match std::fs::read(base.join(MANIFEST_FILE)) {
+        Ok(bytes) => serde_json::from_slice(&bytes).map_err(io::Error::from),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Manifest::default()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Atomically replace the manifest with the catalog's current namespaces
+fn write_manifest(base: &Path, state: &CatalogState) -> io::Result<()> {
+    let manifest = Manifest {
+        namespaces: state.namespaces.values()
+            .filter(|namespace| namespace.id != 0)
+            .map(|namespace| (namespace.name.clone(), namespace.id))
+            .collect(),
+        next_id: state.next_id,
+        quotas: state.quotas.clone(),
+    };
+    let tmp_path = base.join(format!("{}.tmp", MANIFEST_FILE));
+    std::fs::write(&tmp_path, serde_json::to_vec(&manifest).map_err(io::Error::from)?)?;
+    std::fs::File::open(&tmp_path)?.sync_all()?;
+    std::fs::rename(tmp_path, base.join(MANIFEST_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rid;
+
+    fn error<T>(result: Result<T, Box<dyn std::error::Error>>) -> NamespaceError {
+        result.err().and_then(|e| e.downcast_ref::<NamespaceError>().cloned()).expect("a namespace error")
+    }
+
+    #[tokio::test]
+    async fn test_namespaces_are_isolated_and_recovered() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        {
+            let catalog = Catalog::open(temp_dir.path(), CasConfig::default()).await.unwrap();
+            let tenant = catalog.create("tenant-a").await.unwrap();
+            assert_eq!(error(catalog.create("tenant-a").await), NamespaceError::AlreadyExists("tenant-a".into()));
+            assert!(matches!(error(catalog.create("a/b").await), NamespaceError::InvalidName(_)));
+
+            let default = catalog.default_namespace().await;
+            let rid = tenant.graph.read().await.create_node(b"tenant").await.unwrap();
+            default.graph.read().await.create_node(b"default").await.unwrap();
+            default.graph.read().await.create_node(b"default 2").await.unwrap();
+
+            // Each namespace has its own RID space
+            assert_eq!(rid, Rid(1));
+            assert_eq!(tenant.graph.read().await.node_count().await, 1);
+            assert_eq!(default.graph.read().await.get_node(Rid(1)).await.unwrap().unwrap(), b"default");
+
+            // Checkpointing one namespace must not discard the other's WAL records
+            default.graph.read().await.checkpoint().await.unwrap();
+        }
+
+        let catalog = Catalog::open(temp_dir.path(), CasConfig::default()).await.unwrap();
+        assert_eq!(catalog.list().await.iter().map(|ns| ns.name.as_str()).collect::<Vec<_>>(), vec!["default", "tenant-a"]);
+        let tenant = catalog.get("tenant-a").await.unwrap();
+        assert_eq!(tenant.graph.read().await.get_node(Rid(1)).await.unwrap().unwrap(), b"tenant");
+        assert_eq!(catalog.default_namespace().await.graph.read().await.node_count().await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_drop_namespace_and_catalog_gc() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let catalog = Catalog::open(temp_dir.path(), CasConfig::default()).await.unwrap();
+        let first = catalog.create("scratch").await.unwrap();
+        first.graph.read().await.create_node(b"throwaway").await.unwrap();
+        first.graph.read().await.checkpoint().await.unwrap();
+        let default = catalog.default_namespace().await;
+        default.graph.read().await.create_node(b"keep").await.unwrap();
+        catalog.checkpoint().await.unwrap();
+
+        assert_eq!(error(catalog.drop_namespace(DEFAULT_NAMESPACE).await), NamespaceError::DropDefault);
+        catalog.drop_namespace("scratch").await.unwrap();
+        assert_eq!(error(catalog.drop_namespace("scratch").await), NamespaceError::NotFound("scratch".into()));
+        assert!(default.graph.read().await.gc(GcOptions::default()).await.is_err());
+
+        let options = GcOptions { live_ratio_threshold: 1.0, ..Default::default() };
+        let report = catalog.gc(options).await.unwrap();
+        assert!(report.objects_removed > 0);
+        assert_eq!(catalog.storage_usage(&default).await, (4, None));
+        assert_eq!(default.graph.read().await.get_node(Rid(1)).await.unwrap().unwrap(), b"keep");
+
+        // A recreated namespace starts empty under a fresh id
+        let second = catalog.create("scratch").await.unwrap();
+        assert!(second.id > first.id);
+        assert_eq!(second.graph.read().await.node_count().await, 0);
+        drop(catalog);
+
+        let catalog = Catalog::open(temp_dir.path(), CasConfig::default()).await.unwrap();
+        assert_eq!(catalog.get("scratch").await.unwrap().id, second.id);
+        assert_eq!(catalog.storage_usage(&catalog.get("scratch").await.unwrap()).await, (0, None));
+        assert_eq!(catalog.default_namespace().await.graph.read().await.get_node(Rid(1)).await.unwrap().unwrap(), b"keep");
+    }
+
+    #[tokio::test]
+    async fn test_namespace_quota() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        {
+            let catalog = Catalog::open(temp_dir.path(), CasConfig::default()).await.unwrap();
+            let tenant = catalog.create("tenant").await.unwrap();
+            catalog.set_quota("tenant", Some(10)).await.unwrap();
+            assert!(catalog.set_quota("missing", Some(10)).await.is_err());
+
+            let graph = tenant.graph.read().await;
+            graph.create_node(b"12345678").await.unwrap();
+            let err = graph.create_node(b"abc").await.unwrap_err();
+            assert_eq!(fcdb_cas::QuotaExceeded::find(err.as_ref()).unwrap().scope, fcdb_cas::QuotaScope::Namespace(tenant.id));
+            // The refused write left nothing behind
+            assert_eq!(graph.node_count().await, 1);
+            // Other namespaces are not limited by it
+            catalog.default_namespace().await.graph.read().await.create_node(b"abcdefghijk").await.unwrap();
+            catalog.checkpoint().await.unwrap();
+        }
+
+        let catalog = Catalog::open(temp_dir.path(), CasConfig::default()).await.unwrap();
+        let tenant = catalog.get("tenant").await.unwrap();
+        assert_eq!(catalog.storage_usage(&tenant).await, (8, Some(10)));
+        catalog.set_quota("tenant", None).await.unwrap();
+        tenant.graph.read().await.create_node(b"abc").await.unwrap();
+    }
+}
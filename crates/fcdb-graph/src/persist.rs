@@ -0,0 +1,540 @@
+//! Durable graph state: index objects stored in PackCAS plus a root pointer,
+//! and the WAL records for mutations made since the last checkpoint.
+//!
+//! Merkle DAG: enishi_graph -> persist -> graph_root -> {rid_to_cid, temporal, adjacency, reverse_adjacency, postings, dictionary, allocator, indexes, constraints}
+//!
+//! The node- and term-keyed maps are split into shards, each its own
+//! object, and the root points at a table of shard CIDs per map. Mutations
+//! mark the shards and whole objects they touch in [`Dirty`], so a
+//! checkpoint only stores what changed and reuses the CIDs of the rest.
+
+use crate::allocator::RidAllocator;
+use crate::dictionary::{Dictionary, NameKind};
+use crate::index::{IndexDefinition, Indexes};
+use crate::ConstraintDefinition;
+use crate::{AdjEntry, EdgeId, LabelId, Posting, Rid, Timestamp};
+use fcdb_cas::{PackBand, PackCAS};
+use fcdb_core::Cid;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::hash::Hash;
+use std::io;
+use std::path::Path;
+
+/// File (inside the CAS directory) holding the CID of the current graph root
+pub(crate) const ROOT_FILE: &str = "graph.root";
+
+/// CAS object kind for graph index objects
+pub(crate) const KIND_INDEX: u8 = 2;
+
+/// On-disk format version of [`GraphRoot`]
+const ROOT_VERSION: u32 = 1;
+
+/// Consecutive RIDs per shard of the node-keyed maps
+pub(crate) const RID_SHARD_SIZE: u64 = 1024;
+
+/// Map stored as shards
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum Sharded {
+    RidToCid,
+    Temporal,
+    Adjacency,
+    ReverseAdjacency,
+    Postings,
+}
+
+impl Sharded {
+    const ALL: [Sharded; 5] = [
+        Sharded::RidToCid,
+        Sharded::Temporal,
+        Sharded::Adjacency,
+        Sharded::ReverseAdjacency,
+        Sharded::Postings,
+    ];
+}
+
+/// Shard of the node-keyed maps holding `rid`
+pub(crate) fn rid_shard(rid: Rid) -> u64 {
+    rid.0 / RID_SHARD_SIZE
+}
+
+/// Shard of the postings holding `term`: the first byte of its hash
+pub(crate) fn term_shard(term: &str) -> u64 {
+    u64::from(Cid::hash(term.as_bytes()).as_bytes()[0])
+}
+
+/// Index object stored whole
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Whole {
+    Dictionary,
+    Allocator,
+    Indexes,
+    Constraints,
+}
+
+impl Whole {
+    const ALL: [Whole; 4] = [Whole::Dictionary, Whole::Allocator, Whole::Indexes, Whole::Constraints];
+}
+
+/// Shards and objects changed since the last checkpoint
+#[derive(Debug, Default)]
+pub(crate) struct Dirty {
+    shards: HashMap<Sharded, BTreeSet<u64>>,
+    objects: HashSet<Whole>,
+}
+
+impl Dirty {
+    /// The entry of a node-keyed map for `rid` changed
+    pub fn mark_rid(&mut self, map: Sharded, rid: Rid) {
+        self.shards.entry(map).or_default().insert(rid_shard(rid));
+    }
+
+    /// The postings of `term` changed
+    pub fn mark_term(&mut self, term: &str) {
+        self.shards.entry(Sharded::Postings).or_default().insert(term_shard(term));
+    }
+
+    /// A whole object changed
+    pub fn mark_object(&mut self, object: Whole) {
+        self.objects.insert(object);
+    }
+
+    /// Add back what a failed checkpoint did not write
+    pub fn merge(&mut self, other: Dirty) {
+        for (map, shards) in other.shards {
+            self.shards.entry(map).or_default().extend(shards);
+        }
+        self.objects.extend(other.objects);
+    }
+}
+
+/// CIDs of the shards, shard tables and whole objects the last checkpoint wrote
+#[derive(Debug, Default)]
+pub(crate) struct Checkpointed {
+    shards: HashMap<Sharded, BTreeMap<u64, Cid>>,
+    tables: HashMap<Sharded, Cid>,
+    objects: HashMap<Whole, Cid>,
+}
+
+/// Root object tying together the CIDs of every graph index object
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct GraphRoot {
+    pub version: u32,
+    /// Shard tables of the maps
+    pub rid_to_cid: Cid,
+    pub temporal: Cid,
+    pub adjacency: Cid,
+    pub reverse_adjacency: Cid,
+    pub postings: Cid,
+    /// Last WAL record reflected in this root; replay resumes after it
+    #[serde(default)]
+    pub wal_lsn: u64,
+    /// Label / property-key dictionary; absent in roots written before it existed
+    #[serde(default)]
+    pub dictionary: Option<Cid>,
+    /// RID allocator; absent roots resume above the highest known RID
+    #[serde(default)]
+    pub allocator: Option<Cid>,
+    #[serde(default)]
+    pub indexes: Option<Cid>,
+    #[serde(default)]
+    pub constraints: Option<Cid>,
+}
+
+impl GraphRoot {
+    fn table(&self, map: Sharded) -> &Cid {
+        match map {
+            Sharded::RidToCid => &self.rid_to_cid,
+            Sharded::Temporal => &self.temporal,
+            Sharded::Adjacency => &self.adjacency,
+            Sharded::ReverseAdjacency => &self.reverse_adjacency,
+            Sharded::Postings => &self.postings,
+        }
+    }
+
+    fn object(&self, object: Whole) -> Option<Cid> {
+        match object {
+            Whole::Dictionary => self.dictionary,
+            Whole::Allocator => self.allocator,
+            Whole::Indexes => self.indexes,
+            Whole::Constraints => self.constraints,
+        }
+    }
+}
+
+/// In-memory graph structures as loaded from CAS
+#[derive(Default)]
+pub(crate) struct GraphState {
+    pub rid_to_cid: HashMap<Rid, Cid>,
+    pub temporal: HashMap<Rid, BTreeMap<Timestamp, Cid>>,
+    pub adjacency: HashMap<Rid, Vec<AdjEntry>>,
+    pub reverse_adjacency: HashMap<Rid, Vec<AdjEntry>>,
+    pub postings: HashMap<String, Vec<Posting>>,
+    pub dictionary: Dictionary,
+    pub allocator: RidAllocator,
+    pub indexes: Indexes,
+    pub constraints: BTreeMap<String, ConstraintDefinition>,
+    pub wal_lsn: u64,
+    /// CIDs of the objects the state was loaded from
+    pub checkpointed: Checkpointed,
+}
+
+impl GraphState {
+    /// Latest timestamp at which anything in the state changed
+    pub fn last_commit(&self) -> Timestamp {
+        let nodes = self.temporal.values().filter_map(|timeline| timeline.keys().next_back().copied());
+        let edges = self.adjacency.values().flatten()
+            .flat_map(|entry| std::iter::once(entry.timestamp).chain(entry.deleted_at));
+        nodes.chain(edges).max().unwrap_or(Timestamp(0))
+    }
+
+    /// Edge id following the highest one in the state
+    pub fn next_edge_id(&self) -> EdgeId {
+        EdgeId(self.adjacency.values().flatten().map(|entry| entry.id.0 + 1).max().unwrap_or(0))
+    }
+}
+
+/// GraphDB mutation as recorded in the PackCAS WAL
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) enum GraphOp {
+    CreateNode {
+        rid: Rid,
+        cid: Cid,
+        ts: Timestamp,
+        /// External key bound to the node, if any
+        #[serde(default)]
+        key: Option<String>,
+    },
+    UpdateNode { rid: Rid, cid: Cid, ts: Timestamp },
+    CreateEdge { id: EdgeId, from: Rid, to: Rid, label: LabelId, properties: Cid, ts: Timestamp },
+    DeleteNode { rid: Rid, ts: Timestamp },
+    DeleteEdge { from: Rid, to: Rid, id: EdgeId, ts: Timestamp },
+    InternName { kind: NameKind, name: String, id: u32 },
+    /// Every RID below `end` has been handed out, those from `start` for bulk
+    /// loads; `start` is absent in records written before blocks were kept
+    ReserveRids {
+        #[serde(default)]
+        start: Option<u64>,
+        end: u64,
+    },
+    CreateIndex { definition: IndexDefinition },
+    DropIndex { name: String },
+    CreateConstraint { definition: ConstraintDefinition },
+    DropConstraint { name: String },
+    /// Writes of one transaction, applied together at a single timestamp
+    Batch { ops: Vec<GraphOp> },
+}
+
+impl GraphOp {
+    /// Commit timestamp of a node or edge mutation
+    pub fn timestamp(&self) -> Option<Timestamp> {
+        match *self {
+            GraphOp::CreateNode { ts, .. }
+            | GraphOp::UpdateNode { ts, .. }
+            | GraphOp::CreateEdge { ts, .. }
+            | GraphOp::DeleteNode { ts, .. }
+            | GraphOp::DeleteEdge { ts, .. } => Some(ts),
+            GraphOp::Batch { ref ops } => ops.iter().filter_map(GraphOp::timestamp).max(),
+            _ => None,
+        }
+    }
+
+    pub fn encode(&self) -> io::Result<Vec<u8>> {
+        serde_json::to_vec(self).map_err(io::Error::other)
+    }
+
+    pub fn decode(bytes: &[u8]) -> io::Result<Self> {
+        serde_json::from_slice(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// Borrowed graph structures to checkpoint
+pub(crate) struct StateView<'a> {
+    pub rid_to_cid: &'a HashMap<Rid, Cid>,
+    pub temporal: &'a HashMap<Rid, BTreeMap<Timestamp, Cid>>,
+    pub adjacency: &'a HashMap<Rid, Vec<AdjEntry>>,
+    pub reverse_adjacency: &'a HashMap<Rid, Vec<AdjEntry>>,
+    pub postings: &'a HashMap<String, Vec<Posting>>,
+    pub dictionary: &'a Dictionary,
+    pub allocator: &'a RidAllocator,
+    pub indexes: &'a Indexes,
+    pub constraints: &'a BTreeMap<String, ConstraintDefinition>,
+    pub wal_lsn: u64,
+}
+
+/// Changed shards and objects, encoded while the maps are locked and stored after
+pub(crate) struct Encoded {
+    /// `None` for a shard left without entries
+    shards: Vec<(Sharded, u64, Option<Vec<u8>>)>,
+    objects: Vec<(Whole, Vec<u8>)>,
+    wal_lsn: u64,
+}
+
+/// Encode one shard; entries are sorted so identical shards dedup to the same CID
+fn encode_shard<K, V>(entries: BTreeMap<&K, &V>) -> io::Result<Option<Vec<u8>>>
+where
+    K: Serialize + Ord,
+    V: Serialize,
+{
+    if entries.is_empty() {
+        return Ok(None);
+    }
+    let entries: Vec<(&K, &V)> = entries.into_iter().collect();
+    serde_json::to_vec(&entries).map(Some).map_err(io::Error::other)
+}
+
+async fn get_index<K, V>(cas: &PackCAS, cid: &Cid) -> io::Result<HashMap<K, V>>
+where
+    K: DeserializeOwned + Eq + Hash,
+    V: DeserializeOwned,
+{
+    let bytes = cas.get(cid).await?;
+    let entries: Vec<(K, V)> = serde_json::from_slice(&bytes)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(entries.into_iter().collect())
+}
+
+/// Store a single serializable index object
+async fn put_object<T: Serialize>(cas: &mut PackCAS, value: &T) -> io::Result<Cid> {
+    let bytes = serde_json::to_vec(value).map_err(io::Error::other)?;
+    cas.put(&bytes, KIND_INDEX, PackBand::Index).await
+}
+
+async fn get_object<T: DeserializeOwned>(cas: &PackCAS, cid: &Cid) -> io::Result<T> {
+    let bytes = cas.get(cid).await?;
+    serde_json::from_slice(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Encode the marked shards of a node-keyed map, looking each RID up
+fn encode_rid_shards<V: Serialize>(
+    encoded: &mut Vec<(Sharded, u64, Option<Vec<u8>>)>,
+    map: Sharded,
+    entries: &HashMap<Rid, V>,
+    shards: &BTreeSet<u64>,
+) -> io::Result<()> {
+    for &shard in shards {
+        let first = shard * RID_SHARD_SIZE;
+        let shard_entries = (first..=first + (RID_SHARD_SIZE - 1))
+            .filter_map(|rid| entries.get_key_value(&Rid(rid)))
+            .collect();
+        encoded.push((map, shard, encode_shard(shard_entries)?));
+    }
+    Ok(())
+}
+
+/// Encode the marked shards of the postings
+fn encode_term_shards(
+    encoded: &mut Vec<(Sharded, u64, Option<Vec<u8>>)>,
+    postings: &HashMap<String, Vec<Posting>>,
+    shards: &BTreeSet<u64>,
+) -> io::Result<()> {
+    let mut entries: BTreeMap<u64, BTreeMap<&String, &Vec<Posting>>> =
+        shards.iter().map(|&shard| (shard, BTreeMap::new())).collect();
+    for (term, list) in postings {
+        if let Some(shard) = entries.get_mut(&term_shard(term)) {
+            shard.insert(term, list);
+        }
+    }
+    for (shard, shard_entries) in entries {
+        encoded.push((Sharded::Postings, shard, encode_shard(shard_entries)?));
+    }
+    Ok(())
+}
+
+/// Encode the shards and objects marked in `dirty`
+pub(crate) fn encode_state(state: &StateView<'_>, dirty: &Dirty) -> io::Result<Encoded> {
+    let mut shards = Vec::new();
+    for (&map, marked) in &dirty.shards {
+        match map {
+            Sharded::RidToCid => encode_rid_shards(&mut shards, map, state.rid_to_cid, marked)?,
+            Sharded::Temporal => encode_rid_shards(&mut shards, map, state.temporal, marked)?,
+            Sharded::Adjacency => encode_rid_shards(&mut shards, map, state.adjacency, marked)?,
+            Sharded::ReverseAdjacency => encode_rid_shards(&mut shards, map, state.reverse_adjacency, marked)?,
+            Sharded::Postings => encode_term_shards(&mut shards, state.postings, marked)?,
+        }
+    }
+
+    let mut objects = Vec::new();
+    for &object in &dirty.objects {
+        let bytes = match object {
+            Whole::Dictionary => serde_json::to_vec(state.dictionary),
+            Whole::Allocator => serde_json::to_vec(state.allocator),
+            Whole::Indexes => serde_json::to_vec(state.indexes),
+            Whole::Constraints => serde_json::to_vec(state.constraints),
+        };
+        objects.push((object, bytes.map_err(io::Error::other)?));
+    }
+    Ok(Encoded { shards, objects, wal_lsn: state.wal_lsn })
+}
+
+/// Store the encoded shards and objects, the shard tables they changed and
+/// a new root object, and return the root's CID. `checkpointed` only ever points at
+/// stored objects, so a failed write can be retried from it.
+pub(crate) async fn write_state(cas: &mut PackCAS, encoded: Encoded, checkpointed: &mut Checkpointed) -> io::Result<Cid> {
+    let mut changed = BTreeSet::new();
+    for (map, shard, bytes) in encoded.shards {
+        let cid = match bytes {
+            Some(bytes) => Some(cas.put(&bytes, KIND_INDEX, PackBand::Index).await?),
+            None => None,
+        };
+        let table = checkpointed.shards.entry(map).or_default();
+        match cid {
+            Some(cid) => table.insert(shard, cid),
+            None => table.remove(&shard),
+        };
+        changed.insert(map);
+    }
+
+    for map in Sharded::ALL {
+        if changed.contains(&map) || !checkpointed.tables.contains_key(&map) {
+            let table = checkpointed.shards.entry(map).or_default();
+            let cid = put_object(cas, table).await?;
+            checkpointed.tables.insert(map, cid);
+        }
+    }
+
+    for (object, bytes) in encoded.objects {
+        let cid = cas.put(&bytes, KIND_INDEX, PackBand::Index).await?;
+        checkpointed.objects.insert(object, cid);
+    }
+
+    let table = |map| checkpointed.tables[&map];
+    let object = |object| checkpointed.objects.get(&object).copied();
+    let root = GraphRoot {
+        version: ROOT_VERSION,
+        rid_to_cid: table(Sharded::RidToCid),
+        temporal: table(Sharded::Temporal),
+        adjacency: table(Sharded::Adjacency),
+        reverse_adjacency: table(Sharded::ReverseAdjacency),
+        postings: table(Sharded::Postings),
+        wal_lsn: encoded.wal_lsn,
+        dictionary: object(Whole::Dictionary),
+        allocator: object(Whole::Allocator),
+        indexes: object(Whole::Indexes),
+        constraints: object(Whole::Constraints),
+    };
+    put_object(cas, &root).await
+}
+
+async fn read_root(cas: &PackCAS, root_cid: &Cid) -> io::Result<GraphRoot> {
+    let root: GraphRoot = get_object(cas, root_cid).await?;
+    if root.version != ROOT_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unsupported graph root version {}", root.version),
+        ));
+    }
+    Ok(root)
+}
+
+/// Shard tables and whole objects a root points at
+async fn read_tables(cas: &PackCAS, root: &GraphRoot) -> io::Result<Checkpointed> {
+    let mut checkpointed = Checkpointed::default();
+    for map in Sharded::ALL {
+        let cid = *root.table(map);
+        checkpointed.shards.insert(map, get_object(cas, &cid).await?);
+        checkpointed.tables.insert(map, cid);
+    }
+    for object in Whole::ALL {
+        if let Some(cid) = root.object(object) {
+            checkpointed.objects.insert(object, cid);
+        }
+    }
+    Ok(checkpointed)
+}
+
+/// Shard CIDs of every map, as of the root `root_cid`
+#[cfg(test)]
+pub(crate) async fn shard_tables(cas: &PackCAS, root_cid: &Cid) -> io::Result<HashMap<Sharded, BTreeMap<u64, Cid>>> {
+    let root = read_root(cas, root_cid).await?;
+    Ok(read_tables(cas, &root).await?.shards)
+}
+
+/// Load a map from its shards
+async fn get_map<K, V>(cas: &PackCAS, checkpointed: &Checkpointed, map: Sharded) -> io::Result<HashMap<K, V>>
+where
+    K: DeserializeOwned + Eq + Hash,
+    V: DeserializeOwned,
+{
+    let mut entries = HashMap::new();
+    for shard in checkpointed.shards[&map].values() {
+        entries.extend(get_index::<K, V>(cas, shard).await?);
+    }
+    Ok(entries)
+}
+
+/// Load every index object reachable from a root CID
+pub(crate) async fn read_state(cas: &PackCAS, root_cid: &Cid) -> io::Result<GraphState> {
+    let root = read_root(cas, root_cid).await?;
+    let checkpointed = read_tables(cas, &root).await?;
+
+    let temporal = get_map(cas, &checkpointed, Sharded::Temporal).await?;
+    let allocator = match checkpointed.objects.get(&Whole::Allocator) {
+        Some(cid) => get_object(cas, cid).await?,
+        None => RidAllocator::resume(&temporal),
+    };
+
+    Ok(GraphState {
+        rid_to_cid: get_map(cas, &checkpointed, Sharded::RidToCid).await?,
+        temporal,
+        adjacency: get_map(cas, &checkpointed, Sharded::Adjacency).await?,
+        reverse_adjacency: get_map(cas, &checkpointed, Sharded::ReverseAdjacency).await?,
+        postings: get_map(cas, &checkpointed, Sharded::Postings).await?,
+        dictionary: match checkpointed.objects.get(&Whole::Dictionary) {
+            Some(cid) => get_object(cas, cid).await?,
+            None => Dictionary::default(),
+        },
+        allocator,
+        indexes: match checkpointed.objects.get(&Whole::Indexes) {
+            Some(cid) => get_object(cas, cid).await?,
+            None => Indexes::default(),
+        },
+        constraints: match checkpointed.objects.get(&Whole::Constraints) {
+            Some(cid) => get_object(cas, cid).await?,
+            None => BTreeMap::new(),
+        },
+        wal_lsn: root.wal_lsn,
+        checkpointed,
+    })
+}
+
+/// CIDs of a root object and every index object it references
+pub(crate) async fn root_objects(cas: &PackCAS, root_cid: &Cid) -> io::Result<Vec<Cid>> {
+    let root = read_root(cas, root_cid).await?;
+    let checkpointed = read_tables(cas, &root).await?;
+
+    let mut objects = vec![*root_cid];
+    objects.extend(checkpointed.tables.into_values());
+    for shards in checkpointed.shards.into_values() {
+        objects.extend(shards.into_values());
+    }
+    objects.extend(checkpointed.objects.into_values());
+    Ok(objects)
+}
+
+/// Atomically replace the root pointer (write to a temp file, fsync, rename)
+pub(crate) fn write_root_pointer(dir: &Path, root_cid: &Cid) -> io::Result<()> {
+    use std::io::Write;
+
+    let tmp_path = dir.join(format!("{}.tmp", ROOT_FILE));
+    {
+        let mut file = std::fs::File::create(&tmp_path)?;
+        file.write_all(root_cid.as_bytes())?;
+        file.sync_all()?;
+    }
+    std::fs::rename(tmp_path, dir.join(ROOT_FILE))
+}
+
+/// Read the root pointer, if one has been written
+pub(crate) fn read_root_pointer(dir: &Path) -> io::Result<Option<Cid>> {
+    let bytes = match std::fs::read(dir.join(ROOT_FILE)) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    let raw: [u8; 32] = bytes.as_slice().try_into().map_err(|_| {
+        io::Error::new(io::ErrorKind::InvalidData, "graph root pointer is corrupt")
+    })?;
+    Ok(Some(Cid::from_bytes(raw)))
+}
@@ -0,0 +1,375 @@
+//! Property-graph data model: labels and typed properties for nodes and edges.
+//!
+//! Merkle DAG: enishi_graph -> properties -> {property_value, property_record, canonical_encoding}
+//!
+//! Records are stored in CAS in a canonical binary form (sorted keys and
+//! labels, fixed-width floats, zigzag varints), so equal records always hash
+//! to the same CID. Nodes written as plain JSON objects before this model
+//! existed decode to the equivalent record, giving every query layer one
+//! interpretation of node data.
+
+use fcdb_core::varint;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Read};
+
+/// Magic prefix of an encoded [`PropertyRecord`]
+const RECORD_MAGIC: &[u8; 4] = b"FCPG";
+const RECORD_VERSION: u8 = 1;
+
+const TAG_STRING: u8 = 1;
+const TAG_INT: u8 = 2;
+const TAG_FLOAT: u8 = 3;
+const TAG_BOOL: u8 = 4;
+const TAG_LIST: u8 = 5;
+const TAG_MAP: u8 = 6;
+const TAG_DATETIME: u8 = 7;
+const TAG_BYTES: u8 = 8;
+
+/// Property map of a node or edge, ordered by key
+pub type Properties = BTreeMap<String, PropertyValue>;
+
+/// Typed property value
+#[derive(Clone, Debug, PartialEq)]
+pub enum PropertyValue {
+    String(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    List(Vec<PropertyValue>),
+    Map(BTreeMap<String, PropertyValue>),
+    /// Microseconds since the Unix epoch (UTC)
+    DateTime(i64),
+    Bytes(Vec<u8>),
+}
+
+impl PropertyValue {
+    /// Convert from JSON; `null` has no property representation
+    pub fn from_json(value: &serde_json::Value) -> Option<Self> {
+        Some(match value {
+            serde_json::Value::Null => return None,
+            serde_json::Value::Bool(b) => PropertyValue::Bool(*b),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(i) => PropertyValue::Int(i),
+                None => PropertyValue::Float(n.as_f64()?),
+            },
+            serde_json::Value::String(s) => PropertyValue::String(s.clone()),
+            serde_json::Value::Array(items) => {
+                PropertyValue::List(items.iter().filter_map(PropertyValue::from_json).collect())
+            }
+            serde_json::Value::Object(map) => PropertyValue::Map(properties_from_json(map)),
+        })
+    }
+
+    /// Render as JSON (datetimes as RFC 3339 strings, bytes as number arrays)
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            PropertyValue::String(s) => serde_json::Value::String(s.clone()),
+            PropertyValue::Int(i) => serde_json::Value::from(*i),
+            PropertyValue::Float(f) => serde_json::Number::from_f64(*f)
+                .map_or(serde_json::Value::Null, serde_json::Value::Number),
+            PropertyValue::Bool(b) => serde_json::Value::Bool(*b),
+            PropertyValue::List(items) => serde_json::Value::Array(items.iter().map(Self::to_json).collect()),
+            PropertyValue::Map(map) => properties_to_json(map),
+            PropertyValue::DateTime(micros) => chrono::DateTime::from_timestamp_micros(*micros)
+                .map_or(serde_json::Value::from(*micros), |dt| {
+                    serde_json::Value::String(dt.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true))
+                }),
+            PropertyValue::Bytes(bytes) => serde_json::Value::from(bytes.clone()),
+        }
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            PropertyValue::String(s) => {
+                buf.push(TAG_STRING);
+                encode_bytes(s.as_bytes(), buf);
+            }
+            PropertyValue::Int(i) => {
+                buf.push(TAG_INT);
+                varint::encode_u64(zigzag(*i), buf);
+            }
+            PropertyValue::Float(f) => {
+                buf.push(TAG_FLOAT);
+                buf.extend_from_slice(&f.to_le_bytes());
+            }
+            PropertyValue::Bool(b) => {
+                buf.push(TAG_BOOL);
+                buf.push(*b as u8);
+            }
+            PropertyValue::List(items) => {
+                buf.push(TAG_LIST);
+                varint::encode_u64(items.len() as u64, buf);
+                for item in items {
+                    item.encode(buf);
+                }
+            }
+            PropertyValue::Map(map) => {
+                buf.push(TAG_MAP);
+                encode_map(map, buf);
+            }
+            PropertyValue::DateTime(micros) => {
+                buf.push(TAG_DATETIME);
+                varint::encode_u64(zigzag(*micros), buf);
+            }
+            PropertyValue::Bytes(bytes) => {
+                buf.push(TAG_BYTES);
+                encode_bytes(bytes, buf);
+            }
+        }
+    }
+
+    fn decode<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let mut tag = [0u8; 1];
+        reader.read_exact(&mut tag)?;
+        Ok(match tag[0] {
+            TAG_STRING => PropertyValue::String(decode_string(reader)?),
+            TAG_INT => PropertyValue::Int(unzigzag(varint::decode_u64(reader)?)),
+            TAG_FLOAT => {
+                let mut bytes = [0u8; 8];
+                reader.read_exact(&mut bytes)?;
+                PropertyValue::Float(f64::from_le_bytes(bytes))
+            }
+            TAG_BOOL => {
+                let mut byte = [0u8; 1];
+                reader.read_exact(&mut byte)?;
+                PropertyValue::Bool(byte[0] != 0)
+            }
+            TAG_LIST => {
+                let len = varint::decode_u64(reader)?;
+                let mut items = Vec::new();
+                for _ in 0..len {
+                    items.push(PropertyValue::decode(reader)?);
+                }
+                PropertyValue::List(items)
+            }
+            TAG_MAP => PropertyValue::Map(decode_map(reader)?),
+            TAG_DATETIME => PropertyValue::DateTime(unzigzag(varint::decode_u64(reader)?)),
+            TAG_BYTES => PropertyValue::Bytes(decode_bytes(reader)?),
+            other => return Err(invalid(format!("unknown property tag {}", other))),
+        })
+    }
+}
+
+impl From<&str> for PropertyValue {
+    fn from(value: &str) -> Self {
+        PropertyValue::String(value.to_string())
+    }
+}
+
+impl From<String> for PropertyValue {
+    fn from(value: String) -> Self {
+        PropertyValue::String(value)
+    }
+}
+
+impl From<i64> for PropertyValue {
+    fn from(value: i64) -> Self {
+        PropertyValue::Int(value)
+    }
+}
+
+impl From<f64> for PropertyValue {
+    fn from(value: f64) -> Self {
+        PropertyValue::Float(value)
+    }
+}
+
+impl From<bool> for PropertyValue {
+    fn from(value: bool) -> Self {
+        PropertyValue::Bool(value)
+    }
+}
+
+/// Labels and properties of one node or edge
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PropertyRecord {
+    pub labels: BTreeSet<String>,
+    pub properties: Properties,
+}
+
+impl PropertyRecord {
+    pub fn new<I, S>(labels: I, properties: Properties) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            labels: labels.into_iter().map(Into::into).collect(),
+            properties,
+        }
+    }
+
+    /// Canonical binary encoding
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(RECORD_MAGIC);
+        buf.push(RECORD_VERSION);
+        varint::encode_u64(self.labels.len() as u64, &mut buf);
+        for label in &self.labels {
+            encode_bytes(label.as_bytes(), &mut buf);
+        }
+        encode_map(&self.properties, &mut buf);
+        buf
+    }
+
+    /// Decode stored node or edge data. Legacy JSON objects map to their
+    /// properties; any other legacy payload has no properties.
+    pub fn decode(bytes: &[u8]) -> io::Result<Self> {
+        let Some(mut body) = bytes.strip_prefix(RECORD_MAGIC.as_slice()) else {
+            return Ok(match serde_json::from_slice::<serde_json::Value>(bytes) {
+                Ok(serde_json::Value::Object(map)) => Self {
+                    labels: BTreeSet::new(),
+                    properties: properties_from_json(&map),
+                },
+                _ => Self::default(),
+            });
+        };
+
+        let mut version = [0u8; 1];
+        body.read_exact(&mut version)?;
+        if version[0] != RECORD_VERSION {
+            return Err(invalid(format!("unsupported property record version {}", version[0])));
+        }
+
+        let label_count = varint::decode_u64(&mut body)?;
+        let mut labels = BTreeSet::new();
+        for _ in 0..label_count {
+            labels.insert(decode_string(&mut body)?);
+        }
+        let properties = decode_map(&mut body)?;
+
+        if !body.is_empty() {
+            return Err(invalid("trailing bytes after property record".to_string()));
+        }
+        Ok(Self { labels, properties })
+    }
+
+    /// Whether `bytes` holds an encoded record (rather than legacy data)
+    pub fn is_encoded(bytes: &[u8]) -> bool {
+        bytes.starts_with(RECORD_MAGIC)
+    }
+
+    /// Properties as a JSON object
+    pub fn to_json(&self) -> serde_json::Value {
+        properties_to_json(&self.properties)
+    }
+
+    /// Text form of stored node data: encoded records render as their JSON
+    /// properties, legacy data as (lossy) UTF-8
+    pub fn data_to_string(bytes: &[u8]) -> String {
+        match Self::is_encoded(bytes).then(|| Self::decode(bytes)) {
+            Some(Ok(record)) => record.to_json().to_string(),
+            _ => String::from_utf8_lossy(bytes).into_owned(),
+        }
+    }
+}
+
+/// Convert a JSON object to properties, dropping `null` members
+pub fn properties_from_json(map: &serde_json::Map<String, serde_json::Value>) -> Properties {
+    map.iter()
+        .filter_map(|(key, value)| Some((key.clone(), PropertyValue::from_json(value)?)))
+        .collect()
+}
+
+/// Render properties as a JSON object
+pub fn properties_to_json(properties: &Properties) -> serde_json::Value {
+    serde_json::Value::Object(
+        properties.iter().map(|(key, value)| (key.clone(), value.to_json())).collect(),
+    )
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+fn encode_bytes(bytes: &[u8], buf: &mut Vec<u8>) {
+    varint::encode_u64(bytes.len() as u64, buf);
+    buf.extend_from_slice(bytes);
+}
+
+fn encode_map(map: &BTreeMap<String, PropertyValue>, buf: &mut Vec<u8>) {
+    varint::encode_u64(map.len() as u64, buf);
+    for (key, value) in map {
+        encode_bytes(key.as_bytes(), buf);
+        value.encode(buf);
+    }
+}
+
+fn decode_bytes<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let len = varint::decode_u64(reader)?;
+    let mut bytes = Vec::new();
+    reader.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated property record"));
+    }
+    Ok(bytes)
+}
+
+fn decode_string<R: Read>(reader: &mut R) -> io::Result<String> {
+    String::from_utf8(decode_bytes(reader)?).map_err(|e| invalid(e.to_string()))
+}
+
+fn decode_map<R: Read>(reader: &mut R) -> io::Result<BTreeMap<String, PropertyValue>> {
+    let len = varint::decode_u64(reader)?;
+    let mut map = BTreeMap::new();
+    for _ in 0..len {
+        let key = decode_string(reader)?;
+        map.insert(key, PropertyValue::decode(reader)?);
+    }
+    Ok(map)
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_roundtrip_is_canonical() {
+        let mut nested = BTreeMap::new();
+        nested.insert("city".to_string(), PropertyValue::from("Kyoto"));
+
+        let mut properties = Properties::new();
+        properties.insert("name".to_string(), "Alice".into());
+        properties.insert("age".to_string(), PropertyValue::Int(-42));
+        properties.insert("score".to_string(), PropertyValue::Float(0.5));
+        properties.insert("active".to_string(), true.into());
+        properties.insert("tags".to_string(), PropertyValue::List(vec!["a".into(), PropertyValue::Int(1)]));
+        properties.insert("address".to_string(), PropertyValue::Map(nested));
+        properties.insert("born".to_string(), PropertyValue::DateTime(1_700_000_000_000_000));
+        properties.insert("avatar".to_string(), PropertyValue::Bytes(vec![0, 255]));
+
+        let record = PropertyRecord::new(["Person", "Admin"], properties);
+        let encoded = record.encode();
+        assert!(PropertyRecord::is_encoded(&encoded));
+        assert_eq!(PropertyRecord::decode(&encoded).unwrap(), record);
+
+        // Label and key order do not affect the encoding
+        let reordered = PropertyRecord::new(["Admin", "Person"], record.properties.clone());
+        assert_eq!(reordered.encode(), encoded);
+
+        // Truncation is detected
+        assert!(PropertyRecord::decode(&encoded[..encoded.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_legacy_json_and_rendering() {
+        let record = PropertyRecord::decode(br#"{"name": "Bob", "age": 30, "nick": null}"#).unwrap();
+        assert!(record.labels.is_empty());
+        assert_eq!(record.properties["name"], PropertyValue::from("Bob"));
+        assert_eq!(record.properties["age"], PropertyValue::Int(30));
+        assert!(!record.properties.contains_key("nick"));
+
+        assert_eq!(PropertyRecord::decode(b"plain text").unwrap(), PropertyRecord::default());
+
+        assert_eq!(PropertyValue::DateTime(0).to_json(), serde_json::json!("1970-01-01T00:00:00Z"));
+        assert_eq!(record.to_json(), serde_json::json!({"name": "Bob", "age": 30}));
+    }
+}
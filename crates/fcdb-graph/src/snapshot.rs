@@ -0,0 +1,129 @@
+//! Consistent point-in-time reads (MVCC).
+//!
+//! Merkle DAG: enishi_graph -> snapshot -> {commit_clock, pinned_snapshots, snapshot_reads}
+//!
+//! Every commit is stamped above all earlier ones, and the graph's committed
+//! timestamp advances only once a commit is fully applied. A [`Snapshot`]
+//! pins that timestamp and answers reads from the node timelines and edge
+//! lifetimes as of it, so it sees each commit entirely or not at all and is
+//! unaffected by later writes. Locks are held only for each lookup, never for
+//! the snapshot's lifetime, so long readers do not hold up writers; GC keeps
+//! the versions pinned snapshots can still see.
+
+use crate::persist::GraphOp;
+use crate::{AdjEntry, GraphDB, LabelId, PropertyRecord, Rid, Timestamp, TOMBSTONE};
+use std::sync::atomic::Ordering;
+
+/// Read-only view of the graph as of one commit
+pub struct Snapshot<'a> {
+    graph: &'a GraphDB,
+    ts: Timestamp,
+}
+
+impl GraphDB {
+    /// Pin the last completed commit for consistent reads
+    /// Merkle DAG: enishi_graph -> snapshot -> pin
+    pub fn snapshot(&self) -> Snapshot<'_> {
+        let mut pinned = self.pinned.lock().unwrap();
+        let ts = self.committed_timestamp();
+        *pinned.entry(ts).or_insert(0) += 1;
+        Snapshot { graph: self, ts }
+    }
+
+    /// Timestamp of the last completed commit
+    pub fn committed_timestamp(&self) -> Timestamp {
+        Timestamp(self.committed_ts.load(Ordering::SeqCst))
+    }
+
+    /// Oldest timestamp still pinned by a snapshot or transaction
+    pub fn oldest_snapshot(&self) -> Option<Timestamp> {
+        self.pinned.lock().unwrap().keys().next().copied()
+    }
+
+    /// Timestamp for the next commit: the operation clock, or just past the
+    /// last commit if the clock is behind it. Callers hold `write_lock`.
+    pub(crate) async fn next_commit_timestamp(&self) -> Timestamp {
+        let clock = *self.current_timestamp.read().await;
+        Timestamp(clock.0.max(self.committed_ts.load(Ordering::SeqCst) + 1))
+    }
+
+    /// Make an applied mutation visible to readers of "now"
+    pub(crate) fn publish_commit(&self, op: &GraphOp) {
+        if let Some(ts) = op.timestamp() {
+            self.committed_ts.fetch_max(ts.0, Ordering::SeqCst);
+        }
+    }
+
+    /// Timestamp a read at `as_of` (now, if `None`) actually observes
+    pub(crate) fn read_timestamp(&self, as_of: Option<Timestamp>) -> Timestamp {
+        let committed = self.committed_timestamp();
+        as_of.map_or(committed, |ts| ts.min(committed))
+    }
+}
+
+impl<'a> Snapshot<'a> {
+    /// Commit timestamp the snapshot reads at
+    pub fn timestamp(&self) -> Timestamp {
+        self.ts
+    }
+
+    /// Graph the snapshot reads from
+    pub fn graph(&self) -> &'a GraphDB {
+        self.graph
+    }
+
+    /// Node data as of the snapshot
+    pub async fn get_node(&self, rid: Rid) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        self.graph.get_node_at(rid, self.ts).await
+    }
+
+    /// Labels and properties of a node as of the snapshot
+    pub async fn get_record(&self, rid: Rid) -> Result<Option<PropertyRecord>, Box<dyn std::error::Error>> {
+        self.graph.get_record_at(rid, self.ts).await
+    }
+
+    /// Whether a node is live at the snapshot
+    pub async fn node_exists(&self, rid: Rid) -> bool {
+        self.graph.node_exists(rid, Some(self.ts)).await
+    }
+
+    /// Outgoing edges live at the snapshot
+    pub async fn get_edges_from(&self, from: Rid) -> Vec<AdjEntry> {
+        self.graph.get_edges_from_at(from, Some(self.ts)).await
+    }
+
+    /// Incoming edges live at the snapshot
+    pub async fn get_edges_to(&self, to: Rid) -> Vec<AdjEntry> {
+        self.graph.get_edges_to_at(to, Some(self.ts)).await
+    }
+
+    /// [`GraphDB::traverse`] over the edges live at the snapshot
+    pub async fn traverse(&self, from: Rid, labels: Option<&[LabelId]>, max_depth: usize)
+        -> Result<Vec<(Rid, usize)>, Box<dyn std::error::Error>>
+    {
+        self.graph.traverse(from, labels, max_depth, Some(self.ts)).await
+    }
+
+    /// RIDs of the nodes live at the snapshot
+    pub async fn list_rids(&self) -> Vec<Rid> {
+        let temporal = self.graph.temporal_rid_mappings.read().await;
+        let mut rids: Vec<Rid> = temporal.iter()
+            .filter(|(_, timeline)| timeline.range(..=self.ts).next_back().is_some_and(|(_, cid)| *cid != TOMBSTONE))
+            .map(|(rid, _)| *rid)
+            .collect();
+        rids.sort();
+        rids
+    }
+}
+
+impl Drop for Snapshot<'_> {
+    fn drop(&mut self) {
+        let mut pinned = self.graph.pinned.lock().unwrap();
+        if let Some(count) = pinned.get_mut(&self.ts) {
+            *count -= 1;
+            if *count == 0 {
+                pinned.remove(&self.ts);
+            }
+        }
+    }
+}
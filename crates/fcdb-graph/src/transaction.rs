@@ -0,0 +1,709 @@
+//! Multi-statement transactions with buffered writes.
+//!
+//! Merkle DAG: enishi_graph -> transaction -> {begin, read_your_writes, commit, rollback}
+//!
+//! A [`Transaction`] collects node and edge writes in memory; reads through
+//! the handle see them layered over a [`Snapshot`] pinned at `begin`. `commit`
+//! validates the whole batch (conflicts, existence and constraints) under the
+//! graph's write lock and logs it as one WAL record applied at a single
+//! timestamp, so either every write becomes visible or none does. Dropping the
+//! handle without committing discards the writes.
+//!
+//! Concurrent transactions writing the same node are resolved
+//! first-committer-wins: a commit fails with [`TransactionConflict::Write`]
+//! if a node it writes changed after its snapshot. Under
+//! [`IsolationLevel::Serializable`] the nodes and adjacency lists it read
+//! must be unchanged too, which rules out write skew.
+//!
+//! Label names a transaction uses for the first time get ids reserved from
+//! the graph and are interned with its commit, so an uncommitted
+//! transaction leaves the dictionary untouched.
+//!
+//! A transaction can be restricted to a RID range: nodes outside it are
+//! invisible to its reads and writing them fails with [`ScopeViolation`].
+
+use crate::persist::GraphOp;
+use crate::{AdjEntry, EdgeId, GraphDB, IndexValue, LabelId, NameKind, Properties, PropertyRecord, PropertyValue, Rid, Snapshot, Timestamp, TOMBSTONE};
+use fcdb_cas::{Charge, PackBand};
+use fcdb_core::Cid;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::{Bound, Range, RangeBounds};
+use std::sync::Mutex;
+use tracing::debug;
+
+/// How much of a transaction's reads commit validation protects
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IsolationLevel {
+    /// Reads see the snapshot taken at `begin`; only write-write conflicts abort
+    #[default]
+    Snapshot,
+    /// Additionally abort if anything read through the transaction changed
+    /// before it committed
+    Serializable,
+}
+
+/// A commit rejected because a concurrent commit got there first
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum TransactionConflict {
+    #[error("write conflict on node {rid}: changed at {committed_at:?}, after snapshot {snapshot:?}")]
+    Write { rid: Rid, snapshot: Timestamp, committed_at: Timestamp },
+    #[error("serialization conflict on node {rid}: read at snapshot {snapshot:?}, changed at {committed_at:?}")]
+    Read { rid: Rid, snapshot: Timestamp, committed_at: Timestamp },
+}
+
+/// A write to a node outside the transaction's RID scope
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+#[error("node {rid} is outside the transaction's scope")]
+pub struct ScopeViolation {
+    pub rid: Rid,
+}
+
+/// What a serializable transaction read from its snapshot
+#[derive(Debug, Default)]
+struct ReadSet {
+    nodes: BTreeSet<Rid>,
+    /// Nodes whose outgoing edges were read
+    adjacency: BTreeSet<Rid>,
+    /// Nodes whose incoming edges were read
+    incoming: BTreeSet<Rid>,
+}
+
+/// Pending state of one node: new data, or `None` once deleted
+#[derive(Clone, Debug)]
+struct NodeWrite {
+    data: Option<Vec<u8>>,
+    /// Node was created by this transaction
+    created: bool,
+}
+
+/// Pending edge mutation, kept in issue order
+#[derive(Clone, Debug)]
+enum EdgeWrite {
+    Create { id: EdgeId, from: Rid, to: Rid, label: LabelId, properties: Vec<u8> },
+    Delete { id: EdgeId, from: Rid, to: Rid },
+}
+
+/// Handle buffering writes until [`Transaction::commit`]
+pub struct Transaction<'a> {
+    graph: &'a GraphDB,
+    snapshot: Snapshot<'a>,
+    isolation: IsolationLevel,
+    reads: Mutex<ReadSet>,
+    nodes: BTreeMap<Rid, NodeWrite>,
+    edges: Vec<EdgeWrite>,
+    // Label names not yet interned, with the ids reserved for them
+    labels: BTreeMap<String, LabelId>,
+    scope: Option<Range<Rid>>,
+    // Actor whose storage quota the new objects are charged to
+    actor: Option<String>,
+}
+
+impl GraphDB {
+    /// Start a snapshot-isolated transaction
+    /// Merkle DAG: enishi_graph -> transaction -> begin
+    pub fn begin(&self) -> Transaction<'_> {
+        self.begin_with(IsolationLevel::Snapshot)
+    }
+
+    /// Start a transaction at the given isolation level
+    pub fn begin_with(&self, isolation: IsolationLevel) -> Transaction<'_> {
+        Transaction {
+            graph: self,
+            snapshot: self.snapshot(),
+            isolation,
+            reads: Mutex::new(ReadSet::default()),
+            nodes: BTreeMap::new(),
+            edges: Vec::new(),
+            labels: BTreeMap::new(),
+            scope: None,
+            actor: None,
+        }
+    }
+}
+
+impl<'a> Transaction<'a> {
+    /// Graph the transaction writes to
+    pub fn graph(&self) -> &'a GraphDB {
+        self.graph
+    }
+
+    /// Commit timestamp the transaction's reads are pinned to
+    pub fn snapshot_timestamp(&self) -> Timestamp {
+        self.snapshot.timestamp()
+    }
+
+    /// Isolation level chosen at `begin`
+    pub fn isolation(&self) -> IsolationLevel {
+        self.isolation
+    }
+
+    /// Whether any write is buffered
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty() && self.edges.is_empty()
+    }
+
+    /// Confine the transaction to the nodes in `rids`
+    pub fn restrict(&mut self, rids: Range<Rid>) {
+        self.scope = Some(rids);
+    }
+
+    /// Charge the objects the transaction stores to `actor`'s quota as well
+    /// as the graph's namespace
+    pub fn charge_to(&mut self, actor: impl Into<String>) {
+        self.actor = Some(actor.into());
+    }
+
+    /// Whether `rid` is inside the transaction's scope
+    pub fn in_scope(&self, rid: Rid) -> bool {
+        self.scope.as_ref().is_none_or(|scope| scope.contains(&rid))
+    }
+
+    fn check_scope(&self, rid: Rid) -> Result<(), ScopeViolation> {
+        if self.in_scope(rid) { Ok(()) } else { Err(ScopeViolation { rid }) }
+    }
+
+    /// Create a node; its RID is reserved now but the node only exists for
+    /// other readers after commit
+    pub async fn create_node(&mut self, data: &[u8]) -> Result<Rid, Box<dyn std::error::Error>> {
+        let rid = self.graph.allocate_rid().await;
+        self.check_scope(rid)?;
+        self.nodes.insert(rid, NodeWrite { data: Some(data.to_vec()), created: true });
+        Ok(rid)
+    }
+
+    /// Create a node with labels and typed properties
+    pub async fn create_node_with_properties<I, S>(&mut self, labels: I, properties: Properties) -> Result<Rid, Box<dyn std::error::Error>>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.create_node(&PropertyRecord::new(labels, properties).encode()).await
+    }
+
+    /// Replace a node's data
+    pub async fn update_node(&mut self, rid: Rid, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        self.check_scope(rid)?;
+        if !self.node_exists(rid).await {
+            return Err(format!("node {} does not exist", rid).into());
+        }
+        let created = self.nodes.get(&rid).is_some_and(|write| write.created);
+        self.nodes.insert(rid, NodeWrite { data: Some(data.to_vec()), created });
+        Ok(())
+    }
+
+    /// Set one property
+    pub async fn set_property(&mut self, rid: Rid, key: &str, value: PropertyValue) -> Result<(), Box<dyn std::error::Error>> {
+        self.modify_record(rid, |record| {
+            record.properties.insert(key.to_string(), value);
+        }).await
+    }
+
+    /// Remove one property
+    pub async fn remove_property(&mut self, rid: Rid, key: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.modify_record(rid, |record| {
+            record.properties.remove(key);
+        }).await
+    }
+
+    /// Add a label
+    pub async fn add_label(&mut self, rid: Rid, label: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.modify_record(rid, |record| {
+            record.labels.insert(label.to_string());
+        }).await
+    }
+
+    /// Remove a label
+    pub async fn remove_label(&mut self, rid: Rid, label: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.modify_record(rid, |record| {
+            record.labels.remove(label);
+        }).await
+    }
+
+    async fn modify_record<F>(&mut self, rid: Rid, f: F) -> Result<(), Box<dyn std::error::Error>>
+    where
+        F: FnOnce(&mut PropertyRecord),
+    {
+        self.check_scope(rid)?;
+        let data = self.get_node(rid).await?
+            .ok_or_else(|| format!("node {} does not exist", rid))?;
+        let mut record = PropertyRecord::decode(&data)?;
+        let before = record.clone();
+        f(&mut record);
+        if record == before && PropertyRecord::is_encoded(&data) {
+            return Ok(());
+        }
+        self.update_node(rid, &record.encode()).await
+    }
+
+    /// Delete a node and, on commit, its edges. Returns false if the node
+    /// does not exist.
+    pub async fn delete_node(&mut self, rid: Rid) -> Result<bool, Box<dyn std::error::Error>> {
+        self.check_scope(rid)?;
+        if !self.node_exists(rid).await {
+            return Ok(false);
+        }
+        if self.nodes.get(&rid).is_some_and(|write| write.created) {
+            // Never committed: forget it along with the edges it was given
+            self.nodes.remove(&rid);
+            self.edges.retain(|edge| match *edge {
+                EdgeWrite::Create { from, to, .. } | EdgeWrite::Delete { from, to, .. } => from != rid && to != rid,
+            });
+        } else {
+            self.nodes.insert(rid, NodeWrite { data: None, created: false });
+        }
+        Ok(true)
+    }
+
+    /// Id for a label name as seen by this transaction. A name the graph
+    /// does not know yet gets a reserved id and is interned on commit.
+    pub async fn label_id(&mut self, name: &str) -> Result<LabelId, Box<dyn std::error::Error>> {
+        if let Some(id) = self.lookup_label(name).await {
+            return Ok(id);
+        }
+        if name.is_empty() {
+            return Err("empty names cannot be interned".into());
+        }
+        let id = self.graph.reserve_label_id().await;
+        self.labels.insert(name.to_string(), id);
+        Ok(id)
+    }
+
+    /// Id of a label name known to the graph or staged here, without interning
+    pub async fn lookup_label(&self, name: &str) -> Option<LabelId> {
+        match self.labels.get(name) {
+            Some(id) => Some(*id),
+            None => self.graph.lookup_label(name).await,
+        }
+    }
+
+    /// Display form of a label id, including those reserved here
+    pub async fn label_display(&self, id: LabelId) -> String {
+        match self.labels.iter().find(|(_, staged)| **staged == id) {
+            Some((name, _)) => name.clone(),
+            None => self.graph.label_display(id).await,
+        }
+    }
+
+    /// Create an edge between nodes; its id is issued now
+    pub async fn create_edge(&mut self, from: Rid, to: Rid, label: LabelId, properties: &[u8]) -> Result<EdgeId, Box<dyn std::error::Error>> {
+        for rid in [from, to] {
+            self.check_scope(rid)?;
+            if self.is_deleted(rid) {
+                return Err(format!("node {} was deleted in this transaction", rid).into());
+            }
+            if !self.node_exists(rid).await {
+                return Err(format!("node {} does not exist", rid).into());
+            }
+        }
+        let id = self.graph.allocate_edge_id();
+        self.edges.push(EdgeWrite::Create { id, from, to, label, properties: properties.to_vec() });
+        Ok(id)
+    }
+
+    /// Create an edge with typed properties
+    pub async fn create_edge_with_properties(&mut self, from: Rid, to: Rid, label: LabelId, properties: Properties) -> Result<EdgeId, Box<dyn std::error::Error>> {
+        let record = PropertyRecord { labels: Default::default(), properties };
+        self.create_edge(from, to, label, &record.encode()).await
+    }
+
+    /// Replace the data of the live edge `id` leaving `from`. The edge keeps
+    /// its id; readers of earlier timestamps still see the old data. Returns
+    /// false if there is no such edge.
+    pub async fn update_edge(&mut self, from: Rid, id: EdgeId, properties: &[u8]) -> Result<bool, Box<dyn std::error::Error>> {
+        self.check_scope(from)?;
+        let Some(edge) = self.get_edges_from(from).await.into_iter().find(|e| e.id == id) else {
+            return Ok(false);
+        };
+        self.check_scope(edge.target)?;
+        // An edge created here is still pending and can change in place
+        let pending = self.edges.iter_mut().rev().find_map(|write| match write {
+            EdgeWrite::Create { id: created, properties, .. } if *created == id => Some(properties),
+            _ => None,
+        });
+        match pending {
+            Some(pending) => *pending = properties.to_vec(),
+            None => {
+                let (to, label) = (edge.target, edge.label);
+                self.edges.push(EdgeWrite::Delete { id, from, to });
+                self.edges.push(EdgeWrite::Create { id, from, to, label, properties: properties.to_vec() });
+            }
+        }
+        Ok(true)
+    }
+
+    /// Replace an edge's typed properties
+    pub async fn update_edge_with_properties(&mut self, from: Rid, id: EdgeId, properties: Properties) -> Result<bool, Box<dyn std::error::Error>> {
+        let record = PropertyRecord { labels: Default::default(), properties };
+        self.update_edge(from, id, &record.encode()).await
+    }
+
+    /// Delete the live edge `id` leaving `from`, leaving its parallel
+    /// edges alone. Returns false if there is no such edge.
+    pub async fn delete_edge(&mut self, from: Rid, id: EdgeId) -> Result<bool, Box<dyn std::error::Error>> {
+        self.check_scope(from)?;
+        let Some(edge) = self.get_edges_from(from).await.into_iter().find(|e| e.id == id) else {
+            return Ok(false);
+        };
+        self.check_scope(edge.target)?;
+        self.edges.push(EdgeWrite::Delete { id, from, to: edge.target });
+        Ok(true)
+    }
+
+    /// Delete every live edge `from --label--> to`. Returns false if there
+    /// was none.
+    pub async fn delete_edges(&mut self, from: Rid, to: Rid, label: LabelId) -> Result<bool, Box<dyn std::error::Error>> {
+        self.check_scope(from)?;
+        self.check_scope(to)?;
+        let ids: Vec<EdgeId> = self.get_edges_from(from).await.into_iter()
+            .filter(|e| e.target == to && e.label == label)
+            .map(|e| e.id)
+            .collect();
+        for &id in &ids {
+            self.edges.push(EdgeWrite::Delete { id, from, to });
+        }
+        Ok(!ids.is_empty())
+    }
+
+    /// Current data of a node as seen by this transaction
+    pub async fn get_node(&self, rid: Rid) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        if !self.in_scope(rid) {
+            return Ok(None);
+        }
+        match self.nodes.get(&rid) {
+            Some(write) => Ok(write.data.clone()),
+            None => {
+                self.record_read(rid, false);
+                self.snapshot.get_node(rid).await
+            }
+        }
+    }
+
+    /// Labels and properties of a node as seen by this transaction
+    pub async fn get_record(&self, rid: Rid) -> Result<Option<PropertyRecord>, Box<dyn std::error::Error>> {
+        match self.get_node(rid).await? {
+            Some(data) => Ok(Some(PropertyRecord::decode(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Whether a node exists as seen by this transaction
+    pub async fn node_exists(&self, rid: Rid) -> bool {
+        if !self.in_scope(rid) {
+            return false;
+        }
+        match self.nodes.get(&rid) {
+            Some(write) => write.data.is_some(),
+            None => {
+                self.record_read(rid, false);
+                self.snapshot.node_exists(rid).await
+            }
+        }
+    }
+
+    /// Live outgoing edges of a node as seen by this transaction. Edges
+    /// created here carry the timestamp they would commit at if committed now.
+    pub async fn get_edges_from(&self, from: Rid) -> Vec<AdjEntry> {
+        if self.is_deleted(from) || !self.in_scope(from) {
+            return Vec::new();
+        }
+        self.record_read(from, true);
+        let mut edges = self.snapshot.get_edges_from(from).await;
+        let ts = self.graph.next_commit_timestamp().await;
+        for edge in &self.edges {
+            match *edge {
+                EdgeWrite::Create { id, from: source, to, label, ref properties } if source == from => {
+                    edges.push(AdjEntry { id, target: to, label, properties: Cid::hash(properties), timestamp: ts, deleted_at: None });
+                }
+                EdgeWrite::Delete { id, from: source, .. } if source == from => {
+                    edges.retain(|e| e.id != id);
+                }
+                _ => {}
+            }
+        }
+        edges.retain(|e| !self.is_deleted(e.target) && self.in_scope(e.target));
+        edges
+    }
+
+    /// Live incoming edges of a node as seen by this transaction; each
+    /// entry's `target` is the edge's source
+    pub async fn get_edges_to(&self, to: Rid) -> Vec<AdjEntry> {
+        if self.is_deleted(to) || !self.in_scope(to) {
+            return Vec::new();
+        }
+        if self.isolation == IsolationLevel::Serializable {
+            self.reads.lock().unwrap().incoming.insert(to);
+        }
+        let mut edges = self.snapshot.get_edges_to(to).await;
+        let ts = self.graph.next_commit_timestamp().await;
+        for edge in &self.edges {
+            match *edge {
+                EdgeWrite::Create { id, from, to: target, label, ref properties } if target == to => {
+                    edges.push(AdjEntry { id, target: from, label, properties: Cid::hash(properties), timestamp: ts, deleted_at: None });
+                }
+                EdgeWrite::Delete { id, to: target, .. } if target == to => {
+                    edges.retain(|e| e.id != id);
+                }
+                _ => {}
+            }
+        }
+        edges.retain(|e| !self.is_deleted(e.target) && self.in_scope(e.target));
+        edges
+    }
+
+    /// RIDs of the live nodes as seen by this transaction, in order
+    pub async fn list_rids(&self) -> Vec<Rid> {
+        let mut rids: BTreeSet<Rid> = self.snapshot.list_rids().await.into_iter()
+            .filter(|rid| self.in_scope(*rid))
+            .collect();
+        for (rid, write) in &self.nodes {
+            if write.data.is_some() {
+                rids.insert(*rid);
+            } else {
+                rids.remove(rid);
+            }
+        }
+        rids.into_iter().collect()
+    }
+
+    /// Nodes with `label` whose properties equal `values` as seen by this
+    /// transaction: the index read at the snapshot, with nodes written here
+    /// judged by their pending records. `None` when no index covers the
+    /// lookup, as for [`GraphDB::index_lookup`].
+    pub async fn index_lookup(&self, label: Option<&str>, values: &[(&str, &PropertyValue)]) -> Option<Vec<Rid>> {
+        let rids = self.graph.index_lookup(label, values, Some(self.snapshot_timestamp())).await?;
+        Some(self.merge_written(rids, label, |record| {
+            values.iter().all(|(property, value)| {
+                let value = IndexValue::from_property(value);
+                value.is_some() && record.properties.get(*property).and_then(IndexValue::from_property) == value
+            })
+        }))
+    }
+
+    /// Nodes with `label` whose `property` lies within the bounds as seen by
+    /// this transaction; see [`Transaction::index_lookup`] and
+    /// [`GraphDB::index_range`].
+    pub async fn index_range(
+        &self,
+        label: Option<&str>,
+        property: &str,
+        lower: Bound<&PropertyValue>,
+        upper: Bound<&PropertyValue>,
+    ) -> Option<Vec<Rid>> {
+        let rids = self.graph.index_range(label, property, lower, upper, Some(self.snapshot_timestamp())).await?;
+        let convert = |bound: Bound<&PropertyValue>| match bound {
+            Bound::Included(value) => IndexValue::from_property(value).map(Bound::Included),
+            Bound::Excluded(value) => IndexValue::from_property(value).map(Bound::Excluded),
+            Bound::Unbounded => Some(Bound::Unbounded),
+        };
+        let bounds = convert(lower).zip(convert(upper));
+        Some(self.merge_written(rids, label, |record| {
+            let value = record.properties.get(property).and_then(IndexValue::from_property);
+            matches!((&bounds, value), (Some(bounds), Some(value)) if bounds.contains(&value))
+        }))
+    }
+
+    /// Index results from the snapshot with this transaction's writes
+    /// applied: nodes written here are kept when `matches` their new record
+    fn merge_written(&self, rids: Vec<Rid>, label: Option<&str>, matches: impl Fn(&PropertyRecord) -> bool) -> Vec<Rid> {
+        let mut rids: BTreeSet<Rid> = rids.into_iter()
+            .filter(|rid| self.in_scope(*rid) && !self.nodes.contains_key(rid))
+            .collect();
+        for (rid, write) in &self.nodes {
+            let Some(record) = write.data.as_deref().and_then(|data| PropertyRecord::decode(data).ok()) else { continue };
+            if label.is_none_or(|label| record.labels.contains(label)) && matches(&record) {
+                rids.insert(*rid);
+            }
+        }
+        rids.into_iter().collect()
+    }
+
+    /// Typed properties of an edge returned by [`Transaction::get_edges_from`]
+    pub async fn get_edge_properties(&self, edge: &AdjEntry) -> Result<Properties, Box<dyn std::error::Error>> {
+        let pending = self.edges.iter().find_map(|write| match write {
+            EdgeWrite::Create { id, properties, .. } if *id == edge.id => Some(properties),
+            _ => None,
+        });
+        match pending {
+            Some(data) => Ok(PropertyRecord::decode(data)?.properties),
+            None => self.graph.get_edge_properties(edge).await,
+        }
+    }
+
+    fn is_deleted(&self, rid: Rid) -> bool {
+        self.nodes.get(&rid).is_some_and(|write| write.data.is_none())
+    }
+
+    fn record_read(&self, rid: Rid, adjacency: bool) {
+        if self.isolation == IsolationLevel::Serializable {
+            let mut reads = self.reads.lock().unwrap();
+            if adjacency {
+                reads.adjacency.insert(rid);
+            } else {
+                reads.nodes.insert(rid);
+            }
+        }
+    }
+
+    /// First-committer-wins (and, if serializable, read) validation against
+    /// commits made since the snapshot; callers hold `write_lock`
+    async fn check_conflicts(&self) -> Result<(), TransactionConflict> {
+        let snapshot = self.snapshot.timestamp();
+        let temporal = self.graph.temporal_rid_mappings.read().await;
+        let adjacency = self.graph.adjacency.read().await;
+        let reverse_adjacency = self.graph.reverse_adjacency.read().await;
+        let node_changed = |rid: &Rid| temporal.get(rid)
+            .and_then(|timeline| timeline.keys().next_back().copied())
+            .filter(|ts| *ts > snapshot);
+        let list_changed = |lists: &HashMap<Rid, Vec<AdjEntry>>, rid: &Rid, matches: &dyn Fn(&AdjEntry) -> bool| lists.get(rid)
+            .into_iter()
+            .flatten()
+            .filter(|entry| matches(entry))
+            .flat_map(|entry| std::iter::once(entry.timestamp).chain(entry.deleted_at))
+            .filter(|ts| *ts > snapshot)
+            .max();
+        let edges_changed = |rid: &Rid, matches: &dyn Fn(&AdjEntry) -> bool| list_changed(&adjacency, rid, matches);
+        let write = |rid: Rid, committed_at| TransactionConflict::Write { rid, snapshot, committed_at };
+
+        for (rid, _) in self.nodes.iter().filter(|(_, write)| !write.created) {
+            if let Some(committed_at) = node_changed(rid) {
+                return Err(write(*rid, committed_at));
+            }
+        }
+        for edge in &self.edges {
+            match *edge {
+                EdgeWrite::Create { from, to, .. } => {
+                    // Endpoints deleted since the snapshot would leave a dangling edge
+                    for rid in [from, to].into_iter().filter(|rid| !self.nodes.contains_key(rid)) {
+                        let deleted = temporal.get(&rid)
+                            .and_then(|timeline| timeline.iter().next_back())
+                            .filter(|(ts, cid)| **ts > snapshot && **cid == TOMBSTONE);
+                        if let Some((committed_at, _)) = deleted {
+                            return Err(write(rid, *committed_at));
+                        }
+                    }
+                }
+                EdgeWrite::Delete { id, from, .. } => {
+                    if let Some(committed_at) = edges_changed(&from, &|e| e.id == id) {
+                        return Err(write(from, committed_at));
+                    }
+                }
+            }
+        }
+
+        if self.isolation == IsolationLevel::Serializable {
+            let reads = self.reads.lock().unwrap();
+            let read = |rid: Rid, committed_at| TransactionConflict::Read { rid, snapshot, committed_at };
+            for rid in &reads.nodes {
+                if let Some(committed_at) = node_changed(rid) {
+                    return Err(read(*rid, committed_at));
+                }
+            }
+            for rid in &reads.adjacency {
+                if let Some(committed_at) = edges_changed(rid, &|_| true) {
+                    return Err(read(*rid, committed_at));
+                }
+            }
+            for rid in &reads.incoming {
+                if let Some(committed_at) = list_changed(&reverse_adjacency, rid, &|_| true) {
+                    return Err(read(*rid, committed_at));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply every buffered write atomically at one timestamp, which is
+    /// returned. Nothing is written if any write fails validation.
+    /// Merkle DAG: enishi_graph -> transaction -> commit
+    pub async fn commit(self) -> Result<Timestamp, Box<dyn std::error::Error>> {
+        let graph = self.graph;
+        let ts = {
+            let _guard = graph.write_lock.lock().await;
+            if self.is_empty() {
+                return Ok(self.snapshot.timestamp());
+            }
+            self.check_conflicts().await?;
+            let ts = graph.next_commit_timestamp().await;
+
+            // Intern the label names edges written here introduced; another
+            // commit may have interned them meanwhile
+            let mut ops = Vec::new();
+            let mut label_ids = HashMap::new();
+            for (name, &reserved) in &self.labels {
+                if !self.edges.iter().any(|edge| matches!(*edge, EdgeWrite::Create { label, .. } if label == reserved)) {
+                    continue;
+                }
+                match graph.lookup_label(name).await {
+                    Some(id) => {
+                        label_ids.insert(reserved, id);
+                    }
+                    None => ops.push(GraphOp::InternName { kind: NameKind::Label, name: name.clone(), id: reserved.0 }),
+                }
+            }
+            let label_id = |label: LabelId| label_ids.get(&label).copied().unwrap_or(label);
+
+            // Nodes updated or deleted here must not have vanished meanwhile,
+            // and nodes created here must not have taken a RID already in use
+            {
+                let rid_to_cid = graph.rid_to_cid.read().await;
+                if let Some((rid, _)) = self.nodes.iter().find(|(rid, write)| !write.created && !rid_to_cid.contains_key(rid)) {
+                    return Err(format!("node {} does not exist", rid).into());
+                }
+                let temporal = graph.temporal_rid_mappings.read().await;
+                if let Some((rid, _)) = self.nodes.iter().find(|(rid, write)| write.created && write.data.is_some() && temporal.contains_key(rid)) {
+                    return Err(format!("RID {} is already in use", rid).into());
+                }
+            }
+            let writes: Vec<(Rid, Option<&[u8]>)> = self.nodes.iter()
+                .map(|(rid, write)| (*rid, write.data.as_deref()))
+                .collect();
+            graph.check_node_writes(&writes).await?;
+            for edge in &self.edges {
+                if let EdgeWrite::Create { label, ref properties, .. } = *edge {
+                    graph.check_edge_constraints(&self.label_display(label).await, properties).await?;
+                }
+            }
+
+            // Node writes first, then edges in issue order, then deletions so
+            // they also remove edges created in this transaction
+            {
+                let mut cas = graph.cas.write().await;
+                let charge = Charge { namespace: graph.namespace, actor: self.actor.as_deref() };
+                for (&rid, write) in &self.nodes {
+                    if let Some(data) = &write.data {
+                        let cid = cas.put_charged(charge, data, 0, PackBand::Small).await?;
+                        ops.push(if write.created {
+                            GraphOp::CreateNode { rid, cid, ts, key: None }
+                        } else {
+                            GraphOp::UpdateNode { rid, cid, ts }
+                        });
+                    }
+                }
+                for edge in &self.edges {
+                    ops.push(match *edge {
+                        EdgeWrite::Create { id, from, to, label, ref properties } => {
+                            let properties = cas.put_charged(charge, properties, 1, PackBand::Small).await?;
+                            GraphOp::CreateEdge { id, from, to, label: label_id(label), properties, ts }
+                        }
+                        EdgeWrite::Delete { id, from, to } => GraphOp::DeleteEdge { from, to, id, ts },
+                    });
+                }
+            }
+            ops.extend(self.nodes.iter()
+                .filter(|(_, write)| write.data.is_none())
+                .map(|(&rid, _)| GraphOp::DeleteNode { rid, ts }));
+
+            let count = ops.len();
+            graph.log_and_apply(GraphOp::Batch { ops }, None).await?;
+            debug!("Committed transaction of {} writes at {:?}", count, ts);
+            ts
+        };
+        graph.maybe_checkpoint().await?;
+        Ok(ts)
+    }
+
+    /// Discard every buffered write
+    pub fn rollback(self) {
+        debug!("Rolled back transaction of {} node and {} edge writes", self.nodes.len(), self.edges.len());
+    }
+}
@@ -7,7 +7,7 @@ pub mod steps;
 pub use traversal::{Traversal, Traverser};
 pub use steps::Step;
 
-use fcdb_graph::{GraphDB, Rid};
+use fcdb_graph::{GraphDB, PropertyValue, Rid};
 use std::sync::Arc;
 
 /// Execute a Gremlin traversal against the graph database
@@ -56,6 +56,11 @@ impl TraversalBuilder {
         self.add_step(Step::In(label))
     }
 
+    /// Filter by vertex label
+    pub fn has_label(self, label: String) -> Self {
+        self.add_step(Step::HasLabel(label))
+    }
+
     /// Filter by property value
     pub fn has(self, key: String, value: serde_json::Value) -> Self {
         self.add_step(Step::Has(key, value))
@@ -104,7 +109,10 @@ impl<'a> TraversalExecutor<'a> {
             match first_step {
                 Step::V(start_id) => {
                     let start_ids = if let Some(id) = start_id {
-                        vec![*id]
+                        // Deleted (or never created) vertices yield no traversers
+                        if self.graph.node_exists(*id, None).await { vec![*id] } else { Vec::new() }
+                    } else if let Some(rids) = self.indexed_start(&traversal.steps[1..]).await {
+                        rids
                     } else {
                         self.graph.list_rids().await
                     };
@@ -123,10 +131,22 @@ impl<'a> TraversalExecutor<'a> {
 
             for traverser in &traversers {
                 match step {
-                    Step::Out(label) => {
-                        let edges = self.graph.get_edges_from(traverser.current).await;
+                    Step::Out(label) | Step::In(label) => {
+                        let wanted = match label {
+                            Some(name) => match self.graph.lookup_label(name).await {
+                                Some(id) => Some(id),
+                                None => continue, // unknown label: no edges carry it
+                            },
+                            None => None,
+                        };
+                        // Incoming entries come from the reverse adjacency, with
+                        // `target` naming the edge's source
+                        let edges = match step {
+                            Step::Out(_) => self.graph.get_edges_from(traverser.current).await,
+                            _ => self.graph.get_edges_to(traverser.current).await,
+                        };
                         for edge in edges {
-                            if label.is_none() || label.as_ref() == Some(&format!("{}", edge.label.0)) {
+                            if wanted.is_none_or(|id| id == edge.label) {
                                 let mut new_path = traverser.path.clone();
                                 new_path.push(edge.target);
                                 let mut new_traverser = Traverser::new_with_path(edge.target, new_path);
@@ -137,32 +157,30 @@ impl<'a> TraversalExecutor<'a> {
                             }
                         }
                     }
-                    Step::In(label) => {
-                        // For now, simplified - would need reverse index for full implementation
-                        // This is a placeholder
-                        new_traversers.push(traverser.clone());
-                    }
                     Step::Has(key, expected_value) => {
-                        if let Ok(Some(data)) = self.graph.get_node(traverser.current).await {
-                            if let Ok(json) = serde_json::from_slice::<serde_json::Value>(&data) {
-                                if let Some(actual_value) = json.get(key) {
-                                    if actual_value == expected_value {
-                                        new_traversers.push(traverser.clone());
-                                    }
+                        if let Ok(Some(properties)) = self.graph.get_properties(traverser.current).await {
+                            if let Some(actual_value) = properties.get(key) {
+                                if &actual_value.to_json() == expected_value {
+                                    new_traversers.push(traverser.clone());
                                 }
                             }
                         } else {
                             new_traversers.push(traverser.clone());
                         }
                     }
+                    Step::HasLabel(label) => {
+                        if let Ok(Some(labels)) = self.graph.get_labels(traverser.current).await {
+                            if labels.contains(label) {
+                                new_traversers.push(traverser.clone());
+                            }
+                        }
+                    }
                     Step::Values(key) => {
-                        if let Ok(Some(data)) = self.graph.get_node(traverser.current).await {
-                            if let Ok(json) = serde_json::from_slice::<serde_json::Value>(&data) {
-                                if let Some(value) = json.get(&key) {
-                                    let mut new_traverser = traverser.clone();
-                                    new_traverser.attach_side_effect("value".to_string(), value.clone());
-                                    new_traversers.push(new_traverser);
-                                }
+                        if let Ok(Some(properties)) = self.graph.get_properties(traverser.current).await {
+                            if let Some(value) = properties.get(key) {
+                                let mut new_traverser = traverser.clone();
+                                new_traverser.attach_side_effect("value".to_string(), value.to_json());
+                                new_traversers.push(new_traverser);
                             }
                         }
                     }
@@ -183,6 +201,32 @@ impl<'a> TraversalExecutor<'a> {
 
         Ok(TraversalResult { traversers })
     }
+
+    /// Start vertices for `V()` from a secondary index covering the leading
+    /// `hasLabel()` / `has()` filters; the filters still run afterwards
+    async fn indexed_start(&self, steps: &[Step]) -> Option<Vec<Rid>> {
+        let mut label = None;
+        let mut values: Vec<(&str, PropertyValue)> = Vec::new();
+        for step in steps {
+            match step {
+                Step::HasLabel(name) => {
+                    label.get_or_insert(name.as_str());
+                }
+                Step::Has(key, value) => {
+                    if let Some(value) = PropertyValue::from_json(value) {
+                        values.push((key, value));
+                    }
+                }
+                _ => break,
+            }
+        }
+        if values.is_empty() {
+            return None;
+        }
+
+        let values: Vec<(&str, &PropertyValue)> = values.iter().map(|(key, value)| (*key, value)).collect();
+        self.graph.index_lookup(label, &values, None).await
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -257,6 +301,113 @@ mod tests {
         assert_eq!(value, &serde_json::json!("Alice"));
     }
 
+    #[tokio::test]
+    async fn test_traversal_skips_deleted_entities() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let cas = PackCAS::open(temp_dir.path()).await.unwrap();
+        let graph = GraphDB::new(cas).await;
+
+        let alice = graph.create_node(br#"{"name": "Alice"}"#).await.unwrap();
+        let bob = graph.create_node(br#"{"name": "Bob"}"#).await.unwrap();
+        graph.create_edge(alice, bob, 1u32.into(), b"knows").await.unwrap();
+        graph.delete_node(bob).await.unwrap();
+
+        let result = execute_traversal(&graph, g().V().out(None).build()).await.unwrap();
+        assert!(result.traversers.is_empty());
+
+        let result = execute_traversal(&graph, g().V_id(bob.0).build()).await.unwrap();
+        assert!(result.traversers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_out_by_label_name() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let cas = PackCAS::open(temp_dir.path()).await.unwrap();
+        let graph = GraphDB::new(cas).await;
+
+        let alice = graph.create_node(br#"{"name": "Alice"}"#).await.unwrap();
+        let bob = graph.create_node(br#"{"name": "Bob"}"#).await.unwrap();
+        let acme = graph.create_node(br#"{"name": "Acme"}"#).await.unwrap();
+        let knows = graph.label_id("knows").await.unwrap();
+        let works_at = graph.label_id("worksAt").await.unwrap();
+        graph.create_edge(alice, bob, knows, b"").await.unwrap();
+        graph.create_edge(alice, acme, works_at, b"").await.unwrap();
+
+        let traversal = g().V_id(alice.0).out(Some("knows".to_string())).build();
+        let result = execute_traversal(&graph, traversal).await.unwrap();
+        assert_eq!(result.traversers.len(), 1);
+        assert_eq!(result.traversers[0].current, bob);
+
+        let traversal = g().V_id(alice.0).out(Some("likes".to_string())).build();
+        assert!(execute_traversal(&graph, traversal).await.unwrap().traversers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_in_by_label_name() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let cas = PackCAS::open(temp_dir.path()).await.unwrap();
+        let graph = GraphDB::new(cas).await;
+
+        let alice = graph.create_node(br#"{"name": "Alice"}"#).await.unwrap();
+        let bob = graph.create_node(br#"{"name": "Bob"}"#).await.unwrap();
+        let carol = graph.create_node(br#"{"name": "Carol"}"#).await.unwrap();
+        let knows = graph.label_id("knows").await.unwrap();
+        let likes = graph.label_id("likes").await.unwrap();
+        graph.create_edge(alice, bob, knows, b"").await.unwrap();
+        graph.create_edge(carol, bob, likes, b"").await.unwrap();
+        graph.create_edge(bob, carol, knows, b"").await.unwrap();
+
+        let traversal = g().V_id(bob.0).in_(Some("knows".to_string())).build();
+        let result = execute_traversal(&graph, traversal).await.unwrap();
+        assert_eq!(result.traversers.len(), 1);
+        assert_eq!(result.traversers[0].current, alice);
+        assert_eq!(result.traversers[0].path, vec![bob, alice]);
+
+        let traversal = g().V_id(bob.0).in_(None).build();
+        assert_eq!(execute_traversal(&graph, traversal).await.unwrap().traversers.len(), 2);
+
+        // Deleted edges and unknown labels contribute nothing
+        graph.delete_edge(alice, bob, knows).await.unwrap();
+        let traversal = g().V_id(bob.0).in_(Some("knows".to_string())).build();
+        assert!(execute_traversal(&graph, traversal).await.unwrap().traversers.is_empty());
+        let traversal = g().V_id(bob.0).in_(Some("follows".to_string())).build();
+        assert!(execute_traversal(&graph, traversal).await.unwrap().traversers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_has_uses_index() {
+        use fcdb_graph::{IndexDefinition, IndexKind, Properties};
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let cas = PackCAS::open(temp_dir.path()).await.unwrap();
+        let graph = GraphDB::new(cas).await;
+
+        let mut alice = Properties::new();
+        alice.insert("name".to_string(), "Alice".into());
+        let alice = graph.create_node_with_properties(["Person"], alice).await.unwrap();
+        let mut robot = Properties::new();
+        robot.insert("name".to_string(), "Alice".into());
+        graph.create_node_with_properties(["Robot"], robot).await.unwrap();
+        graph.create_index(IndexDefinition::new("person_name", Some("Person"), ["name"], IndexKind::Hash)).await.unwrap();
+
+        let traversal = g()
+            .V()
+            .has_label("Person".to_string())
+            .has("name".to_string(), serde_json::json!("Alice"))
+            .build();
+        let executor = TraversalExecutor::new(&graph);
+        assert_eq!(executor.indexed_start(&traversal.steps[1..]).await, Some(vec![alice]));
+
+        let result = execute_traversal(&graph, traversal).await.unwrap();
+        assert_eq!(result.traversers.len(), 1);
+        assert_eq!(result.traversers[0].current, alice);
+
+        // Without a label no index covers the filter, so every vertex is scanned
+        let traversal = g().V().has("name".to_string(), serde_json::json!("Alice")).build();
+        assert_eq!(executor.indexed_start(&traversal.steps[1..]).await, None);
+        assert_eq!(execute_traversal(&graph, traversal).await.unwrap().traversers.len(), 2);
+    }
+
     #[tokio::test]
     async fn test_traversal_builder_path() {
         let temp_dir = tempfile::tempdir().unwrap();
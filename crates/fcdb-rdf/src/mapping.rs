@@ -1,4 +1,4 @@
-use fcdb_graph::{GraphDB, Rid, LabelId, AdjEntry};
+use fcdb_graph::{GraphDB, Rid, LabelId, AdjEntry, PropertyRecord, PropertyValue};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
@@ -41,16 +41,28 @@ impl<'a> RdfExporter<'a> {
 
         for rid in rids {
             let subj = self.iri_for_rid(rid);
-            // node data triple
             if let Ok(Some(bytes)) = self.graph.get_node(rid).await {
-                let data = escape_literal(&String::from_utf8_lossy(&bytes));
-                out.push_str(&format!("<{}> <{}data> \"{}\" .\n", subj, self.base_iri, data));
+                if PropertyRecord::is_encoded(&bytes) {
+                    // Labels become rdf:type, properties typed literals
+                    let record = PropertyRecord::decode(&bytes).map_err(|e| RdfError::Graph(e.to_string()))?;
+                    for label in &record.labels {
+                        out.push_str(&format!("<{}> <{}> <{}label/{}> .\n", subj, RDF_TYPE, self.base_iri, escape_iri_segment(label)));
+                    }
+                    for (key, value) in &record.properties {
+                        out.push_str(&format!("<{}> <{}prop/{}> {} .\n", subj, self.base_iri, escape_iri_segment(key), literal_for(value)));
+                    }
+                } else {
+                    // Legacy node data triple
+                    let data = escape_literal(&String::from_utf8_lossy(&bytes));
+                    out.push_str(&format!("<{}> <{}data> \"{}\" .\n", subj, self.base_iri, data));
+                }
             }
 
             // edges
             let edges = self.graph.get_edges_from(rid).await;
             for e in edges {
-                let pred = format!("{}rel/{}", self.base_iri, e.label.0);
+                let label = self.graph.label_display(e.label).await;
+                let pred = format!("{}rel/{}", self.base_iri, escape_iri_segment(&label));
                 let obj = self.iri_for_rid(e.target);
                 out.push_str(&format!("<{}> <{}> <{}> .\n", subj, pred, obj));
             }
@@ -68,4 +80,38 @@ fn escape_literal(s: &str) -> String {
     s.replace('\\', "\\\\").replace('"', "\\\"")
 }
 
+const RDF_TYPE: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#type";
+const XSD: &str = "http://www.w3.org/2001/XMLSchema#";
+
+/// Percent-encode characters not allowed in an N-Triples IRI
+fn escape_iri_segment(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        if c.is_control() || " <>\"{}|^`\\%/#?".contains(c) {
+            let mut buf = [0u8; 4];
+            for byte in c.encode_utf8(&mut buf).bytes() {
+                out.push_str(&format!("%{:02X}", byte));
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// N-Triples literal for a property value; composite values are JSON strings
+fn literal_for(value: &PropertyValue) -> String {
+    let (lexical, datatype) = match value {
+        PropertyValue::String(s) => return format!("\"{}\"", escape_literal(s)),
+        PropertyValue::Int(i) => (i.to_string(), "integer"),
+        PropertyValue::Float(f) => (f.to_string(), "double"),
+        PropertyValue::Bool(b) => (b.to_string(), "boolean"),
+        PropertyValue::DateTime(_) => (value.to_json().as_str().unwrap_or_default().to_string(), "dateTime"),
+        PropertyValue::List(_) | PropertyValue::Map(_) | PropertyValue::Bytes(_) => {
+            return format!("\"{}\"", escape_literal(&value.to_json().to_string()));
+        }
+    };
+    format!("\"{}\"^^<{}{}>", escape_literal(&lexical), XSD, datatype)
+}
+
 
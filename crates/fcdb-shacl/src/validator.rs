@@ -1,6 +1,6 @@
 use crate::shapes::*;
 use crate::report::*;
-use fcdb_graph::{GraphDB, Rid, LabelId};
+use fcdb_graph::{GraphDB, Rid, LabelId, PropertyRecord};
 use regex::Regex;
 use std::collections::HashMap;
 
@@ -138,7 +138,7 @@ impl ShaclValidator {
         match &constraint.component {
             ConstraintComponent::Datatype { datatype } => {
                 if let Ok(Some(data)) = data_graph.get_node(rid).await {
-                    let data_str = String::from_utf8_lossy(&data);
+                    let data_str = PropertyRecord::data_to_string(&data);
                     if !self.validate_datatype(&data_str, datatype) {
                         result.add_violation(
                             Violation::new(
@@ -292,7 +292,7 @@ impl ShaclValidator {
                     for edge in edges {
                         if edge.label.0 == label_id {
                             if let Ok(Some(data)) = data_graph.get_node(edge.target).await {
-                                values.push(String::from_utf8_lossy(&data).to_string());
+                                values.push(PropertyRecord::data_to_string(&data));
                             }
                         }
                     }
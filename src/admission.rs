@@ -0,0 +1,159 @@
+//! Admission control for HTTP requests
+//!
+//! Every request takes a slot from the server-wide pool
+//! (`server.max_connections`) and one from its endpoint class: query
+//! endpoints share `performance.max_concurrent_queries` and batch writes
+//! `performance.max_concurrent_writes`. A request that finds no free slot is
+//! turned away at once with 429 and a `Retry-After` header instead of being
+//! queued. Query work is abandoned with 503 after `server.timeout_secs`;
+//! commits are never interrupted, so a write that has started committing
+//! always completes.
+
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Json, Response};
+use serde_json::json;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::config::Config;
+
+/// Seconds a refused client is told to wait before retrying
+pub const RETRY_AFTER_SECS: u64 = 1;
+
+/// Endpoints that share a concurrency limit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EndpointClass {
+    /// Cypher, SPARQL, Gremlin, SHACL, OWL and RDF export
+    Query,
+    /// Batch writes
+    Write,
+    /// Capabilities, leases, namespaces and the audit log
+    Admin,
+}
+
+impl EndpointClass {
+    /// Class of the endpoint at `path`, with or without a `/ns/{name}` prefix
+    pub fn of(path: &str) -> Self {
+        let path = match path.strip_prefix("/ns/") {
+            Some(rest) => rest.find('/').map_or("", |i| &rest[i..]),
+            None => path,
+        };
+        match path {
+            "/cypher" | "/sparql" | "/gremlin" | "/shacl/validate" | "/owl/classify" | "/rdf/export" => EndpointClass::Query,
+            "/batch" => EndpointClass::Write,
+            _ => EndpointClass::Admin,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EndpointClass::Query => "query",
+            EndpointClass::Write => "write",
+            EndpointClass::Admin => "admin",
+        }
+    }
+}
+
+/// Slots held by an admitted request until it is answered
+pub struct Permit {
+    _request: OwnedSemaphorePermit,
+    _class: Option<OwnedSemaphorePermit>,
+}
+
+/// Concurrency limits and the query timeout
+pub struct Admission {
+    requests: Arc<Semaphore>,
+    queries: Arc<Semaphore>,
+    writes: Arc<Semaphore>,
+    timeout: Duration,
+}
+
+impl Admission {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            requests: Arc::new(Semaphore::new(config.server.max_connections)),
+            queries: Arc::new(Semaphore::new(config.performance.max_concurrent_queries)),
+            writes: Arc::new(Semaphore::new(config.performance.max_concurrent_writes)),
+            timeout: Duration::from_secs(config.server.timeout_secs),
+        }
+    }
+
+    /// Slots for a request of `class`, or `None` if its limits are reached
+    pub fn try_admit(&self, class: EndpointClass) -> Option<Permit> {
+        let request = self.requests.clone().try_acquire_owned().ok()?;
+        let class = match class {
+            EndpointClass::Query => Some(self.queries.clone().try_acquire_owned().ok()?),
+            EndpointClass::Write => Some(self.writes.clone().try_acquire_owned().ok()?),
+            EndpointClass::Admin => None,
+        };
+        Some(Permit { _request: request, _class: class })
+    }
+
+    /// Run query work, giving up with 503 once the timeout has passed
+    pub async fn within<T>(&self, work: impl Future<Output = Result<T, (StatusCode, Json<serde_json::Value>)>>)
+        -> Result<T, (StatusCode, Json<serde_json::Value>)>
+    {
+        tokio::time::timeout(self.timeout, work).await.unwrap_or_else(|_| Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({
+                "error": format!("query exceeded the {}s timeout", self.timeout.as_secs()),
+                "code": "TIMEOUT",
+            })),
+        )))
+    }
+}
+
+/// 429 for a request refused by [`Admission::try_admit`]
+pub fn too_many_requests(class: EndpointClass) -> Response {
+    let body = json!({
+        "error": format!("too many concurrent {} requests", class.as_str()),
+        "code": "TOO_MANY_REQUESTS",
+    });
+    with_retry_after((StatusCode::TOO_MANY_REQUESTS, Json(body)).into_response())
+}
+
+/// Tell the client when to retry a refused request
+pub fn with_retry_after(mut response: Response) -> Response {
+    response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(RETRY_AFTER_SECS));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_admission_limits() {
+        let mut config = Config::default();
+        config.server.max_connections = 3;
+        config.performance.max_concurrent_queries = 1;
+        config.server.timeout_secs = 1;
+        let admission = Admission::new(&config);
+
+        assert_eq!(EndpointClass::of("/ns/tenant/cypher"), EndpointClass::Query);
+        assert_eq!(EndpointClass::of("/batch"), EndpointClass::Write);
+        assert_eq!(EndpointClass::of("/ns/cypher"), EndpointClass::Admin);
+
+        let query = admission.try_admit(EndpointClass::Query).unwrap();
+        assert!(admission.try_admit(EndpointClass::Query).is_none());
+        let _write = admission.try_admit(EndpointClass::Write).unwrap();
+        let _admin = admission.try_admit(EndpointClass::Admin).unwrap();
+        // The server-wide pool is full too
+        assert!(admission.try_admit(EndpointClass::Admin).is_none());
+        drop(query);
+        assert!(admission.try_admit(EndpointClass::Query).is_some());
+
+        let slow = admission.within(async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(())
+        });
+        assert_eq!(slow.await.unwrap_err().0, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(admission.within(async { Ok(7) }).await.unwrap(), 7);
+
+        let response = too_many_requests(EndpointClass::Write);
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+    }
+}
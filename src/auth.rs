@@ -0,0 +1,203 @@
+//! Capability checks for HTTP requests
+//!
+//! Requests present a capability token as `Authorization: Bearer <token>`.
+//! The token must verify against the server's key (401 otherwise) and grant
+//! the permissions and RID range an endpoint needs (403 otherwise); every
+//! decision is recorded in the capability audit trail. Auditing fails closed:
+//! a request whose decision cannot be written to the audit log gets a 500.
+//!
+//! Each namespace verifies caps with its own key, derived from the server's,
+//! so a token only works in the namespace it was minted for. The default
+//! namespace uses the server key itself.
+
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::Json;
+use fcdb_concur::{gate, CapAuthority, CapGate, CapRefusal, CapTracer};
+use fcdb_core::Cap;
+use fcdb_graph::Rid;
+use serde_json::json;
+use std::ops::Range;
+
+pub use fcdb_concur::gate::{holder, resource};
+pub use fcdb_concur::perms;
+
+/// Error status and JSON body, as returned by the handlers
+pub type AuthError = (StatusCode, Json<serde_json::Value>);
+
+/// Verifies request capabilities and audits the outcome, through a
+/// [`CapGate`] shared with the GraphQL schema
+#[derive(Clone)]
+pub struct Authorizer {
+    gate: CapGate,
+}
+
+impl Authorizer {
+    /// Authorizer auditing to `tracer`, or not auditing at all if `None`
+    pub fn new(authority: CapAuthority, tracer: Option<CapTracer>) -> Self {
+        Self { gate: CapGate::new(authority, tracer) }
+    }
+
+    /// Authorizer for namespace `name` (with id `id`), auditing to the same
+    /// trail under operations named `name/operation`
+    pub fn for_namespace(&self, name: &str, id: u32) -> Self {
+        Self { gate: self.gate.for_namespace(name, id) }
+    }
+
+    pub fn authority(&self) -> &CapAuthority {
+        self.gate.authority()
+    }
+
+    pub fn tracer(&self) -> &CapTracer {
+        self.gate.tracer()
+    }
+
+    /// Verified cap of the request, which must grant every bit of `required`
+    pub async fn authorize(&self, headers: &HeaderMap, operation: &str, required: u32) -> Result<Cap, AuthError> {
+        let token = headers.get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        self.gate.authorize(token, operation, required).await.map_err(refused)
+    }
+
+    /// 403 for an authenticated request the cap does not cover, audited
+    pub async fn deny(&self, cap: &Cap, operation: &str, details: &str) -> AuthError {
+        refused(self.gate.deny(cap, operation, details).await)
+    }
+
+    /// Audit a refusal that was detected below the HTTP layer
+    pub async fn audit_denied(&self, cap: &Cap, operation: &str, details: &str) -> Result<(), AuthError> {
+        self.gate.audit_denied(cap, operation, details).await.map_err(refused)
+    }
+
+    /// Require `rid` to lie in the cap's region
+    pub async fn check_rid(&self, cap: &Cap, operation: &str, rid: Rid) -> Result<(), AuthError> {
+        self.gate.check_rid(cap, operation, rid.0).await.map_err(refused)
+    }
+
+    /// Require the cap to span every RID issued so far, for operations that
+    /// read the whole graph
+    pub async fn check_whole_graph(&self, cap: &Cap, operation: &str, next_rid: Rid) -> Result<(), AuthError> {
+        self.gate.check_whole_graph(cap, operation, next_rid.0).await.map_err(refused)
+    }
+}
+
+/// Every permission, as granted to the root capability
+pub const ALL_PERMS: u32 = perms::READ | perms::WRITE | perms::EXECUTE | perms::DERIVE | perms::DELEGATE;
+
+/// Whether `cap` is as strong as the root token: every permission over every
+/// RID, not bound to a lease
+pub fn is_root(cap: &Cap) -> bool {
+    cap.perms == ALL_PERMS && cap.base == 0 && cap.len == u64::MAX && cap.lease == 0
+}
+
+/// Permission bits from a list of names ("read", "write", "execute",
+/// "derive", "delegate")
+pub fn parse_perms(names: &[serde_json::Value]) -> Result<u32, String> {
+    names.iter().try_fold(0, |bits, name| {
+        let bit = match name.as_str().unwrap_or("") {
+            "read" => perms::READ,
+            "write" => perms::WRITE,
+            "execute" => perms::EXECUTE,
+            "derive" => perms::DERIVE,
+            "delegate" => perms::DELEGATE,
+            other => return Err(format!("unknown permission {:?}", other)),
+        };
+        Ok(bits | bit)
+    })
+}
+
+/// RIDs a cap covers, for [`fcdb_graph::Transaction::restrict`]
+pub fn scope(cap: &Cap) -> Range<Rid> {
+    let rids = gate::scope(cap);
+    Rid(rids.start)..Rid(rids.end)
+}
+
+/// 401, 403 or (if the decision could not be audited) 500
+fn refused(refusal: CapRefusal) -> AuthError {
+    match refusal {
+        CapRefusal::Unauthenticated(message) => error(StatusCode::UNAUTHORIZED, "UNAUTHENTICATED", &message),
+        CapRefusal::Forbidden(message) => error(StatusCode::FORBIDDEN, "FORBIDDEN", &message),
+        CapRefusal::AuditFailed(e) => error(StatusCode::INTERNAL_SERVER_ERROR, "AUDIT_FAILED", &e.to_string()),
+    }
+}
+
+fn error(status: StatusCode, code: &str, message: &str) -> AuthError {
+    (status, Json(json!({ "error": message, "code": code })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn bearer(cap: &Cap) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_str(&format!("Bearer {}", cap.to_token())).unwrap());
+        headers
+    }
+
+    #[tokio::test]
+    async fn test_authorize() {
+        let auth = Authorizer::new(CapAuthority::generate(), Some(CapTracer::new()));
+        let reader = auth.authority().mint(1, 10, perms::READ | perms::EXECUTE, 0);
+
+        assert_eq!(auth.authorize(&HeaderMap::new(), "cypher", perms::READ).await.unwrap_err().0, StatusCode::UNAUTHORIZED);
+        let forged = CapAuthority::generate().mint(1, 10, perms::READ, 0);
+        assert_eq!(auth.authorize(&bearer(&forged), "cypher", perms::READ).await.unwrap_err().0, StatusCode::UNAUTHORIZED);
+
+        let cap = auth.authorize(&bearer(&reader), "cypher", perms::READ | perms::EXECUTE).await.unwrap();
+        assert_eq!(auth.authorize(&bearer(&reader), "batch", perms::WRITE).await.unwrap_err().0, StatusCode::FORBIDDEN);
+        assert!(auth.check_rid(&cap, "node", Rid(10)).await.is_ok());
+        assert_eq!(auth.check_rid(&cap, "node", Rid(11)).await.unwrap_err().0, StatusCode::FORBIDDEN);
+        assert!(auth.check_whole_graph(&cap, "sparql", Rid(11)).await.is_ok());
+        assert!(auth.check_whole_graph(&cap, "sparql", Rid(12)).await.is_err());
+
+        assert_eq!(parse_perms(&[json!("read"), json!("write")]), Ok(perms::READ | perms::WRITE));
+        assert!(parse_perms(&[json!("admin")]).is_err());
+        assert!(is_root(&auth.authority().mint(0, u64::MAX, ALL_PERMS, 0)));
+        assert!(!is_root(&cap));
+
+        let trail = auth.tracer().get_audit_trail(&resource("cypher")).await;
+        assert_eq!(trail.iter().map(|entry| entry.success).collect::<Vec<_>>(), vec![false, false, true]);
+        assert_eq!(trail[0].actor, "anonymous");
+    }
+
+    #[tokio::test]
+    async fn test_audit_log_survives_restart() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("audit.log");
+        let authority = CapAuthority::generate();
+        let reader = authority.mint(1, 10, perms::READ, 0);
+
+        {
+            let auth = Authorizer::new(authority.clone(), Some(CapTracer::persistent(&path, 1 << 20).unwrap()));
+            auth.authorize(&bearer(&reader), "cypher", perms::READ).await.unwrap();
+            assert!(auth.authorize(&bearer(&reader), "batch", perms::WRITE).await.is_err());
+        }
+
+        let auth = Authorizer::new(authority, Some(CapTracer::persistent(&path, 1 << 20).unwrap()));
+        let query = fcdb_concur::AuditQuery { resource: Some(resource("batch")), ..Default::default() };
+        let records = auth.tracer().query(&query).await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert!(!records[0].entry.success);
+        assert_eq!(records[0].entry.actor, holder(&reader));
+        assert_eq!(auth.tracer().verify().await.unwrap().records, 2);
+    }
+
+    #[tokio::test]
+    async fn test_namespace_authorizers() {
+        let auth = Authorizer::new(CapAuthority::generate(), Some(CapTracer::new()));
+        let tenant = auth.for_namespace("tenant", 1);
+        let root = auth.authority().mint(0, u64::MAX, ALL_PERMS, 0);
+        let tenant_root = tenant.authority().mint(0, u64::MAX, ALL_PERMS, 0);
+
+        // Tokens only work in the namespace they were minted for
+        assert!(tenant.authorize(&bearer(&tenant_root), "cypher", perms::READ).await.is_ok());
+        assert_eq!(tenant.authorize(&bearer(&root), "cypher", perms::READ).await.unwrap_err().0, StatusCode::UNAUTHORIZED);
+        assert_eq!(auth.authorize(&bearer(&tenant_root), "cypher", perms::READ).await.unwrap_err().0, StatusCode::UNAUTHORIZED);
+        assert!(auth.for_namespace("default", 0).authorize(&bearer(&root), "cypher", perms::READ).await.is_ok());
+
+        let trail = auth.tracer().get_audit_trail(&resource("tenant/cypher")).await;
+        assert_eq!(trail.iter().map(|entry| entry.success).collect::<Vec<_>>(), vec![true, false]);
+    }
+}
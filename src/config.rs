@@ -36,7 +36,9 @@ pub struct ServerConfig {
     pub port: u16,
     pub host: String,
     pub workers: usize,
+    /// Requests served at once; more are turned away with 429
     pub max_connections: usize,
+    /// Time a query may run before it is abandoned with 503
     pub timeout_secs: u64,
 }
 
@@ -57,7 +59,23 @@ pub struct StorageConfig {
     pub path: PathBuf,
     pub max_size_gb: u64,
     pub compression: bool,
+    /// "zstd" or "lz4"; only used when `compression` is on
+    #[serde(default = "default_compression_codec")]
+    pub compression_codec: String,
+    #[serde(default = "default_compression_level")]
+    pub compression_level: i32,
     pub sync_writes: bool,
+    /// Bytes each token holder may add to the store; unlimited if unset
+    #[serde(default)]
+    pub actor_quota_bytes: Option<u64>,
+}
+
+fn default_compression_codec() -> String {
+    "zstd".to_string()
+}
+
+fn default_compression_level() -> i32 {
+    3
 }
 
 impl Default for StorageConfig {
@@ -66,7 +84,10 @@ impl Default for StorageConfig {
             path: PathBuf::from("./data"),
             max_size_gb: 100,
             compression: true,
+            compression_codec: default_compression_codec(),
+            compression_level: default_compression_level(),
             sync_writes: false,
+            actor_quota_bytes: None,
         }
     }
 }
@@ -75,16 +96,25 @@ impl Default for StorageConfig {
 pub struct PerformanceConfig {
     pub query_cache_size: usize,
     pub bloom_filter_size: usize,
+    /// Query endpoints (Cypher, SPARQL, Gremlin, ...) served at once
     pub max_concurrent_queries: usize,
+    /// Batch writes served at once
+    #[serde(default = "default_max_concurrent_writes")]
+    pub max_concurrent_writes: usize,
     pub adaptive_optimization: bool,
 }
 
+fn default_max_concurrent_writes() -> usize {
+    64
+}
+
 impl Default for PerformanceConfig {
     fn default() -> Self {
         Self {
             query_cache_size: 1000000,
             bloom_filter_size: 10000000,
             max_concurrent_queries: 1000,
+            max_concurrent_writes: default_max_concurrent_writes(),
             adaptive_optimization: true,
         }
     }
@@ -94,8 +124,18 @@ impl Default for PerformanceConfig {
 pub struct SecurityConfig {
     pub enable_audit: bool,
     pub audit_log_path: PathBuf,
+    /// Size at which the audit log is rotated into a numbered segment
+    #[serde(default = "default_audit_segment_bytes")]
+    pub audit_segment_bytes: u64,
     pub max_sessions: usize,
     pub session_timeout_secs: u64,
+    /// Hex-encoded 32-byte key that mints and verifies capability tokens;
+    /// a random key is generated at startup if unset
+    #[serde(default)]
+    pub cap_key: Option<String>,
+    /// How often expired capability leases are reaped
+    #[serde(default = "default_lease_reap_interval_secs")]
+    pub lease_reap_interval_secs: u64,
 }
 
 impl Default for SecurityConfig {
@@ -103,12 +143,23 @@ impl Default for SecurityConfig {
         Self {
             enable_audit: true,
             audit_log_path: PathBuf::from("./logs/audit.log"),
+            audit_segment_bytes: default_audit_segment_bytes(),
             max_sessions: 10000,
             session_timeout_secs: 3600,
+            cap_key: None,
+            lease_reap_interval_secs: default_lease_reap_interval_secs(),
         }
     }
 }
 
+fn default_audit_segment_bytes() -> u64 {
+    fcdb_concur::DEFAULT_SEGMENT_BYTES
+}
+
+fn default_lease_reap_interval_secs() -> u64 {
+    30
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MonitoringConfig {
     pub metrics_port: u16,
@@ -145,6 +196,10 @@ pub fn load_config() -> Result<Config, Box<dyn std::error::Error>> {
         config.storage.path = PathBuf::from(storage_path);
     }
 
+    if let Ok(cap_key) = env::var("ENISHI_CAP_KEY") {
+        config.security.cap_key = Some(cap_key);
+    }
+
     if let Ok(log_level) = env::var("RUST_LOG") {
         config.monitoring.log_level = log_level;
     }
@@ -184,14 +239,40 @@ fn validate_config(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
         return Err("Invalid server port".into());
     }
 
+    if config.server.max_connections == 0 || config.server.timeout_secs == 0 {
+        return Err("Invalid server connection limit or timeout".into());
+    }
+
     if config.storage.max_size_gb == 0 {
         return Err("Invalid storage size".into());
     }
 
+    if config.performance.max_concurrent_queries == 0 || config.performance.max_concurrent_writes == 0 {
+        return Err("Invalid concurrency limit".into());
+    }
+
+    if !matches!(config.storage.compression_codec.as_str(), "zstd" | "lz4") {
+        return Err(format!("Unknown compression codec: {}", config.storage.compression_codec).into());
+    }
+
     if config.performance.query_cache_size == 0 {
         return Err("Invalid cache size".into());
     }
 
+    if config.security.audit_segment_bytes == 0 {
+        return Err("Invalid audit segment size".into());
+    }
+
+    if config.security.lease_reap_interval_secs == 0 {
+        return Err("Invalid lease reap interval".into());
+    }
+
+    if let Some(key) = &config.security.cap_key {
+        if fcdb_core::CapKey::from_hex(key).is_none() {
+            return Err("Capability key must be 64 hex digits".into());
+        }
+    }
+
     Ok(())
 }
 
@@ -212,5 +293,19 @@ mod tests {
         let mut config = Config::default();
         config.server.port = 0;
         assert!(validate_config(&config).is_err());
+
+        let mut config = Config::default();
+        config.storage.compression_codec = "brotli".to_string();
+        assert!(validate_config(&config).is_err());
+
+        let mut config = Config::default();
+        config.performance.max_concurrent_writes = 0;
+        assert!(validate_config(&config).is_err());
+
+        let mut config = Config::default();
+        config.security.cap_key = Some("not a key".to_string());
+        assert!(validate_config(&config).is_err());
+        config.security.cap_key = Some("0f".repeat(32));
+        assert!(validate_config(&config).is_ok());
     }
 }
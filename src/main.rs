@@ -5,17 +5,19 @@
 
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::signal;
 use tracing::{info, warn, error};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+mod admission;
+mod auth;
 mod config;
 mod server;
 mod metrics;
 mod health;
-use fcdb_cas::PackCAS;
-use fcdb_graph::GraphDB;
-use tokio::sync::RwLock;
+use fcdb_cas::{CasConfig, Compression};
+use fcdb_graph::Catalog;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -41,12 +43,48 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let health_checker = std::sync::Arc::new(health::HealthChecker::new());
 
     // Initialize system components
-    let cas = PackCAS::open(&config.storage.path).await?;
-    let graph = Arc::new(RwLock::new(GraphDB::new(cas).await));
+    let compression = match (config.storage.compression, config.storage.compression_codec.as_str()) {
+        (false, _) => Compression::None,
+        (true, "lz4") => Compression::Lz4,
+        (true, _) => Compression::Zstd { level: config.storage.compression_level },
+    };
+    let cas_config = CasConfig {
+        sync_writes: config.storage.sync_writes,
+        compression,
+        max_bytes: Some(config.storage.max_size_gb.saturating_mul(1 << 30)),
+        actor_quota_bytes: config.storage.actor_quota_bytes,
+        ..CasConfig::default()
+    };
+    // Every namespace's graph, over one shared PackCAS
+    let catalog = Arc::new(Catalog::open(&config.storage.path, cas_config).await?);
+
+    // Capability authority; the root token grants everything and is the
+    // starting point for deriving narrower tokens through /caps
+    let cap_key = match config.security.cap_key.as_deref() {
+        Some(key) => fcdb_core::CapKey::from_hex(key).ok_or("invalid capability key")?,
+        None => {
+            warn!("No capability key configured; tokens will not survive a restart");
+            fcdb_core::CapKey::generate()
+        }
+    };
+    // Leases bind tokens to a client session; they persist next to the data
+    // and expired ones are reaped in the background
+    let leases = fcdb_concur::LeaseManager::open(&config.storage.path.join("leases.json"))?;
+    leases.spawn_reaper(Duration::from_secs(config.security.lease_reap_interval_secs));
+    let authority = fcdb_concur::CapAuthority::new(cap_key).with_leases(leases);
+    info!("🔑 Root capability token: {}", authority.mint(0, u64::MAX, auth::ALL_PERMS, 0).to_token());
+    let tracer = if config.security.enable_audit {
+        let tracer = fcdb_concur::CapTracer::persistent(&config.security.audit_log_path, config.security.audit_segment_bytes)?;
+        info!("📜 Audit log at {}", config.security.audit_log_path.display());
+        Some(tracer)
+    } else {
+        None
+    };
+    let authorizer = Arc::new(auth::Authorizer::new(authority, tracer));
 
     // Start HTTP server
     let addr = SocketAddr::from(([0, 0, 0, 0], config.server.port));
-    let server = server::Server::new(config, metrics.clone(), health_checker.clone(), graph.clone());
+    let server = server::Server::new(config, metrics.clone(), health_checker.clone(), catalog.clone(), authorizer);
 
     info!("🌐 Starting HTTP server on {}", addr);
     let server_handle = tokio::spawn(async move {
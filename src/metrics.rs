@@ -1,6 +1,7 @@
 //! Metrics collection system for Own-CFA-Enishi
 
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
@@ -49,6 +50,15 @@ pub struct Metrics {
     pub load_average: f64,
 }
 
+/// Request counters of one namespace
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NamespaceMetrics {
+    pub requests: u64,
+    /// Requests answered with a 4xx or 5xx status
+    pub errors: u64,
+    pub duration_ms_sum: f64,
+}
+
 /// Metrics collector
 #[derive(Clone)]
 pub struct MetricsCollector {
@@ -60,6 +70,10 @@ pub struct MetricsCollector {
     query_count: Arc<AtomicU64>,
     error_count: Arc<AtomicU64>,
     total_connections: Arc<AtomicU64>,
+
+    namespaces: Arc<std::sync::Mutex<BTreeMap<String, NamespaceMetrics>>>,
+    // Turned-away requests by (endpoint class, reason)
+    rejections: Arc<std::sync::Mutex<BTreeMap<(String, String), u64>>>,
 }
 
 impl MetricsCollector {
@@ -98,6 +112,8 @@ impl MetricsCollector {
             query_count: Arc::new(AtomicU64::new(0)),
             error_count: Arc::new(AtomicU64::new(0)),
             total_connections: Arc::new(AtomicU64::new(0)),
+            namespaces: Arc::new(std::sync::Mutex::new(BTreeMap::new())),
+            rejections: Arc::new(std::sync::Mutex::new(BTreeMap::new())),
         }
     }
 
@@ -176,6 +192,36 @@ impl MetricsCollector {
         });
     }
 
+    /// Record a request served by `namespace`
+    pub fn record_namespace_request(&self, namespace: &str, duration_ms: f64, failed: bool) {
+        let mut namespaces = self.namespaces.lock().unwrap();
+        let metrics = namespaces.entry(namespace.to_string()).or_default();
+        metrics.requests += 1;
+        metrics.errors += failed as u64;
+        metrics.duration_ms_sum += duration_ms;
+    }
+
+    /// Drop the counters of a dropped namespace
+    pub fn forget_namespace(&self, namespace: &str) {
+        self.namespaces.lock().unwrap().remove(namespace);
+    }
+
+    /// Request counters by namespace
+    pub fn namespace_metrics(&self) -> BTreeMap<String, NamespaceMetrics> {
+        self.namespaces.lock().unwrap().clone()
+    }
+
+    /// Record a request of endpoint `class` refused for `reason`
+    /// ("concurrency", "timeout" or "quota")
+    pub fn record_rejection(&self, class: &str, reason: &str) {
+        *self.rejections.lock().unwrap().entry((class.to_string(), reason.to_string())).or_default() += 1;
+    }
+
+    /// Refused requests by endpoint class and reason
+    pub fn rejections(&self) -> BTreeMap<(String, String), u64> {
+        self.rejections.lock().unwrap().clone()
+    }
+
     /// Update metrics with fresh data
     async fn update_metrics(data: &Arc<RwLock<Metrics>>, start_time: Instant) {
         let uptime_seconds = start_time.elapsed().as_secs();
@@ -287,6 +333,28 @@ mod tests {
         collector.stop_collection().await;
     }
 
+    #[test]
+    fn test_namespace_metrics() {
+        let collector = MetricsCollector::new();
+        collector.record_namespace_request("tenant", 4.0, false);
+        collector.record_namespace_request("tenant", 6.0, true);
+        collector.record_namespace_request("default", 1.0, false);
+
+        let namespaces = collector.namespace_metrics();
+        assert_eq!((namespaces["tenant"].requests, namespaces["tenant"].errors), (2, 1));
+        assert_eq!(namespaces["tenant"].duration_ms_sum, 10.0);
+
+        collector.forget_namespace("tenant");
+        assert_eq!(collector.namespace_metrics().keys().collect::<Vec<_>>(), vec!["default"]);
+
+        collector.record_rejection("query", "concurrency");
+        collector.record_rejection("query", "concurrency");
+        collector.record_rejection("write", "quota");
+        let rejections = collector.rejections();
+        assert_eq!(rejections[&("query".to_string(), "concurrency".to_string())], 2);
+        assert_eq!(rejections.len(), 2);
+    }
+
     #[test]
     fn test_latency_histogram() {
         let histogram = LatencyHistogram::new();
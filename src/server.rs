@@ -1,27 +1,35 @@
 //! HTTP server implementation for Own-CFA-Enishi
 
 use axum::{
-    extract::State,
-    http::StatusCode,
-    response::Json,
+    extract::{FromRequestParts, OriginalUri, Path, Query, Request, State},
+    http::{request::Parts, HeaderMap, StatusCode, Uri},
+    middleware::{self, Next},
+    response::{Json, Response},
     routing::{get, post},
     Router,
 };
 use serde_json::json;
 use std::sync::Arc;
+use std::time::Instant;
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::trace::TraceLayer;
-use tokio::sync::RwLock;
 
+use crate::admission::{self, Admission, EndpointClass};
+use crate::auth::{self, perms, Authorizer};
 use crate::config::Config;
 use crate::metrics::MetricsCollector;
 use crate::health::HealthChecker;
-use fcdb_graph::GraphDB;
+use fcdb_graph::{properties_from_json, Catalog, ConstraintViolation, Namespace, NamespaceError, PropertyValue, QuotaExceeded, QuotaScope, Rid, ScopeViolation, Transaction, TransactionConflict};
 use fcdb_rdf::{RdfExporter, SparqlRunner};
 use fcdb_shacl::{validate_shapes, ValidationConfig};
-use fcdb_cypher::execute_cypher;
+use fcdb_cypher::{execute_cypher_in, CypherError};
 use fcdb_gremlin::{execute_traversal, g, TraversalBuilder};
 use fcdb_owl::classify_ontology;
+use fcdb_concur::{AuditError, AuditQuery, AuditRecord, ConcurError, LeaseTerms};
+use fcdb_core::Cap;
+
+/// Header selecting the namespace of requests outside `/ns/{name}/...`
+pub const NAMESPACE_HEADER: &str = "x-enishi-namespace";
 
 /// Shared application state
 #[derive(Clone)]
@@ -29,7 +37,10 @@ pub struct AppState {
     pub config: Config,
     pub metrics: Arc<MetricsCollector>,
     pub health: Arc<HealthChecker>,
-    pub graph_db: Arc<RwLock<GraphDB>>,
+    pub catalog: Arc<Catalog>,
+    /// Authorizer of the default namespace; see [`Tenant`] for the others
+    pub auth: Arc<Authorizer>,
+    pub admission: Arc<Admission>,
 }
 
 /// HTTP server for Own-CFA-Enishi
@@ -42,14 +53,18 @@ impl Server {
         config: Config,
         metrics: Arc<MetricsCollector>,
         health: Arc<HealthChecker>,
-        graph_db: Arc<RwLock<GraphDB>>,
+        catalog: Arc<Catalog>,
+        auth: Arc<Authorizer>,
     ) -> Self {
+        let admission = Arc::new(Admission::new(&config));
         Self {
             state: AppState {
                 config,
                 metrics,
                 health,
-                graph_db,
+                catalog,
+                auth,
+                admission,
             },
         }
     }
@@ -66,8 +81,36 @@ impl Server {
         Ok(())
     }
 
-    /// Create the Axum router with all routes
+    /// Create the Axum router with all routes. Namespaced routes are served
+    /// both at the top level (default namespace, or the one named by the
+    /// namespace header) and under `/ns/{name}`. Everything but the
+    /// monitoring endpoints goes through admission control.
     fn create_router(self) -> Router {
+        let namespaced = Router::new()
+            .route("/rdf/export", get(rdf_export))
+            .route("/sparql", post(sparql_query))
+            .route("/shacl/validate", post(shacl_validate))
+            .route("/cypher", post(cypher_query))
+            .route("/batch", post(batch_write))
+            .route("/gremlin", post(gremlin_traversal))
+            .route("/owl/classify", post(owl_classify))
+            .route("/caps", post(derive_capability))
+            .route("/leases", post(grant_lease))
+            .route("/leases/heartbeat", post(lease_heartbeat))
+            .route("/leases/revoke", post(revoke_lease))
+            .route_layer(middleware::from_fn_with_state(self.state.clone(), track_namespace));
+
+        let admitted = Router::new()
+            .route("/namespaces", get(list_namespaces).post(create_namespace))
+            .route("/namespaces/:name", axum::routing::delete(drop_namespace))
+            .route("/namespaces/:name/token", post(namespace_token))
+            .route("/namespaces/:name/quota", axum::routing::put(set_namespace_quota))
+            .route("/audit", get(audit_log))
+            .route("/audit/verify", get(audit_verify))
+            .merge(namespaced.clone())
+            .nest("/ns/:namespace", namespaced)
+            .route_layer(middleware::from_fn_with_state(self.state.clone(), admit));
+
         Router::new()
             .route("/", get(root))
             .route("/health", get(health_check))
@@ -75,18 +118,84 @@ impl Server {
             .route("/metrics", get(metrics_endpoint))
             .route("/version", get(version_info))
             .route("/status", get(system_status))
-            .route("/rdf/export", get(rdf_export))
-            .route("/sparql", post(sparql_query))
-            .route("/shacl/validate", post(shacl_validate))
-            .route("/cypher", post(cypher_query))
-            .route("/gremlin", post(gremlin_traversal))
-            .route("/owl/classify", post(owl_classify))
+            .merge(admitted)
             .layer(TraceLayer::new_for_http())
             .layer(CorsLayer::new().allow_origin(Any))
             .with_state(self.state)
     }
 }
 
+/// Namespace a request addresses, with the authorizer for its tokens
+pub struct Tenant {
+    pub namespace: Namespace,
+    pub auth: Authorizer,
+}
+
+/// Namespace named by the `/ns/{name}` path prefix, else by the namespace
+/// header, else the default one
+fn requested_namespace(uri: &Uri, headers: &HeaderMap) -> String {
+    if let Some(rest) = uri.path().strip_prefix("/ns/") {
+        return rest.split('/').next().unwrap_or_default().to_string();
+    }
+    headers.get(NAMESPACE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or(fcdb_graph::DEFAULT_NAMESPACE)
+        .to_string()
+}
+
+/// Path of the request as the client sent it, before any nesting stripped it
+fn original_uri(parts_uri: &Uri, extensions: &axum::http::Extensions) -> Uri {
+    extensions.get::<OriginalUri>().map_or_else(|| parts_uri.clone(), |uri| uri.0.clone())
+}
+
+#[axum::async_trait]
+impl FromRequestParts<AppState> for Tenant {
+    type Rejection = ErrorResponse;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let name = requested_namespace(&original_uri(&parts.uri, &parts.extensions), &parts.headers);
+        let namespace = state.catalog.get(&name).await
+            .ok_or_else(|| namespace_error_response(NamespaceError::NotFound(name).into()))?;
+        let auth = state.auth.for_namespace(&namespace.name, namespace.id);
+        Ok(Self { namespace, auth })
+    }
+}
+
+/// Admit a request if its endpoint class has a free slot, else answer 429,
+/// and count requests turned away by limits, timeouts and quotas
+async fn admit(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let class = EndpointClass::of(original_uri(request.uri(), request.extensions()).path());
+    let Some(_permit) = state.admission.try_admit(class) else {
+        state.metrics.record_rejection(class.as_str(), "concurrency");
+        return admission::too_many_requests(class);
+    };
+    let response = next.run(request).await;
+    match response.status() {
+        StatusCode::SERVICE_UNAVAILABLE => {
+            state.metrics.record_rejection(class.as_str(), "timeout");
+            admission::with_retry_after(response)
+        }
+        StatusCode::INSUFFICIENT_STORAGE => {
+            state.metrics.record_rejection(class.as_str(), "quota");
+            response
+        }
+        _ => response,
+    }
+}
+
+/// Count requests, errors and time spent per namespace
+async fn track_namespace(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let name = requested_namespace(&original_uri(request.uri(), request.extensions()), request.headers());
+    let started = Instant::now();
+    let response = next.run(request).await;
+    // Requests to unknown namespaces would otherwise create counters for them
+    if state.catalog.get(&name).await.is_some() {
+        let failed = response.status().is_client_error() || response.status().is_server_error();
+        state.metrics.record_namespace_request(&name, started.elapsed().as_secs_f64() * 1000.0, failed);
+    }
+    response
+}
+
 /// Root endpoint
 async fn root() -> Json<serde_json::Value> {
     Json(json!({
@@ -98,7 +207,12 @@ async fn root() -> Json<serde_json::Value> {
             "ready": "/ready",
             "metrics": "/metrics",
             "version": "/version",
-            "status": "/status"
+            "status": "/status",
+            "batch": "/batch",
+            "caps": "/caps",
+            "leases": "/leases",
+            "namespaces": "/namespaces",
+            "audit": "/audit"
         }
     }))
 }
@@ -178,6 +292,34 @@ async fn metrics_endpoint(
     output.push_str(&format!("# TYPE enishi_cache_hit_ratio gauge\n"));
     output.push_str(&format!("enishi_cache_hit_ratio {}\n", metrics.cache_hit_ratio));
 
+    let namespaces = state.metrics.namespace_metrics();
+    output.push_str("\n# HELP enishi_namespace_requests_total Requests served per namespace\n");
+    output.push_str("# TYPE enishi_namespace_requests_total counter\n");
+    for (name, ns) in &namespaces {
+        output.push_str(&format!("enishi_namespace_requests_total{{namespace=\"{}\"}} {}\n", name, ns.requests));
+    }
+    output.push_str("\n# HELP enishi_namespace_errors_total Requests per namespace answered with an error status\n");
+    output.push_str("# TYPE enishi_namespace_errors_total counter\n");
+    for (name, ns) in &namespaces {
+        output.push_str(&format!("enishi_namespace_errors_total{{namespace=\"{}\"}} {}\n", name, ns.errors));
+    }
+    output.push_str("\n# HELP enishi_namespace_request_duration_seconds_sum Time spent serving each namespace\n");
+    output.push_str("# TYPE enishi_namespace_request_duration_seconds_sum counter\n");
+    for (name, ns) in &namespaces {
+        output.push_str(&format!("enishi_namespace_request_duration_seconds_sum{{namespace=\"{}\"}} {}\n", name, ns.duration_ms_sum / 1000.0));
+    }
+    output.push_str("\n# HELP enishi_namespace_nodes Live nodes per namespace\n");
+    output.push_str("# TYPE enishi_namespace_nodes gauge\n");
+    for namespace in state.catalog.list().await {
+        let nodes = namespace.graph.read().await.node_count().await;
+        output.push_str(&format!("enishi_namespace_nodes{{namespace=\"{}\"}} {}\n", namespace.name, nodes));
+    }
+    output.push_str("\n# HELP enishi_rejected_requests_total Requests refused by admission control or storage quotas\n");
+    output.push_str("# TYPE enishi_rejected_requests_total counter\n");
+    for ((class, reason), count) in state.metrics.rejections() {
+        output.push_str(&format!("enishi_rejected_requests_total{{class=\"{}\",reason=\"{}\"}} {}\n", class, reason, count));
+    }
+
     Ok(output)
 }
 
@@ -199,6 +341,18 @@ async fn system_status(
 ) -> Json<serde_json::Value> {
     let health = state.health.check().await;
     let metrics = state.metrics.collect().await;
+    // Namespaces share one PackCAS, so any of them reports the same stats
+    let compression: serde_json::Map<String, serde_json::Value> = state.catalog.default_namespace().await.graph.read().await
+        .storage_stats().await
+        .into_iter()
+        .map(|(band, stats)| (format!("{:?}", band).to_lowercase(), json!({
+            "objects": stats.objects,
+            "compressed_objects": stats.compressed_objects,
+            "raw_bytes": stats.raw_bytes,
+            "stored_bytes": stats.stored_bytes,
+            "ratio": stats.ratio()
+        })))
+        .collect();
 
     Json(json!({
         "status": if health.healthy { "operational" } else { "degraded" },
@@ -216,6 +370,10 @@ async fn system_status(
             "storage_path": state.config.storage.path.display().to_string(),
             "adaptive_optimization": state.config.performance.adaptive_optimization
         },
+        "storage": {
+            "compression": compression,
+            "namespaces": state.catalog.list().await.len()
+        },
         "phases": {
             "A": "completed", // P4 Core
             "B": "completed", // P10 Optimization
@@ -226,33 +384,49 @@ async fn system_status(
     }))
 }
 
-/// RDF export endpoint (N-Triples)
+/// RDF export endpoint (N-Triples); needs READ over the whole graph
 async fn rdf_export(
     State(state): State<AppState>,
-) -> Result<String, StatusCode> {
-    let graph = state.graph_db.read().await;
-    let exporter = RdfExporter::new(&*graph, "https://enishi.local/");
-    exporter.export_ntriples().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    tenant: Tenant,
+    headers: HeaderMap,
+) -> Result<String, ErrorResponse> {
+    let cap = tenant.auth.authorize(&headers, "rdf_export", perms::READ).await?;
+    state.admission.within(async {
+        let graph = tenant.namespace.graph.read().await;
+        tenant.auth.check_whole_graph(&cap, "rdf_export", graph.next_rid().await).await?;
+        let exporter = RdfExporter::new(&*graph, "https://enishi.local/");
+        exporter.export_ntriples().await.map_err(internal_error)
+    }).await
 }
 
-/// SPARQL query endpoint (returns JSON for SELECT/Boolean, N-Triples for CONSTRUCT)
+/// SPARQL query endpoint (returns JSON for SELECT/Boolean, N-Triples for
+/// CONSTRUCT); needs READ and EXECUTE over the whole graph
 async fn sparql_query(
     State(state): State<AppState>,
+    tenant: Tenant,
+    headers: HeaderMap,
     axum::extract::Json(body): axum::extract::Json<serde_json::Value>,
-) -> Result<String, StatusCode> {
+) -> Result<String, ErrorResponse> {
+    let cap = tenant.auth.authorize(&headers, "sparql", perms::READ | perms::EXECUTE).await?;
     let query = body.get("query").and_then(|v| v.as_str()).unwrap_or("");
-    if query.is_empty() { return Err(StatusCode::BAD_REQUEST); }
-    let graph = state.graph_db.read().await;
-    let exporter = RdfExporter::new(&*graph, "https://enishi.local/");
-    let runner = SparqlRunner::new(exporter);
-    runner.execute(query).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    if query.is_empty() { return Err(bad_request("missing query")); }
+    state.admission.within(async {
+        let graph = tenant.namespace.graph.read().await;
+        tenant.auth.check_whole_graph(&cap, "sparql", graph.next_rid().await).await?;
+        let exporter = RdfExporter::new(&*graph, "https://enishi.local/");
+        let runner = SparqlRunner::new(exporter);
+        runner.execute(query).await.map_err(internal_error)
+    }).await
 }
 
-/// SHACL validation endpoint
+/// SHACL validation endpoint; needs READ and EXECUTE over the whole graph
 async fn shacl_validate(
     State(state): State<AppState>,
+    tenant: Tenant,
+    headers: HeaderMap,
     axum::extract::Json(body): axum::extract::Json<serde_json::Value>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
+) -> Result<Json<serde_json::Value>, ErrorResponse> {
+    let cap = tenant.auth.authorize(&headers, "shacl_validate", perms::READ | perms::EXECUTE).await?;
     let shapes = body.get("shapes").and_then(|v| v.as_str()).unwrap_or("");
     let max_violations = body.get("maxViolations").and_then(|v| v.as_u64()).unwrap_or(100) as usize;
     let strict_mode = body.get("strictMode").and_then(|v| v.as_bool()).unwrap_or(false);
@@ -262,9 +436,11 @@ async fn shacl_validate(
         strict_mode,
     };
 
-    let graph = state.graph_db.read().await;
-    let report = validate_shapes(&*graph, shapes, config).await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let report = state.admission.within(async {
+        let graph = tenant.namespace.graph.read().await;
+        tenant.auth.check_whole_graph(&cap, "shacl_validate", graph.next_rid().await).await?;
+        validate_shapes(&*graph, shapes, config).await.map_err(internal_error)
+    }).await?;
 
     // Convert to JSON response
     let response = serde_json::json!({
@@ -287,17 +463,74 @@ async fn shacl_validate(
     Ok(Json(response))
 }
 
-/// Cypher query endpoint
+/// Status and JSON body for a failed Cypher query; constraint violations
+/// and lost transaction races are conflicts, malformed queries are client
+/// errors
+fn cypher_error_response(err: CypherError) -> (StatusCode, Json<serde_json::Value>) {
+    match &err {
+        CypherError::Constraint(violation) => (
+            StatusCode::CONFLICT,
+            Json(json!({
+                "error": err.to_string(),
+                "code": "CONSTRAINT_VIOLATION",
+                "constraint": violation.constraint(),
+            })),
+        ),
+        CypherError::Conflict(_) => (
+            StatusCode::CONFLICT,
+            Json(json!({ "error": err.to_string(), "code": "TRANSACTION_CONFLICT" })),
+        ),
+        CypherError::Forbidden(_) => (
+            StatusCode::FORBIDDEN,
+            Json(json!({ "error": err.to_string(), "code": "FORBIDDEN" })),
+        ),
+        CypherError::Quota(exceeded) => quota_error_response(exceeded),
+        CypherError::Parse(_) | CypherError::Planning(_) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": err.to_string() })),
+        ),
+        CypherError::Execution(_) | CypherError::Graph(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": err.to_string() })),
+        ),
+    }
+}
+
+/// Cypher query endpoint. Needs READ and EXECUTE, plus WRITE for queries
+/// that write; the query only sees and may only touch nodes in the
+/// capability's RID range.
 async fn cypher_query(
     State(state): State<AppState>,
+    tenant: Tenant,
+    headers: HeaderMap,
     axum::extract::Json(body): axum::extract::Json<serde_json::Value>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let cap = tenant.auth.authorize(&headers, "cypher", perms::READ | perms::EXECUTE).await?;
     let query = body.get("query").and_then(|v| v.as_str()).unwrap_or("");
-    if query.is_empty() { return Err(StatusCode::BAD_REQUEST); }
+    if query.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, Json(json!({ "error": "missing query" }))));
+    }
 
-    let graph = state.graph_db.read().await;
-    let result = execute_cypher(query, &*graph).await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let graph = tenant.namespace.graph.read().await;
+    let mut tx = graph.begin();
+    tx.restrict(auth::scope(&cap));
+    tx.charge_to(auth::holder(&cap));
+    // Only execution is bounded by the timeout; a started commit completes
+    let executed = state.admission.within(async {
+        Ok(execute_cypher_in(query, &mut tx).await)
+    }).await?;
+    let result = match executed {
+        Ok(result) => result,
+        Err(CypherError::Forbidden(violation)) => {
+            return Err(tenant.auth.deny(&cap, "cypher", &violation.to_string()).await);
+        }
+        Err(err) => return Err(cypher_error_response(err)),
+    };
+    if !tx.is_empty() && !cap.has_perm(perms::WRITE) {
+        return Err(tenant.auth.deny(&cap, "cypher", "query writes but the capability lacks WRITE").await);
+    }
+    tx.commit().await
+        .map_err(|err| cypher_error_response(CypherError::graph(err)))?;
 
     // Convert to JSON response
     let response = serde_json::json!({
@@ -318,17 +551,204 @@ async fn cypher_query(
     Ok(Json(response))
 }
 
-/// Gremlin traversal endpoint
+type ErrorResponse = (StatusCode, Json<serde_json::Value>);
+
+fn bad_request(message: impl std::fmt::Display) -> ErrorResponse {
+    (StatusCode::BAD_REQUEST, Json(json!({ "error": message.to_string() })))
+}
+
+fn internal_error(message: impl std::fmt::Display) -> ErrorResponse {
+    (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": message.to_string() })))
+}
+
+/// Status and JSON body for a failed graph write; constraint violations and
+/// transaction conflicts are conflicts like in [`cypher_error_response`]
+fn write_error_response(err: Box<dyn std::error::Error>, status: StatusCode) -> ErrorResponse {
+    if let Some(violation) = err.downcast_ref::<ConstraintViolation>() {
+        return (
+            StatusCode::CONFLICT,
+            Json(json!({
+                "error": err.to_string(),
+                "code": "CONSTRAINT_VIOLATION",
+                "constraint": violation.constraint(),
+            })),
+        );
+    }
+    if err.downcast_ref::<TransactionConflict>().is_some() {
+        return (
+            StatusCode::CONFLICT,
+            Json(json!({ "error": err.to_string(), "code": "TRANSACTION_CONFLICT" })),
+        );
+    }
+    if err.downcast_ref::<ScopeViolation>().is_some() {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({ "error": err.to_string(), "code": "FORBIDDEN" })),
+        );
+    }
+    if let Some(exceeded) = QuotaExceeded::find(err.as_ref()) {
+        return quota_error_response(exceeded);
+    }
+    (status, Json(json!({ "error": err.to_string() })))
+}
+
+/// 507 for a write refused by a storage quota
+fn quota_error_response(exceeded: &QuotaExceeded) -> ErrorResponse {
+    let scope = match &exceeded.scope {
+        QuotaScope::Store => "store",
+        QuotaScope::Namespace(_) => "namespace",
+        QuotaScope::Actor(_) => "actor",
+    };
+    (
+        StatusCode::INSUFFICIENT_STORAGE,
+        Json(json!({
+            "error": exceeded.to_string(),
+            "code": "QUOTA_EXCEEDED",
+            "scope": scope,
+            "used": exceeded.used,
+            "limit": exceeded.limit,
+        })),
+    )
+}
+
+/// Node named by a batch operation: a RID, or the `ref` of a node created
+/// earlier in the same batch
+fn batch_node(
+    op: &serde_json::Value,
+    field: &str,
+    refs: &serde_json::Map<String, serde_json::Value>,
+) -> Result<Rid, ErrorResponse> {
+    match op.get(field) {
+        Some(serde_json::Value::Number(n)) => n.as_u64().map(Rid)
+            .ok_or_else(|| bad_request(format!("invalid node id in {:?}", field))),
+        Some(serde_json::Value::String(name)) => refs.get(name).and_then(|v| v.as_u64()).map(Rid)
+            .ok_or_else(|| bad_request(format!("unknown node ref {:?}", name))),
+        _ => Err(bad_request(format!("missing {:?}", field))),
+    }
+}
+
+/// Apply one batch operation to the transaction
+async fn apply_batch_op(
+    tx: &mut Transaction<'_>,
+    op: &serde_json::Value,
+    refs: &mut serde_json::Map<String, serde_json::Value>,
+    results: &mut Vec<serde_json::Value>,
+) -> Result<(), ErrorResponse> {
+    let properties = op.get("properties").and_then(|v| v.as_object());
+    let failed = |err| write_error_response(err, StatusCode::BAD_REQUEST);
+
+    let kind = op.get("op").and_then(|v| v.as_str()).unwrap_or("");
+    match kind {
+        "createNode" => {
+            let labels: Vec<String> = op.get("labels").and_then(|v| v.as_array())
+                .map(|arr| arr.iter().filter_map(|v| v.as_str()).map(str::to_string).collect())
+                .unwrap_or_default();
+            let properties = properties.map(properties_from_json).unwrap_or_default();
+            let rid = tx.create_node_with_properties(labels, properties).await.map_err(failed)?;
+            if let Some(name) = op.get("ref").and_then(|v| v.as_str()) {
+                refs.insert(name.to_string(), json!(rid.0));
+            }
+        }
+        "updateNode" => {
+            // Merges properties; null values remove them
+            let rid = batch_node(op, "node", refs)?;
+            for (key, value) in properties.into_iter().flatten() {
+                let result = match PropertyValue::from_json(value) {
+                    Some(value) => tx.set_property(rid, key, value).await,
+                    None => tx.remove_property(rid, key).await,
+                };
+                result.map_err(failed)?;
+            }
+        }
+        "deleteNode" => {
+            let rid = batch_node(op, "node", refs)?;
+            tx.delete_node(rid).await.map_err(failed)?;
+        }
+        "createEdge" | "deleteEdge" => {
+            let from = batch_node(op, "from", refs)?;
+            let to = batch_node(op, "to", refs)?;
+            let label = op.get("label").and_then(|v| v.as_str())
+                .ok_or_else(|| bad_request("missing \"label\""))?;
+            if kind == "createEdge" {
+                let label = tx.label_id(label).await.map_err(failed)?;
+                let properties = properties.map(properties_from_json).unwrap_or_default();
+                tx.create_edge_with_properties(from, to, label, properties).await.map_err(failed)?;
+            } else if let Some(label) = tx.lookup_label(label).await {
+                tx.delete_edges(from, to, label).await.map_err(failed)?;
+            }
+        }
+        "cypher" => {
+            let query = op.get("query").and_then(|v| v.as_str())
+                .ok_or_else(|| bad_request("missing \"query\""))?;
+            let result = execute_cypher_in(query, tx).await.map_err(cypher_error_response)?;
+            results.push(json!({ "columns": result.columns, "rows": result.rows }));
+        }
+        other => return Err(bad_request(format!("unknown batch op {:?}", other))),
+    }
+    Ok(())
+}
+
+/// Batch write endpoint: applies every operation in one transaction, so
+/// either all of them commit at a single timestamp or none do. Needs WRITE
+/// (and READ and EXECUTE for Cypher operations) over the nodes touched.
+async fn batch_write(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    headers: HeaderMap,
+    axum::extract::Json(body): axum::extract::Json<serde_json::Value>,
+) -> Result<Json<serde_json::Value>, ErrorResponse> {
+    let operations = body.get("operations").and_then(|v| v.as_array())
+        .ok_or_else(|| bad_request("missing operations"))?;
+    let runs_cypher = operations.iter().any(|op| op.get("op").and_then(|v| v.as_str()) == Some("cypher"));
+    let required = if runs_cypher { perms::WRITE | perms::READ | perms::EXECUTE } else { perms::WRITE };
+    let cap = tenant.auth.authorize(&headers, "batch", required).await?;
+
+    let graph = tenant.namespace.graph.read().await;
+    let mut tx = graph.begin();
+    tx.restrict(auth::scope(&cap));
+    tx.charge_to(auth::holder(&cap));
+    let mut refs = serde_json::Map::new();
+    let mut results = Vec::new();
+    // Operations only stage writes, so they may be abandoned on timeout
+    state.admission.within(async {
+        for (i, op) in operations.iter().enumerate() {
+            if let Err((status, Json(mut error))) = apply_batch_op(&mut tx, op, &mut refs, &mut results).await {
+                if status == StatusCode::FORBIDDEN {
+                    tenant.auth.audit_denied(&cap, "batch", error["error"].as_str().unwrap_or_default()).await?;
+                }
+                error["operation"] = json!(i);
+                return Err((status, Json(error)));
+            }
+        }
+        Ok(())
+    }).await?;
+
+    let timestamp = tx.commit().await
+        .map_err(|err| write_error_response(err, StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok(Json(json!({
+        "committed": operations.len(),
+        "timestamp": timestamp.0,
+        "nodes": refs,
+        "results": results,
+    })))
+}
+
+/// Gremlin traversal endpoint. Needs READ and EXECUTE; traversers that
+/// pass through nodes outside the capability's RID range are dropped.
 async fn gremlin_traversal(
     State(state): State<AppState>,
+    tenant: Tenant,
+    headers: HeaderMap,
     axum::extract::Json(body): axum::extract::Json<serde_json::Value>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
+) -> Result<Json<serde_json::Value>, ErrorResponse> {
+    let cap = tenant.auth.authorize(&headers, "gremlin", perms::READ | perms::EXECUTE).await?;
     let start = body.get("start").and_then(|v| v.as_str()).unwrap_or("V");
     let steps = body.get("steps").and_then(|v| v.as_array())
         .map(|arr| arr.iter().filter_map(|v| v.as_str()).map(|s| s.to_string()).collect::<Vec<_>>())
         .unwrap_or_default();
 
-    let graph = state.graph_db.read().await;
+    let graph = tenant.namespace.graph.read().await;
 
     // Build traversal from input
     let mut traversal_builder = g();
@@ -339,26 +759,32 @@ async fn gremlin_traversal(
     } else if start.starts_with("V(") && start.ends_with(")") {
         let id_str = &start[2..start.len() - 1];
         if let Ok(id) = id_str.parse::<u64>() {
+            tenant.auth.check_rid(&cap, "gremlin", Rid(id)).await?;
             traversal_builder = traversal_builder.V_id(id);
         } else {
-            return Err(StatusCode::BAD_REQUEST);
+            return Err(bad_request("invalid vertex id in start"));
         }
     } else {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(bad_request("invalid start step"));
     }
 
     // Parse and apply steps
     for step in &steps {
-        traversal_builder = parse_step_for_rest(traversal_builder, step)?;
+        traversal_builder = parse_step_for_rest(traversal_builder, step)
+            .map_err(|_| bad_request(format!("unsupported step {:?}", step)))?;
     }
 
     let traversal = traversal_builder.build();
-    let result = execute_traversal(&*graph, traversal).await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let result = state.admission.within(async {
+        execute_traversal(&*graph, traversal).await.map_err(internal_error)
+    }).await?;
 
     // Convert to JSON response
+    let visible = |rid: &Rid| cap.contains(rid.0);
     let response = serde_json::json!({
-        "traversers": result.traversers.into_iter().map(|t| serde_json::json!({
+        "traversers": result.traversers.into_iter()
+            .filter(|t| visible(&t.current) && t.path.iter().all(visible))
+            .map(|t| serde_json::json!({
             "current": t.current.0,
             "path": t.path.iter().map(|rid| rid.0).collect::<Vec<_>>(),
             "value": t.get_side_effect("value")
@@ -404,20 +830,25 @@ fn parse_step_for_rest(builder: TraversalBuilder, step: &str) -> Result<Traversa
     }
 }
 
-/// OWL classification endpoint
+/// OWL classification endpoint; needs READ and EXECUTE over the whole graph
 async fn owl_classify(
     State(state): State<AppState>,
+    tenant: Tenant,
+    headers: HeaderMap,
     axum::extract::Json(body): axum::extract::Json<serde_json::Value>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
+) -> Result<Json<serde_json::Value>, ErrorResponse> {
+    let cap = tenant.auth.authorize(&headers, "owl_classify", perms::READ | perms::EXECUTE).await?;
     let ontology = body.get("ontology").and_then(|v| v.as_str()).unwrap_or("");
 
     if ontology.is_empty() {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(bad_request("missing ontology"));
     }
 
-    let graph = state.graph_db.read().await;
-    let inferred_triples = classify_ontology(ontology, &*graph).await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let inferred_triples = state.admission.within(async {
+        let graph = tenant.namespace.graph.read().await;
+        tenant.auth.check_whole_graph(&cap, "owl_classify", graph.next_rid().await).await?;
+        classify_ontology(ontology, &*graph).await.map_err(internal_error)
+    }).await?;
 
     // Convert triples to N-Triples format
     let mut ntriples = Vec::new();
@@ -433,6 +864,331 @@ async fn owl_classify(
     Ok(Json(response))
 }
 
+/// Capability derivation endpoint: mints a token attenuated from the
+/// caller's. With `"delegate": true` it keeps the caller's region and needs
+/// DELEGATE; otherwise it may narrow the region and needs DERIVE.
+async fn derive_capability(
+    tenant: Tenant,
+    headers: HeaderMap,
+    axum::extract::Json(body): axum::extract::Json<serde_json::Value>,
+) -> Result<Json<serde_json::Value>, ErrorResponse> {
+    let delegate = body.get("delegate").and_then(|v| v.as_bool()).unwrap_or(false);
+    let required = if delegate { perms::DELEGATE } else { perms::DERIVE };
+    let cap = tenant.auth.authorize(&headers, "caps", required).await?;
+
+    let requested = match body.get("perms").and_then(|v| v.as_array()) {
+        Some(names) => auth::parse_perms(names).map_err(bad_request)?,
+        None => auth::ALL_PERMS,
+    };
+    let expiry = body.get("expiry").and_then(|v| v.as_u64()).unwrap_or(0);
+    let authority = tenant.auth.authority();
+    let derived = if delegate {
+        authority.delegate(&cap, requested, expiry)
+    } else {
+        let base = body.get("base").and_then(|v| v.as_u64()).unwrap_or(cap.base);
+        let len = body.get("len").and_then(|v| v.as_u64()).unwrap_or(cap.len);
+        authority.derive(&cap, base, len, requested, expiry)
+    };
+    let derived = match derived {
+        Ok(derived) => derived,
+        Err(e) => return Err(tenant.auth.deny(&cap, "caps", &e.to_string()).await),
+    };
+
+    Ok(Json(json!({
+        "token": derived.to_token(),
+        "base": derived.base,
+        "len": derived.len,
+        "perms": derived.perms,
+        "expiry": derived.expiry,
+    })))
+}
+
+/// Lease grant endpoint: derives a token like `/caps` (needing DERIVE) but
+/// bound to a new lease of `ttl_secs`, under the caller's lease if it has
+/// one. The token dies when the lease expires or is revoked; holders of an
+/// auto-renewing lease (the default) keep it alive through
+/// `/leases/heartbeat`.
+async fn grant_lease(
+    tenant: Tenant,
+    headers: HeaderMap,
+    axum::extract::Json(body): axum::extract::Json<serde_json::Value>,
+) -> Result<Json<serde_json::Value>, ErrorResponse> {
+    let cap = tenant.auth.authorize(&headers, "leases", perms::DERIVE).await?;
+    let ttl_secs = body.get("ttl_secs").and_then(|v| v.as_u64())
+        .filter(|ttl| *ttl > 0)
+        .ok_or_else(|| bad_request("ttl_secs must be a positive number of seconds"))?;
+    let requested = match body.get("perms").and_then(|v| v.as_array()) {
+        Some(names) => auth::parse_perms(names).map_err(bad_request)?,
+        None => auth::ALL_PERMS,
+    };
+    let terms = LeaseTerms {
+        holder: body.get("holder").and_then(|v| v.as_str()).unwrap_or("anonymous").to_string(),
+        ttl_secs,
+        auto_renew: body.get("auto_renew").and_then(|v| v.as_bool()).unwrap_or(true),
+    };
+    let base = body.get("base").and_then(|v| v.as_u64()).unwrap_or(cap.base);
+    let len = body.get("len").and_then(|v| v.as_u64()).unwrap_or(cap.len);
+    let (leased, lease) = match tenant.auth.authority().derive_leased(&cap, base, len, requested, terms).await {
+        Ok(granted) => granted,
+        Err(ConcurError::LeaseStore(e)) => return Err(internal_error(e)),
+        Err(e) => return Err(tenant.auth.deny(&cap, "leases", &e.to_string()).await),
+    };
+    let expires_at = lease_expiry(&tenant.auth, lease).await?;
+
+    Ok(Json(json!({
+        "token": leased.to_token(),
+        "lease": lease,
+        "expires_at": expires_at,
+        "base": leased.base,
+        "len": leased.len,
+        "perms": leased.perms,
+    })))
+}
+
+/// Heartbeat endpoint: extends the lease the presented token is bound to
+/// by its TTL and returns the new expiry
+async fn lease_heartbeat(
+    tenant: Tenant,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, ErrorResponse> {
+    let cap = tenant.auth.authorize(&headers, "leases", 0).await?;
+    if cap.lease == 0 {
+        return Err(bad_request("token is not bound to a lease"));
+    }
+    let leases = tenant.auth.authority().leases().ok_or_else(|| internal_error("leases are not enabled"))?;
+    match leases.heartbeat(cap.lease).await {
+        Ok(expires_at) => Ok(Json(json!({ "lease": cap.lease, "expires_at": expires_at }))),
+        Err(ConcurError::LeaseStore(e)) => Err(internal_error(e)),
+        Err(e) => Err(tenant.auth.deny(&cap, "leases", &e.to_string()).await),
+    }
+}
+
+/// Lease revocation endpoint: revokes `lease` (default: the caller's own)
+/// and every lease granted under it, invalidating their tokens. Callers may
+/// revoke their own lease and those below it; a root-equivalent token may
+/// revoke any.
+async fn revoke_lease(
+    tenant: Tenant,
+    headers: HeaderMap,
+    axum::extract::Json(body): axum::extract::Json<serde_json::Value>,
+) -> Result<Json<serde_json::Value>, ErrorResponse> {
+    let cap = tenant.auth.authorize(&headers, "leases", 0).await?;
+    let lease = body.get("lease").and_then(|v| v.as_u64()).unwrap_or(cap.lease);
+    if lease == 0 {
+        return Err(bad_request("missing lease"));
+    }
+    let leases = tenant.auth.authority().leases().ok_or_else(|| internal_error("leases are not enabled"))?;
+    if !auth::is_root(&cap) && (cap.lease == 0 || !leases.is_within(lease, cap.lease)) {
+        return Err(tenant.auth.deny(&cap, "leases", &format!("lease {} was not granted under the caller's lease", lease)).await);
+    }
+    leases.revoke_lease(lease).await.map_err(internal_error)?;
+    Ok(Json(json!({ "revoked": lease })))
+}
+
+async fn lease_expiry(auth: &Authorizer, lease: u64) -> Result<u64, ErrorResponse> {
+    let leases = auth.authority().leases().ok_or_else(|| internal_error("leases are not enabled"))?;
+    leases.check_lease(lease).await.map(|info| info.expires_at).map_err(internal_error)
+}
+
+/// Namespace listing endpoint; needs the root token
+async fn list_namespaces(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, ErrorResponse> {
+    authorize_admin(&state, &headers).await?;
+    let mut namespaces = Vec::new();
+    for namespace in state.catalog.list().await {
+        let nodes = namespace.graph.read().await.node_count().await;
+        let (used_bytes, quota_bytes) = state.catalog.storage_usage(&namespace).await;
+        namespaces.push(json!({
+            "name": namespace.name,
+            "id": namespace.id,
+            "nodes": nodes,
+            "used_bytes": used_bytes,
+            "quota_bytes": quota_bytes,
+        }));
+    }
+    Ok(Json(json!({ "namespaces": namespaces })))
+}
+
+/// Namespace creation endpoint; needs the root token and returns the root
+/// token of the new namespace, from which its narrower tokens are derived.
+/// An optional `quota_bytes` caps the namespace's storage.
+async fn create_namespace(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    axum::extract::Json(body): axum::extract::Json<serde_json::Value>,
+) -> Result<Json<serde_json::Value>, ErrorResponse> {
+    authorize_admin(&state, &headers).await?;
+    let name = body.get("name").and_then(|v| v.as_str()).ok_or_else(|| bad_request("missing name"))?;
+    let quota = match body.get("quota_bytes") {
+        None | Some(serde_json::Value::Null) => None,
+        Some(value) => Some(value.as_u64().ok_or_else(|| bad_request("quota_bytes must be a non-negative integer"))?),
+    };
+    let namespace = state.catalog.create(name).await.map_err(namespace_error_response)?;
+    if quota.is_some() {
+        state.catalog.set_quota(name, quota).await.map_err(namespace_error_response)?;
+    }
+    Ok(Json(namespace_root_json(&state, &namespace)))
+}
+
+/// Namespace quota endpoint; needs the root token. `{"bytes": null}`
+/// removes the quota.
+async fn set_namespace_quota(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+    axum::extract::Json(body): axum::extract::Json<serde_json::Value>,
+) -> Result<Json<serde_json::Value>, ErrorResponse> {
+    authorize_admin(&state, &headers).await?;
+    let quota = match body.get("bytes") {
+        Some(serde_json::Value::Null) => None,
+        Some(value) => Some(value.as_u64().ok_or_else(|| bad_request("bytes must be a non-negative integer"))?),
+        None => return Err(bad_request("missing bytes")),
+    };
+    state.catalog.set_quota(&name, quota).await.map_err(namespace_error_response)?;
+    Ok(Json(json!({ "name": name, "quota_bytes": quota })))
+}
+
+/// Namespace drop endpoint; needs the root token. The namespace's data is
+/// reclaimed by the next garbage collection.
+async fn drop_namespace(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+) -> Result<Json<serde_json::Value>, ErrorResponse> {
+    authorize_admin(&state, &headers).await?;
+    state.catalog.drop_namespace(&name).await.map_err(namespace_error_response)?;
+    state.metrics.forget_namespace(&name);
+    Ok(Json(json!({ "dropped": name })))
+}
+
+/// Root token of an existing namespace; needs the server's root token.
+/// Namespace keys are derived from the server key, so the same token is
+/// returned every time.
+async fn namespace_token(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+) -> Result<Json<serde_json::Value>, ErrorResponse> {
+    authorize_admin(&state, &headers).await?;
+    let namespace = state.catalog.get(&name).await
+        .ok_or_else(|| namespace_error_response(NamespaceError::NotFound(name).into()))?;
+    Ok(Json(namespace_root_json(&state, &namespace)))
+}
+
+async fn authorize_admin(state: &AppState, headers: &HeaderMap) -> Result<Cap, ErrorResponse> {
+    let cap = state.auth.authorize(headers, "namespaces", auth::ALL_PERMS).await?;
+    if !auth::is_root(&cap) {
+        return Err(state.auth.deny(&cap, "namespaces", "managing namespaces needs the root token").await);
+    }
+    Ok(cap)
+}
+
+fn namespace_root_json(state: &AppState, namespace: &Namespace) -> serde_json::Value {
+    let root = state.auth.for_namespace(&namespace.name, namespace.id).authority()
+        .mint(0, u64::MAX, auth::ALL_PERMS, 0);
+    json!({ "name": namespace.name, "id": namespace.id, "token": root.to_token() })
+}
+
+fn namespace_error_response(err: Box<dyn std::error::Error>) -> ErrorResponse {
+    let status = match err.downcast_ref::<NamespaceError>() {
+        Some(NamespaceError::NotFound(_)) => StatusCode::NOT_FOUND,
+        Some(NamespaceError::AlreadyExists(_)) => StatusCode::CONFLICT,
+        Some(NamespaceError::InvalidName(_) | NamespaceError::DropDefault) => StatusCode::BAD_REQUEST,
+        None => return internal_error(err),
+    };
+    (status, Json(json!({ "error": err.to_string(), "code": "NAMESPACE" })))
+}
+
+/// Filters of the audit endpoint; `resource` is an endpoint name and the
+/// time range is inclusive unix seconds
+#[derive(Debug, Default, serde::Deserialize)]
+struct AuditParams {
+    actor: Option<String>,
+    resource: Option<String>,
+    since: Option<u64>,
+    until: Option<u64>,
+    limit: Option<usize>,
+}
+
+/// Audit trail query endpoint; the trail covers every caller, so it needs a
+/// root-equivalent token (all permissions over the whole graph)
+async fn audit_log(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<AuditParams>,
+) -> Result<Json<serde_json::Value>, ErrorResponse> {
+    authorize_auditor(&state, &headers).await?;
+    let query = AuditQuery {
+        actor: params.actor,
+        resource: params.resource.as_deref().map(auth::resource),
+        operation: None,
+        since: params.since,
+        until: params.until,
+        limit: params.limit,
+    };
+    let records = state.auth.tracer().query(&query).await.map_err(audit_error_response)?;
+    Ok(Json(json!({
+        "count": records.len(),
+        "records": records.iter().map(audit_record_json).collect::<Vec<_>>(),
+    })))
+}
+
+/// Audit chain verification endpoint: 200 with the record count and head
+/// Cid if the log is intact, 409 naming the first bad record otherwise
+async fn audit_verify(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, ErrorResponse> {
+    authorize_auditor(&state, &headers).await?;
+    let verification = state.auth.tracer().verify().await.map_err(audit_error_response)?;
+    Ok(Json(json!({
+        "valid": true,
+        "records": verification.records,
+        "segments": verification.segments,
+        "head": verification.head.to_string(),
+    })))
+}
+
+async fn authorize_auditor(state: &AppState, headers: &HeaderMap) -> Result<(), ErrorResponse> {
+    let cap = state.auth.authorize(headers, "audit", auth::ALL_PERMS).await?;
+    let graph = state.catalog.default_namespace().await.graph;
+    let next_rid = graph.read().await.next_rid().await;
+    state.auth.check_whole_graph(&cap, "audit", next_rid).await
+}
+
+fn audit_error_response(err: AuditError) -> ErrorResponse {
+    match err {
+        AuditError::Tampered { seq, reason } => (
+            StatusCode::CONFLICT,
+            Json(json!({ "valid": false, "error": reason, "code": "AUDIT_TAMPERED", "seq": seq })),
+        ),
+        err => internal_error(err),
+    }
+}
+
+fn audit_record_json(record: &AuditRecord) -> serde_json::Value {
+    let entry = &record.entry;
+    json!({
+        "seq": record.seq,
+        "prev": record.prev.to_string(),
+        "cid": record.cid.to_string(),
+        "timestamp": entry.timestamp,
+        "operation": entry.operation,
+        "actor": entry.actor,
+        "resource": entry.resource.to_string(),
+        "success": entry.success,
+        "details": entry.details,
+        "capability": {
+            "base": entry.capability.base,
+            "len": entry.capability.len,
+            "perms": entry.capability.perms,
+            "expiry": entry.capability.expiry,
+        },
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -451,6 +1207,170 @@ mod tests {
         assert!(json["version"].is_string());
     }
 
+    #[tokio::test]
+    async fn test_namespaced_requests() {
+        use axum::body::Body;
+        use tower::Service;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let catalog = Arc::new(Catalog::open(temp_dir.path(), Default::default()).await.unwrap());
+        let auth = Arc::new(Authorizer::new(fcdb_concur::CapAuthority::generate(), None));
+        let metrics = Arc::new(MetricsCollector::new());
+        let root = auth.authority().mint(0, u64::MAX, auth::ALL_PERMS, 0).to_token();
+        let router = Server::new(Config::default(), metrics.clone(), Arc::new(HealthChecker::new()), catalog, auth).create_router();
+
+        let call = |method: &str, uri: &str, token: &str, namespace: Option<&str>, body: serde_json::Value| {
+            let mut request = axum::http::Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("authorization", format!("Bearer {}", token))
+                .header("content-type", "application/json");
+            if let Some(namespace) = namespace {
+                request = request.header(NAMESPACE_HEADER, namespace);
+            }
+            let request = request.body(Body::from(body.to_string())).unwrap();
+            let mut router = router.clone();
+            async move {
+                let response = router.call(request).await.unwrap();
+                let status = response.status();
+                let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, serde_json::from_slice::<serde_json::Value>(&bytes).unwrap_or_default())
+            }
+        };
+        let create = json!({ "operations": [{ "op": "createNode", "labels": ["Item"] }] });
+
+        let (status, created) = call("POST", "/namespaces", &root, None, json!({ "name": "tenant" })).await;
+        assert_eq!(status, StatusCode::OK);
+        let tenant_root = created["token"].as_str().unwrap().to_string();
+        assert_eq!(call("POST", "/namespaces", &root, None, json!({ "name": "tenant" })).await.0, StatusCode::CONFLICT);
+        assert_eq!(call("POST", "/namespaces", &tenant_root, None, json!({ "name": "other" })).await.0, StatusCode::UNAUTHORIZED);
+
+        // Tokens only work in their own namespace, selected by path or header
+        assert_eq!(call("POST", "/ns/tenant/batch", &tenant_root, None, create.clone()).await.0, StatusCode::OK);
+        assert_eq!(call("POST", "/batch", &tenant_root, Some("tenant"), create.clone()).await.0, StatusCode::OK);
+        assert_eq!(call("POST", "/ns/tenant/batch", &root, None, create.clone()).await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(call("POST", "/batch", &root, None, create.clone()).await.0, StatusCode::OK);
+        assert_eq!(call("POST", "/ns/missing/batch", &root, None, create.clone()).await.0, StatusCode::NOT_FOUND);
+
+        let (_, listed) = call("GET", "/namespaces", &root, None, json!({})).await;
+        let listed: Vec<_> = listed["namespaces"].as_array().unwrap().iter()
+            .map(|ns| (ns["name"].clone(), ns["id"].clone(), ns["nodes"].clone()))
+            .collect();
+        assert_eq!(listed, vec![
+            (json!("default"), json!(0), json!(1)),
+            (json!("tenant"), json!(1), json!(2)),
+        ]);
+        assert_eq!(metrics.namespace_metrics()["tenant"].requests, 3);
+        assert_eq!(metrics.namespace_metrics()["tenant"].errors, 1);
+        assert!(!metrics.namespace_metrics().contains_key("missing"));
+
+        let (_, token) = call("POST", "/namespaces/tenant/token", &root, None, json!({})).await;
+        assert_eq!(token["token"].as_str(), Some(tenant_root.as_str()));
+        assert_eq!(call("DELETE", "/namespaces/default", &root, None, json!({})).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(call("DELETE", "/namespaces/tenant", &root, None, json!({})).await.0, StatusCode::OK);
+        assert_eq!(call("POST", "/ns/tenant/batch", &tenant_root, None, create).await.0, StatusCode::NOT_FOUND);
+        assert!(!metrics.namespace_metrics().contains_key("tenant"));
+    }
+
+    #[tokio::test]
+    async fn test_admission_and_quotas() {
+        use axum::body::Body;
+        use tower::Service;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let catalog = Arc::new(Catalog::open(temp_dir.path(), Default::default()).await.unwrap());
+        let auth = Arc::new(Authorizer::new(fcdb_concur::CapAuthority::generate(), None));
+        let metrics = Arc::new(MetricsCollector::new());
+        let root = auth.authority().mint(0, u64::MAX, auth::ALL_PERMS, 0).to_token();
+        let mut config = Config::default();
+        config.performance.max_concurrent_writes = 1;
+        let server = Server::new(config, metrics.clone(), Arc::new(HealthChecker::new()), catalog, auth);
+        let admission = server.state.admission.clone();
+        let router = server.create_router();
+
+        let call = |method: &str, uri: &str, token: &str, body: serde_json::Value| {
+            let request = axum::http::Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("authorization", format!("Bearer {}", token))
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap();
+            let mut router = router.clone();
+            async move { router.call(request).await.unwrap() }
+        };
+        let create = json!({ "operations": [{ "op": "createNode", "labels": ["Item"], "properties": { "name": "widget" } }] });
+
+        // A full write class turns batches away without touching queries
+        let held = admission.try_admit(EndpointClass::Write).unwrap();
+        let refused = call("POST", "/batch", &root, create.clone()).await;
+        assert_eq!(refused.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(refused.headers()["retry-after"], "1");
+        assert_eq!(call("GET", "/rdf/export", &root, json!({})).await.status(), StatusCode::OK);
+        drop(held);
+        assert_eq!(call("POST", "/batch", &root, create.clone()).await.status(), StatusCode::OK);
+
+        let created = call("POST", "/namespaces", &root, json!({ "name": "small", "quota_bytes": 1 })).await;
+        let bytes = axum::body::to_bytes(created.into_body(), usize::MAX).await.unwrap();
+        let small_root = serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()["token"].as_str().unwrap().to_string();
+        // Content already stored by another namespace is free, so write something new
+        let create = json!({ "operations": [{ "op": "createNode", "labels": ["Item"], "properties": { "name": "gadget" } }] });
+        let full = call("POST", "/ns/small/batch", &small_root, create.clone()).await;
+        assert_eq!(full.status(), StatusCode::INSUFFICIENT_STORAGE);
+        let bytes = axum::body::to_bytes(full.into_body(), usize::MAX).await.unwrap();
+        let error: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(error["code"], "QUOTA_EXCEEDED");
+        assert_eq!(error["scope"], "namespace");
+
+        assert_eq!(call("PUT", "/namespaces/small/quota", &root, json!({ "bytes": null })).await.status(), StatusCode::OK);
+        assert_eq!(call("POST", "/ns/small/batch", &small_root, create).await.status(), StatusCode::OK);
+
+        let rejections = metrics.rejections();
+        assert_eq!(rejections[&("write".to_string(), "concurrency".to_string())], 1);
+        assert_eq!(rejections[&("write".to_string(), "quota".to_string())], 1);
+    }
+
+    #[tokio::test]
+    async fn test_cypher_writes_need_write() {
+        use axum::body::Body;
+        use tower::Service;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let catalog = Arc::new(Catalog::open(temp_dir.path(), Default::default()).await.unwrap());
+        let auth = Arc::new(Authorizer::new(fcdb_concur::CapAuthority::generate(), None));
+        let root = auth.authority().mint(0, u64::MAX, auth::ALL_PERMS, 0).to_token();
+        let reader = auth.authority().mint(0, u64::MAX, perms::READ | perms::EXECUTE, 0).to_token();
+        let router = Server::new(Config::default(), Arc::new(MetricsCollector::new()), Arc::new(HealthChecker::new()), catalog.clone(), auth)
+            .create_router();
+
+        let call = |uri: &str, token: &str, body: serde_json::Value| {
+            let request = axum::http::Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("authorization", format!("Bearer {}", token))
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap();
+            let mut router = router.clone();
+            async move { router.call(request).await.unwrap().status() }
+        };
+        let create = json!({ "operations": [{ "op": "createNode", "labels": ["Item"] }] });
+        assert_eq!(call("/batch", &root, create).await, StatusCode::OK);
+
+        // A denied query leaves no trace, not even the relationship type it named
+        let link = json!({ "query": "MATCH (a:Item) CREATE (a)-[:FRESH]->(a)" });
+        let namespace = catalog.default_namespace().await;
+        let version = namespace.graph.read().await.dictionary_version().await;
+        assert_eq!(call("/cypher", &reader, link.clone()).await, StatusCode::FORBIDDEN);
+        let graph = namespace.graph.read().await;
+        assert_eq!(graph.dictionary_version().await, version);
+        assert_eq!(graph.lookup_label("FRESH").await, None);
+        drop(graph);
+
+        assert_eq!(call("/cypher", &root, link).await, StatusCode::OK);
+        assert!(namespace.graph.read().await.lookup_label("FRESH").await.is_some());
+    }
+
     #[tokio::test]
     async fn test_version_endpoint() {
         let response = version_info().await;